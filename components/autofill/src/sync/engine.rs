@@ -111,7 +111,6 @@ impl<T: SyncRecord + std::fmt::Debug> SyncEngine for ConfigSyncEngine<T> {
         // Stage all incoming items.
         let mut incoming_telemetry = telemetry::EngineIncoming::new();
         let timestamp = inbound.timestamp;
-        let num_incoming = inbound.changes.len() as u32;
         let tx = db.writer.unchecked_transaction()?;
         let incoming_impl = self.storage_impl.get_incoming_impl(&self.local_enc_key)?;
         let outgoing_impl = self.storage_impl.get_outgoing_impl(&self.local_enc_key)?;
@@ -123,9 +122,12 @@ impl<T: SyncRecord + std::fmt::Debug> SyncEngine for ConfigSyncEngine<T> {
             signal.err_if_interrupted()?;
             // Finally get a "plan" and apply it.
             let action = plan_incoming(&*incoming_impl, &tx, state)?;
+            match action.reconcile_reason() {
+                Some(reason) => incoming_telemetry.reconciled_with(reason, 1),
+                None => incoming_telemetry.applied(1),
+            }
             super::apply_incoming_action(&*incoming_impl, &tx, action)?;
         }
-        incoming_telemetry.applied(num_incoming);
         telem.incoming(incoming_telemetry);
 
         // write the timestamp now, so if we are interrupted merging or
@@ -12,7 +12,7 @@ pub(crate) use crate::db::models::Metadata;
 use crate::error::Result;
 use interrupt_support::Interruptee;
 use rusqlite::Transaction;
-use sync15::{OutgoingChangeset, Payload, ServerTimestamp};
+use sync15::{telemetry::ReconcileReason, OutgoingChangeset, Payload, ServerTimestamp};
 use sync_guid::Guid;
 use types::Timestamp;
 
@@ -238,6 +238,26 @@ enum IncomingAction<T> {
     DoNothing,
 }
 
+impl<T> IncomingAction<T> {
+    /// If this action reconciles the incoming record with a local one, why.
+    /// `None` means the incoming record is applied as-is.
+    fn reconcile_reason(&self) -> Option<ReconcileReason> {
+        match self {
+            IncomingAction::Update {
+                was_merged: true, ..
+            } => Some(ReconcileReason::Merge),
+            IncomingAction::Fork { .. } => Some(ReconcileReason::Fork),
+            IncomingAction::UpdateLocalGuid { .. } => Some(ReconcileReason::Dedupe),
+            IncomingAction::ResurrectRemoteTombstone { .. } => Some(ReconcileReason::LocalWins),
+            IncomingAction::ResurrectLocalTombstone { .. } => Some(ReconcileReason::RemoteWins),
+            IncomingAction::DeleteLocalRecord { .. }
+            | IncomingAction::Insert { .. }
+            | IncomingAction::Update { .. }
+            | IncomingAction::DoNothing => None,
+        }
+    }
+}
+
 /// Convert a IncomingState to an IncomingAction - this is where the "policy"
 /// lives for when we resurrect, or merge etc.
 fn plan_incoming<T: std::fmt::Debug + SyncRecord>(
@@ -139,10 +139,13 @@ class PersistedFirefoxAccount(inner: FirefoxAccount, persistCallback: PersistCal
      * Modifies the FirefoxAccount state.
      *
      * This performs network requests, and should not be used on the main thread.
+     *
+     * @return the verified [IdTokenClaims] if the flow requested the `openid` scope, `null` otherwise
      */
-    fun completeOAuthFlow(code: String, state: String) {
-        this.inner.completeOauthFlow(code, state)
+    fun completeOAuthFlow(code: String, state: String): IdTokenClaims? {
+        val claims = this.inner.completeOauthFlow(code, state)
         this.tryPersistState()
+        return claims
     }
 
     /**
@@ -251,7 +254,11 @@ class PersistedFirefoxAccount(inner: FirefoxAccount, persistCallback: PersistCal
     }
 
     fun checkAuthorizationStatus(): AuthorizationInfo {
-        return this.inner.checkAuthorizationStatus()
+        try {
+            return this.inner.checkAuthorizationStatus()
+        } finally {
+            this.tryPersistState()
+        }
     }
 
     /**
@@ -417,7 +424,21 @@ class PersistedFirefoxAccount(inner: FirefoxAccount, persistCallback: PersistCal
      * This performs network requests, and should not be used on the main thread.
      */
     fun getDevices(ignoreCache: Boolean = false): Array<Device> {
-        return this.inner.getDevices(ignoreCache).toTypedArray()
+        try {
+            return this.inner.getDevices(ignoreCache).toTypedArray()
+        } finally {
+            this.tryPersistState()
+        }
+    }
+
+    /**
+     * Set how long [getDevices] caches the list of devices for.
+     * This setting isn't persisted, and should be set again after restoring the account.
+     *
+     * @param ttlSecs How long the list of devices is fresh for, in seconds.
+     */
+    fun setDevicesCacheTtl(ttlSecs: Long) {
+        this.inner.setDevicesCacheTtl(ttlSecs)
     }
 
     /**
@@ -431,6 +452,71 @@ class PersistedFirefoxAccount(inner: FirefoxAccount, persistCallback: PersistCal
         this.tryPersistState()
     }
 
+    /**
+     * Get the current state of the account.
+     *
+     * This does not make network requests, and can be used on the main thread.
+     */
+    fun getState(): AccountState {
+        return this.inner.getState()
+    }
+
+    /**
+     * Register an observer to be notified every time the account changes state.
+     *
+     * The observer is called on the thread of the method which caused the transition,
+     * and must not call methods of this object itself.
+     */
+    fun registerStateObserver(observer: AccountStateObserver) {
+        this.inner.registerStateObserver(observer)
+    }
+
+    /**
+     * Start pairing a new device, as the pairing authority.
+     *
+     * @param channelId The id of the channel opened on the pairing channel server
+     * @param transport Sends the (already encrypted) messages for the new device on the channel
+     * @return The pairing URL for the new device to scan
+     *
+     * This does not make network requests, and can be used on the main thread.
+     */
+    fun beginPairingAuthority(channelId: String, transport: PairingChannelTransport): String {
+        return this.inner.beginPairingAuthority(channelId, transport)
+    }
+
+    /**
+     * Handle a message received on the pairing channel.
+     *
+     * This performs network requests, and should not be used on the main thread.
+     */
+    fun handlePairingMessage(message: String): PairingAuthorityState {
+        try {
+            return this.inner.handlePairingMessage(message)
+        } finally {
+            this.tryPersistState()
+        }
+    }
+
+    /**
+     * Approve the new device's authorization request, once the user has agreed to it.
+     *
+     * This performs network requests, and should not be used on the main thread.
+     */
+    fun approvePairing(): PairingAuthorityState {
+        try {
+            return this.inner.approvePairing()
+        } finally {
+            this.tryPersistState()
+        }
+    }
+
+    /**
+     * Stop the pairing in progress, if any. The caller should close the channel.
+     */
+    fun cancelPairing() {
+        this.inner.cancelPairing()
+    }
+
     /**
      * Retrieves any pending commands for the current device.
      * This should be called semi-regularly as the main method of commands delivery (push)
@@ -466,6 +552,21 @@ class PersistedFirefoxAccount(inner: FirefoxAccount, persistCallback: PersistCal
         }
     }
 
+    /**
+     * Take the account events which were detected locally rather than delivered by push,
+     * such as a scoped key changing when refreshing an access token with [getAccessToken].
+     * Each event is only returned once.
+     *
+     * @return A collection of [AccountEvent] that should be handled by the caller.
+     */
+    fun takeAccountEvents(): Array<AccountEvent> {
+        try {
+            return this.inner.takeAccountEvents().toTypedArray()
+        } finally {
+            this.tryPersistState()
+        }
+    }
+
     /**
      * Ensure the current device is registered with the specified name and device type, with
      * the required capabilities (at this time only Send Tab).
@@ -503,16 +604,62 @@ class PersistedFirefoxAccount(inner: FirefoxAccount, persistCallback: PersistCal
      * @param url The url of the tab being sent
      */
     fun sendSingleTab(targetDeviceId: String, title: String, url: String) {
-        this.inner.sendSingleTab(targetDeviceId, title, url)
+        try {
+            this.inner.sendSingleTab(targetDeviceId, title, url)
+        } finally {
+            this.tryPersistState()
+        }
+    }
+
+    /**
+     * Send several tabs, with their navigation history, to another device identified by its device ID.
+     *
+     * This performs network requests, and should not be used on the main thread.
+     *
+     * @param targetDeviceId The target Device ID
+     * @param tabs The tabs to send, the last history entry of each being the page to display
+     */
+    fun sendTabs(targetDeviceId: String, tabs: List<TabToSend>) {
+        try {
+            this.inner.sendTabs(targetDeviceId, tabs)
+        } finally {
+            this.tryPersistState()
+        }
+    }
+
+    /**
+     * Close the tabs with the given URLs on another device identified by its device ID.
+     *
+     * This performs network requests, and should not be used on the main thread.
+     *
+     * @param targetDeviceId The target Device ID
+     * @param urls The urls of the tabs to close
+     */
+    fun closeTabs(targetDeviceId: String, urls: List<String>) {
+        try {
+            this.inner.closeTabs(targetDeviceId, urls)
+        } finally {
+            this.tryPersistState()
+        }
+    }
+
+    /**
+     * Get the device commands which could not be sent because the device was offline,
+     * and will be sent once it's back online.
+     *
+     * This does not make network requests, and can be used on the main thread.
+     */
+    fun getPendingOutgoingCommands(): List<OutgoingDeviceCommand> {
+        return this.inner.getPendingOutgoingCommands()
     }
 
     /**
-     * Gather any telemetry which has been collected internally and return
-     * the result as a JSON string.
+     * Gather any telemetry which has been collected internally since the last call,
+     * for the caller to record as Glean metrics.
      *
      * This does not make network requests, and can be used on the main thread.
      */
-    fun gatherTelemetry(): String {
+    fun gatherTelemetry(): AccountTelemetry {
         return this.inner.gatherTelemetry()
     }
 
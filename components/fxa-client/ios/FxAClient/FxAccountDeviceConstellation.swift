@@ -94,6 +94,12 @@ public class DeviceConstellation {
                 case let .sendTab(title, url): do {
                         try self.account.sendSingleTab(targetDeviceId: targetDeviceId, title: title, url: url)
                     }
+                case let .sendTabs(tabs): do {
+                        try self.account.sendTabs(targetDeviceId: targetDeviceId, tabs: tabs)
+                    }
+                case let .closeTabs(urls): do {
+                        try self.account.closeTabs(targetDeviceId: targetDeviceId, urls: urls)
+                    }
                 }
             } catch {
                 FxALog.error("Error sending event to another device: \(error).")
@@ -167,4 +173,6 @@ public class DeviceConstellation {
 
 public enum DeviceEventOutgoing {
     case sendTab(title: String, url: String)
+    case sendTabs(tabs: [TabToSend])
+    case closeTabs(urls: [String])
 }
@@ -317,15 +317,14 @@ open class FxAccountManager {
         }
     }
 
-    /// Returns a JSON string containing telemetry events to submit in the next
-    /// Sync ping. This is used to collect telemetry for services like Send Tab.
-    /// This method can be called anytime, and returns `nil` if the account is
-    /// not initialized or there are no events to record.
-    public func gatherTelemetry() throws -> String? {
+    /// Returns the telemetry events recorded since the last call, for the caller
+    /// to submit as Glean metrics or in the next Sync ping. This method can be
+    /// called anytime, and returns `nil` if the account is not initialized.
+    public func gatherTelemetry() -> AccountTelemetry? {
         guard let acct = account else {
             return nil
         }
-        return try acct.gatherTelemetry()
+        return acct.gatherTelemetry()
     }
 
     let fxaFsmQueue = DispatchQueue(label: "com.mozilla.fxa-mgr-queue")
@@ -87,9 +87,10 @@ class PersistedFirefoxAccount {
         }
     }
 
-    public func completeOAuthFlow(code: String, state: String) throws {
+    @discardableResult
+    public func completeOAuthFlow(code: String, state: String) throws -> IdTokenClaims? {
         defer { tryPersistState() }
-        try notifyAuthErrors {
+        return try notifyAuthErrors {
             try self.inner.completeOauthFlow(code: code, state: state)
         }
     }
@@ -106,6 +107,38 @@ class PersistedFirefoxAccount {
         inner.disconnect()
     }
 
+    public func getState() -> AccountState {
+        return inner.getState()
+    }
+
+    public func registerStateObserver(observer: AccountStateObserver) {
+        inner.registerStateObserver(observer: observer)
+    }
+
+    public func beginPairingAuthority(channelId: String, transport: PairingChannelTransport) throws -> String {
+        return try notifyAuthErrors {
+            try self.inner.beginPairingAuthority(channelId: channelId, transport: transport)
+        }
+    }
+
+    public func handlePairingMessage(message: String) throws -> PairingAuthorityState {
+        defer { tryPersistState() }
+        return try notifyAuthErrors {
+            try self.inner.handlePairingMessage(message: message)
+        }
+    }
+
+    public func approvePairing() throws -> PairingAuthorityState {
+        defer { tryPersistState() }
+        return try notifyAuthErrors {
+            try self.inner.approvePairing()
+        }
+    }
+
+    public func cancelPairing() {
+        inner.cancelPairing()
+    }
+
     public func getProfile(ignoreCache: Bool) throws -> Profile {
         defer { tryPersistState() }
         return try notifyAuthErrors {
@@ -133,11 +166,16 @@ class PersistedFirefoxAccount {
     }
 
     public func getDevices(ignoreCache: Bool = false) throws -> [Device] {
+        defer { tryPersistState() }
         return try notifyAuthErrors {
             try self.inner.getDevices(ignoreCache: ignoreCache)
         }
     }
 
+    public func setDevicesCacheTtl(ttlSecs: UInt64) {
+        inner.setDevicesCacheTtl(ttlSecs: Int64(clamping: ttlSecs))
+    }
+
     public func getAttachedClients() throws -> [AttachedClient] {
         return try notifyAuthErrors {
             try self.inner.getAttachedClients()
@@ -178,6 +216,11 @@ class PersistedFirefoxAccount {
         }
     }
 
+    public func takeAccountEvents() -> [AccountEvent] {
+        defer { tryPersistState() }
+        return inner.takeAccountEvents()
+    }
+
     public func pollDeviceCommands() throws -> [IncomingDeviceCommand] {
         defer { tryPersistState() }
         return try notifyAuthErrors {
@@ -186,11 +229,30 @@ class PersistedFirefoxAccount {
     }
 
     public func sendSingleTab(targetDeviceId: String, title: String, url: String) throws {
+        defer { tryPersistState() }
         return try notifyAuthErrors {
             try self.inner.sendSingleTab(targetDeviceId: targetDeviceId, title: title, url: url)
         }
     }
 
+    public func sendTabs(targetDeviceId: String, tabs: [TabToSend]) throws {
+        defer { tryPersistState() }
+        return try notifyAuthErrors {
+            try self.inner.sendTabs(targetDeviceId: targetDeviceId, tabs: tabs)
+        }
+    }
+
+    public func closeTabs(targetDeviceId: String, urls: [String]) throws {
+        defer { tryPersistState() }
+        return try notifyAuthErrors {
+            try self.inner.closeTabs(targetDeviceId: targetDeviceId, urls: urls)
+        }
+    }
+
+    public func getPendingOutgoingCommands() -> [OutgoingDeviceCommand] {
+        return inner.getPendingOutgoingCommands()
+    }
+
     public func getTokenServerEndpointURL() throws -> URL {
         return URL(string: try inner.getTokenServerEndpointUrl())!
     }
@@ -240,10 +302,8 @@ class PersistedFirefoxAccount {
         inner.clearAccessTokenCache()
     }
 
-    public func gatherTelemetry() throws -> String {
-        return try notifyAuthErrors {
-            try self.inner.gatherTelemetry()
-        }
+    public func gatherTelemetry() -> AccountTelemetry {
+        return inner.gatherTelemetry()
     }
 
     // TODO: not sure why we switched to returning a bool for the Swift wrapper here,
@@ -0,0 +1,402 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The state machine of the application's connection to the user's account.
+//!
+//! The current [`AccountState`] isn't stored on its own, it's derived from the rest of
+//! the account state: whether we hold a refresh token, whether the server has rejected it,
+//! whether a migration is in flight and whether an OAuth flow is in progress. That way
+//! it can't get out of sync with the data it describes, and it survives a round-trip
+//! through `to_json`/`from_json`.
+//!
+//! Methods which can move the account to another state call `update_account_state`
+//! once they're done, which tells the registered [`AccountStateObserver`] about it.
+
+pub use crate::{AccountState, AccountStateObserver};
+
+use super::{error::*, FirefoxAccount};
+
+/// Whether the server rejected the refresh token we authenticated a request with.
+pub(crate) fn is_auth_error(err: &Error) -> bool {
+    matches!(err.kind(), ErrorKind::RemoteError { code: 401, .. })
+}
+
+impl FirefoxAccount {
+    /// Get the current state of the account.
+    pub fn get_state(&self) -> AccountState {
+        if self.state.in_flight_migration.is_some() {
+            AccountState::Migrating
+        } else if self.state.refresh_token.is_some() {
+            if self.state.auth_problem {
+                AccountState::AuthProblem
+            } else {
+                AccountState::Connected
+            }
+        } else if !self.flow_store.is_empty() {
+            AccountState::Authenticating
+        } else {
+            AccountState::Disconnected
+        }
+    }
+
+    /// Register an observer which is called every time the account changes state.
+    /// It replaces any previously-registered observer.
+    pub fn register_state_observer(&mut self, observer: Box<dyn AccountStateObserver>) {
+        self.state_observer = Some(observer);
+    }
+
+    /// Record whether the server accepts our refresh token, and notify the observer
+    /// if that moved the account in or out of the `AuthProblem` state.
+    ///
+    /// **💾 This method alters the persisted account state.**
+    pub(crate) fn set_auth_problem(&mut self, auth_problem: bool) {
+        self.state.auth_problem = auth_problem;
+        self.update_account_state();
+    }
+
+    /// Notify the observer if the account has changed state since it was last notified.
+    pub(crate) fn update_account_state(&mut self) {
+        let new_state = self.get_state();
+        if new_state == self.account_state {
+            return;
+        }
+        let old_state = std::mem::replace(&mut self.account_state, new_state);
+        log::info!(
+            "Account state changed from {:?} to {:?}",
+            old_state,
+            new_state
+        );
+        if let Some(ref observer) = self.state_observer {
+            observer.on_state_changed(old_state, new_state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        http_client::*,
+        oauth::{OAuthFlow, RefreshToken},
+        telemetry::{FailureReason, OAuthFlowEvent, OAuthFlowKind, TokenRefreshFailure},
+        Config,
+    };
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+
+    type Transitions = Arc<Mutex<Vec<(AccountState, AccountState)>>>;
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        transitions: Transitions,
+    }
+
+    impl AccountStateObserver for RecordingObserver {
+        fn on_state_changed(&self, old_state: AccountState, new_state: AccountState) {
+            self.transitions
+                .lock()
+                .unwrap()
+                .push((old_state, new_state));
+        }
+    }
+
+    fn setup() -> (FirefoxAccount, Transitions) {
+        let config = Config::stable_dev("12345678", "https://foo.bar");
+        let mut fxa = FirefoxAccount::with_config(config);
+        let observer = RecordingObserver::default();
+        let transitions = observer.transitions.clone();
+        fxa.register_state_observer(Box::new(observer));
+        (fxa, transitions)
+    }
+
+    fn connect(fxa: &mut FirefoxAccount) {
+        let mut refresh_token_scopes = HashSet::new();
+        refresh_token_scopes.insert("profile".to_owned());
+        fxa.state.refresh_token = Some(RefreshToken {
+            token: "refreshtok".to_owned(),
+            scopes: refresh_token_scopes,
+        });
+        fxa.update_account_state();
+    }
+
+    fn unauthorized() -> Error {
+        ErrorKind::RemoteError {
+            code: 401,
+            errno: 110,
+            error: "Unauthorized".to_owned(),
+            message: "Invalid authentication token in request signature".to_owned(),
+            info: "".to_owned(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_new_account_is_disconnected() {
+        let (fxa, transitions) = setup();
+        assert_eq!(fxa.get_state(), AccountState::Disconnected);
+        assert!(transitions.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_complete_oauth_flow_connects() {
+        let (mut fxa, transitions) = setup();
+        fxa.flow_store.insert(
+            "state".to_owned(),
+            OAuthFlow {
+                scoped_keys_flow: None,
+                code_verifier: "verifier".to_owned(),
+                nonce: None,
+                kind: OAuthFlowKind::SignIn,
+                entrypoint: "test".to_owned(),
+            },
+        );
+        fxa.update_account_state();
+        assert_eq!(fxa.get_state(), AccountState::Authenticating);
+
+        let mut client = FxAClientMock::new();
+        client
+            .expect_create_refresh_token_using_authorization_code(
+                mockiato::Argument::any,
+                |code| code.partial_eq("code"),
+                |verifier| verifier.partial_eq("verifier"),
+            )
+            .times(1)
+            .returns_once(Ok(OAuthTokenResponse {
+                keys_jwe: None,
+                refresh_token: Some("refreshtok".to_owned()),
+                session_token: None,
+                expires_in: 6_000_000,
+                scope: "profile".to_owned(),
+                access_token: "accesstok".to_owned(),
+                id_token: None,
+            }));
+        client
+            .expect_destroy_access_token(mockiato::Argument::any, mockiato::Argument::any)
+            .times(1)
+            .returns_once(Ok(()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.complete_oauth_flow("code", "state").unwrap();
+        assert_eq!(fxa.get_state(), AccountState::Connected);
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![
+                (AccountState::Disconnected, AccountState::Authenticating),
+                (AccountState::Authenticating, AccountState::Connected),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_oauth_flow_goes_back_to_disconnected() {
+        let (mut fxa, transitions) = setup();
+        fxa.flow_store.insert(
+            "state".to_owned(),
+            OAuthFlow {
+                scoped_keys_flow: None,
+                code_verifier: "verifier".to_owned(),
+                nonce: None,
+                kind: OAuthFlowKind::SignIn,
+                entrypoint: "test".to_owned(),
+            },
+        );
+        fxa.update_account_state();
+
+        let mut client = FxAClientMock::new();
+        client
+            .expect_create_refresh_token_using_authorization_code(
+                mockiato::Argument::any,
+                mockiato::Argument::any,
+                mockiato::Argument::any,
+            )
+            .times(1)
+            .returns_once(Err(unauthorized()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.complete_oauth_flow("code", "state").unwrap_err();
+        assert_eq!(fxa.get_state(), AccountState::Disconnected);
+        assert_eq!(
+            transitions.lock().unwrap().last(),
+            Some(&(AccountState::Authenticating, AccountState::Disconnected))
+        );
+        assert_eq!(
+            fxa.gather_telemetry().oauth_flows,
+            vec![OAuthFlowEvent {
+                kind: OAuthFlowKind::SignIn,
+                entrypoint: "test".to_owned(),
+                failure_reason: Some(FailureReason::Authentication),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_refresh_token_rejected_is_auth_problem() {
+        let (mut fxa, transitions) = setup();
+        connect(&mut fxa);
+
+        let mut client = FxAClientMock::new();
+        client
+            .expect_create_access_token_using_refresh_token(
+                mockiato::Argument::any,
+                |token| token.partial_eq("refreshtok"),
+                mockiato::Argument::any,
+                mockiato::Argument::any,
+            )
+            .times(1)
+            .returns_once(Err(unauthorized()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.get_access_token("profile", None).unwrap_err();
+        assert_eq!(fxa.get_state(), AccountState::AuthProblem);
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![
+                (AccountState::Disconnected, AccountState::Connected),
+                (AccountState::Connected, AccountState::AuthProblem),
+            ]
+        );
+        assert_eq!(
+            fxa.gather_telemetry().token_refresh_failures,
+            vec![TokenRefreshFailure {
+                scope: "profile".to_owned(),
+                reason: FailureReason::Authentication,
+            }]
+        );
+
+        // The auth problem is remembered in the persisted state.
+        let fxa = FirefoxAccount::from_json(&fxa.to_json().unwrap()).unwrap();
+        assert_eq!(fxa.get_state(), AccountState::AuthProblem);
+    }
+
+    #[test]
+    fn test_network_errors_are_not_auth_problems() {
+        let (mut fxa, _) = setup();
+        connect(&mut fxa);
+
+        let mut client = FxAClientMock::new();
+        client
+            .expect_create_access_token_using_refresh_token(
+                mockiato::Argument::any,
+                mockiato::Argument::any,
+                mockiato::Argument::any,
+                mockiato::Argument::any,
+            )
+            .times(1)
+            .returns_once(Err(ErrorKind::RequestError(viaduct::Error::NetworkError(
+                "offline".to_owned(),
+            ))
+            .into()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.get_access_token("profile", None).unwrap_err();
+        assert_eq!(fxa.get_state(), AccountState::Connected);
+    }
+
+    #[test]
+    fn test_check_authorization_status_updates_auth_problem() {
+        let (mut fxa, transitions) = setup();
+        connect(&mut fxa);
+
+        let mut client = FxAClientMock::new();
+        client
+            .expect_check_refresh_token_status(mockiato::Argument::any, |token| {
+                token.partial_eq("refreshtok")
+            })
+            .times(1)
+            .returns_once(Ok(IntrospectResponse { active: false }));
+        fxa.set_client(Arc::new(client));
+        assert!(!fxa.check_authorization_status().unwrap().active);
+        assert_eq!(fxa.get_state(), AccountState::AuthProblem);
+
+        let mut client = FxAClientMock::new();
+        client
+            .expect_check_refresh_token_status(mockiato::Argument::any, |token| {
+                token.partial_eq("refreshtok")
+            })
+            .times(1)
+            .returns_once(Ok(IntrospectResponse { active: true }));
+        fxa.set_client(Arc::new(client));
+        assert!(fxa.check_authorization_status().unwrap().active);
+        assert_eq!(fxa.get_state(), AccountState::Connected);
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![
+                (AccountState::Disconnected, AccountState::Connected),
+                (AccountState::Connected, AccountState::AuthProblem),
+                (AccountState::AuthProblem, AccountState::Connected),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disconnect() {
+        let (mut fxa, transitions) = setup();
+        connect(&mut fxa);
+        fxa.state.auth_problem = true;
+
+        let mut client = FxAClientMock::new();
+        client
+            .expect_get_devices(mockiato::Argument::any, mockiato::Argument::any)
+            .times(1)
+            .returns_once(Err(unauthorized()));
+        client
+            .expect_destroy_refresh_token(mockiato::Argument::any, |token| {
+                token.partial_eq("refreshtok")
+            })
+            .times(1)
+            .returns_once(Ok(()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.disconnect();
+        assert_eq!(fxa.get_state(), AccountState::Disconnected);
+        assert!(!fxa.state.auth_problem);
+        assert_eq!(
+            transitions.lock().unwrap().last(),
+            Some(&(AccountState::Connected, AccountState::Disconnected))
+        );
+    }
+
+    #[test]
+    fn test_migration_in_flight() {
+        let (mut fxa, transitions) = setup();
+
+        let mut client = FxAClientMock::new();
+        client
+            .expect_duplicate_session_token(mockiato::Argument::any, |arg| {
+                arg.partial_eq("session")
+            })
+            .returns_once(Err(ErrorKind::RemoteError {
+                code: 500,
+                errno: 999,
+                error: "server error".to_string(),
+                message: "there was a server error".to_string(),
+                info: "fyi, there was a server error".to_string(),
+            }
+            .into()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.migrate_from_session_token("session", "aabbcc", "ddeeff", true)
+            .unwrap_err();
+        assert_eq!(fxa.get_state(), AccountState::Migrating);
+
+        // An error the migration can't recover from puts us back where we started.
+        let mut client = FxAClientMock::new();
+        client
+            .expect_duplicate_session_token(mockiato::Argument::any, |arg| {
+                arg.partial_eq("session")
+            })
+            .returns_once(Err(unauthorized()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.try_migration().unwrap_err();
+        assert_eq!(fxa.get_state(), AccountState::Disconnected);
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![
+                (AccountState::Disconnected, AccountState::Migrating),
+                (AccountState::Migrating, AccountState::Disconnected),
+            ]
+        );
+    }
+}
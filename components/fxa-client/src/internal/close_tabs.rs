@@ -0,0 +1,48 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{
+    commands::{
+        close_tabs::{CloseTabsPayload, EncryptedCloseTabsPayload},
+        IncomingDeviceCommand,
+    },
+    error::*,
+    http_client::GetDeviceResponse,
+    outbox::OutgoingCommandPayload,
+    telemetry, FirefoxAccount,
+};
+
+impl FirefoxAccount {
+    /// Close the tabs with the given URLs on another device designated by its
+    /// device ID. If we're offline, the tabs are closed once we're back online.
+    pub fn close_tabs(&mut self, target_device_id: &str, urls: Vec<String>) -> Result<()> {
+        self.send_or_queue_command(
+            target_device_id,
+            OutgoingCommandPayload::CloseTabs(CloseTabsPayload::new(urls)),
+        )
+    }
+
+    pub(crate) fn handle_close_tabs_command(
+        &mut self,
+        sender: Option<GetDeviceResponse>,
+        payload: serde_json::Value,
+        reason: telemetry::ReceivedReason,
+    ) -> Result<IncomingDeviceCommand> {
+        let close_tabs_key = self.get_send_tab_keys()?;
+        let encrypted_payload: EncryptedCloseTabsPayload = serde_json::from_value(payload)?;
+        // If we can't decrypt the payload, our keys are likely out of sync with
+        // the ones we registered, which the next Send Tab command will notice
+        // and fix, so we don't reset them here.
+        let payload = encrypted_payload.decrypt(&close_tabs_key)?;
+        self.telemetry
+            .borrow_mut()
+            .record_command_received(telemetry::ReceivedCommand {
+                command: telemetry::CommandKind::CloseTabs,
+                flow_id: payload.flow_id.clone(),
+                stream_id: payload.stream_id.clone(),
+                reason,
+            });
+        Ok(IncomingDeviceCommand::TabsClosed { sender, payload })
+    }
+}
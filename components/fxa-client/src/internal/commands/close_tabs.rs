@@ -0,0 +1,117 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+/// The Close Tabs functionality is also backed by Firefox Accounts device commands.
+/// A device shows it can close tabs by advertising the "close-uri" command in its
+/// own device record, with the same `SendTabKeysPayload` as its "open-uri" command.
+///
+/// When a device closes tabs on another, it decrypts that `SendTabKeysPayload`
+/// and uses the obtained public key to encrypt the `CloseTabsPayload` listing the
+/// URLs of the tabs to close, just as it would encrypt a `SendTabPayload`.
+use serde_derive::*;
+
+use super::super::{device::Device, error::*, scoped_keys::ScopedKey, telemetry};
+use super::send_tab::{self, PrivateSendTabKeys, PublicSendTabKeys};
+
+pub const COMMAND_NAME: &str = "https://identity.mozilla.com/cmd/close-uri/v1";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptedCloseTabsPayload {
+    /// URL Safe Base 64 encrypted close-tabs payload.
+    encrypted: String,
+}
+
+impl EncryptedCloseTabsPayload {
+    pub(crate) fn decrypt(self, keys: &PrivateSendTabKeys) -> Result<CloseTabsPayload> {
+        keys.decrypt_payload(&self.encrypted)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CloseTabsPayload {
+    pub urls: Vec<String>,
+    #[serde(rename = "flowID", default)]
+    pub flow_id: String,
+    #[serde(rename = "streamID", default)]
+    pub stream_id: String,
+}
+
+impl From<CloseTabsPayload> for crate::CloseTabsPayload {
+    fn from(payload: CloseTabsPayload) -> Self {
+        crate::CloseTabsPayload {
+            urls: payload.urls,
+            flow_id: payload.flow_id,
+            stream_id: payload.stream_id,
+        }
+    }
+}
+
+impl CloseTabsPayload {
+    pub fn new(urls: Vec<String>) -> Self {
+        let sent_telemetry = telemetry::SentCommand::new_flow(telemetry::CommandKind::CloseTabs);
+        CloseTabsPayload {
+            urls,
+            flow_id: sent_telemetry.flow_id,
+            stream_id: sent_telemetry.stream_id,
+        }
+    }
+
+    /// The telemetry to record once the payload is sent.
+    pub fn sent_telemetry(&self) -> telemetry::SentCommand {
+        telemetry::SentCommand {
+            command: telemetry::CommandKind::CloseTabs,
+            flow_id: self.flow_id.clone(),
+            stream_id: self.stream_id.clone(),
+        }
+    }
+
+    fn encrypt(&self, keys: PublicSendTabKeys) -> Result<EncryptedCloseTabsPayload> {
+        Ok(EncryptedCloseTabsPayload {
+            encrypted: keys.encrypt_payload(self)?,
+        })
+    }
+}
+
+pub fn build_close_tabs_command(
+    scoped_key: &ScopedKey,
+    target: &Device,
+    close_tabs_payload: &CloseTabsPayload,
+) -> Result<serde_json::Value> {
+    let public_keys = send_tab::get_public_keys(scoped_key, target, COMMAND_NAME)?;
+    let encrypted_payload = close_tabs_payload.encrypt(public_keys)?;
+    Ok(serde_json::to_value(&encrypted_payload)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_payload() {
+        let keys = PrivateSendTabKeys::from_random().unwrap();
+        let public_keys: PublicSendTabKeys = keys.clone().into();
+        let payload = CloseTabsPayload::new(vec![
+            "https://example.com/".to_string(),
+            "https://example.org/".to_string(),
+        ]);
+        let encrypted = payload.encrypt(public_keys).unwrap();
+        let json = serde_json::to_string(&encrypted).unwrap();
+        assert!(!json.contains("example.com"));
+
+        let encrypted: EncryptedCloseTabsPayload = serde_json::from_str(&json).unwrap();
+        let decrypted = encrypted.decrypt(&keys).unwrap();
+        assert_eq!(decrypted.urls, payload.urls);
+        assert_eq!(decrypted.flow_id, payload.flow_id);
+        assert_eq!(decrypted.stream_id, payload.stream_id);
+    }
+
+    #[test]
+    fn test_decrypt_with_other_keys_fails() {
+        let keys = PrivateSendTabKeys::from_random().unwrap();
+        let other_keys = PrivateSendTabKeys::from_random().unwrap();
+        let payload = CloseTabsPayload::new(vec!["https://example.com/".to_string()]);
+        let encrypted = payload.encrypt(keys.into()).unwrap();
+        assert!(encrypted.decrypt(&other_keys).is_err());
+    }
+}
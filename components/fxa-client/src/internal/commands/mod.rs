@@ -4,7 +4,9 @@
 
 use std::convert::TryFrom;
 
+pub mod close_tabs;
 pub mod send_tab;
+pub use close_tabs::CloseTabsPayload;
 pub use send_tab::SendTabPayload;
 
 use super::device::Device;
@@ -17,6 +19,10 @@ pub enum IncomingDeviceCommand {
         sender: Option<Device>,
         payload: SendTabPayload,
     },
+    TabsClosed {
+        sender: Option<Device>,
+        payload: CloseTabsPayload,
+    },
 }
 
 impl TryFrom<IncomingDeviceCommand> for crate::IncomingDeviceCommand {
@@ -29,6 +35,12 @@ impl TryFrom<IncomingDeviceCommand> for crate::IncomingDeviceCommand {
                     payload: payload.into(),
                 }
             }
+            IncomingDeviceCommand::TabsClosed { sender, payload } => {
+                crate::IncomingDeviceCommand::TabsClosed {
+                    sender: sender.map(crate::Device::try_from).transpose()?,
+                    payload: payload.into(),
+                }
+            }
         })
     }
 }
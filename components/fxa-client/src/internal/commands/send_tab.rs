@@ -13,6 +13,11 @@
 /// uses the obtained public key to encrypt the `SendTabPayload` it created that
 /// contains the tab to send and finally forms the `EncryptedSendTabPayload` that is
 /// then sent to the target device.
+///
+/// Several tabs can be sent in one command: the first is the `SendTabPayload`
+/// itself, and the others are listed in its `additionalTabs`. Devices which don't
+/// know about `additionalTabs` only open the first tab.
+use serde::de::DeserializeOwned;
 use serde_derive::*;
 
 use rc_crypto::ece::{self, EcKeyComponents};
@@ -22,6 +27,11 @@ use super::super::{device::Device, error::*, scoped_keys::ScopedKey, scopes, tel
 
 pub const COMMAND_NAME: &str = "https://identity.mozilla.com/cmd/open-uri";
 
+// The FxA server limits the size of command payloads, so we split the tabs we
+// send across as many commands as needed to keep the JSON of each one, before
+// it's encrypted and encoded, under this many bytes.
+const MAX_PAYLOAD_SIZE: usize = 16 * 1024;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EncryptedSendTabPayload {
     /// URL Safe Base 64 encrypted send-tab payload.
@@ -30,10 +40,7 @@ pub struct EncryptedSendTabPayload {
 
 impl EncryptedSendTabPayload {
     pub(crate) fn decrypt(self, keys: &PrivateSendTabKeysV1) -> Result<SendTabPayload> {
-        rc_crypto::ensure_initialized();
-        let encrypted = base64::decode_config(&self.encrypted, base64::URL_SAFE_NO_PAD)?;
-        let decrypted = ece::decrypt(&keys.p256key, &keys.auth_secret, &encrypted)?;
-        Ok(serde_json::from_slice(&decrypted)?)
+        keys.decrypt_payload(&self.encrypted)
     }
 }
 
@@ -44,6 +51,13 @@ pub struct SendTabPayload {
     pub flow_id: String,
     #[serde(rename = "streamID", default)]
     pub stream_id: String,
+    /// The other tabs sent in the same command.
+    #[serde(
+        rename = "additionalTabs",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub additional_tabs: Vec<SendTabPayload>,
 }
 
 impl From<SendTabPayload> for crate::SendTabPayload {
@@ -57,28 +71,90 @@ impl From<SendTabPayload> for crate::SendTabPayload {
 }
 
 impl SendTabPayload {
-    pub fn single_tab(title: &str, url: &str) -> (Self, telemetry::SentCommand) {
-        let sent_telemetry: telemetry::SentCommand = Default::default();
-        (
-            SendTabPayload {
-                entries: vec![TabHistoryEntry {
-                    title: title.to_string(),
-                    url: url.to_string(),
-                }],
-                flow_id: sent_telemetry.flow_id.clone(),
-                stream_id: sent_telemetry.stream_id.clone(),
-            },
-            sent_telemetry,
-        )
+    pub fn single_tab(title: &str, url: &str) -> Self {
+        Self::tab(vec![TabHistoryEntry {
+            title: title.to_string(),
+            url: url.to_string(),
+        }])
+    }
+
+    fn tab(entries: Vec<TabHistoryEntry>) -> Self {
+        let sent_telemetry = telemetry::SentCommand::new_flow(telemetry::CommandKind::SendTab);
+        SendTabPayload {
+            entries,
+            flow_id: sent_telemetry.flow_id,
+            stream_id: sent_telemetry.stream_id,
+            additional_tabs: Vec::new(),
+        }
+    }
+
+    /// Pack `tabs`, each the navigation history of a tab, into as few payloads
+    /// as fit under the size limit. If a tab's history doesn't fit in a
+    /// payload by itself, its oldest entries are dropped.
+    pub fn multiple_tabs(tabs: Vec<Vec<TabHistoryEntry>>) -> Result<Vec<Self>> {
+        let mut payloads: Vec<Self> = Vec::new();
+        for entries in tabs {
+            if entries.is_empty() {
+                return Err(
+                    ErrorKind::IllegalState("Cannot send a tab without any entries.").into(),
+                );
+            }
+            let mut tab = Self::tab(entries);
+            tab.trim_history()?;
+            if let Some(payload) = payloads.last_mut() {
+                payload.additional_tabs.push(tab);
+                if payload.size()? <= MAX_PAYLOAD_SIZE {
+                    continue;
+                }
+                // It doesn't fit, so it starts the next payload instead.
+                tab = payload.additional_tabs.pop().unwrap();
+            }
+            payloads.push(tab);
+        }
+        Ok(payloads)
+    }
+
+    fn trim_history(&mut self) -> Result<()> {
+        while self.size()? > MAX_PAYLOAD_SIZE {
+            if self.entries.len() <= 1 {
+                return Err(ErrorKind::TabTooLarge.into());
+            }
+            self.entries.remove(0);
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Result<usize> {
+        Ok(serde_json::to_vec(self)?.len())
+    }
+
+    /// The telemetry of each tab in the payload, to record once it's sent.
+    pub fn sent_telemetry(&self) -> Vec<telemetry::SentCommand> {
+        let mut sent = vec![telemetry::SentCommand {
+            command: telemetry::CommandKind::SendTab,
+            flow_id: self.flow_id.clone(),
+            stream_id: self.stream_id.clone(),
+        }];
+        for tab in &self.additional_tabs {
+            sent.extend(tab.sent_telemetry());
+        }
+        sent
+    }
+
+    /// Split a received payload into its tabs, the first of which is the
+    /// payload itself.
+    pub fn into_tabs(mut self) -> Vec<Self> {
+        let additional_tabs = std::mem::take(&mut self.additional_tabs);
+        let mut tabs = vec![self];
+        for tab in additional_tabs {
+            tabs.extend(tab.into_tabs());
+        }
+        tabs
     }
     fn encrypt(&self, keys: PublicSendTabKeys) -> Result<EncryptedSendTabPayload> {
-        rc_crypto::ensure_initialized();
-        let bytes = serde_json::to_vec(&self)?;
-        let public_key = base64::decode_config(&keys.public_key, base64::URL_SAFE_NO_PAD)?;
-        let auth_secret = base64::decode_config(&keys.auth_secret, base64::URL_SAFE_NO_PAD)?;
-        let encrypted = ece::encrypt(&public_key, &auth_secret, &bytes)?;
-        let encrypted = base64::encode_config(&encrypted, base64::URL_SAFE_NO_PAD);
-        Ok(EncryptedSendTabPayload { encrypted })
+        Ok(EncryptedSendTabPayload {
+            encrypted: keys.encrypt_payload(self)?,
+        })
     }
 }
 
@@ -97,6 +173,15 @@ impl From<TabHistoryEntry> for crate::TabHistoryEntry {
     }
 }
 
+impl From<crate::TabHistoryEntry> for TabHistoryEntry {
+    fn from(e: crate::TabHistoryEntry) -> Self {
+        TabHistoryEntry {
+            title: e.title,
+            url: e.url,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) enum VersionnedPrivateSendTabKeys {
     V1(PrivateSendTabKeysV1),
@@ -137,6 +222,15 @@ impl PrivateSendTabKeys {
             auth_secret: auth_secret.to_vec(),
         })
     }
+
+    /// Decrypt the URL Safe Base 64 encoded payload of a command another
+    /// device encrypted with our public keys.
+    pub(crate) fn decrypt_payload<T: DeserializeOwned>(&self, encrypted: &str) -> Result<T> {
+        rc_crypto::ensure_initialized();
+        let encrypted = base64::decode_config(encrypted, base64::URL_SAFE_NO_PAD)?;
+        let decrypted = ece::decrypt(&self.p256key, &self.auth_secret, &encrypted)?;
+        Ok(serde_json::from_slice(&decrypted)?)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -194,6 +288,16 @@ impl PublicSendTabKeys {
         let encrypted_public_keys = self.encrypt(scoped_key)?;
         Ok(serde_json::to_string(&encrypted_public_keys)?)
     }
+    /// Encrypt the payload of a command for the device these keys belong to,
+    /// as URL Safe Base 64.
+    pub(crate) fn encrypt_payload<T: serde::Serialize>(&self, payload: &T) -> Result<String> {
+        rc_crypto::ensure_initialized();
+        let bytes = serde_json::to_vec(payload)?;
+        let public_key = base64::decode_config(&self.public_key, base64::URL_SAFE_NO_PAD)?;
+        let auth_secret = base64::decode_config(&self.auth_secret, base64::URL_SAFE_NO_PAD)?;
+        let encrypted = ece::encrypt(&public_key, &auth_secret, &bytes)?;
+        Ok(base64::encode_config(&encrypted, base64::URL_SAFE_NO_PAD))
+    }
     pub(crate) fn public_key(&self) -> &str {
         &self.public_key
     }
@@ -219,14 +323,24 @@ pub fn build_send_command(
     target: &Device,
     send_tab_payload: &SendTabPayload,
 ) -> Result<serde_json::Value> {
+    let public_keys = get_public_keys(scoped_key, target, COMMAND_NAME)?;
+    let encrypted_payload = send_tab_payload.encrypt(public_keys)?;
+    Ok(serde_json::to_value(&encrypted_payload)?)
+}
+
+/// Decrypt the public keys `target` registered with `command_name`. Every
+/// command which encrypts its payloads registers the Send Tab keys.
+pub(crate) fn get_public_keys(
+    scoped_key: &ScopedKey,
+    target: &Device,
+    command_name: &'static str,
+) -> Result<PublicSendTabKeys> {
     let command = target
         .available_commands
-        .get(COMMAND_NAME)
-        .ok_or(ErrorKind::UnsupportedCommand(COMMAND_NAME))?;
+        .get(command_name)
+        .ok_or(ErrorKind::UnsupportedCommand(command_name))?;
     let bundle: SendTabKeysPayload = serde_json::from_str(command)?;
-    let public_keys = bundle.decrypt(scoped_key)?;
-    let encrypted_payload = send_tab_payload.encrypt(public_keys)?;
-    Ok(serde_json::to_value(&encrypted_payload)?)
+    bundle.decrypt(scoped_key)
 }
 
 fn extract_oldsync_key_components(oldsync_key: &ScopedKey) -> Result<(Vec<u8>, Vec<u8>)> {
@@ -255,8 +369,9 @@ mod tests {
 
     #[test]
     fn test_payload() {
-        let (payload, telem) = SendTabPayload::single_tab("title", "http://example.com");
+        let payload = SendTabPayload::single_tab("title", "http://example.com");
         let json = serde_json::to_string(&payload).expect("should work");
+        let telem = payload.sent_telemetry().pop().unwrap();
         assert_eq!(telem.flow_id.len(), 12);
         assert_eq!(telem.stream_id.len(), 12);
         assert_ne!(telem.flow_id, telem.stream_id);
@@ -265,5 +380,77 @@ mod tests {
         assert_eq!(payload.entries[0].url, "http://example.com".to_string());
         assert_eq!(payload.flow_id, p2.flow_id);
         assert_eq!(payload.stream_id, p2.stream_id);
+        // A single tab is sent as older devices expect.
+        assert!(!json.contains("additionalTabs"));
+    }
+
+    fn history(count: usize, url_len: usize) -> Vec<TabHistoryEntry> {
+        (0..count)
+            .map(|i| TabHistoryEntry {
+                title: format!("title {}", i),
+                url: format!("https://example.com/{}/{}", i, "x".repeat(url_len)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_multiple_tabs() {
+        let payloads =
+            SendTabPayload::multiple_tabs(vec![history(1, 10), history(3, 10), history(2, 10)])
+                .expect("should work");
+        assert_eq!(payloads.len(), 1);
+        let payload = payloads.into_iter().next().unwrap();
+        let telem = payload.sent_telemetry();
+        assert_eq!(telem.len(), 3);
+
+        let json = serde_json::to_string(&payload).expect("should work");
+        let received: SendTabPayload = serde_json::from_str(&json).expect("should work");
+        let tabs = received.into_tabs();
+        assert_eq!(tabs.len(), 3);
+        for ((tab, telem), len) in tabs.iter().zip(telem.iter()).zip(&[1, 3, 2]) {
+            assert_eq!(tab.entries.len(), *len);
+            assert_eq!(tab.flow_id, telem.flow_id);
+            assert_eq!(tab.stream_id, telem.stream_id);
+            assert!(tab.additional_tabs.is_empty());
+        }
+        assert_ne!(tabs[0].flow_id, tabs[1].flow_id);
+    }
+
+    #[test]
+    fn test_multiple_tabs_are_chunked() {
+        let tabs: Vec<_> = (0..50).map(|_| history(1, 1000)).collect();
+        let payloads = SendTabPayload::multiple_tabs(tabs).expect("should work");
+        assert!(payloads.len() > 1);
+        let mut count = 0;
+        for payload in payloads {
+            assert!(payload.size().unwrap() <= MAX_PAYLOAD_SIZE);
+            let telem = payload.sent_telemetry();
+            let tabs = payload.into_tabs();
+            assert_eq!(tabs.len(), telem.len());
+            count += tabs.len();
+        }
+        assert_eq!(count, 50);
+    }
+
+    #[test]
+    fn test_multiple_tabs_trims_history() {
+        let payloads = SendTabPayload::multiple_tabs(vec![history(50, 1000)]).expect("should work");
+        assert_eq!(payloads.len(), 1);
+        let payload = &payloads[0];
+        assert!(payload.entries.len() < 50);
+        assert!(payload.size().unwrap() <= MAX_PAYLOAD_SIZE);
+        // The page to open is the last entry, which is kept.
+        assert!(payload
+            .entries
+            .last()
+            .unwrap()
+            .url
+            .starts_with("https://example.com/49/"));
+    }
+
+    #[test]
+    fn test_multiple_tabs_too_large() {
+        assert!(SendTabPayload::multiple_tabs(vec![history(1, MAX_PAYLOAD_SIZE)]).is_err());
+        assert!(SendTabPayload::multiple_tabs(vec![vec![]]).is_err());
     }
 }
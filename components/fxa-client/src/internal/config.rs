@@ -116,6 +116,28 @@ impl Config {
         Ok(remote_config)
     }
 
+    /// A `stable_dev` config whose remote config is already known, so that
+    /// tests which need it don't make network requests.
+    #[cfg(test)]
+    pub(crate) fn stable_dev_offline(client_id: &str, redirect_uri: &str) -> Self {
+        let config = Self::stable_dev(client_id, redirect_uri);
+        config.set_remote_config(RemoteConfig {
+            auth_url: "https://stable.dev.lcip.org/auth/".to_string(),
+            oauth_url: "https://oauth-stable.dev.lcip.org/".to_string(),
+            profile_url: "https://stable.dev.lcip.org/profile/".to_string(),
+            token_server_endpoint_url: "https://stable.dev.lcip.org/syncserver/token/1.0/sync/1.5"
+                .to_string(),
+            authorization_endpoint: "https://oauth-stable.dev.lcip.org/v1/authorization"
+                .to_string(),
+            issuer: "https://dev.lcip.org/".to_string(),
+            jwks_uri: "https://oauth-stable.dev.lcip.org/v1/jwks".to_string(),
+            token_endpoint: "https://stable.dev.lcip.org/auth/v1/oauth/token".to_string(),
+            introspection_endpoint: "https://oauth-stable.dev.lcip.org/v1/introspect".to_string(),
+            userinfo_endpoint: "https://stable.dev.lcip.org/profile/v1/profile".to_string(),
+        });
+        config
+    }
+
     fn set_remote_config(&self, remote_config: RemoteConfig) -> Arc<RemoteConfig> {
         let rc = Arc::new(remote_config);
         let result = rc.clone();
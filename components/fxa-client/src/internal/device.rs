@@ -16,11 +16,19 @@ use super::{
     commands::{self, IncomingDeviceCommand},
     error::*,
     http_client::{DeviceUpdateRequest, DeviceUpdateRequestBuilder, PendingCommand},
-    telemetry, util, CachedResponse, FirefoxAccount,
+    telemetry::{self, DeviceRegistrationAction, DeviceRegistrationEvent},
+    util, CachedResponse, FirefoxAccount,
 };
 
-// An devices response is considered fresh for `DEVICES_FRESHNESS_THRESHOLD` ms.
-const DEVICES_FRESHNESS_THRESHOLD: u64 = 60_000; // 1 minute
+// An devices response is considered fresh for `DEVICES_FRESHNESS_THRESHOLD` ms,
+// unless the application sets another TTL with `set_devices_cache_ttl`.
+pub(crate) const DEVICES_FRESHNESS_THRESHOLD: u64 = 60_000; // 1 minute
+
+// A device which was used in the last `DEVICE_ONLINE_THRESHOLD` ms is probably online,
+// and one which wasn't used in the last `DEVICE_INACTIVE_THRESHOLD` ms probably isn't
+// used anymore.
+const DEVICE_ONLINE_THRESHOLD: u64 = 60 * 60 * 1000; // 1 hour
+const DEVICE_INACTIVE_THRESHOLD: u64 = 21 * 24 * 60 * 60 * 1000; // 21 days
 
 /// The reason we are fetching commands.
 #[derive(Clone, Copy)]
@@ -35,11 +43,13 @@ impl FirefoxAccount {
     /// Fetches the list of devices from the current account including
     /// the current one.
     ///
-    /// * `ignore_cache` - If set to true, bypass the in-memory cache
+    /// * `ignore_cache` - If set to true, bypass the cache
     /// and fetch devices from the server.
+    ///
+    /// **💾 This method alters the persisted account state.**
     pub fn get_devices(&mut self, ignore_cache: bool) -> Result<Vec<Device>> {
-        if let Some(d) = &self.devices_cache {
-            if !ignore_cache && util::now() < d.cached_at + DEVICES_FRESHNESS_THRESHOLD {
+        if let Some(d) = &self.state.devices_cache {
+            if !ignore_cache && util::now() < d.cached_at.saturating_add(self.devices_cache_ttl) {
                 return Ok(d.response.clone());
             }
         }
@@ -47,15 +57,32 @@ impl FirefoxAccount {
         let refresh_token = self.get_refresh_token()?;
         let response = self.client.get_devices(&self.state.config, refresh_token)?;
 
-        self.devices_cache = Some(CachedResponse {
+        self.state.devices_cache = Some(CachedResponse {
             response: response.clone(),
             cached_at: util::now(),
             etag: "".into(),
         });
+        // We're online, so try sending any commands we couldn't send earlier.
+        self.flush_outgoing_commands();
 
         Ok(response)
     }
 
+    /// Set how long the list of devices is cached for, in milliseconds.
+    pub fn set_devices_cache_ttl(&mut self, ttl: u64) {
+        self.devices_cache_ttl = ttl;
+    }
+
+    /// Remove a device which disconnected from the cached list of devices,
+    /// so that we don't need to fetch the whole list again.
+    ///
+    /// **💾 This method alters the persisted account state.**
+    pub(crate) fn remove_cached_device(&mut self, device_id: &str) {
+        if let Some(ref mut cache) = self.state.devices_cache {
+            cache.response.retain(|d| d.id != device_id);
+        }
+    }
+
     pub fn get_current_device(&mut self) -> Result<Option<Device>> {
         Ok(self
             .get_devices(false)?
@@ -82,6 +109,16 @@ impl FirefoxAccount {
                     );
                     capabilities_set.insert(Capability::SendTab);
                 }
+                Capability::CloseTabs => {
+                    // Closing tabs uses the same keys as sending them, so we
+                    // register the same command data.
+                    let close_tabs_command = self.generate_send_tab_command_data()?;
+                    commands.insert(
+                        commands::close_tabs::COMMAND_NAME.to_owned(),
+                        close_tabs_command,
+                    );
+                    capabilities_set.insert(Capability::CloseTabs);
+                }
             }
         }
         // Remember what capabilities we've registered, so we don't register the same ones again.
@@ -107,14 +144,14 @@ impl FirefoxAccount {
             .device_type(&device_type)
             .available_commands(&commands)
             .build();
-        self.update_device(update)
+        self.update_device(DeviceRegistrationAction::Register, update)
     }
 
     /// Register a set of device capabilities against the current device.
     ///
-    /// As the only capability is Send Tab now, its command is registered with the server.
+    /// The commands of the capabilities are registered with the server.
     /// Don't forget to also call this if the Sync Keys change as they
-    /// encrypt the Send Tab command data.
+    /// encrypt the Send Tab and Close Tabs command data.
     ///
     /// **💾 This method alters the persisted account state.**
     pub fn ensure_capabilities(&mut self, capabilities: &[Capability]) -> Result<()> {
@@ -135,7 +172,7 @@ impl FirefoxAccount {
         let update = DeviceUpdateRequestBuilder::new()
             .available_commands(&commands)
             .build();
-        self.update_device(update)
+        self.update_device(DeviceRegistrationAction::UpdateCapabilities, update)
     }
 
     /// Re-register the device capabilities, this should only be used internally.
@@ -146,7 +183,7 @@ impl FirefoxAccount {
         let update = DeviceUpdateRequestBuilder::new()
             .available_commands(&commands)
             .build();
-        self.update_device(update)?;
+        self.update_device(DeviceRegistrationAction::UpdateCapabilities, update)?;
         Ok(())
     }
 
@@ -183,6 +220,8 @@ impl FirefoxAccount {
     }
 
     /// Retrieve and parse a specific command designated by its index.
+    /// A single command can hold several tabs, so this can return several
+    /// `IncomingDeviceCommand`s.
     ///
     /// **💾 This method alters the persisted account state.**
     ///
@@ -193,16 +232,13 @@ impl FirefoxAccount {
     /// (What's wrong: if we get a push for tab-1 and a push for tab-3, and
     /// between them I've never explicitly polled, I'll miss tab-2, even if I
     /// try polling now)
-    pub fn ios_fetch_device_command(&mut self, index: u64) -> Result<IncomingDeviceCommand> {
-        let mut device_commands =
+    pub fn ios_fetch_device_command(&mut self, index: u64) -> Result<Vec<IncomingDeviceCommand>> {
+        let device_commands =
             self.fetch_and_parse_commands(index, Some(1), CommandFetchReason::Push(index))?;
-        let device_command = device_commands
-            .pop()
-            .ok_or(ErrorKind::IllegalState("Index fetch came out empty."))?;
-        if !device_commands.is_empty() {
-            log::warn!("Index fetch resulted in more than 1 element");
+        if device_commands.is_empty() {
+            return Err(ErrorKind::IllegalState("Index fetch came out empty.").into());
         }
-        Ok(device_command)
+        Ok(device_commands)
     }
 
     fn fetch_and_parse_commands(
@@ -215,6 +251,7 @@ impl FirefoxAccount {
         let pending_commands =
             self.client
                 .get_pending_commands(&self.state.config, refresh_token, index, limit)?;
+        self.flush_outgoing_commands();
         if pending_commands.messages.is_empty() {
             return Ok(Vec::new());
         }
@@ -232,11 +269,11 @@ impl FirefoxAccount {
         let devices = self.get_devices(false)?;
         let parsed_commands = messages
             .into_iter()
-            .filter_map(|msg| match self.parse_command(msg, &devices, reason) {
-                Ok(device_command) => Some(device_command),
+            .flat_map(|msg| match self.parse_command(msg, &devices, reason) {
+                Ok(device_commands) => device_commands,
                 Err(e) => {
                     log::error!("Error while processing command: {}", e);
-                    None
+                    Vec::new()
                 }
             })
             .collect();
@@ -248,7 +285,7 @@ impl FirefoxAccount {
         command: PendingCommand,
         devices: &[Device],
         reason: CommandFetchReason,
-    ) -> Result<IncomingDeviceCommand> {
+    ) -> Result<Vec<IncomingDeviceCommand>> {
         let telem_reason = match reason {
             CommandFetchReason::Poll => telemetry::ReceivedReason::Poll,
             CommandFetchReason::Push(index) if command.index < index => {
@@ -264,27 +301,32 @@ impl FirefoxAccount {
             commands::send_tab::COMMAND_NAME => {
                 self.handle_send_tab_command(sender, command_data.payload, telem_reason)
             }
+            commands::close_tabs::COMMAND_NAME => Ok(vec![self.handle_close_tabs_command(
+                sender,
+                command_data.payload,
+                telem_reason,
+            )?]),
             _ => Err(ErrorKind::UnknownCommand(command_data.command).into()),
         }
     }
 
     pub fn set_device_name(&mut self, name: &str) -> Result<()> {
         let update = DeviceUpdateRequestBuilder::new().display_name(name).build();
-        self.update_device(update)
+        self.update_device(DeviceRegistrationAction::UpdateName, update)
     }
 
     pub fn clear_device_name(&mut self) -> Result<()> {
         let update = DeviceUpdateRequestBuilder::new()
             .clear_display_name()
             .build();
-        self.update_device(update)
+        self.update_device(DeviceRegistrationAction::UpdateName, update)
     }
 
     pub fn set_push_subscription(&mut self, push_subscription: PushSubscription) -> Result<()> {
         let update = DeviceUpdateRequestBuilder::new()
             .push_subscription(&push_subscription)
             .build();
-        self.update_device(update)
+        self.update_device(DeviceRegistrationAction::UpdatePushSubscription, update)
     }
 
     // TODO: this currently overwrites every other registered command
@@ -298,7 +340,7 @@ impl FirefoxAccount {
         let update = DeviceUpdateRequestBuilder::new()
             .available_commands(&commands)
             .build();
-        self.update_device(update)
+        self.update_device(DeviceRegistrationAction::UpdateCapabilities, update)
     }
 
     // TODO: this currently deletes every command registered for the device
@@ -310,7 +352,7 @@ impl FirefoxAccount {
         let update = DeviceUpdateRequestBuilder::new()
             .available_commands(&commands)
             .build();
-        self.update_device(update)
+        self.update_device(DeviceRegistrationAction::UpdateCapabilities, update)
     }
 
     #[allow(dead_code)]
@@ -319,7 +361,7 @@ impl FirefoxAccount {
         let update = DeviceUpdateRequestBuilder::new()
             .clear_available_commands()
             .build();
-        self.update_device(update)
+        self.update_device(DeviceRegistrationAction::UpdateCapabilities, update)
     }
 
     pub(crate) fn replace_device(
@@ -337,17 +379,29 @@ impl FirefoxAccount {
         if let Some(push_subscription) = push_subscription {
             builder = builder.push_subscription(push_subscription)
         }
-        self.update_device(builder.build())
+        self.update_device(DeviceRegistrationAction::Restore, builder.build())
     }
 
-    fn update_device(&mut self, update: DeviceUpdateRequest<'_>) -> Result<()> {
+    fn update_device(
+        &mut self,
+        action: DeviceRegistrationAction,
+        update: DeviceUpdateRequest<'_>,
+    ) -> Result<()> {
         let refresh_token = self.get_refresh_token()?;
         let res = self
             .client
             .update_device_record(&self.state.config, refresh_token, update);
+        self.telemetry
+            .borrow_mut()
+            .record_device_registration(DeviceRegistrationEvent {
+                action,
+                failure_reason: res.as_ref().err().map(Into::into),
+            });
         match res {
             Ok(resp) => {
                 self.state.current_device_id = Option::from(resp.id);
+                // Our own record in the cached list of devices is out of date.
+                self.state.devices_cache = None;
                 Ok(())
             }
             Err(err) => {
@@ -371,12 +425,14 @@ impl FirefoxAccount {
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Capability {
     SendTab,
+    CloseTabs,
 }
 
 impl From<crate::DeviceCapability> for Capability {
     fn from(cap: crate::DeviceCapability) -> Self {
         match cap {
             crate::DeviceCapability::SendTab => Capability::SendTab,
+            crate::DeviceCapability::CloseTabs => Capability::CloseTabs,
         }
     }
 }
@@ -385,6 +441,7 @@ impl From<Capability> for crate::DeviceCapability {
     fn from(cap: Capability) -> Self {
         match cap {
             Capability::SendTab => crate::DeviceCapability::SendTab,
+            Capability::CloseTabs => crate::DeviceCapability::CloseTabs,
         }
     }
 }
@@ -397,6 +454,7 @@ impl TryFrom<Device> for crate::Device {
             .keys()
             .filter_map(|k| match k.as_str() {
                 commands::send_tab::COMMAND_NAME => Some(Capability::SendTab),
+                commands::close_tabs::COMMAND_NAME => Some(Capability::CloseTabs),
                 _ => None,
             })
             .map(Into::into)
@@ -409,11 +467,24 @@ impl TryFrom<Device> for crate::Device {
             push_subscription: d.common.push_subscription.map(Into::into),
             push_endpoint_expired: d.common.push_endpoint_expired,
             is_current_device: d.is_current_device,
+            activity: device_activity(d.last_access_time, util::now()),
             last_access_time: d.last_access_time.map(TryFrom::try_from).transpose()?,
         })
     }
 }
 
+fn device_activity(last_access_time: Option<u64>, now: u64) -> crate::DeviceActivity {
+    match last_access_time {
+        // The clocks of the server and the device may disagree, hence `saturating_sub`.
+        Some(t) if now.saturating_sub(t) < DEVICE_ONLINE_THRESHOLD => crate::DeviceActivity::Online,
+        Some(t) if now.saturating_sub(t) < DEVICE_INACTIVE_THRESHOLD => {
+            crate::DeviceActivity::Recent
+        }
+        Some(_) => crate::DeviceActivity::Inactive,
+        None => crate::DeviceActivity::Unknown,
+    }
+}
+
 impl From<Type> for crate::DeviceType {
     fn from(type_: Type) -> Self {
         match type_ {
@@ -709,6 +780,7 @@ mod tests {
                 expires_in: 12345,
                 scope: "profile".to_string(),
                 access_token: "accesstok".to_string(),
+                id_token: None,
             },
             None,
         )
@@ -780,6 +852,82 @@ mod tests {
         fxa.set_client(Arc::new(client));
 
         fxa.ensure_capabilities(&[Capability::SendTab]).unwrap();
+
+        assert_eq!(
+            fxa.gather_telemetry().device_registrations,
+            vec![
+                DeviceRegistrationEvent {
+                    action: DeviceRegistrationAction::UpdateCapabilities,
+                    failure_reason: Some(crate::FailureReason::Server),
+                },
+                DeviceRegistrationEvent {
+                    action: DeviceRegistrationAction::UpdateCapabilities,
+                    failure_reason: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_close_tabs_registers_the_send_tab_keys() {
+        let mut fxa = setup();
+        let registered = fxa
+            .register_capabilities(&[Capability::SendTab, Capability::CloseTabs])
+            .unwrap();
+        assert_eq!(registered.len(), 2);
+        assert!(fxa.state.device_capabilities.contains(&Capability::SendTab));
+        assert!(fxa
+            .state
+            .device_capabilities
+            .contains(&Capability::CloseTabs));
+
+        let device = Device {
+            common: DeviceResponseCommon {
+                id: "device1".into(),
+                display_name: "".to_string(),
+                device_type: DeviceType::Desktop,
+                push_subscription: None,
+                available_commands: registered,
+                push_endpoint_expired: false,
+            },
+            is_current_device: true,
+            location: DeviceLocation {
+                city: None,
+                country: None,
+                state: None,
+                state_code: None,
+            },
+            last_access_time: None,
+        };
+        let oldsync_key = fxa
+            .get_scoped_key(crate::internal::scopes::OLD_SYNC)
+            .unwrap();
+        let send_tab_keys = commands::send_tab::get_public_keys(
+            oldsync_key,
+            &device,
+            commands::send_tab::COMMAND_NAME,
+        )
+        .unwrap();
+        let close_tabs_keys = commands::send_tab::get_public_keys(
+            oldsync_key,
+            &device,
+            commands::close_tabs::COMMAND_NAME,
+        )
+        .unwrap();
+        assert_eq!(send_tab_keys.public_key(), close_tabs_keys.public_key());
+        assert_eq!(send_tab_keys.auth_secret(), close_tabs_keys.auth_secret());
+
+        let device = crate::Device::try_from(device).unwrap();
+        assert!(matches!(
+            device.capabilities.as_slice(),
+            [
+                crate::DeviceCapability::SendTab,
+                crate::DeviceCapability::CloseTabs
+            ] | [
+                crate::DeviceCapability::CloseTabs,
+                crate::DeviceCapability::SendTab
+            ]
+        ));
     }
 
     #[test]
@@ -809,12 +957,12 @@ mod tests {
             }]));
 
         fxa.set_client(Arc::new(client));
-        assert!(fxa.devices_cache.is_none());
+        assert!(fxa.state.devices_cache.is_none());
 
         assert!(fxa.get_devices(false).is_ok());
-        assert!(fxa.devices_cache.is_some());
+        assert!(fxa.state.devices_cache.is_some());
 
-        let cache = fxa.devices_cache.clone().unwrap();
+        let cache = fxa.state.devices_cache.clone().unwrap();
         assert!(!cache.response.is_empty());
         assert!(cache.cached_at > 0);
 
@@ -823,9 +971,9 @@ mod tests {
 
         // Check that a second call to get_devices doesn't hit the server
         assert!(fxa.get_devices(false).is_ok());
-        assert!(fxa.devices_cache.is_some());
+        assert!(fxa.state.devices_cache.is_some());
 
-        let cache2 = fxa.devices_cache.unwrap();
+        let cache2 = fxa.state.devices_cache.unwrap();
         let cached_devices2 = cache2.response;
 
         assert_eq!(cache.cached_at, cache2.cached_at);
@@ -850,11 +998,126 @@ mod tests {
             .into()));
 
         fxa.set_client(Arc::new(client));
-        assert!(fxa.devices_cache.is_none());
+        assert!(fxa.state.devices_cache.is_none());
 
         let res = fxa.get_devices(false);
 
         assert!(res.is_err());
-        assert!(fxa.devices_cache.is_none());
+        assert!(fxa.state.devices_cache.is_none());
+    }
+
+    fn remote_device(id: &str, last_access_time: Option<u64>) -> Device {
+        Device {
+            common: DeviceResponseCommon {
+                id: id.into(),
+                display_name: "".to_string(),
+                device_type: DeviceType::Mobile,
+                push_subscription: None,
+                available_commands: HashMap::new(),
+                push_endpoint_expired: false,
+            },
+            is_current_device: false,
+            location: DeviceLocation {
+                city: None,
+                country: None,
+                state: None,
+                state_code: None,
+            },
+            last_access_time,
+        }
+    }
+
+    #[test]
+    fn test_devices_cache_is_persisted() {
+        let mut fxa = setup();
+        let mut client = FxAClientMock::new();
+        client
+            .expect_get_devices(mockiato::Argument::any, mockiato::Argument::any)
+            .times(1)
+            .returns_once(Ok(vec![remote_device("device1", None)]));
+        fxa.set_client(Arc::new(client));
+        fxa.get_devices(false).unwrap();
+
+        // The restored account doesn't have a mocked client, so it has to use the cache.
+        let mut fxa = FirefoxAccount::from_json(&fxa.to_json().unwrap()).unwrap();
+        let devices = fxa.get_devices(false).unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].id, "device1");
+    }
+
+    #[test]
+    fn test_devices_cache_ttl() {
+        let mut fxa = setup();
+        let mut client = FxAClientMock::new();
+        client
+            .expect_get_devices(mockiato::Argument::any, mockiato::Argument::any)
+            .times(1)
+            .returns_once(Ok(vec![remote_device("device1", None)]));
+        fxa.set_client(Arc::new(client));
+        fxa.get_devices(false).unwrap();
+
+        fxa.set_devices_cache_ttl(0);
+        let mut client = FxAClientMock::new();
+        client
+            .expect_get_devices(mockiato::Argument::any, mockiato::Argument::any)
+            .times(1)
+            .returns_once(Ok(vec![
+                remote_device("device1", None),
+                remote_device("device2", None),
+            ]));
+        fxa.set_client(Arc::new(client));
+        assert_eq!(fxa.get_devices(false).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_updating_the_device_invalidates_the_devices_cache() {
+        let mut fxa = setup();
+        fxa.state.devices_cache = Some(CachedResponse {
+            response: vec![remote_device("device1", None)],
+            cached_at: util::now(),
+            etag: "".into(),
+        });
+        let mut client = FxAClientMock::new();
+        client
+            .expect_update_device_record(
+                mockiato::Argument::any,
+                mockiato::Argument::any,
+                mockiato::Argument::any,
+            )
+            .times(1)
+            .returns_once(Ok(UpdateDeviceResponse {
+                id: "device1".to_string(),
+                display_name: "new name".to_string(),
+                device_type: DeviceType::Mobile,
+                push_subscription: None,
+                available_commands: HashMap::new(),
+                push_endpoint_expired: false,
+            }));
+        fxa.set_client(Arc::new(client));
+        fxa.set_device_name("new name").unwrap();
+        assert!(fxa.state.devices_cache.is_none());
+    }
+
+    #[test]
+    fn test_device_activity() {
+        let now = 1_600_000_000_000;
+        assert_eq!(device_activity(None, now), crate::DeviceActivity::Unknown);
+        assert_eq!(
+            device_activity(Some(now - 60_000), now),
+            crate::DeviceActivity::Online
+        );
+        // The server's clock may be ahead of ours.
+        assert_eq!(
+            device_activity(Some(now + 60_000), now),
+            crate::DeviceActivity::Online
+        );
+        assert_eq!(
+            device_activity(Some(now - 2 * 24 * 60 * 60 * 1000), now),
+            crate::DeviceActivity::Recent
+        );
+        assert_eq!(
+            device_activity(Some(now - 30 * 24 * 60 * 60 * 1000), now),
+            crate::DeviceActivity::Inactive
+        );
     }
 }
@@ -48,6 +48,18 @@ pub enum ErrorKind {
     #[error("Send Tab diagnosis error: {0}")]
     SendTabDiagnosisError(&'static str),
 
+    #[error("Tab is too large to send")]
+    TabTooLarge,
+
+    #[error("Invalid ID token: {0}")]
+    InvalidIdToken(&'static str),
+
+    #[error("No pairing channel is open")]
+    NoPairingChannel,
+
+    #[error("Pairing channel error: {0}")]
+    PairingChannelError(&'static str),
+
     #[error("Cannot xor arrays with different lengths: {0} and {1}")]
     XorLengthMismatch(usize, usize),
 
@@ -137,6 +137,7 @@ pub(crate) trait FxAClient {
     ) -> Result<HashMap<String, ScopedKeyDataResponse>>;
     fn get_fxa_client_configuration(&self, config: &Config) -> Result<ClientConfigurationResponse>;
     fn get_openid_configuration(&self, config: &Config) -> Result<OpenIdConfigurationResponse>;
+    fn get_jwks(&self, config: &Config) -> Result<JwksResponse>;
 }
 
 enum HttpClientState {
@@ -161,6 +162,11 @@ impl FxAClient for Client {
         openid_configuration(config.openid_config_url()?)
     }
 
+    fn get_jwks(&self, config: &Config) -> Result<JwksResponse> {
+        let request = Request::get(config.jwks_uri()?);
+        Ok(self.make_request(request)?.json()?)
+    }
+
     fn get_profile(
         &self,
         config: &Config,
@@ -687,6 +693,26 @@ pub(crate) struct ClientConfigurationResponse {
     pub(crate) sync_tokenserver_base_url: String,
 }
 
+/// The keys the server signs ID tokens with, as a JSON Web Key Set.
+#[derive(Clone, Deserialize)]
+pub struct JwksResponse {
+    pub keys: Vec<JsonWebKey>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct JsonWebKey {
+    pub kty: String,
+    pub kid: Option<String>,
+    pub alg: Option<String>,
+    // EC keys.
+    pub crv: Option<String>,
+    pub x: Option<String>,
+    pub y: Option<String>,
+    // RSA keys.
+    pub n: Option<String>,
+    pub e: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub(crate) struct OpenIdConfigurationResponse {
     pub(crate) authorization_endpoint: String,
@@ -994,6 +1020,9 @@ pub struct OAuthTokenResponse {
     pub expires_in: u64,
     pub scope: String,
     pub access_token: String,
+    // Only returned if the `openid` scope was requested.
+    #[serde(default)]
+    pub id_token: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
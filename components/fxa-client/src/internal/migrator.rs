@@ -52,6 +52,7 @@ impl FirefoxAccount {
             copy_session_token,
             session_token: session_token.to_string(),
         });
+        self.update_account_state();
 
         self.try_migration()
     }
@@ -91,6 +92,7 @@ impl FirefoxAccount {
                         // probably will not recover
 
                         self.state.in_flight_migration = None;
+                        self.update_account_state();
 
                         return Err(err);
                     }
@@ -99,6 +101,7 @@ impl FirefoxAccount {
         }
 
         self.state.in_flight_migration = None;
+        self.update_account_state();
 
         let metrics = FxAMigrationResult {
             // The foreign-language bindings are limited to an i64.
@@ -165,9 +168,11 @@ impl FirefoxAccount {
         // We do this all at one at the end to avoid leaving partial state.
         self.state.session_token = Some(migration_session_token);
         self.handle_oauth_response(oauth_response, None)?;
-        self.state
-            .scoped_keys
-            .insert(scopes::OLD_SYNC.to_string(), k_sync_scoped_key);
+        self.store_scoped_key(scopes::OLD_SYNC.to_string(), k_sync_scoped_key);
+        self.state.scoped_key_rotation_timestamps.insert(
+            scopes::OLD_SYNC.to_string(),
+            oldsync_key_data.key_rotation_timestamp,
+        );
 
         Ok(())
     }
@@ -264,6 +269,7 @@ mod tests {
                 expires_in: 12345,
                 scope: "profile oldsync".to_string(),
                 access_token: "access".to_string(),
+                id_token: None,
             }));
         client
             .expect_destroy_access_token(mockiato::Argument::any, |arg| arg.partial_eq("access"))
@@ -6,13 +6,15 @@
 //!
 
 // Currently public for use by example crates, but should be made private eventually.
-pub use self::{commands::IncomingDeviceCommand, config::Config};
 use self::{
+    account_state::{AccountState, AccountStateObserver},
     error::*,
     oauth::{AuthCircuitBreaker, OAuthFlow, OAUTH_WEBCHANNEL_REDIRECT},
+    pairing::PairingChannel,
     state_persistence::State,
-    telemetry::FxaTelemetry,
+    telemetry::{AccountTelemetry, DeviceRegistrationAction, DeviceRegistrationEvent},
 };
+pub use self::{commands::IncomingDeviceCommand, config::Config};
 use serde_derive::*;
 use std::{
     cell::RefCell,
@@ -21,15 +23,20 @@ use std::{
 };
 use url::Url;
 
+mod account_state;
 #[cfg(feature = "integration_test")]
 pub mod auth;
+mod close_tabs;
 mod commands;
 pub mod config;
 pub mod device;
 pub mod error;
 mod http_client;
+mod id_token;
 mod migrator;
 mod oauth;
+mod outbox;
+mod pairing;
 mod profile;
 mod push;
 mod scoped_keys;
@@ -55,24 +62,38 @@ pub struct FirefoxAccount {
     state: State,
     flow_store: HashMap<String, OAuthFlow>,
     attached_clients_cache: Option<CachedResponse<Vec<http_client::GetAttachedClientResponse>>>,
-    devices_cache: Option<CachedResponse<Vec<http_client::GetDeviceResponse>>>,
+    // How long `state.devices_cache` is fresh for, in milliseconds.
+    devices_cache_ttl: u64,
     auth_circuit_breaker: AuthCircuitBreaker,
+    // The keys the server signs ID tokens with, which aren't specific to the account.
+    jwks_cache: Option<Vec<http_client::JsonWebKey>>,
     // 'telemetry' is only currently used by `&mut self` functions, but that's
     // not something we want to insist on going forward, so RefCell<> it.
-    telemetry: RefCell<FxaTelemetry>,
+    telemetry: RefCell<AccountTelemetry>,
+    // The state the observer was last told about, see `update_account_state`.
+    account_state: AccountState,
+    state_observer: Option<Box<dyn AccountStateObserver>>,
+    // The pairing in progress, if we're signing in another device.
+    pairing_channel: Option<PairingChannel>,
 }
 
 impl FirefoxAccount {
     fn from_state(state: State) -> Self {
-        Self {
+        let mut fxa = Self {
             client: Arc::new(http_client::Client::new()),
             state,
             flow_store: HashMap::new(),
             attached_clients_cache: None,
-            devices_cache: None,
+            devices_cache_ttl: device::DEVICES_FRESHNESS_THRESHOLD,
             auth_circuit_breaker: Default::default(),
-            telemetry: RefCell::new(FxaTelemetry::new()),
-        }
+            jwks_cache: None,
+            telemetry: RefCell::new(AccountTelemetry::default()),
+            account_state: AccountState::Disconnected,
+            state_observer: None,
+            pairing_channel: None,
+        };
+        fxa.account_state = fxa.get_state();
+        fxa
     }
 
     /// Create a new `FirefoxAccount` instance using a `Config`.
@@ -91,6 +112,11 @@ impl FirefoxAccount {
             last_seen_profile: None,
             access_token_cache: HashMap::new(),
             in_flight_migration: None,
+            outgoing_commands: Vec::new(),
+            auth_problem: false,
+            scoped_key_changes: Vec::new(),
+            scoped_key_rotation_timestamps: HashMap::new(),
+            devices_cache: None,
         })
     }
 
@@ -137,7 +163,7 @@ impl FirefoxAccount {
     /// Clear the attached clients and devices cache
     pub fn clear_devices_and_attached_clients_cache(&mut self) {
         self.attached_clients_cache = None;
-        self.devices_cache = None;
+        self.state.devices_cache = None;
     }
 
     /// Clear the whole persisted/cached state of the account, but keep just
@@ -145,8 +171,10 @@ impl FirefoxAccount {
     pub fn start_over(&mut self) {
         self.state = self.state.start_over();
         self.flow_store.clear();
+        self.pairing_channel = None;
         self.clear_devices_and_attached_clients_cache();
-        self.telemetry.replace(FxaTelemetry::new());
+        self.telemetry.replace(AccountTelemetry::default());
+        self.update_account_state();
     }
 
     /// Get the Sync Token Server endpoint URL.
@@ -236,17 +264,25 @@ impl FirefoxAccount {
             current_device_result = self.get_current_device();
         }
 
+        let mut device_destroyed = None;
         if let Some(ref refresh_token) = self.state.refresh_token {
             // Delete the current device (which deletes the refresh token), or
             // the refresh token directly if we don't have a device.
             let destroy_result = match current_device_result {
                 // If we get an error trying to fetch our device record we'll at least
                 // still try to delete the refresh token itself.
-                Ok(Some(device)) => self.client.destroy_device_record(
-                    &self.state.config,
-                    &refresh_token.token,
-                    &device.id,
-                ),
+                Ok(Some(device)) => {
+                    let result = self.client.destroy_device_record(
+                        &self.state.config,
+                        &refresh_token.token,
+                        &device.id,
+                    );
+                    device_destroyed = Some(DeviceRegistrationEvent {
+                        action: DeviceRegistrationAction::Destroy,
+                        failure_reason: result.as_ref().err().map(Into::into),
+                    });
+                    result
+                }
                 _ => self
                     .client
                     .destroy_refresh_token(&self.state.config, &refresh_token.token),
@@ -256,6 +292,12 @@ impl FirefoxAccount {
             }
         }
         self.start_over();
+        // Starting over clears the telemetry, but the app may still want to know about this.
+        if let Some(event) = device_destroyed {
+            self.telemetry
+                .borrow_mut()
+                .record_device_registration(event);
+        }
     }
 }
 
@@ -447,6 +489,13 @@ mod tests {
         assert!(fxa.state.refresh_token.is_some());
         fxa.disconnect();
         assert!(fxa.state.refresh_token.is_none());
+        assert_eq!(
+            fxa.gather_telemetry().device_registrations,
+            vec![DeviceRegistrationEvent {
+                action: DeviceRegistrationAction::Destroy,
+                failure_reason: None,
+            }]
+        );
     }
 
     #[test]
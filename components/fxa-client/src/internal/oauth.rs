@@ -4,11 +4,16 @@
 
 pub mod attached_clients;
 use super::{
+    account_state::is_auth_error,
     error::*,
     http_client::{
-        AuthorizationRequestParameters, IntrospectResponse as IntrospectInfo, OAuthTokenResponse,
+        AuthorizationRequestParameters, IntrospectResponse as IntrospectInfo, OAuthAuthResponse,
+        OAuthTokenResponse,
     },
+    id_token::IdTokenClaims,
     scoped_keys::{ScopedKey, ScopedKeysFlow},
+    scopes,
+    telemetry::{OAuthFlowEvent, OAuthFlowKind, TokenRefreshFailure},
     util, FirefoxAccount,
 };
 pub use crate::{AuthorizationParameters, MetricsParams};
@@ -52,25 +57,49 @@ impl FirefoxAccount {
         let resp = match self.state.refresh_token {
             Some(ref refresh_token) => {
                 if refresh_token.scopes.contains(scope) {
-                    self.client.create_access_token_using_refresh_token(
+                    match self.client.create_access_token_using_refresh_token(
                         &self.state.config,
                         &refresh_token.token,
                         ttl,
                         &[scope],
-                    )?
+                    ) {
+                        Ok(resp) => {
+                            self.set_auth_problem(false);
+                            resp
+                        }
+                        Err(err) => {
+                            if is_auth_error(&err) {
+                                self.set_auth_problem(true);
+                            }
+                            self.record_token_refresh_failure(scope, &err);
+                            return Err(err);
+                        }
+                    }
                 } else {
                     return Err(ErrorKind::NoCachedToken(scope.to_string()).into());
                 }
             }
             None => match self.state.session_token {
-                Some(ref session_token) => self.client.create_access_token_using_session_token(
-                    &self.state.config,
-                    session_token,
-                    &[scope],
-                )?,
+                Some(ref session_token) => {
+                    match self.client.create_access_token_using_session_token(
+                        &self.state.config,
+                        session_token,
+                        &[scope],
+                    ) {
+                        Ok(resp) => resp,
+                        Err(err) => {
+                            self.record_token_refresh_failure(scope, &err);
+                            return Err(err);
+                        }
+                    }
+                }
                 None => return Err(ErrorKind::NoCachedToken(scope.to_string()).into()),
             },
         };
+        // Make sure the key we hand out along with the token is still the current one.
+        if let Err(err) = self.check_scoped_key_rotation(scope) {
+            log::warn!("Could not check whether the key was rotated: {:?}", err);
+        }
         let since_epoch = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .map_err(|_| ErrorKind::IllegalState("Current date before Unix Epoch."))?;
@@ -84,9 +113,19 @@ impl FirefoxAccount {
         self.state
             .access_token_cache
             .insert(scope.to_string(), token_info.clone());
+        self.flush_outgoing_commands();
         Ok(token_info)
     }
 
+    fn record_token_refresh_failure(&self, scope: &str, err: &Error) {
+        self.telemetry
+            .borrow_mut()
+            .record_token_refresh_failure(TokenRefreshFailure {
+                scope: scope.to_string(),
+                reason: err.into(),
+            });
+    }
+
     /// Retrieve the current session token from state
     pub fn get_session_token(&self) -> Result<String> {
         match self.state.session_token {
@@ -96,15 +135,23 @@ impl FirefoxAccount {
     }
 
     /// Check whether user is authorized using our refresh token.
+    ///
+    /// **💾 This method may alter the persisted account state.**
     pub fn check_authorization_status(&mut self) -> Result<IntrospectInfo> {
         let resp = match self.state.refresh_token {
             Some(ref refresh_token) => {
                 self.auth_circuit_breaker.check()?;
                 self.client
-                    .check_refresh_token_status(&self.state.config, &refresh_token.token)?
+                    .check_refresh_token_status(&self.state.config, &refresh_token.token)
             }
             None => return Err(ErrorKind::NoRefreshToken.into()),
         };
+        match resp {
+            Ok(ref resp) => self.set_auth_problem(!resp.active),
+            Err(ref err) if is_auth_error(err) => self.set_auth_problem(true),
+            Err(_) => {}
+        }
+        let resp = resp?;
         Ok(IntrospectInfo {
             active: resp.active,
         })
@@ -134,7 +181,7 @@ impl FirefoxAccount {
             return Err(ErrorKind::OriginMismatch.into());
         }
         url.set_fragment(pairing_url.fragment());
-        self.oauth_flow(url, scopes)
+        self.oauth_flow(url, scopes, OAuthFlowKind::Pairing, entrypoint)
     }
 
     /// Initiate an OAuth login flow and return a URL that should be navigated to.
@@ -181,7 +228,7 @@ impl FirefoxAccount {
             None => scopes.iter().map(ToString::to_string).collect(),
         };
         let scopes: Vec<&str> = scopes.iter().map(<_>::as_ref).collect();
-        self.oauth_flow(url, &scopes)
+        self.oauth_flow(url, &scopes, OAuthFlowKind::SignIn, entrypoint)
     }
 
     /// Fetch an OAuth code for a particular client using a session token from the account state.
@@ -197,6 +244,15 @@ impl FirefoxAccount {
         &self,
         auth_params: AuthorizationParameters,
     ) -> Result<String> {
+        Ok(self.create_authorization_code(auth_params)?.code)
+    }
+
+    /// Like `authorize_code_using_session_token`, but returns the whole response,
+    /// which also has the URL the other client should be redirected to.
+    pub(crate) fn create_authorization_code(
+        &self,
+        auth_params: AuthorizationParameters,
+    ) -> Result<OAuthAuthResponse> {
         let session_token = self.get_session_token()?;
 
         // Validate request to ensure that the client is actually allowed to request
@@ -257,16 +313,20 @@ impl FirefoxAccount {
             keys_jwe,
         };
 
-        let resp = self.client.create_authorization_code_using_session_token(
+        self.client.create_authorization_code_using_session_token(
             &self.state.config,
             &session_token,
             auth_request_params,
-        )?;
-
-        Ok(resp.code)
+        )
     }
 
-    fn oauth_flow(&mut self, mut url: Url, scopes: &[&str]) -> Result<String> {
+    fn oauth_flow(
+        &mut self,
+        mut url: Url,
+        scopes: &[&str],
+        kind: OAuthFlowKind,
+        entrypoint: &str,
+    ) -> Result<String> {
         self.clear_access_token_cache();
         let state = util::random_base64_url_string(16)?;
         let code_verifier = util::random_base64_url_string(43)?;
@@ -276,6 +336,13 @@ impl FirefoxAccount {
         let jwk = scoped_keys_flow.get_public_key_jwk()?;
         let jwk_json = serde_json::to_string(&jwk)?;
         let keys_jwk = base64::encode_config(&jwk_json, base64::URL_SAFE_NO_PAD);
+        // The server only issues an ID token if we ask for the `openid` scope, and
+        // we'll need the nonce to check that the token it issues is for this flow.
+        let nonce = if scopes.contains(&scopes::OPENID) {
+            Some(util::random_base64_url_string(16)?)
+        } else {
+            None
+        };
         url.query_pairs_mut()
             .append_pair("client_id", &self.state.config.client_id)
             .append_pair("scope", &scopes.join(" "))
@@ -285,6 +352,10 @@ impl FirefoxAccount {
             .append_pair("access_type", "offline")
             .append_pair("keys_jwk", &keys_jwk);
 
+        if let Some(ref nonce) = nonce {
+            url.query_pairs_mut().append_pair("nonce", nonce);
+        }
+
         if self.state.config.redirect_uri == OAUTH_WEBCHANNEL_REDIRECT {
             url.query_pairs_mut()
                 .append_pair("context", "oauth_webchannel_v1");
@@ -298,8 +369,12 @@ impl FirefoxAccount {
             OAuthFlow {
                 scoped_keys_flow: Some(scoped_keys_flow),
                 code_verifier,
+                nonce,
+                kind,
+                entrypoint: entrypoint.to_string(),
             },
         );
+        self.update_account_state();
         Ok(url.to_string())
     }
 
@@ -307,19 +382,54 @@ impl FirefoxAccount {
     /// The `code` and `state` parameters can be obtained by parsing out the
     /// redirect URL after a successful login.
     ///
+    /// If the flow requested the `openid` scope, the ID token issued by the server is
+    /// verified and its claims are returned.
+    ///
     /// **💾 This method alters the persisted account state.**
-    pub fn complete_oauth_flow(&mut self, code: &str, state: &str) -> Result<()> {
+    pub fn complete_oauth_flow(
+        &mut self,
+        code: &str,
+        state: &str,
+    ) -> Result<Option<IdTokenClaims>> {
         self.clear_access_token_cache();
         let oauth_flow = match self.flow_store.remove(state) {
             Some(oauth_flow) => oauth_flow,
             None => return Err(ErrorKind::UnknownOAuthState.into()),
         };
-        let resp = self.client.create_refresh_token_using_authorization_code(
-            &self.state.config,
-            code,
-            &oauth_flow.code_verifier,
-        )?;
-        self.handle_oauth_response(resp, oauth_flow.scoped_keys_flow)
+        let kind = oauth_flow.kind;
+        let entrypoint = oauth_flow.entrypoint.clone();
+        let result = self
+            .client
+            .create_refresh_token_using_authorization_code(
+                &self.state.config,
+                code,
+                &oauth_flow.code_verifier,
+            )
+            .and_then(|resp| {
+                // Check the ID token before we accept any of the other tokens.
+                let claims = match oauth_flow.nonce {
+                    Some(ref nonce) => {
+                        let id_token = resp
+                            .id_token
+                            .as_ref()
+                            .ok_or(ErrorKind::InvalidIdToken("Missing ID token"))?;
+                        Some(self.verify_id_token(id_token, nonce)?)
+                    }
+                    None => None,
+                };
+                self.handle_oauth_response(resp, oauth_flow.scoped_keys_flow)?;
+                Ok(claims)
+            });
+        // Whether it succeeded or not, this flow is over.
+        self.telemetry
+            .borrow_mut()
+            .record_oauth_flow(OAuthFlowEvent {
+                kind,
+                entrypoint,
+                failure_reason: result.as_ref().err().map(Into::into),
+            });
+        self.update_account_state();
+        result
     }
 
     pub(crate) fn handle_oauth_response(
@@ -336,7 +446,7 @@ impl FirefoxAccount {
                 serde_json::from_str(&decrypted_keys)?;
             for (scope, key) in scoped_keys {
                 let scoped_key: ScopedKey = serde_json::from_value(key)?;
-                self.state.scoped_keys.insert(scope, scoped_key);
+                self.store_scoped_key(scope, scoped_key);
             }
         }
 
@@ -377,6 +487,7 @@ impl FirefoxAccount {
             token: new_refresh_token,
             scopes: resp.scope.split(' ').map(ToString::to_string).collect(),
         });
+        self.state.auth_problem = false;
         // In order to keep 1 and only 1 refresh token alive per client instance,
         // we also destroy the existing refresh token.
         if let Some(ref refresh_token) = old_refresh_token {
@@ -437,6 +548,7 @@ impl FirefoxAccount {
         // When our keys change, we might need to re-register device capabilities with the server.
         // Ensure that this happens on the next call to ensure_capabilities.
         self.state.device_capabilities.clear();
+        self.set_auth_problem(false);
         Ok(())
     }
 
@@ -552,6 +664,11 @@ impl std::fmt::Debug for RefreshToken {
 pub struct OAuthFlow {
     pub scoped_keys_flow: Option<ScopedKeysFlow>,
     pub code_verifier: String,
+    // Only set if the flow requested the `openid` scope.
+    pub nonce: Option<String>,
+    // For telemetry.
+    pub kind: OAuthFlowKind,
+    pub entrypoint: String,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
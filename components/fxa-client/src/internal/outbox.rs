@@ -0,0 +1,475 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The outbox of commands for other devices which we couldn't send because
+//! we were offline.
+//!
+//! The commands are kept in the persisted state, unencrypted, as the keys of
+//! their target device might change before we send them, and we try sending
+//! them again after our next successful request to the server. Commands which
+//! are still in the outbox after `OUTGOING_COMMAND_TTL`, or which fail for
+//! reasons other than being offline `MAX_OUTGOING_COMMAND_ATTEMPTS` times,
+//! are dropped.
+
+use serde_derive::*;
+
+use super::{
+    commands::{
+        close_tabs::{self, CloseTabsPayload},
+        send_tab::{self, SendTabPayload},
+    },
+    error::*,
+    scopes, util, FirefoxAccount,
+};
+
+// How long we keep trying to send a command, in ms (24 hours).
+const OUTGOING_COMMAND_TTL: u64 = 24 * 60 * 60 * 1000;
+// How many times we try sending a command which fails for reasons other than
+// being offline.
+const MAX_OUTGOING_COMMAND_ATTEMPTS: u32 = 5;
+// How many commands we keep, dropping the oldest, so that a device which is
+// offline for a long time doesn't grow its state unboundedly.
+const MAX_OUTGOING_COMMANDS: usize = 50;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum OutgoingCommandPayload {
+    SendTab(SendTabPayload),
+    CloseTabs(CloseTabsPayload),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutgoingCommand {
+    pub target_device_id: String,
+    pub payload: OutgoingCommandPayload,
+    /// When we first tried to send the command, in ms since the Unix epoch.
+    pub created_at: u64,
+    /// How many times we failed to send the command for reasons other than
+    /// being offline.
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+impl OutgoingCommand {
+    /// Convert the command to the public representation, which has one
+    /// command per tab.
+    pub(crate) fn into_device_commands(self) -> Vec<crate::OutgoingDeviceCommand> {
+        let target_device_id = self.target_device_id;
+        match self.payload {
+            OutgoingCommandPayload::SendTab(payload) => payload
+                .into_tabs()
+                .into_iter()
+                .map(|tab| crate::OutgoingDeviceCommand::SendTab {
+                    target_device_id: target_device_id.clone(),
+                    payload: tab.into(),
+                })
+                .collect(),
+            OutgoingCommandPayload::CloseTabs(payload) => {
+                vec![crate::OutgoingDeviceCommand::CloseTabs {
+                    target_device_id,
+                    payload: payload.into(),
+                }]
+            }
+        }
+    }
+}
+
+/// Whether we failed because we couldn't reach the server at all, as opposed
+/// to the server rejecting what we sent.
+fn is_offline_error(err: &Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::RequestError(viaduct::Error::NetworkError(_))
+    )
+}
+
+impl FirefoxAccount {
+    /// Send `payload` to the device `target_device_id` or, if we're offline,
+    /// put it in the outbox to send later.
+    ///
+    /// **💾 This method alters the persisted account state.**
+    pub(crate) fn send_or_queue_command(
+        &mut self,
+        target_device_id: &str,
+        payload: OutgoingCommandPayload,
+    ) -> Result<()> {
+        match self.send_command(target_device_id, &payload) {
+            Ok(()) => {
+                self.flush_outgoing_commands();
+                Ok(())
+            }
+            Err(e) if is_offline_error(&e) => {
+                log::info!(
+                    "Couldn't send the command, will send it once back online: {}",
+                    e
+                );
+                let outbox = &mut self.state.outgoing_commands;
+                outbox.push(OutgoingCommand {
+                    target_device_id: target_device_id.to_owned(),
+                    payload,
+                    created_at: util::now(),
+                    attempts: 0,
+                });
+                if outbox.len() > MAX_OUTGOING_COMMANDS {
+                    let excess = outbox.len() - MAX_OUTGOING_COMMANDS;
+                    outbox.drain(..excess);
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn send_command(
+        &mut self,
+        target_device_id: &str,
+        payload: &OutgoingCommandPayload,
+    ) -> Result<()> {
+        let devices = self.get_devices(false)?;
+        let target = devices
+            .iter()
+            .find(|d| d.id == target_device_id)
+            .ok_or_else(|| ErrorKind::UnknownTargetDevice(target_device_id.to_owned()))?;
+        let oldsync_key = self.get_scoped_key(scopes::OLD_SYNC)?;
+        match payload {
+            OutgoingCommandPayload::SendTab(payload) => {
+                let command_payload = send_tab::build_send_command(oldsync_key, target, payload)?;
+                self.invoke_command(send_tab::COMMAND_NAME, target, &command_payload)?;
+                // Only record the tabs once they're delivered, not when they're queued.
+                let mut telem = self.telemetry.borrow_mut();
+                for sent in payload.sent_telemetry() {
+                    telem.record_command_sent(sent);
+                }
+                Ok(())
+            }
+            OutgoingCommandPayload::CloseTabs(payload) => {
+                let command_payload =
+                    close_tabs::build_close_tabs_command(oldsync_key, target, payload)?;
+                self.invoke_command(close_tabs::COMMAND_NAME, target, &command_payload)?;
+                self.telemetry
+                    .borrow_mut()
+                    .record_command_sent(payload.sent_telemetry());
+                Ok(())
+            }
+        }
+    }
+
+    /// Try sending the commands in the outbox. This never fails: the commands
+    /// we can't send yet stay in the outbox.
+    ///
+    /// **💾 This method alters the persisted account state.**
+    pub(crate) fn flush_outgoing_commands(&mut self) {
+        if self.state.outgoing_commands.is_empty() {
+            return;
+        }
+        // Taking the commands out of the state means that the requests we make
+        // to send them don't try to flush them again.
+        let outbox = std::mem::take(&mut self.state.outgoing_commands);
+        let now = util::now();
+        let mut remaining = Vec::new();
+        let mut offline = false;
+        for mut command in outbox {
+            if now >= command.created_at + OUTGOING_COMMAND_TTL {
+                log::warn!("Dropping a command which we couldn't send in time");
+                continue;
+            }
+            if offline {
+                remaining.push(command);
+                continue;
+            }
+            match self.send_command(&command.target_device_id, &command.payload) {
+                Ok(()) => log::info!("Sent a command from the outbox"),
+                Err(e) if is_offline_error(&e) => {
+                    offline = true;
+                    remaining.push(command);
+                }
+                Err(e) => {
+                    command.attempts += 1;
+                    if command.attempts >= MAX_OUTGOING_COMMAND_ATTEMPTS {
+                        log::error!("Dropping a command which we failed to send: {}", e);
+                    } else {
+                        log::warn!("Failed to send a command, will try again: {}", e);
+                        remaining.push(command);
+                    }
+                }
+            }
+        }
+        remaining.append(&mut self.state.outgoing_commands);
+        self.state.outgoing_commands = remaining;
+    }
+
+    /// Get the commands which we couldn't send because we were offline, and
+    /// will send once we're back online.
+    pub fn get_pending_outgoing_commands(&self) -> Vec<OutgoingCommand> {
+        self.state.outgoing_commands.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::http_client::*;
+    use crate::internal::oauth::RefreshToken;
+    use crate::internal::scoped_keys::ScopedKey;
+    use crate::internal::{commands::send_tab::TabHistoryEntry, telemetry, Config};
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+
+    fn setup() -> FirefoxAccount {
+        let config = Config::stable_dev("12345678", "https://foo.bar");
+        let mut fxa = FirefoxAccount::with_config(config);
+        fxa.state.refresh_token = Some(RefreshToken {
+            token: "refreshtok".to_string(),
+            scopes: HashSet::default(),
+        });
+        fxa.state.scoped_keys.insert("https://identity.mozilla.com/apps/oldsync".to_string(), ScopedKey {
+            kty: "oct".to_string(),
+            scope: "https://identity.mozilla.com/apps/oldsync".to_string(),
+            k: "kMtwpVC0ZaYFJymPza8rXK_0CgCp3KMwRStwGfBRBDtL6hXRDVJgQFaoOQ2dimw0Bko5WVv2gNTy7RX5zFYZHg".to_string(),
+            kid: "1542236016429-Ox1FbJfFfwTe5t-xq4v2hQ".to_string(),
+        });
+        fxa
+    }
+
+    fn offline_error() -> Error {
+        ErrorKind::RequestError(viaduct::Error::NetworkError("offline".to_string())).into()
+    }
+
+    fn queued_tab(created_at: u64) -> OutgoingCommand {
+        let payload = SendTabPayload::single_tab("title", "https://example.com");
+        OutgoingCommand {
+            target_device_id: "device2".to_string(),
+            payload: OutgoingCommandPayload::SendTab(payload),
+            created_at,
+            attempts: 0,
+        }
+    }
+
+    #[test]
+    fn test_send_tab_offline_is_queued() {
+        let mut fxa = setup();
+        let mut client = FxAClientMock::new();
+        client
+            .expect_get_devices(mockiato::Argument::any, mockiato::Argument::any)
+            .returns_once(Err(offline_error()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.send_single_tab("device2", "title", "https://example.com")
+            .unwrap();
+        let pending = fxa.get_pending_outgoing_commands();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].target_device_id, "device2");
+        match &pending[0].payload {
+            OutgoingCommandPayload::SendTab(payload) => {
+                assert_eq!(payload.entries[0].url, "https://example.com")
+            }
+            _ => panic!("should be a send-tab command"),
+        }
+        // The tab isn't recorded as sent until it's delivered.
+        assert!(fxa.gather_telemetry().commands_sent.is_empty());
+
+        // The outbox is persisted.
+        let restored = FirefoxAccount::from_json(&fxa.to_json().unwrap()).unwrap();
+        assert_eq!(restored.get_pending_outgoing_commands().len(), 1);
+
+        // The public representation has one command per tab.
+        let payload = SendTabPayload::multiple_tabs(vec![
+            vec![TabHistoryEntry {
+                title: "one".to_string(),
+                url: "https://example.com/1".to_string(),
+            }],
+            vec![TabHistoryEntry {
+                title: "two".to_string(),
+                url: "https://example.com/2".to_string(),
+            }],
+        ])
+        .unwrap()
+        .pop()
+        .unwrap();
+        let command = OutgoingCommand {
+            target_device_id: "device2".to_string(),
+            payload: OutgoingCommandPayload::SendTab(payload),
+            created_at: util::now(),
+            attempts: 0,
+        };
+        assert_eq!(command.into_device_commands().len(), 2);
+    }
+
+    #[test]
+    fn test_send_tab_other_errors_are_not_queued() {
+        let mut fxa = setup();
+        let mut client = FxAClientMock::new();
+        client
+            .expect_get_devices(mockiato::Argument::any, mockiato::Argument::any)
+            .returns_once(Err(ErrorKind::RemoteError {
+                code: 500,
+                errno: 999,
+                error: "server error".to_string(),
+                message: "this will be ignored anyway".to_string(),
+                info: "".to_string(),
+            }
+            .into()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.send_single_tab("device2", "title", "https://example.com")
+            .unwrap_err();
+        assert!(fxa.get_pending_outgoing_commands().is_empty());
+    }
+
+    #[test]
+    fn test_flush_outgoing_commands() {
+        let mut fxa = setup();
+        let now = util::now();
+        fxa.state.outgoing_commands = vec![
+            // Expired.
+            queued_tab(now - OUTGOING_COMMAND_TTL),
+            // Sent to an unknown device, which has failed too often.
+            OutgoingCommand {
+                target_device_id: "unknown".to_string(),
+                attempts: MAX_OUTGOING_COMMAND_ATTEMPTS - 1,
+                ..queued_tab(now)
+            },
+            // Sent to an unknown device, which we try again.
+            OutgoingCommand {
+                target_device_id: "unknown".to_string(),
+                ..queued_tab(now)
+            },
+        ];
+        let mut client = FxAClientMock::new();
+        client
+            .expect_get_devices(mockiato::Argument::any, mockiato::Argument::any)
+            .returns_once(Ok(Vec::new()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.flush_outgoing_commands();
+        let pending = fxa.get_pending_outgoing_commands();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 1);
+    }
+
+    #[test]
+    fn test_flush_outgoing_commands_offline() {
+        let mut fxa = setup();
+        let now = util::now();
+        fxa.state.outgoing_commands = vec![queued_tab(now), queued_tab(now)];
+        let mut client = FxAClientMock::new();
+        // We stop trying once we know we're offline.
+        client
+            .expect_get_devices(mockiato::Argument::any, mockiato::Argument::any)
+            .returns_once(Err(offline_error()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.flush_outgoing_commands();
+        let pending = fxa.get_pending_outgoing_commands();
+        assert_eq!(pending.len(), 2);
+        assert!(pending.iter().all(|c| c.attempts == 0));
+        assert!(fxa.gather_telemetry().commands_sent.is_empty());
+    }
+
+    #[test]
+    fn test_close_tabs_telemetry() {
+        let mut fxa = setup();
+        // Close the tabs on ourselves, so that we can receive the command we send.
+        let mut available_commands = HashMap::new();
+        available_commands.insert(
+            close_tabs::COMMAND_NAME.to_string(),
+            fxa.generate_send_tab_command_data().unwrap(),
+        );
+        let device = GetDeviceResponse {
+            common: DeviceResponseCommon {
+                id: "device2".into(),
+                display_name: "".to_string(),
+                device_type: DeviceType::Desktop,
+                push_subscription: None,
+                available_commands,
+                push_endpoint_expired: false,
+            },
+            is_current_device: false,
+            location: DeviceLocation {
+                city: None,
+                country: None,
+                state: None,
+                state_code: None,
+            },
+            last_access_time: None,
+        };
+        let mut client = FxAClientMock::new();
+        client
+            .expect_get_devices(mockiato::Argument::any, mockiato::Argument::any)
+            .returns_once(Ok(vec![device.clone()]));
+        client
+            .expect_invoke_command(
+                mockiato::Argument::any,
+                mockiato::Argument::any,
+                |arg| arg.partial_eq(close_tabs::COMMAND_NAME),
+                |arg| arg.partial_eq("device2"),
+                mockiato::Argument::any,
+            )
+            .returns_once(Ok(()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.close_tabs("device2", vec!["https://example.com".to_string()])
+            .unwrap();
+        assert!(fxa.get_pending_outgoing_commands().is_empty());
+        let sent = fxa.gather_telemetry().commands_sent;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].command, telemetry::CommandKind::CloseTabs);
+
+        // The receiving device records the same ids.
+        let payload = CloseTabsPayload {
+            flow_id: sent[0].flow_id.clone(),
+            stream_id: sent[0].stream_id.clone(),
+            ..CloseTabsPayload::new(vec!["https://example.com".to_string()])
+        };
+        let oldsync_key = fxa.get_scoped_key(scopes::OLD_SYNC).unwrap();
+        let command_payload =
+            close_tabs::build_close_tabs_command(oldsync_key, &device, &payload).unwrap();
+        fxa.handle_close_tabs_command(None, command_payload, telemetry::ReceivedReason::Push)
+            .unwrap();
+        let received = fxa.gather_telemetry().commands_received;
+        assert_eq!(
+            received,
+            vec![telemetry::ReceivedCommand {
+                command: telemetry::CommandKind::CloseTabs,
+                flow_id: sent[0].flow_id.clone(),
+                stream_id: sent[0].stream_id.clone(),
+                reason: telemetry::ReceivedReason::Push,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_outbox_is_bounded() {
+        let mut fxa = setup();
+        let now = util::now();
+        fxa.state.outgoing_commands = (0..MAX_OUTGOING_COMMANDS)
+            .map(|i| queued_tab(now + i as u64))
+            .collect();
+        let mut client = FxAClientMock::new();
+        client
+            .expect_get_devices(mockiato::Argument::any, mockiato::Argument::any)
+            .returns_once(Err(offline_error()));
+        fxa.set_client(Arc::new(client));
+
+        fxa.close_tabs("device2", vec!["https://example.com".to_string()])
+            .unwrap();
+        let pending = fxa.get_pending_outgoing_commands();
+        assert_eq!(pending.len(), MAX_OUTGOING_COMMANDS);
+        // The oldest command was dropped.
+        assert_eq!(pending[0].created_at, now + 1);
+        match &pending[MAX_OUTGOING_COMMANDS - 1].payload {
+            OutgoingCommandPayload::CloseTabs(payload) => {
+                assert_eq!(payload.urls, vec!["https://example.com".to_string()])
+            }
+            _ => panic!("should be a close-tabs command"),
+        }
+    }
+
+    #[test]
+    fn test_outbox_cleared_on_start_over() {
+        let mut fxa = setup();
+        fxa.state.outgoing_commands = vec![queued_tab(util::now())];
+        fxa.state = fxa.state.start_over();
+        assert!(fxa.get_pending_outgoing_commands().is_empty());
+    }
+}
@@ -0,0 +1,581 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The authority side of the device pairing protocol.
+//!
+//! The authority (an application already connected to the account) and the supplicant
+//! (the new device) talk through a channel on the pairing channel server, which relays
+//! messages between them without being able to read them: every message is a compact JWE,
+//! encrypted with a symmetric key that the authority generates and only shares with the
+//! supplicant through the pairing URL it shows as a QR code.
+//!
+//! The handshake goes:
+//!
+//!   1. The supplicant sends a `pair:supp:request` message with its OAuth authorization
+//!      request, and the authority replies with a `pair:auth:metadata` message describing
+//!      the account, so the new device can show which account it's being signed in to.
+//!   2. Both users approve the pairing, in any order: the authority's user through
+//!      `approve_pairing`, the supplicant's by the supplicant sending `pair:supp:authorize`.
+//!   3. The authority creates an authorization code for the supplicant with its session
+//!      token, and sends it in a `pair:auth:authorize` message.
+//!
+//! Sending the messages is left to the application, through a [`PairingChannelTransport`],
+//! so that tests can pair two accounts over an in-memory channel.
+
+pub use crate::{PairingAuthorityState, PairingChannelTransport};
+
+use super::{error::*, FirefoxAccount};
+use crate::AuthorizationParameters;
+use jwcrypto::{
+    DecryptionParameters, EncryptionAlgorithm, EncryptionParameters, Jwk, JwkKeyParameters,
+};
+use serde_derive::*;
+
+const DEFAULT_ACCESS_TYPE: &str = "offline";
+
+pub(crate) struct PairingChannel {
+    channel_key: Jwk,
+    transport: Box<dyn PairingChannelTransport>,
+    handshake: Handshake,
+}
+
+enum Handshake {
+    WaitingForRequest,
+    AwaitingApproval {
+        request: SupplicantRequest,
+        authority_approved: bool,
+        supplicant_approved: bool,
+    },
+    Completed,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "message", content = "data")]
+enum PairingMessage {
+    #[serde(rename = "pair:supp:request")]
+    SupplicantRequest(SupplicantRequest),
+    #[serde(rename = "pair:supp:authorize")]
+    SupplicantAuthorize,
+    #[serde(rename = "pair:auth:metadata")]
+    AuthorityMetadata(AuthorityMetadata),
+    #[serde(rename = "pair:auth:authorize")]
+    AuthorityAuthorize(AuthorityAuthorize),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SupplicantRequest {
+    client_id: String,
+    // Space-separated, as in an OAuth authorization URL.
+    scope: String,
+    state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    access_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code_challenge: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code_challenge_method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keys_jwk: Option<String>,
+}
+
+impl SupplicantRequest {
+    fn scopes(&self) -> Vec<String> {
+        self.scope.split_whitespace().map(String::from).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct AuthorityMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    avatar: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    device_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthorityAuthorize {
+    code: String,
+    state: String,
+    redirect: String,
+}
+
+impl PairingChannel {
+    fn encrypt(&self, message: &PairingMessage) -> Result<String> {
+        let plaintext = serde_json::to_string(message)?;
+        Ok(jwcrypto::encrypt_to_jwe(
+            plaintext.as_bytes(),
+            EncryptionParameters::Direct {
+                enc: EncryptionAlgorithm::A256GCM,
+                jwk: &self.channel_key,
+            },
+        )?)
+    }
+
+    fn decrypt(&self, jwe: &str) -> Result<PairingMessage> {
+        let plaintext = jwcrypto::decrypt_jwe(
+            jwe,
+            DecryptionParameters::Direct {
+                jwk: self.channel_key.clone(),
+            },
+        )?;
+        Ok(serde_json::from_str(&plaintext)?)
+    }
+
+    fn send(&self, message: &PairingMessage) -> Result<()> {
+        self.transport.send(self.encrypt(message)?);
+        Ok(())
+    }
+
+    fn state(&self) -> PairingAuthorityState {
+        match &self.handshake {
+            Handshake::WaitingForRequest => PairingAuthorityState::WaitingForRequest,
+            Handshake::AwaitingApproval {
+                authority_approved: true,
+                ..
+            } => PairingAuthorityState::AwaitingSupplicantApproval,
+            Handshake::AwaitingApproval { request, .. } => {
+                PairingAuthorityState::AwaitingApproval {
+                    client_id: request.client_id.clone(),
+                    scopes: request.scopes(),
+                }
+            }
+            Handshake::Completed => PairingAuthorityState::Completed,
+        }
+    }
+}
+
+impl FirefoxAccount {
+    /// Start pairing a new device as the authority, and return the pairing URL
+    /// for the new device to scan.
+    pub fn begin_pairing_authority(
+        &mut self,
+        channel_id: &str,
+        transport: Box<dyn PairingChannelTransport>,
+    ) -> Result<String> {
+        // We'll need the session token to authorize the new device, so fail early
+        // rather than after both users have approved the pairing.
+        self.get_session_token()?;
+        let channel_key = Jwk::new_direct_key(None)?;
+        let key = match &channel_key.key_parameters {
+            JwkKeyParameters::Direct { k } => k.clone(),
+            _ => unreachable!("Direct keys always have direct key parameters"),
+        };
+        let mut url = url::Url::parse(&self.get_pairing_authority_url()?)?;
+        let fragment = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("channel_id", channel_id)
+            .append_pair("channel_key", &key)
+            .finish();
+        url.set_fragment(Some(&fragment));
+        self.pairing_channel = Some(PairingChannel {
+            channel_key,
+            transport,
+            handshake: Handshake::WaitingForRequest,
+        });
+        Ok(url.into())
+    }
+
+    /// Handle an encrypted message from the new device.
+    pub fn handle_pairing_message(&mut self, message: &str) -> Result<PairingAuthorityState> {
+        let mut channel = self
+            .pairing_channel
+            .take()
+            .ok_or(ErrorKind::NoPairingChannel)?;
+        let result = self.handle_pairing_message_helper(&mut channel, message);
+        self.pairing_channel = Some(channel);
+        result
+    }
+
+    fn handle_pairing_message_helper(
+        &mut self,
+        channel: &mut PairingChannel,
+        message: &str,
+    ) -> Result<PairingAuthorityState> {
+        match channel.decrypt(message)? {
+            PairingMessage::SupplicantRequest(request)
+                if matches!(channel.handshake, Handshake::WaitingForRequest) =>
+            {
+                channel.send(&PairingMessage::AuthorityMetadata(
+                    self.pairing_authority_metadata(),
+                ))?;
+                channel.handshake = Handshake::AwaitingApproval {
+                    request,
+                    authority_approved: false,
+                    supplicant_approved: false,
+                };
+            }
+            PairingMessage::SupplicantAuthorize => {
+                match &mut channel.handshake {
+                    Handshake::AwaitingApproval {
+                        supplicant_approved,
+                        ..
+                    } => *supplicant_approved = true,
+                    _ => return Err(ErrorKind::PairingChannelError("Unexpected message").into()),
+                }
+                self.maybe_complete_pairing(channel)?;
+            }
+            _ => return Err(ErrorKind::PairingChannelError("Unexpected message").into()),
+        }
+        Ok(channel.state())
+    }
+
+    /// Approve the new device's authorization request, on behalf of the user.
+    pub fn approve_pairing(&mut self) -> Result<PairingAuthorityState> {
+        let mut channel = self
+            .pairing_channel
+            .take()
+            .ok_or(ErrorKind::NoPairingChannel)?;
+        let result = self.approve_pairing_helper(&mut channel);
+        self.pairing_channel = Some(channel);
+        result
+    }
+
+    fn approve_pairing_helper(
+        &mut self,
+        channel: &mut PairingChannel,
+    ) -> Result<PairingAuthorityState> {
+        match &mut channel.handshake {
+            Handshake::AwaitingApproval {
+                authority_approved, ..
+            } => *authority_approved = true,
+            _ => {
+                return Err(ErrorKind::IllegalState("No pairing request to approve").into());
+            }
+        }
+        self.maybe_complete_pairing(channel)?;
+        Ok(channel.state())
+    }
+
+    /// Stop the pairing in progress, if any.
+    pub fn cancel_pairing(&mut self) {
+        self.pairing_channel = None;
+    }
+
+    // Once both users have approved the pairing, authorize the new device.
+    // If that fails, the handshake is left as it was so that it can be retried
+    // by approving the pairing again.
+    fn maybe_complete_pairing(&mut self, channel: &mut PairingChannel) -> Result<()> {
+        let request = match &channel.handshake {
+            Handshake::AwaitingApproval {
+                request,
+                authority_approved: true,
+                supplicant_approved: true,
+            } => request.clone(),
+            _ => return Ok(()),
+        };
+        let scope = request.scopes();
+        let response = self.create_authorization_code(AuthorizationParameters {
+            client_id: request.client_id,
+            scope,
+            state: request.state,
+            access_type: request
+                .access_type
+                .unwrap_or_else(|| DEFAULT_ACCESS_TYPE.to_string()),
+            code_challenge: request.code_challenge,
+            code_challenge_method: request.code_challenge_method,
+            keys_jwk: request.keys_jwk,
+        })?;
+        channel.send(&PairingMessage::AuthorityAuthorize(AuthorityAuthorize {
+            code: response.code,
+            state: response.state,
+            redirect: response.redirect,
+        }))?;
+        channel.handshake = Handshake::Completed;
+        Ok(())
+    }
+
+    // What we tell the new device about the account. This is only for display,
+    // so failing to fetch any of it shouldn't stop the pairing.
+    fn pairing_authority_metadata(&mut self) -> AuthorityMetadata {
+        let mut metadata = AuthorityMetadata::default();
+        match self.get_profile(false) {
+            Ok(profile) => {
+                metadata.email = Some(profile.email);
+                metadata.display_name = profile.display_name;
+                if !profile.avatar_default {
+                    metadata.avatar = Some(profile.avatar);
+                }
+            }
+            Err(e) => log::warn!("Could not get the profile for pairing: {}", e),
+        }
+        match self.get_current_device() {
+            Ok(device) => metadata.device_name = device.map(|d| d.common.display_name),
+            Err(e) => log::warn!("Could not get the current device for pairing: {}", e),
+        }
+        metadata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        http_client::{FxAClientMock, OAuthAuthResponse, ScopedKeyDataResponse},
+        scopes, Config,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Clone, Default)]
+    struct InMemoryTransport {
+        sent: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl PairingChannelTransport for InMemoryTransport {
+        fn send(&self, message: String) {
+            self.sent.lock().unwrap().push(message);
+        }
+    }
+
+    impl InMemoryTransport {
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut *self.sent.lock().unwrap())
+        }
+    }
+
+    // The new device's end of the channel, which gets the key from the pairing URL.
+    struct Supplicant {
+        channel_key: Jwk,
+    }
+
+    impl Supplicant {
+        fn from_pairing_url(pairing_url: &str) -> Self {
+            let url = url::Url::parse(pairing_url).unwrap();
+            let k = url::form_urlencoded::parse(url.fragment().unwrap().as_bytes())
+                .find(|(name, _)| name == "channel_key")
+                .unwrap()
+                .1
+                .into_owned();
+            Self {
+                channel_key: Jwk {
+                    kid: None,
+                    key_parameters: JwkKeyParameters::Direct { k },
+                },
+            }
+        }
+
+        fn encrypt(&self, message: serde_json::Value) -> String {
+            jwcrypto::encrypt_to_jwe(
+                message.to_string().as_bytes(),
+                EncryptionParameters::Direct {
+                    enc: EncryptionAlgorithm::A256GCM,
+                    jwk: &self.channel_key,
+                },
+            )
+            .unwrap()
+        }
+
+        fn decrypt(&self, jwe: &str) -> serde_json::Value {
+            let plaintext = jwcrypto::decrypt_jwe(
+                jwe,
+                DecryptionParameters::Direct {
+                    jwk: self.channel_key.clone(),
+                },
+            )
+            .unwrap();
+            serde_json::from_str(&plaintext).unwrap()
+        }
+
+        fn request(&self) -> String {
+            self.encrypt(serde_json::json!({
+                "message": "pair:supp:request",
+                "data": {
+                    "client_id": "supplicant",
+                    "scope": scopes::OLD_SYNC,
+                    "state": "supplicant-state",
+                    "code_challenge": "challenge",
+                    "code_challenge_method": "S256",
+                }
+            }))
+        }
+
+        fn authorize(&self) -> String {
+            self.encrypt(serde_json::json!({ "message": "pair:supp:authorize" }))
+        }
+    }
+
+    fn setup() -> (FirefoxAccount, InMemoryTransport, Supplicant) {
+        let config = Config::stable_dev("12345678", "https://foo.bar");
+        let mut fxa = FirefoxAccount::with_config(config);
+        fxa.set_session_token("session");
+        fxa.add_cached_profile("123", "test@example.com");
+        let transport = InMemoryTransport::default();
+        let pairing_url = fxa
+            .begin_pairing_authority("channel", Box::new(transport.clone()))
+            .unwrap();
+        let supplicant = Supplicant::from_pairing_url(&pairing_url);
+        (fxa, transport, supplicant)
+    }
+
+    fn mock_authorization(fxa: &mut FirefoxAccount) {
+        let mut client = FxAClientMock::new();
+        let mut allowed_scopes = HashMap::new();
+        allowed_scopes.insert(
+            scopes::OLD_SYNC.to_string(),
+            ScopedKeyDataResponse {
+                identifier: scopes::OLD_SYNC.to_string(),
+                key_rotation_secret: "".to_string(),
+                key_rotation_timestamp: 0,
+            },
+        );
+        client
+            .expect_get_scoped_key_data(
+                mockiato::Argument::any,
+                |arg| arg.partial_eq("session"),
+                |arg| arg.partial_eq("supplicant"),
+                |arg| arg.partial_eq(scopes::OLD_SYNC),
+            )
+            .times(1)
+            .returns_once(Ok(allowed_scopes));
+        client
+            .expect_create_authorization_code_using_session_token(
+                mockiato::Argument::any,
+                |arg| arg.partial_eq("session"),
+                mockiato::Argument::any,
+            )
+            .times(1)
+            .returns_once(Ok(OAuthAuthResponse {
+                redirect: "https://example.com/redirect".to_string(),
+                code: "code".to_string(),
+                state: "supplicant-state".to_string(),
+            }));
+        fxa.set_client(Arc::new(client));
+    }
+
+    #[test]
+    fn test_pairing_url() {
+        let (mut fxa, _, _) = setup();
+        let pairing_url = fxa
+            .begin_pairing_authority("channel", Box::new(InMemoryTransport::default()))
+            .unwrap();
+        let url = url::Url::parse(&pairing_url).unwrap();
+        assert_eq!(
+            url[..url::Position::AfterPath],
+            fxa.get_pairing_authority_url().unwrap()
+        );
+        let fragment: HashMap<_, _> =
+            url::form_urlencoded::parse(url.fragment().unwrap().as_bytes()).collect();
+        assert_eq!(fragment["channel_id"], "channel");
+        // A base64url-encoded 32-byte key.
+        assert_eq!(fragment["channel_key"].len(), 43);
+    }
+
+    #[test]
+    fn test_pairing_requires_session_token() {
+        let config = Config::stable_dev("12345678", "https://foo.bar");
+        let mut fxa = FirefoxAccount::with_config(config);
+        let err = fxa
+            .begin_pairing_authority("channel", Box::new(InMemoryTransport::default()))
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NoSessionToken));
+    }
+
+    #[test]
+    fn test_pairing_handshake() {
+        let (mut fxa, transport, supplicant) = setup();
+
+        let state = fxa.handle_pairing_message(&supplicant.request()).unwrap();
+        assert_eq!(
+            state,
+            PairingAuthorityState::AwaitingApproval {
+                client_id: "supplicant".to_string(),
+                scopes: vec![scopes::OLD_SYNC.to_string()],
+            }
+        );
+        let sent = transport.take();
+        assert_eq!(sent.len(), 1);
+        let metadata = supplicant.decrypt(&sent[0]);
+        assert_eq!(metadata["message"], "pair:auth:metadata");
+        assert_eq!(metadata["data"]["email"], "test@example.com");
+
+        let state = fxa.approve_pairing().unwrap();
+        assert_eq!(state, PairingAuthorityState::AwaitingSupplicantApproval);
+        assert!(transport.take().is_empty());
+
+        mock_authorization(&mut fxa);
+        let state = fxa.handle_pairing_message(&supplicant.authorize()).unwrap();
+        assert_eq!(state, PairingAuthorityState::Completed);
+        let sent = transport.take();
+        assert_eq!(sent.len(), 1);
+        let authorize = supplicant.decrypt(&sent[0]);
+        assert_eq!(authorize["message"], "pair:auth:authorize");
+        assert_eq!(authorize["data"]["code"], "code");
+        assert_eq!(authorize["data"]["state"], "supplicant-state");
+        assert_eq!(
+            authorize["data"]["redirect"],
+            "https://example.com/redirect"
+        );
+    }
+
+    #[test]
+    fn test_pairing_supplicant_approves_first() {
+        let (mut fxa, transport, supplicant) = setup();
+        fxa.handle_pairing_message(&supplicant.request()).unwrap();
+        transport.take();
+
+        // The user of the new device approving first doesn't skip our own approval.
+        let state = fxa.handle_pairing_message(&supplicant.authorize()).unwrap();
+        assert!(matches!(
+            state,
+            PairingAuthorityState::AwaitingApproval { .. }
+        ));
+        assert!(transport.take().is_empty());
+
+        mock_authorization(&mut fxa);
+        let state = fxa.approve_pairing().unwrap();
+        assert_eq!(state, PairingAuthorityState::Completed);
+        let sent = transport.take();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            supplicant.decrypt(&sent[0])["message"],
+            "pair:auth:authorize"
+        );
+    }
+
+    #[test]
+    fn test_pairing_unexpected_message() {
+        let (mut fxa, transport, supplicant) = setup();
+
+        let err = fxa
+            .handle_pairing_message(&supplicant.authorize())
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::PairingChannelError(_)));
+        let err = fxa.approve_pairing().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::IllegalState(_)));
+        assert!(transport.take().is_empty());
+
+        // The handshake can still go on after an unexpected message.
+        let state = fxa.handle_pairing_message(&supplicant.request()).unwrap();
+        assert!(matches!(
+            state,
+            PairingAuthorityState::AwaitingApproval { .. }
+        ));
+    }
+
+    #[test]
+    fn test_pairing_message_with_wrong_key() {
+        let (mut fxa, transport, _) = setup();
+        let impostor = Supplicant {
+            channel_key: Jwk::new_direct_key(None).unwrap(),
+        };
+        assert!(fxa.handle_pairing_message(&impostor.request()).is_err());
+        assert!(transport.take().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_pairing() {
+        let (mut fxa, _, supplicant) = setup();
+        fxa.cancel_pairing();
+        let err = fxa
+            .handle_pairing_message(&supplicant.request())
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::NoPairingChannel));
+    }
+}
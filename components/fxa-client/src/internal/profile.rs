@@ -198,6 +198,7 @@ mod tests {
                 scope: "profile".to_owned(),
                 access_token: "good_profile_token".to_owned(),
                 session_token: None,
+                id_token: None,
             }));
         // Then hooray it works!
         client
@@ -35,21 +35,18 @@ impl FirefoxAccount {
         })?;
         match payload {
             PushPayload::CommandReceived(CommandReceivedPushPayload { index, .. }) => {
-                if cfg!(target_os = "ios") {
-                    let cmd = self.ios_fetch_device_command(index)?;
-                    Ok(vec![AccountEvent::CommandReceived {
-                        command: cmd.try_into()?,
-                    }])
+                let cmds = if cfg!(target_os = "ios") {
+                    self.ios_fetch_device_command(index)?
                 } else {
-                    let cmds = self.poll_device_commands(CommandFetchReason::Push(index))?;
-                    cmds.into_iter()
-                        .map(|command| {
-                            Ok(AccountEvent::CommandReceived {
-                                command: command.try_into()?,
-                            })
+                    self.poll_device_commands(CommandFetchReason::Push(index))?
+                };
+                cmds.into_iter()
+                    .map(|command| {
+                        Ok(AccountEvent::CommandReceived {
+                            command: command.try_into()?,
                         })
-                        .collect()
-                }
+                    })
+                    .collect()
             }
             PushPayload::ProfileUpdated => {
                 self.state.last_seen_profile = None;
@@ -68,6 +65,9 @@ impl FirefoxAccount {
                 if is_local_device {
                     // Note: self.disconnect calls self.start_over which clears the state for the FirefoxAccount instance
                     self.disconnect();
+                } else {
+                    self.remove_cached_device(&device_id);
+                    self.attached_clients_cache = None;
                 }
                 Ok(vec![AccountEvent::DeviceDisconnected {
                     device_id,
@@ -101,6 +101,17 @@ impl FirefoxAccount {
             }
         }
     }
+
+    /// Take the events which were detected locally rather than pushed by the server,
+    /// such as a scoped key changing when we refreshed an access token.
+    ///
+    /// **💾 This method alters the persisted account state.**
+    pub fn take_account_events(&mut self) -> Vec<AccountEvent> {
+        std::mem::take(&mut self.state.scoped_key_changes)
+            .into_iter()
+            .map(|scope| AccountEvent::ScopedKeyChanged { scope })
+            .collect()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -227,15 +238,15 @@ mod tests {
             scopes: refresh_token_scopes,
         });
         fxa.state.current_device_id = Some("my_id".to_owned());
-        fxa.devices_cache = Some(CachedResponse {
+        fxa.state.devices_cache = Some(CachedResponse {
             response: vec![],
             cached_at: 0,
             etag: "".to_string(),
         });
         let json = "{\"version\":1,\"command\":\"fxaccounts:password_reset\"}";
-        assert!(fxa.devices_cache.is_some());
+        assert!(fxa.state.devices_cache.is_some());
         fxa.handle_push_message(json).unwrap();
-        assert!(fxa.devices_cache.is_none());
+        assert!(fxa.state.devices_cache.is_none());
     }
 
     #[test]
@@ -259,6 +270,41 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_push_device_disconnected_remote_updates_devices_cache() {
+        use crate::internal::http_client::{DeviceLocation, DeviceResponseCommon, DeviceType};
+        let device = |id: &str| crate::internal::device::Device {
+            common: DeviceResponseCommon {
+                id: id.to_owned(),
+                display_name: "".to_owned(),
+                device_type: DeviceType::Mobile,
+                push_subscription: None,
+                available_commands: std::collections::HashMap::new(),
+                push_endpoint_expired: false,
+            },
+            is_current_device: false,
+            location: DeviceLocation {
+                city: None,
+                country: None,
+                state: None,
+                state_code: None,
+            },
+            last_access_time: None,
+        };
+        let mut fxa =
+            FirefoxAccount::with_config(Config::stable_dev("12345678", "https://foo.bar"));
+        fxa.state.devices_cache = Some(CachedResponse {
+            response: vec![device("remote_id"), device("other_id")],
+            cached_at: 0,
+            etag: "".to_string(),
+        });
+        let json = "{\"version\":1,\"command\":\"fxaccounts:device_disconnected\",\"data\":{\"id\":\"remote_id\"}}";
+        fxa.handle_push_message(json).unwrap();
+        let cache = fxa.state.devices_cache.unwrap();
+        assert_eq!(cache.response.len(), 1);
+        assert_eq!(cache.response[0].id, "other_id");
+    }
+
     #[test]
     fn test_handle_push_message_ignores_unknown_command() {
         let mut fxa =
@@ -15,6 +15,88 @@ impl FirefoxAccount {
             .get(scope)
             .ok_or_else(|| ErrorKind::NoScopedKey(scope.to_string()).into())
     }
+
+    /// Store a key we were just given for `scope`.
+    ///
+    /// If it replaces a different key, for example because the user reset their password
+    /// since we last signed in, data encrypted with the old key can't be read anymore and
+    /// the application is told about it with an `AccountEvent::ScopedKeyChanged`.
+    pub(crate) fn store_scoped_key(&mut self, scope: String, key: ScopedKey) {
+        match self.state.scoped_keys.get(&scope) {
+            Some(old_key) if old_key.kid == key.kid => {}
+            Some(_) => {
+                log::info!("The key for {} has changed", scope);
+                self.queue_scoped_key_change(&scope);
+                self.state.scoped_key_rotation_timestamps.remove(&scope);
+            }
+            None => {
+                self.state.scoped_key_rotation_timestamps.remove(&scope);
+            }
+        }
+        self.state.scoped_keys.insert(scope, key);
+    }
+
+    /// Ask the server whether the key for `scope` was rotated since we got it.
+    ///
+    /// We can only derive the new key in a new OAuth flow, so if it was, we drop the
+    /// stale key instead of handing it out, flag the account as having an auth problem,
+    /// and queue an `AccountEvent::ScopedKeyChanged`.
+    ///
+    /// The `kid` of a key starts with when it was rotated, but not always in the same
+    /// unit, so we compare the server's rotation timestamp with the one it gave us the
+    /// first time we checked this key instead.
+    ///
+    /// **💾 This method may alter the persisted account state.**
+    pub(crate) fn check_scoped_key_rotation(&mut self, scope: &str) -> Result<()> {
+        if !self.state.scoped_keys.contains_key(scope) {
+            return Ok(());
+        }
+        // Fetching the key data requires a session token.
+        let session_token = match self.state.session_token {
+            Some(ref session_token) => session_token.clone(),
+            None => return Ok(()),
+        };
+        let key_data = self.client.get_scoped_key_data(
+            &self.state.config,
+            &session_token,
+            &self.state.config.client_id,
+            scope,
+        )?;
+        let current_rotation_timestamp = match key_data.get(scope) {
+            Some(key_data) => key_data.key_rotation_timestamp,
+            None => return Ok(()),
+        };
+        match self.state.scoped_key_rotation_timestamps.get(scope) {
+            Some(&rotation_timestamp) if rotation_timestamp == current_rotation_timestamp => {
+                return Ok(())
+            }
+            Some(_) => {}
+            // We only just got the key, so it's the current one.
+            None => {
+                self.state
+                    .scoped_key_rotation_timestamps
+                    .insert(scope.to_owned(), current_rotation_timestamp);
+                return Ok(());
+            }
+        }
+        log::warn!(
+            "The key for {} was rotated at {}, discarding it",
+            scope,
+            current_rotation_timestamp
+        );
+        self.state.scoped_keys.remove(scope);
+        self.state.scoped_key_rotation_timestamps.remove(scope);
+        self.state.access_token_cache.remove(scope);
+        self.queue_scoped_key_change(scope);
+        self.set_auth_problem(true);
+        Ok(())
+    }
+
+    fn queue_scoped_key_change(&mut self, scope: &str) {
+        if !self.state.scoped_key_changes.iter().any(|s| s == scope) {
+            self.state.scoped_key_changes.push(scope.to_owned());
+        }
+    }
 }
 
 impl ScopedKey {
@@ -66,8 +148,183 @@ impl ScopedKeysFlow {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        internal::{http_client::*, oauth::RefreshToken, Config},
+        AccountEvent, AccountState,
+    };
     use jwcrypto::JwkKeyParameters;
     use rc_crypto::agreement::{KeyPair, PrivateKey};
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+
+    const OLD_SYNC: &str = "https://identity.mozilla.com/apps/oldsync";
+
+    fn scoped_key(kid: &str) -> ScopedKey {
+        ScopedKey {
+            kty: "oct".to_string(),
+            scope: OLD_SYNC.to_string(),
+            k: "kMtwpVC0ZaYFJymPza8rXK_0CgCp3KMwRStwGfBRBDtL6hXRDVJgQFaoOQ2dimw0Bko5WVv2gNTy7RX5zFYZHg".to_string(),
+            kid: kid.to_string(),
+        }
+    }
+
+    fn logged_in_account() -> FirefoxAccount {
+        let mut fxa =
+            FirefoxAccount::with_config(Config::stable_dev("12345678", "https://foo.bar"));
+        let mut refresh_token_scopes = HashSet::new();
+        refresh_token_scopes.insert(OLD_SYNC.to_string());
+        fxa.state.refresh_token = Some(RefreshToken {
+            token: "refreshtok".to_string(),
+            scopes: refresh_token_scopes,
+        });
+        fxa.state.session_token = Some("session".to_string());
+        fxa.store_scoped_key(
+            OLD_SYNC.to_string(),
+            scoped_key("1542236016429-Ox1FbJfFfwTe5t-xq4v2hQ"),
+        );
+        fxa.state
+            .scoped_key_rotation_timestamps
+            .insert(OLD_SYNC.to_string(), 1_542_236_016_429);
+        fxa.update_account_state();
+        fxa
+    }
+
+    fn mock_token_refresh(key_rotation_timestamp: u64) -> FxAClientMock {
+        let mut client = FxAClientMock::new();
+        client
+            .expect_create_access_token_using_refresh_token(
+                mockiato::Argument::any,
+                |token| token.partial_eq("refreshtok"),
+                mockiato::Argument::any,
+                mockiato::Argument::any,
+            )
+            .times(1)
+            .returns_once(Ok(OAuthTokenResponse {
+                keys_jwe: None,
+                refresh_token: None,
+                session_token: None,
+                expires_in: 6_000_000,
+                scope: OLD_SYNC.to_string(),
+                access_token: "accesstok".to_string(),
+                id_token: None,
+            }));
+        let mut key_data = HashMap::new();
+        key_data.insert(
+            OLD_SYNC.to_string(),
+            ScopedKeyDataResponse {
+                identifier: OLD_SYNC.to_string(),
+                key_rotation_secret: "00000000000000000000000000000000".to_string(),
+                key_rotation_timestamp,
+            },
+        );
+        client
+            .expect_get_scoped_key_data(
+                mockiato::Argument::any,
+                |arg| arg.partial_eq("session"),
+                |arg| arg.partial_eq("12345678"),
+                |arg| arg.partial_eq(OLD_SYNC),
+            )
+            .times(1)
+            .returns_once(Ok(key_data));
+        client
+    }
+
+    #[test]
+    fn test_store_scoped_key_detects_changes() {
+        let mut fxa = logged_in_account();
+        // Getting the same key again isn't a change.
+        fxa.store_scoped_key(
+            OLD_SYNC.to_string(),
+            scoped_key("1542236016429-Ox1FbJfFfwTe5t-xq4v2hQ"),
+        );
+        assert!(fxa.take_account_events().is_empty());
+
+        fxa.store_scoped_key(
+            OLD_SYNC.to_string(),
+            scoped_key("1542236016429-zgTjf5oXmPmBjxwXWFsDWg"),
+        );
+        assert_eq!(
+            fxa.get_scoped_key(OLD_SYNC).unwrap().kid,
+            "1542236016429-zgTjf5oXmPmBjxwXWFsDWg"
+        );
+        let events = fxa.take_account_events();
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            AccountEvent::ScopedKeyChanged { scope } => assert_eq!(scope, OLD_SYNC),
+            _ => unreachable!(),
+        }
+        // Events are only returned once.
+        assert!(fxa.take_account_events().is_empty());
+    }
+
+    #[test]
+    fn test_key_rotation_detected_on_token_refresh() {
+        let mut fxa = logged_in_account();
+        fxa.set_client(Arc::new(mock_token_refresh(1_600_000_000_000)));
+
+        let token_info = fxa.get_access_token(OLD_SYNC, None).unwrap();
+        // The stale key isn't handed out, and we need to sign in again to get the new one.
+        assert!(token_info.key.is_none());
+        assert!(fxa.get_scoped_key(OLD_SYNC).is_err());
+        assert_eq!(fxa.get_state(), AccountState::AuthProblem);
+
+        // The change survives a round-trip through the persisted state.
+        let mut fxa = FirefoxAccount::from_json(&fxa.to_json().unwrap()).unwrap();
+        let events = fxa.take_account_events();
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            AccountEvent::ScopedKeyChanged { scope } => assert_eq!(scope, OLD_SYNC),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_unchanged_key_on_token_refresh() {
+        let mut fxa = logged_in_account();
+        fxa.set_client(Arc::new(mock_token_refresh(1_542_236_016_429)));
+
+        let token_info = fxa.get_access_token(OLD_SYNC, None).unwrap();
+        assert_eq!(
+            token_info.key.unwrap().kid,
+            "1542236016429-Ox1FbJfFfwTe5t-xq4v2hQ"
+        );
+        assert_eq!(fxa.get_state(), AccountState::Connected);
+        assert!(fxa.take_account_events().is_empty());
+    }
+
+    #[test]
+    fn test_key_rotation_with_seconds_kid() {
+        let mut fxa = logged_in_account();
+        // Some kids have their rotation timestamp in seconds, unlike the server's.
+        fxa.store_scoped_key(
+            OLD_SYNC.to_string(),
+            scoped_key("1231014287-KDVj0DFaO3wGpPJD8oPwVg"),
+        );
+        fxa.take_account_events();
+
+        // The first check after getting the key remembers the server's timestamp...
+        fxa.set_client(Arc::new(mock_token_refresh(1_231_014_287_000)));
+        let token_info = fxa.get_access_token(OLD_SYNC, None).unwrap();
+        assert_eq!(
+            token_info.key.unwrap().kid,
+            "1231014287-KDVj0DFaO3wGpPJD8oPwVg"
+        );
+        assert_eq!(fxa.get_state(), AccountState::Connected);
+
+        // ...which later checks compare with.
+        fxa.state.access_token_cache.clear();
+        fxa.set_client(Arc::new(mock_token_refresh(1_231_014_287_000)));
+        let token_info = fxa.get_access_token(OLD_SYNC, None).unwrap();
+        assert!(token_info.key.is_some());
+        assert_eq!(fxa.get_state(), AccountState::Connected);
+        assert!(fxa.take_account_events().is_empty());
+
+        fxa.state.access_token_cache.clear();
+        fxa.set_client(Arc::new(mock_token_refresh(1_600_000_000_000)));
+        let token_info = fxa.get_access_token(OLD_SYNC, None).unwrap();
+        assert!(token_info.key.is_none());
+        assert_eq!(fxa.get_state(), AccountState::AuthProblem);
+    }
 
     #[test]
     fn test_flow() {
@@ -3,4 +3,5 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 pub const PROFILE: &str = "profile";
+pub const OPENID: &str = "openid";
 pub const OLD_SYNC: &str = "https://identity.mozilla.com/apps/oldsync";
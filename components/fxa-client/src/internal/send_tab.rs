@@ -6,12 +6,13 @@ use super::{
     commands::{
         send_tab::{
             self, EncryptedSendTabPayload, PrivateSendTabKeys, PublicSendTabKeys,
-            SendTabKeysPayload, SendTabPayload,
+            SendTabKeysPayload, SendTabPayload, TabHistoryEntry,
         },
         IncomingDeviceCommand,
     },
     error::*,
     http_client::GetDeviceResponse,
+    outbox::OutgoingCommandPayload,
     scopes, telemetry, FirefoxAccount,
 };
 
@@ -41,6 +42,7 @@ impl FirefoxAccount {
     }
 
     /// Send a single tab to another device designated by its device ID.
+    /// If we're offline, the tab is sent once we're back online.
     /// XXX - We need a new send_tabs_to_devices() so we can correctly record
     /// telemetry for these cases.
     /// This probably requires a new "Tab" struct with the title and url.
@@ -52,49 +54,69 @@ impl FirefoxAccount {
         title: &str,
         url: &str,
     ) -> Result<()> {
-        let devices = self.get_devices(false)?;
-        let target = devices
-            .iter()
-            .find(|d| d.id == target_device_id)
-            .ok_or_else(|| ErrorKind::UnknownTargetDevice(target_device_id.to_owned()))?;
-        let (payload, sent_telemetry) = SendTabPayload::single_tab(title, url);
-        let oldsync_key = self.get_scoped_key(scopes::OLD_SYNC)?;
-        let command_payload = send_tab::build_send_command(oldsync_key, target, &payload)?;
-        self.invoke_command(send_tab::COMMAND_NAME, target, &command_payload)?;
-        self.telemetry.borrow_mut().record_tab_sent(sent_telemetry);
+        let payload = SendTabPayload::single_tab(title, url);
+        self.send_or_queue_command(target_device_id, OutgoingCommandPayload::SendTab(payload))
+    }
+
+    /// Send several tabs, each given as its navigation history, to another
+    /// device designated by its device ID.
+    /// The tabs are sent in as few commands as the server's limit on the size
+    /// of their payloads allows. If sending one of those commands fails, the
+    /// tabs in the commands before it have still been sent. If we're offline,
+    /// the tabs are sent once we're back online.
+    pub fn send_tabs(
+        &mut self,
+        target_device_id: &str,
+        tabs: Vec<Vec<TabHistoryEntry>>,
+    ) -> Result<()> {
+        let payloads = SendTabPayload::multiple_tabs(tabs)?;
+        for payload in payloads {
+            self.send_or_queue_command(target_device_id, OutgoingCommandPayload::SendTab(payload))?;
+        }
         Ok(())
     }
 
+    /// Get the private keys which other devices encrypt the payloads of the
+    /// commands they send us with.
+    pub(crate) fn get_send_tab_keys(&self) -> Result<PrivateSendTabKeys> {
+        match self.state.commands_data.get(send_tab::COMMAND_NAME) {
+            Some(s) => PrivateSendTabKeys::deserialize(s),
+            None => Err(ErrorKind::IllegalState(
+                "Cannot find send-tab keys. Has initialize_device been called before?",
+            )
+            .into()),
+        }
+    }
+
     pub(crate) fn handle_send_tab_command(
         &mut self,
         sender: Option<GetDeviceResponse>,
         payload: serde_json::Value,
         reason: telemetry::ReceivedReason,
-    ) -> Result<IncomingDeviceCommand> {
-        let send_tab_key: PrivateSendTabKeys =
-            match self.state.commands_data.get(send_tab::COMMAND_NAME) {
-                Some(s) => PrivateSendTabKeys::deserialize(s)?,
-                None => {
-                    return Err(ErrorKind::IllegalState(
-                        "Cannot find send-tab keys. Has initialize_device been called before?",
-                    )
-                    .into());
-                }
-            };
+    ) -> Result<Vec<IncomingDeviceCommand>> {
+        let send_tab_key = self.get_send_tab_keys()?;
         let encrypted_payload: EncryptedSendTabPayload = serde_json::from_value(payload)?;
         match encrypted_payload.decrypt(&send_tab_key) {
             Ok(payload) => {
-                // It's an incoming tab, which we record telemetry for.
-                let recd_telemetry = telemetry::ReceivedCommand {
-                    flow_id: payload.flow_id.clone(),
-                    stream_id: payload.stream_id.clone(),
-                    reason,
-                };
-                self.telemetry
-                    .borrow_mut()
-                    .record_tab_received(recd_telemetry);
+                // They're incoming tabs, which we record telemetry for.
+                let tabs = payload.into_tabs();
+                let mut telem = self.telemetry.borrow_mut();
+                for tab in &tabs {
+                    telem.record_command_received(telemetry::ReceivedCommand {
+                        command: telemetry::CommandKind::SendTab,
+                        flow_id: tab.flow_id.clone(),
+                        stream_id: tab.stream_id.clone(),
+                        reason,
+                    });
+                }
                 // The telemetry IDs escape to the consumer, but that's OK...
-                Ok(IncomingDeviceCommand::TabReceived { sender, payload })
+                Ok(tabs
+                    .into_iter()
+                    .map(|payload| IncomingDeviceCommand::TabReceived {
+                        sender: sender.clone(),
+                        payload,
+                    })
+                    .collect())
             }
             Err(e) => {
                 // XXX - this seems ripe for telemetry collection!?
@@ -34,9 +34,10 @@ use std::collections::{HashMap, HashSet};
 
 use super::{
     config::Config,
-    device::Capability as DeviceCapability,
+    device::{Capability as DeviceCapability, Device},
     migrator::MigrationData,
     oauth::{AccessTokenInfo, RefreshToken},
+    outbox::OutgoingCommand,
     profile::Profile,
     scoped_keys::ScopedKey,
     CachedResponse, Result,
@@ -107,6 +108,19 @@ pub(crate) struct StateV2 {
     pub(crate) session_token: Option<String>, // Hex-formatted string.
     pub(crate) last_seen_profile: Option<CachedResponse<Profile>>,
     pub(crate) in_flight_migration: Option<MigrationData>,
+    #[serde(default)]
+    pub(crate) outgoing_commands: Vec<OutgoingCommand>,
+    // Whether the server rejected `refresh_token`, see `account_state.rs`.
+    #[serde(default)]
+    pub(crate) auth_problem: bool,
+    // Scopes whose key changed, to be reported as `AccountEvent::ScopedKeyChanged`.
+    #[serde(default)]
+    pub(crate) scoped_key_changes: Vec<String>,
+    // The server's rotation timestamp of each of `scoped_keys`, see `scoped_keys.rs`.
+    #[serde(default)]
+    pub(crate) scoped_key_rotation_timestamps: HashMap<String, u64>,
+    #[serde(default)]
+    pub(crate) devices_cache: Option<CachedResponse<Vec<Device>>>,
 }
 
 impl StateV2 {
@@ -130,6 +144,12 @@ impl StateV2 {
             device_capabilities: HashSet::new(),
             session_token: None,
             in_flight_migration: None,
+            // These commands were for devices of the account we're leaving.
+            outgoing_commands: Vec::new(),
+            auth_problem: false,
+            scoped_key_changes: Vec::new(),
+            scoped_key_rotation_timestamps: HashMap::new(),
+            devices_cache: None,
         }
     }
 }
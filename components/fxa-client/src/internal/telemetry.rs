@@ -2,97 +2,132 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+pub use crate::{
+    AccountTelemetry, CommandKind, DeviceRegistrationAction, DeviceRegistrationEvent,
+    FailureReason, OAuthFlowEvent, OAuthFlowKind, ReceivedCommand, ReceivedReason, SentCommand,
+    TokenRefreshFailure,
+};
+
 use super::{error::*, FirefoxAccount};
-use serde_derive::*;
 use sync_guid::Guid;
 
 impl FirefoxAccount {
     /// Gathers and resets telemetry for this account instance.
-    /// This should be considered a short-term solution to telemetry gathering
-    /// and should called whenever consumers expect there might be telemetry,
-    /// and it should submit the telemetry to whatever telemetry system is in
-    /// use (probably glean).
-    ///
-    /// The data is returned as a JSON string, which consumers should parse
-    /// forgivingly (eg, be tolerant of things not existing) to try and avoid
-    /// too many changes as telemetry comes and goes.
-    pub fn gather_telemetry(&mut self) -> Result<String> {
-        let telem = self.telemetry.replace(FxaTelemetry::new());
-        Ok(serde_json::to_string(&telem)?)
+    /// This should be called whenever consumers expect there might be telemetry,
+    /// and they should submit it to whatever telemetry system is in use
+    /// (probably glean).
+    pub fn gather_telemetry(&mut self) -> AccountTelemetry {
+        self.telemetry.replace(AccountTelemetry::default())
     }
 }
 
-// A somewhat mixed-bag of all telemetry we want to collect. The idea is that
-// the app will "pull" telemetry via a new API whenever it thinks there might
-// be something to record.
-// It's considered a temporary solution until either we can record it directly
-// (eg, via glean) or we come up with something better.
+// The telemetry is kept in memory until the app "pulls" it, whenever it thinks
+// there might be something to record.
 // Note that this means we'll lose telemetry if we crash between gathering it
 // here and the app submitting it, but that should be rare (in practice,
-// apps will submit it directly after an operation that generated telememtry)
-
-/// The reason a tab/command was received.
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "kebab-case")]
-pub enum ReceivedReason {
-    /// A push notification for the command was received.
-    Push,
-    /// Discovered while handling a push notification for a later message.
-    PushMissed,
-    /// Explicit polling for missed commands.
-    Poll,
+// apps will submit it directly after an operation that generated telemetry)
+
+// We have a naive strategy to avoid unbounded memory growth - the intention
+// is that if any platform lets things grow to hit these limits, it's probably
+// never going to consume anything - so it doesn't matter what we discard (ie,
+// there's no good reason to have a smarter circular buffer etc)
+const MAX_EVENTS: usize = 200;
+
+fn push_bounded<T>(events: &mut Vec<T>, event: T) {
+    if events.len() < MAX_EVENTS {
+        events.push(event);
+    }
 }
 
-#[derive(Debug, Serialize)]
-pub struct SentCommand {
-    pub flow_id: String,
-    pub stream_id: String,
+impl AccountTelemetry {
+    pub(crate) fn record_oauth_flow(&mut self, event: OAuthFlowEvent) {
+        push_bounded(&mut self.oauth_flows, event);
+    }
+
+    pub(crate) fn record_token_refresh_failure(&mut self, failure: TokenRefreshFailure) {
+        push_bounded(&mut self.token_refresh_failures, failure);
+    }
+
+    pub(crate) fn record_command_sent(&mut self, sent: SentCommand) {
+        push_bounded(&mut self.commands_sent, sent);
+    }
+
+    pub(crate) fn record_command_received(&mut self, recd: ReceivedCommand) {
+        push_bounded(&mut self.commands_received, recd);
+    }
+
+    pub(crate) fn record_device_registration(&mut self, event: DeviceRegistrationEvent) {
+        push_bounded(&mut self.device_registrations, event);
+    }
 }
 
-impl Default for SentCommand {
-    fn default() -> Self {
+impl SentCommand {
+    /// Start a new flow for `command`, with random ids.
+    pub(crate) fn new_flow(command: CommandKind) -> Self {
         Self {
+            command,
             flow_id: Guid::random().to_string(),
             stream_id: Guid::random().to_string(),
         }
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct ReceivedCommand {
-    pub flow_id: String,
-    pub stream_id: String,
-    pub reason: ReceivedReason,
+impl From<&Error> for FailureReason {
+    fn from(err: &Error) -> Self {
+        match err.kind() {
+            ErrorKind::RemoteError { code: 401, .. }
+            | ErrorKind::NoRefreshToken
+            | ErrorKind::NoSessionToken
+            | ErrorKind::NoCachedToken(_) => FailureReason::Authentication,
+            ErrorKind::RequestError(_) => FailureReason::Network,
+            ErrorKind::BackoffError(_) => FailureReason::Backoff,
+            ErrorKind::RemoteError { .. }
+            | ErrorKind::UnexpectedStatus(_)
+            | ErrorKind::UnrecoverableServerError(_) => FailureReason::Server,
+            _ => FailureReason::Other,
+        }
+    }
 }
 
-// We have a naive strategy to avoid unbounded memory growth - the intention
-// is that if any platform lets things grow to hit these limits, it's probably
-// never going to consume anything - so it doesn't matter what we discard (ie,
-// there's no good reason to have a smarter circular buffer etc)
-const MAX_TAB_EVENTS: usize = 200;
-
-#[derive(Debug, Default, Serialize)]
-pub struct FxaTelemetry {
-    commands_sent: Vec<SentCommand>,
-    commands_received: Vec<ReceivedCommand>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl FxaTelemetry {
-    pub fn new() -> Self {
-        FxaTelemetry {
-            ..Default::default()
+    #[test]
+    fn test_failure_reason() {
+        let auth_error: Error = ErrorKind::RemoteError {
+            code: 401,
+            errno: 110,
+            error: "Unauthorized".to_string(),
+            message: "Invalid authentication token".to_string(),
+            info: "".to_string(),
         }
-    }
-
-    pub fn record_tab_sent(&mut self, sent: SentCommand) {
-        if self.commands_sent.len() < MAX_TAB_EVENTS {
-            self.commands_sent.push(sent);
+        .into();
+        assert_eq!(
+            FailureReason::from(&auth_error),
+            FailureReason::Authentication
+        );
+        let server_error: Error = ErrorKind::RemoteError {
+            code: 500,
+            errno: 999,
+            error: "Internal Server Error".to_string(),
+            message: "Unspecified error".to_string(),
+            info: "".to_string(),
         }
+        .into();
+        assert_eq!(FailureReason::from(&server_error), FailureReason::Server);
+        let backoff_error: Error = ErrorKind::BackoffError(30).into();
+        assert_eq!(FailureReason::from(&backoff_error), FailureReason::Backoff);
+        let other_error: Error = ErrorKind::TabTooLarge.into();
+        assert_eq!(FailureReason::from(&other_error), FailureReason::Other);
     }
 
-    pub fn record_tab_received(&mut self, recd: ReceivedCommand) {
-        if self.commands_received.len() < MAX_TAB_EVENTS {
-            self.commands_received.push(recd);
+    #[test]
+    fn test_events_are_bounded() {
+        let mut telemetry = AccountTelemetry::default();
+        for _ in 0..MAX_EVENTS + 1 {
+            telemetry.record_command_sent(SentCommand::new_flow(CommandKind::SendTab));
         }
+        assert_eq!(telemetry.commands_sent.len(), MAX_EVENTS);
     }
 }
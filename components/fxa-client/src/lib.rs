@@ -35,6 +35,10 @@
 //!
 //! * If the user opts to sign out of the application, calling [`disconnect`](FirefoxAccount::disconnect)
 //!   and then discarding any persisted account data.
+//!
+//! * Throughout, keep the application's UI in sync with the [`AccountState`] of the account
+//!   by registering an [`AccountStateObserver`] with
+//!   [`register_state_observer`](FirefoxAccount::register_state_observer).
 
 #![allow(unknown_lints)]
 #![warn(rust_2018_idioms)]
@@ -318,7 +322,17 @@ impl FirefoxAccount {
     ///   - `code` - the OAuth authorization code obtained from the redirect URI.
     ///   - `state` - the OAuth state parameter obtained from the redirect URI.
     ///
-    pub fn complete_oauth_flow(&self, code: &str, state: &str) -> Result<(), FxaError> {
+    /// # Notes
+    ///
+    ///    - If the flow requested the `openid` scope, the ID token issued by the server
+    ///      is verified against the server's published keys, and its claims are returned
+    ///      as an [`IdTokenClaims`] struct. Otherwise this method returns `None`.
+    ///
+    pub fn complete_oauth_flow(
+        &self,
+        code: &str,
+        state: &str,
+    ) -> Result<Option<IdTokenClaims>, FxaError> {
         Ok(self
             .internal
             .lock()
@@ -359,6 +373,131 @@ impl FirefoxAccount {
     pub fn disconnect(&self) {
         self.internal.lock().unwrap().disconnect()
     }
+
+    /// Get the current state of the account.
+    ///
+    /// This method returns an [`AccountState`] describing whether the application is
+    /// connected to the user's account, in the process of connecting, or needs the user
+    /// to sign in again. It does not make any network requests.
+    ///
+    pub fn get_state(&self) -> AccountState {
+        self.internal.lock().unwrap().get_state()
+    }
+
+    /// Register an observer to be notified every time the account changes state.
+    ///
+    /// The observer is told about transitions caused by the methods of this object,
+    /// such as [`complete_oauth_flow`](FirefoxAccount::complete_oauth_flow) moving the
+    /// account to [`Connected`](AccountState::Connected) or the server rejecting the
+    /// account's tokens moving it to [`AuthProblem`](AccountState::AuthProblem).
+    /// It replaces any previously-registered observer.
+    ///
+    /// # Notes
+    ///
+    ///    - The observer is called on the thread of the method which caused the transition,
+    ///      while the account is locked. It must not call methods of this object itself; it
+    ///      should instead dispatch any further work to be done asynchronously.
+    ///    - The observer isn't part of the persisted account state, and needs to be
+    ///      registered again on any object created by [`from_json`](FirefoxAccount::from_json).
+    ///
+    pub fn register_state_observer(&self, observer: Box<dyn AccountStateObserver>) {
+        self.internal
+            .lock()
+            .unwrap()
+            .register_state_observer(observer)
+    }
+}
+
+/// # Pairing other devices
+///
+/// An application which is connected to the user's account can act as the "authority"
+/// in a device pairing flow, signing in a new device (the "supplicant") without the user
+/// having to type their password on it.
+///
+/// The application opens a channel on the pairing channel server, passes its id to
+/// [`begin_pairing_authority`](FirefoxAccount::begin_pairing_authority), and shows the
+/// returned pairing URL as a QR code for the new device to scan. From then on, it relays
+/// every message it receives on the channel to
+/// [`handle_pairing_message`](FirefoxAccount::handle_pairing_message), and sends the
+/// messages given to its [`PairingChannelTransport`]. Once the new device has sent its
+/// authorization request, the application asks the user whether to approve it and calls
+/// [`approve_pairing`](FirefoxAccount::approve_pairing) or
+/// [`cancel_pairing`](FirefoxAccount::cancel_pairing).
+///
+/// All the messages on the channel are encrypted with a key which is only shared through
+/// the QR code, so the channel server can't read them.
+///
+impl FirefoxAccount {
+    /// Start pairing a new device, as the pairing authority.
+    ///
+    /// This method returns the pairing URL that the new device should scan, which holds
+    /// the channel id and a freshly-generated key for encrypting the channel's messages.
+    /// Any pairing that was already in progress is cancelled.
+    ///
+    /// # Arguments
+    ///
+    ///    - `channel_id` - the id of the channel the application opened on the pairing
+    ///      channel server.
+    ///    - `transport` - where to send the messages for the new device. They're already
+    ///      encrypted and should be sent on the channel as they are.
+    ///
+    /// # Notes
+    ///
+    ///    - The account must hold a session token, which is used to authorize the new
+    ///      device; this is the case if it signed in with the
+    ///      `https://identity.mozilla.com/tokens/session` scope.
+    ///    - The pairing isn't part of the persisted account state: it has to be started
+    ///      over if the application is restarted.
+    ///
+    pub fn begin_pairing_authority(
+        &self,
+        channel_id: &str,
+        transport: Box<dyn PairingChannelTransport>,
+    ) -> Result<String, FxaError> {
+        Ok(self
+            .internal
+            .lock()
+            .unwrap()
+            .begin_pairing_authority(channel_id, transport)?)
+    }
+
+    /// Handle a message received on the pairing channel.
+    ///
+    /// This method decrypts the message, advances the pairing accordingly, and returns
+    /// the resulting [`PairingAuthorityState`]. When the new device sends its authorization
+    /// request, the state becomes [`AwaitingApproval`](PairingAuthorityState::AwaitingApproval)
+    /// and the application should ask the user whether to approve it.
+    ///
+    /// # Arguments
+    ///
+    ///    - `message` - the message, exactly as it was received on the channel.
+    ///
+    pub fn handle_pairing_message(&self, message: &str) -> Result<PairingAuthorityState, FxaError> {
+        Ok(self
+            .internal
+            .lock()
+            .unwrap()
+            .handle_pairing_message(message)?)
+    }
+
+    /// Approve the new device's authorization request, once the user has agreed to it.
+    ///
+    /// The user of the new device has to confirm the pairing too. Once both have, an OAuth
+    /// authorization code for the new device is created with the account's session token
+    /// and sent to it, and the state becomes [`Completed`](PairingAuthorityState::Completed).
+    ///
+    pub fn approve_pairing(&self) -> Result<PairingAuthorityState, FxaError> {
+        Ok(self.internal.lock().unwrap().approve_pairing()?)
+    }
+
+    /// Stop the pairing in progress, if any.
+    ///
+    /// Applications should call this when the user declines the new device's request,
+    /// or when the channel is closed, and then close the channel themselves.
+    ///
+    pub fn cancel_pairing(&self) {
+        self.internal.lock().unwrap().cancel_pairing()
+    }
 }
 
 /// # User Profile info
@@ -482,6 +621,9 @@ impl FirefoxAccount {
     ///
     ///    - Device metadata is only visible to applications that have been
     ///      granted the `https://identity.mozilla.com/apps/oldsync` scope.
+    ///    - The list is cached in the persisted account state for one minute, or the TTL
+    ///      set with [`set_devices_cache_ttl`](FirefoxAccount::set_devices_cache_ttl).
+    ///      Push messages about devices connecting or disconnecting update the cache.
     ///
     pub fn get_devices(&self, ignore_cache: bool) -> Result<Vec<Device>, FxaError> {
         Ok(self
@@ -494,6 +636,26 @@ impl FirefoxAccount {
             .collect::<Result<_, _>>()?)
     }
 
+    /// Set how long [`get_devices`](FirefoxAccount::get_devices) caches the list of devices for.
+    ///
+    /// # Arguments
+    ///
+    ///    - `ttl_secs` - how long the list of devices is fresh for, in seconds.
+    ///
+    /// # Notes
+    ///
+    ///    - This setting isn't persisted, applications should set it again when
+    ///      restoring the account with [`from_json`](FirefoxAccount::from_json).
+    ///
+    pub fn set_devices_cache_ttl(&self, ttl_secs: i64) {
+        // Signedness converstion for Kotlin compatibility :-/
+        let ttl_secs = u64::try_from(ttl_secs).unwrap_or_default();
+        self.internal
+            .lock()
+            .unwrap()
+            .set_devices_cache_ttl(ttl_secs.saturating_mul(1000))
+    }
+
     /// Get the list of all client applications attached to the user's account.
     ///
     /// This method returns a list of [`AttachedClient`] structs representing all the applications
@@ -641,6 +803,21 @@ impl FirefoxAccount {
         Ok(self.internal.lock().unwrap().handle_push_message(payload)?)
     }
 
+    /// Take the account events which were detected locally rather than delivered by push.
+    ///
+    /// **💾 This method alters the persisted account state.**
+    ///
+    /// Some changes to the user's account are noticed by the client itself, for example
+    /// that a scoped key has changed when refreshing an access token with
+    /// [`get_access_token`](FirefoxAccount::get_access_token). Applications should call
+    /// this method after such calls and process the returned [`AccountEvent`]s like those
+    /// returned by [`handle_push_message`](FirefoxAccount::handle_push_message).
+    /// Each event is only returned once.
+    ///
+    pub fn take_account_events(&self) -> Vec<AccountEvent> {
+        self.internal.lock().unwrap().take_account_events()
+    }
+
     /// Poll the server for any pending device commands.
     ///
     /// **💾 This method alters the persisted account state.**
@@ -677,6 +854,9 @@ impl FirefoxAccount {
     ///
     /// # Notes
     ///
+    ///    - If the server can't be reached, the command is kept in the persisted account state
+    ///      and sent later; see [`get_pending_outgoing_commands`](
+    ///      FirefoxAccount::get_pending_outgoing_commands).
     ///    - If the given device id does not existing or is not capable of receiving tabs,
     ///      this method will throw an [`Other`](FxaError::Other) error.
     ///        - (Yeah...sorry. This should be changed to do something better.)
@@ -697,6 +877,90 @@ impl FirefoxAccount {
             .unwrap()
             .send_single_tab(target_device_id, title, url)?)
     }
+
+    /// Use device commands to send several tabs, with their navigation history, to another device.
+    ///
+    /// **💾 This method alters the persisted account state.**
+    ///
+    /// If a device on the account has registered the [`SendTab`](DeviceCapability::SendTab)
+    /// capability, this method can be used to send it tabs. The receiving device gets a
+    /// [`TabReceived`](IncomingDeviceCommand::TabReceived) command for each tab.
+    ///
+    /// # Notes
+    ///
+    ///    - The tabs are packed into as few device commands as the server's limit on their size
+    ///      allows. If the history of a tab doesn't fit in one command, its oldest entries are
+    ///      left out, and if its current page doesn't fit, an [`Other`](FxaError::Other) error
+    ///      is thrown before any tab is sent.
+    ///    - Devices running older versions of this component, or of Firefox, only open the first
+    ///      tab in each command.
+    ///    - If the server can't be reached, the command is kept in the persisted account state
+    ///      and sent later; see [`get_pending_outgoing_commands`](
+    ///      FirefoxAccount::get_pending_outgoing_commands).
+    ///    - If the given device id does not existing or is not capable of receiving tabs,
+    ///      this method will throw an [`Other`](FxaError::Other) error.
+    ///    - Device commands functionality is only available to applications that have been
+    ///      granted the `https://identity.mozilla.com/apps/oldsync` scope.
+    ///
+    pub fn send_tabs(&self, target_device_id: &str, tabs: Vec<TabToSend>) -> Result<(), FxaError> {
+        let tabs = tabs
+            .into_iter()
+            .map(|tab| tab.entries.into_iter().map(From::from).collect::<Vec<_>>())
+            .collect();
+        Ok(self
+            .internal
+            .lock()
+            .unwrap()
+            .send_tabs(target_device_id, tabs)?)
+    }
+
+    /// Use device commands to close tabs on another device.
+    ///
+    /// **💾 This method alters the persisted account state.**
+    ///
+    /// If a device on the account has registered the [`CloseTabs`](DeviceCapability::CloseTabs)
+    /// capability, this method can be used to close the tabs it has open at any of the given URLs.
+    ///
+    /// # Notes
+    ///
+    ///    - If the server can't be reached, the command is kept in the persisted account state
+    ///      and sent later; see [`get_pending_outgoing_commands`](
+    ///      FirefoxAccount::get_pending_outgoing_commands).
+    ///    - If the given device id does not existing or is not capable of closing tabs,
+    ///      this method will throw an [`Other`](FxaError::Other) error.
+    ///    - Device commands functionality is only available to applications that have been
+    ///      granted the `https://identity.mozilla.com/apps/oldsync` scope.
+    ///
+    pub fn close_tabs(&self, target_device_id: &str, urls: Vec<String>) -> Result<(), FxaError> {
+        Ok(self
+            .internal
+            .lock()
+            .unwrap()
+            .close_tabs(target_device_id, urls)?)
+    }
+
+    /// Get the device commands which could not be sent because the application was offline.
+    ///
+    /// When [`send_single_tab`](FirefoxAccount::send_single_tab), [`send_tabs`](
+    /// FirefoxAccount::send_tabs) or [`close_tabs`](FirefoxAccount::close_tabs) can't reach
+    /// the server, the commands are kept in the persisted account state and sent after the
+    /// next successful request to the server. Applications can use this method to show
+    /// the user which tabs will be sent once they're back online.
+    ///
+    /// # Notes
+    ///
+    ///    - Commands which still haven't been sent a day later, or which the server keeps
+    ///      rejecting, are dropped.
+    ///
+    pub fn get_pending_outgoing_commands(&self) -> Vec<OutgoingDeviceCommand> {
+        self.internal
+            .lock()
+            .unwrap()
+            .get_pending_outgoing_commands()
+            .into_iter()
+            .flat_map(|command| command.into_device_commands())
+            .collect()
+    }
 }
 
 /// # Account Management URLs
@@ -877,8 +1141,9 @@ impl FirefoxAccount {
     /// When a signed-in application receives an incoming device pairing request, it can
     /// use this method to grant the request and generate a corresponding OAuth authorization
     /// code. This code would then be passed back to the connecting device over the
-    /// pairing channel (a process which is not currently supported by any code in this
-    /// component).
+    /// pairing channel. Applications which would rather not implement the pairing protocol
+    /// themselves can use [`begin_pairing_authority`](FirefoxAccount::begin_pairing_authority),
+    /// which does all of this.
     ///
     /// # Arguments
     ///
@@ -910,23 +1175,33 @@ impl FirefoxAccount {
 
 /// # Telemetry Methods
 ///
-/// This component does not currently submit telemetry via Glean, but it *does* gather
-/// a small amount of telemetry about send-tab that the application may submit on its
-/// behalf.
+/// This component does not submit telemetry via Glean itself, but it *does* record
+/// what happened to the account, so that the application can submit it on its behalf:
+/// the outcome of OAuth flows, failures to refresh access tokens, the device commands
+/// sent and received, and changes to the device record.
 ///
 impl FirefoxAccount {
-    /// Collect and return telemetry about send-tab attempts.
+    /// Collect and return the telemetry recorded since the last call.
+    ///
+    /// This method returns an [`AccountTelemetry`] struct with the events recorded since
+    /// the last time it was called, or since the account was created, and clears them.
+    /// Applications would typically call it after any operation on the account, and
+    /// record the events as Glean metrics.
     ///
     /// Applications that register the [`SendTab`](DeviceCapability::SendTab) capability
-    /// should also arrange to submit "sync ping" telemetry. Calling this method will
-    /// return a JSON string of telemetry data that can be incorporated into that ping.
+    /// should also arrange to submit "sync ping" telemetry, which includes the flow ids
+    /// of the [`commands_sent`](AccountTelemetry::commands_sent) and
+    /// [`commands_received`](AccountTelemetry::commands_received).
     ///
-    /// Sorry, this is not particularly carefully documented because it is intended
-    /// as a stop-gap until we get native Glean support. If you know how to submit
-    /// a sync ping, you'll know what to do with the contents of the JSON string.
+    /// # Notes
     ///
-    pub fn gather_telemetry(&self) -> Result<String, FxaError> {
-        Ok(self.internal.lock().unwrap().gather_telemetry()?)
+    ///    - The telemetry isn't part of the persisted account state, so any events that
+    ///      haven't been gathered are lost when the application is restarted.
+    ///    - Only a limited number of each kind of event is kept, so applications that
+    ///      never call this method don't grow their memory usage unboundedly.
+    ///
+    pub fn gather_telemetry(&self) -> AccountTelemetry {
+        self.internal.lock().unwrap().gather_telemetry()
     }
 }
 
@@ -1014,6 +1289,95 @@ pub struct AuthorizationInfo {
     pub active: bool,
 }
 
+/// The verified claims of the OpenID Connect ID token issued at the end of an OAuth flow.
+///
+/// See [`complete_oauth_flow`](FirefoxAccount::complete_oauth_flow).
+///
+pub struct IdTokenClaims {
+    /// The user's account id.
+    pub uid: String,
+    /// The server which issued the token.
+    pub issuer: String,
+    /// When the token was issued, in seconds since the epoch.
+    pub issued_at: i64,
+    /// When the token expires, in seconds since the epoch.
+    pub expires_at: i64,
+    /// When the user last authenticated, in seconds since the epoch.
+    pub auth_time: Option<i64>,
+    /// How the user authenticated, for example `pwd` or `otp`.
+    pub amr: Vec<String>,
+}
+
+/// The state of the application's connection to the user's account.
+///
+/// See [`get_state`](FirefoxAccount::get_state) and [`AccountStateObserver`].
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountState {
+    /// Not connected to any account, and not in the process of connecting.
+    Disconnected,
+    /// An OAuth flow was started with [`begin_oauth_flow`](FirefoxAccount::begin_oauth_flow)
+    /// or [`begin_pairing_flow`](FirefoxAccount::begin_pairing_flow), and is waiting to be
+    /// completed with [`complete_oauth_flow`](FirefoxAccount::complete_oauth_flow).
+    Authenticating,
+    /// Connected to the user's account.
+    Connected,
+    /// Connected to the user's account, but the server rejected the account's tokens,
+    /// for example because the user changed their password on another device.
+    ///
+    /// The application should ask the user to sign in again. The account goes back
+    /// to [`Connected`](AccountState::Connected) once it does, or if a later call to
+    /// [`check_authorization_status`](FirefoxAccount::check_authorization_status) finds
+    /// that the tokens are valid after all.
+    AuthProblem,
+    /// A migration from legacy sign-in data is in flight, see
+    /// [`migrate_from_session_token`](FirefoxAccount::migrate_from_session_token).
+    Migrating,
+}
+
+/// An observer of the [`AccountState`] of a [`FirefoxAccount`].
+///
+/// See [`register_state_observer`](FirefoxAccount::register_state_observer).
+///
+pub trait AccountStateObserver: Send + Sync {
+    /// Called when the account moves from `old_state` to `new_state`.
+    fn on_state_changed(&self, old_state: AccountState, new_state: AccountState);
+}
+
+/// Where the messages of a pairing started with
+/// [`begin_pairing_authority`](FirefoxAccount::begin_pairing_authority) are sent.
+///
+/// Applications implement this to send the messages on the pairing channel they opened.
+///
+pub trait PairingChannelTransport: Send + Sync {
+    /// Send an encrypted message to the new device.
+    fn send(&self, message: String);
+}
+
+/// How far along a pairing started with
+/// [`begin_pairing_authority`](FirefoxAccount::begin_pairing_authority) is.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PairingAuthorityState {
+    /// Waiting for the new device to send its authorization request.
+    WaitingForRequest,
+    /// The new device asked for access to the account. The application should show what it
+    /// asked for, and call [`approve_pairing`](FirefoxAccount::approve_pairing) if the user
+    /// agrees to it or [`cancel_pairing`](FirefoxAccount::cancel_pairing) otherwise.
+    AwaitingApproval {
+        /// The OAuth client id of the new device's application.
+        client_id: String,
+        /// The OAuth scopes requested by the new device.
+        scopes: Vec<String>,
+    },
+    /// The user approved the request, and we're waiting for the user of the new device
+    /// to confirm the pairing.
+    AwaitingSupplicantApproval,
+    /// The new device was sent an authorization code to sign in with. The application can
+    /// close the channel.
+    Completed,
+}
+
 /// Additional metrics tracking parameters to include in an OAuth request.
 ///
 pub struct MetricsParams {
@@ -1110,6 +1474,25 @@ pub struct Device {
     pub push_endpoint_expired: bool,
     pub is_current_device: bool,
     pub last_access_time: Option<i64>,
+    /// How recently the device was used, derived from `last_access_time`.
+    pub activity: DeviceActivity,
+}
+
+/// How recently a [`Device`] was used.
+///
+/// Applications can use this to pick sensible "send tab" targets, for example by
+/// listing online devices first and leaving out inactive ones.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceActivity {
+    /// Used in the last hour, so probably online.
+    Online,
+    /// Used in the last three weeks.
+    Recent,
+    /// Not used in the last three weeks.
+    Inactive,
+    /// The server didn't say when the device was last used.
+    Unknown,
 }
 
 /// Enumeration for the different types of device.
@@ -1153,11 +1536,13 @@ pub struct DevicePushSubscription {
 /// so consumers simply need to select which ones they want to support, and can
 /// use the variants of this enum to do so.
 ///
-/// In practice, the only currently-supported command is the ability to receive a tab.
+/// In practice, the only currently-supported commands are the abilities to receive a tab
+/// and to close tabs.
 ///
 #[derive(Debug)]
 pub enum DeviceCapability {
     SendTab,
+    CloseTabs,
 }
 
 /// An event that happened on the user's account.
@@ -1212,6 +1597,33 @@ pub enum AccountEvent {
         device_id: String,
         is_local_device: bool,
     },
+    /// Sent when the key for a scope has changed, because it was rotated by the server
+    /// or because the user reset their password.
+    ///
+    /// Data encrypted with the old key can't be decrypted anymore. When receiving this
+    /// event, the application should reset its local state for that scope, such as the
+    /// sync metadata for `https://identity.mozilla.com/apps/oldsync`, rather than keep
+    /// failing to decrypt. If the new key isn't known yet, the account is in the
+    /// [`AuthProblem`](AccountState::AuthProblem) state until the user signs in again.
+    ScopedKeyChanged { scope: String },
+}
+
+/// A command for another device which is waiting to be sent.
+///
+/// See [`get_pending_outgoing_commands`](FirefoxAccount::get_pending_outgoing_commands).
+///
+#[derive(Debug)]
+pub enum OutgoingDeviceCommand {
+    /// A tab which will be sent to the device with the id `target_device_id`.
+    SendTab {
+        target_device_id: String,
+        payload: SendTabPayload,
+    },
+    /// Tabs which the device with the id `target_device_id` will be asked to close.
+    CloseTabs {
+        target_device_id: String,
+        payload: CloseTabsPayload,
+    },
 }
 
 /// A command invoked by another device.
@@ -1227,6 +1639,11 @@ pub enum IncomingDeviceCommand {
         sender: Option<Device>,
         payload: SendTabPayload,
     },
+    /// Indicates that another device has asked this device to close some tabs.
+    TabsClosed {
+        sender: Option<Device>,
+        payload: CloseTabsPayload,
+    },
 }
 
 /// The payload sent when invoking a "send tab" command.
@@ -1257,6 +1674,34 @@ pub struct TabHistoryEntry {
     pub url: String,
 }
 
+/// A tab to send to another device with [`send_tabs`](FirefoxAccount::send_tabs).
+///
+#[derive(Debug)]
+pub struct TabToSend {
+    /// The navigation history of the tab.
+    ///
+    /// The last item in this list is the page to be displayed, and must be given.
+    pub entries: Vec<TabHistoryEntry>,
+}
+
+/// The payload sent when invoking a "close tabs" command.
+///
+#[derive(Debug)]
+pub struct CloseTabsPayload {
+    /// The URLs of the tabs to close.
+    ///
+    /// The application should close every open tab whose URL is in this list.
+    pub urls: Vec<String>,
+    /// A unique identifier to be included in close-tabs metrics.
+    ///
+    /// The application should treat this as opaque.
+    pub flow_id: String,
+    /// A unique identifier to be included in close-tabs metrics.
+    ///
+    /// The application should treat this as opaque.
+    pub stream_id: String,
+}
+
 /// A client connected to the user's account.
 ///
 /// This struct provides metadata about a client connected to the user's account.
@@ -1335,3 +1780,156 @@ pub struct FxAMigrationResult {
     /// that do not have unsigned integers.
     pub total_duration: i64,
 }
+
+/// Telemetry about the account, returned by [`gather_telemetry`](FirefoxAccount::gather_telemetry).
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccountTelemetry {
+    /// The OAuth flows which were completed, successfully or not.
+    pub oauth_flows: Vec<OAuthFlowEvent>,
+    /// The attempts to get a new access token which failed.
+    pub token_refresh_failures: Vec<TokenRefreshFailure>,
+    /// The send-tab and close-tabs commands sent to other devices, once they were
+    /// delivered. Each tab sent is recorded separately.
+    pub commands_sent: Vec<SentCommand>,
+    /// The send-tab and close-tabs commands received from other devices. Each tab
+    /// received is recorded separately.
+    pub commands_received: Vec<ReceivedCommand>,
+    /// The changes made to this device's record on the server.
+    pub device_registrations: Vec<DeviceRegistrationEvent>,
+}
+
+/// The outcome of an OAuth flow completed with
+/// [`complete_oauth_flow`](FirefoxAccount::complete_oauth_flow).
+///
+/// Attempts to complete a flow with an unknown `state` aren't recorded, since there's
+/// no flow they belong to.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OAuthFlowEvent {
+    /// How the flow was started.
+    pub kind: OAuthFlowKind,
+    /// The `entrypoint` the flow was started with.
+    pub entrypoint: String,
+    /// Why the flow failed, or `None` if the account is now connected.
+    pub failure_reason: Option<FailureReason>,
+}
+
+/// How an OAuth flow was started.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OAuthFlowKind {
+    /// With [`begin_oauth_flow`](FirefoxAccount::begin_oauth_flow).
+    SignIn,
+    /// With [`begin_pairing_flow`](FirefoxAccount::begin_pairing_flow).
+    Pairing,
+}
+
+/// An attempt to get a new access token which failed.
+///
+/// Access tokens are fetched by [`get_access_token`](FirefoxAccount::get_access_token),
+/// and by the methods which use them, such as [`get_profile`](FirefoxAccount::get_profile).
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenRefreshFailure {
+    /// The scope of the access token.
+    pub scope: String,
+    /// Why the token couldn't be fetched.
+    pub reason: FailureReason,
+}
+
+/// Why an operation recorded in [`AccountTelemetry`] failed.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureReason {
+    /// The server rejected the account's tokens, or the account doesn't have the
+    /// tokens needed for the operation.
+    Authentication,
+    /// The server couldn't be reached.
+    Network,
+    /// The server asked us to back off, and we didn't make the request.
+    Backoff,
+    /// The server returned an error.
+    Server,
+    /// Anything else, such as a response which couldn't be decrypted.
+    Other,
+}
+
+/// A tab sent to another device with a send-tab command, or tabs closed on
+/// another device with a close-tabs command.
+///
+/// The flow and stream ids are also sent to the receiving device, so that the sending
+/// and receiving of a command can be matched in telemetry.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SentCommand {
+    /// Which command was sent.
+    pub command: CommandKind,
+    pub flow_id: String,
+    pub stream_id: String,
+}
+
+/// A tab received from another device with a send-tab command, or a request
+/// from another device to close tabs with a close-tabs command.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReceivedCommand {
+    /// Which command was received.
+    pub command: CommandKind,
+    pub flow_id: String,
+    pub stream_id: String,
+    /// How the command was received.
+    pub reason: ReceivedReason,
+}
+
+/// The kind of device command recorded in [`AccountTelemetry`].
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandKind {
+    /// A send-tab command, for a single tab.
+    SendTab,
+    /// A close-tabs command.
+    CloseTabs,
+}
+
+/// How a device command was received.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReceivedReason {
+    /// A push notification for the command was received.
+    Push,
+    /// Discovered while handling a push notification for a later message.
+    PushMissed,
+    /// Explicit polling for missed commands.
+    Poll,
+}
+
+/// A change to this device's record on the server.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceRegistrationEvent {
+    /// What was changed.
+    pub action: DeviceRegistrationAction,
+    /// Why the change failed, or `None` if the server accepted it.
+    pub failure_reason: Option<FailureReason>,
+}
+
+/// A change to this device's record on the server.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceRegistrationAction {
+    /// The device record was created by [`initialize_device`](FirefoxAccount::initialize_device).
+    Register,
+    /// The device record was re-created after the account got a new refresh token.
+    Restore,
+    /// The device's commands were updated, for example by
+    /// [`ensure_capabilities`](FirefoxAccount::ensure_capabilities).
+    UpdateCapabilities,
+    /// The device was renamed with [`set_device_name`](FirefoxAccount::set_device_name).
+    UpdateName,
+    /// The device's push subscription was updated with
+    /// [`set_push_subscription`](FirefoxAccount::set_push_subscription).
+    UpdatePushSubscription,
+    /// The device record was destroyed by [`disconnect`](FirefoxAccount::disconnect).
+    Destroy,
+}
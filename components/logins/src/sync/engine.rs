@@ -19,8 +19,9 @@ use sql_support::{self, ConnExt};
 use std::collections::HashSet;
 use std::sync::Arc;
 use sync15::{
-    telemetry, CollSyncIds, CollectionRequest, EngineSyncAssociation, IncomingChangeset,
-    OutgoingChangeset, Payload, ServerTimestamp, SyncEngine,
+    telemetry::{self, ReconcileReason},
+    CollSyncIds, CollectionRequest, EngineSyncAssociation, IncomingChangeset, OutgoingChangeset,
+    Payload, ServerTimestamp, SyncEngine,
 };
 use sync_guid::Guid;
 
@@ -82,7 +83,7 @@ impl LoginsSyncEngine {
                         server_now,
                         encdec,
                     )?;
-                    telem.reconciled(1);
+                    telem.reconciled_with(ReconcileReason::Merge, 1);
                 }
                 (Some(_mirror), None) => {
                     log::debug!("  Forwarding mirror to remote");
@@ -91,8 +92,8 @@ impl LoginsSyncEngine {
                 }
                 (None, Some(local)) => {
                     log::debug!("  Conflicting record without shared parent, using newer");
-                    plan.plan_two_way_merge(&local.login, (upstream, upstream_time));
-                    telem.reconciled(1);
+                    let reason = plan.plan_two_way_merge(&local.login, (upstream, upstream_time));
+                    telem.reconciled_with(reason, 1);
                 }
                 (None, None) => {
                     if let Some(dupe) = self.find_dupe_login(&upstream)? {
@@ -102,11 +103,12 @@ impl LoginsSyncEngine {
                             dupe.guid()
                         );
                         plan.plan_two_way_merge(&dupe, (upstream, upstream_time));
+                        telem.reconciled_with(ReconcileReason::Dedupe, 1);
                     } else {
                         log::debug!("  No dupe found, inserting into mirror");
                         plan.plan_mirror_insert(upstream, upstream_time, false);
+                        telem.applied(1);
                     }
-                    telem.applied(1);
                 }
             }
         }
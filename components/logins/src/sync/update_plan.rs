@@ -11,7 +11,7 @@ use crate::util;
 use rusqlite::{named_params, Connection};
 use sql_support::SqlInterruptScope;
 use std::time::SystemTime;
-use sync15::ServerTimestamp;
+use sync15::{telemetry::ReconcileReason, ServerTimestamp};
 use sync_guid::Guid;
 
 #[derive(Default, Debug, Clone)]
@@ -25,17 +25,21 @@ pub(crate) struct UpdatePlan {
 }
 
 impl UpdatePlan {
+    // Returns whether the local or the upstream login was kept.
     pub fn plan_two_way_merge(
         &mut self,
         local: &EncryptedLogin,
         upstream: (EncryptedLogin, ServerTimestamp),
-    ) {
+    ) -> ReconcileReason {
         let is_override =
             local.record.time_password_changed > upstream.0.record.time_password_changed;
         self.mirror_inserts
             .push((upstream.0, upstream.1.as_millis() as i64, is_override));
-        if !is_override {
+        if is_override {
+            ReconcileReason::LocalWins
+        } else {
             self.delete_local.push(local.guid());
+            ReconcileReason::RemoteWins
         }
     }
 
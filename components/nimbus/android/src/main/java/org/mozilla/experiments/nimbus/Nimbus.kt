@@ -27,7 +27,9 @@ import org.mozilla.experiments.nimbus.internal.AvailableRandomizationUnits
 import org.mozilla.experiments.nimbus.internal.EnrolledExperiment
 import org.mozilla.experiments.nimbus.internal.EnrollmentChangeEvent
 import org.mozilla.experiments.nimbus.internal.EnrollmentChangeEventType
+import org.mozilla.experiments.nimbus.internal.EnrollmentExplanation
 import org.mozilla.experiments.nimbus.internal.ExperimentBranch
+import org.mozilla.experiments.nimbus.internal.FeatureProvenance
 import org.mozilla.experiments.nimbus.internal.NimbusException
 import org.mozilla.experiments.nimbus.internal.NimbusClient
 import org.mozilla.experiments.nimbus.internal.NimbusClientInterface
@@ -41,6 +43,8 @@ private const val NIMBUS_DATA_DIR: String = "nimbus_data"
 typealias Branch = ExperimentBranch
 typealias AvailableExperiment = AvailableExperiment
 typealias EnrolledExperiment = EnrolledExperiment
+typealias EnrollmentExplanation = EnrollmentExplanation
+typealias FeatureProvenance = FeatureProvenance
 
 /**
  * This is the main experiments API, which is exposed through the global [Nimbus] object.
@@ -79,6 +83,17 @@ interface NimbusInterface {
      */
     fun getExperimentBranches(experimentId: String): List<Branch>? = listOf()
 
+    /**
+     * Explain how the given experiment is evaluated for this client: whether the app name and
+     * channel match, which part of the targeting failed, and the bucket and branch the client
+     * falls in. This doesn't change any enrollments, so is safe to call from debugging tools.
+     *
+     * @param experimentId The string experiment-id or "slug" to explain
+     *
+     * @return An [EnrollmentExplanation], or `null` if there's no such experiment
+     */
+    fun explainEnrollment(experimentId: String): EnrollmentExplanation? = null
+
     /**
      * Get the variables needed to configure the feature given by `featureId`.
      *
@@ -97,6 +112,18 @@ interface NimbusInterface {
     @AnyThread
     fun getVariables(featureId: String, recordExposureEvent: Boolean = true): Variables = NullVariables.instance
 
+    /**
+     * Get the experiments and rollouts the variables for the given feature came from, highest
+     * precedence first. There's only more than one experiment for features listed in
+     * [NimbusAppInfo.coenrollingFeatureIds].
+     *
+     * @param featureId The string feature id that identifies to the feature under experiment.
+     *
+     * @return A list of [FeatureProvenance]s, which is empty if the feature isn't being configured.
+     */
+    @AnyThread
+    fun getFeatureConfigProvenance(featureId: String): List<FeatureProvenance> = listOf()
+
     /**
      * Open the database and populate the SDK so as make it usable by feature developers.
      *
@@ -149,6 +176,18 @@ interface NimbusInterface {
      */
     fun setExperimentsLocally(@RawRes file: Int) = Unit
 
+    /**
+     * Sets the app's feature manifest, as the JSON of its intermediate representation (ie, the
+     * output of `nimbus-fml --language ir`). Once it's set, branches whose feature values don't
+     * match the manifest aren't enrolled in.
+     *
+     * Like [setExperimentsLocally], this takes effect the next time [applyPendingExperiments] is
+     * called, and uses the same single threaded worker scope.
+     *
+     * @param manifestJson string representation of the feature manifest's JSON.
+     */
+    fun setFeatureManifest(manifestJson: String) = Unit
+
     /**
      * Opt into a specific branch for the given experiment.
      *
@@ -199,6 +238,24 @@ interface NimbusInterface {
      */
     fun recordExposureEvent(featureId: String) = Unit
 
+    /**
+     * Records that a behavioral event happened [count] times, so that experiments can target
+     * on it with the `eventCount` JEXL transform, eg, `'app_opened'|eventCount(7) >= 3`.
+     *
+     * This is performed on a background thread.
+     *
+     * @param eventId The string id of the event, eg, `"app_opened"`.
+     * @param count The number of times the event happened. Negative counts are recorded as 0.
+     */
+    fun recordEvent(eventId: String, count: Int = 1) = Unit
+
+    /**
+     * Forgets all the events recorded with [recordEvent].
+     *
+     * This is performed on a background thread.
+     */
+    fun clearEvents() = Unit
+
     /**
      * Control the opt out for all experiments at once. This is likely a user action.
      */
@@ -228,10 +285,15 @@ interface NimbusInterface {
  * This class allows client apps to configure Nimbus to point to your own server.
  * Client app developers should set up their own Nimbus infrastructure, to avoid different
  * organizations running conflicting experiments or hitting servers with extra network traffic.
+ *
+ * [signatureRootHash] is the SHA-256 fingerprint of the root certificate the server's
+ * content signatures chain up to. It defaults to the production root, so needs to be
+ * set when pointing at the stage or dev servers.
  */
 data class NimbusServerSettings(
     val url: Uri,
-    val collection: String = EXPERIMENT_COLLECTION_NAME
+    val collection: String = EXPERIMENT_COLLECTION_NAME,
+    val signatureRootHash: String? = null
 )
 
 typealias ErrorReporter = (message: String, e: Throwable) -> Unit
@@ -267,7 +329,12 @@ data class NimbusAppInfo(
      *
      * Example: mapOf("userType": "casual", "isFirstTime": "true")
      */
-    val customTargetingAttributes: Map<String, String> = mapOf()
+    val customTargetingAttributes: Map<String, String> = mapOf(),
+    /**
+     * Features which more than one experiment may configure at the same time. The variables of
+     * every experiment enrolled on such a feature are merged.
+     */
+    val coenrollingFeatureIds: List<String> = listOf()
 )
 
 /**
@@ -336,12 +403,14 @@ open class Nimbus(
         val remoteSettingsConfig = server?.let {
             RemoteSettingsConfig(
                 serverUrl = it.url.toString(),
-                collectionName = it.collection
+                collectionName = it.collection,
+                signatureRootHash = it.signatureRootHash
             )
         }
 
         nimbusClient = NimbusClient(
             experimentContext,
+            appInfo.coenrollingFeatureIds,
             dataDir.path,
             remoteSettingsConfig,
             // The "dummy" field here is required for obscure reasons when generating code on desktop,
@@ -382,11 +451,22 @@ open class Nimbus(
         }
         ?: NullVariables.instance
 
+    @AnyThread
+    override fun getFeatureConfigProvenance(featureId: String): List<FeatureProvenance> =
+        withCatchAll {
+            nimbusClient.getFeatureConfigProvenance(featureId)
+        } ?: emptyList()
+
     @WorkerThread
     override fun getExperimentBranches(experimentId: String): List<Branch>? = withCatchAll {
         nimbusClient.getExperimentBranches(experimentId)
     }
 
+    @WorkerThread
+    override fun explainEnrollment(experimentId: String): EnrollmentExplanation? = withCatchAll {
+        nimbusClient.explainEnrollment(experimentId)
+    }
+
     // Method and apparatus to catch any uncaught exceptions
     @SuppressWarnings("TooGenericExceptionCaught")
     private fun <R> withCatchAll(thunk: () -> R) =
@@ -485,6 +565,18 @@ open class Nimbus(
         nimbusClient.setExperimentsLocally(payload)
     }
 
+    override fun setFeatureManifest(manifestJson: String) {
+        dbScope.launch {
+            setFeatureManifestOnThisThread(manifestJson)
+        }
+    }
+
+    @WorkerThread
+    @VisibleForTesting(otherwise = VisibleForTesting.PRIVATE)
+    internal fun setFeatureManifestOnThisThread(manifestJson: String) = withCatchAll {
+        nimbusClient.setFeatureManifest(manifestJson)
+    }
+
     @WorkerThread
     @VisibleForTesting(otherwise = VisibleForTesting.PRIVATE)
     internal fun setGlobalUserParticipationOnThisThread(active: Boolean) = withCatchAll {
@@ -534,6 +626,27 @@ open class Nimbus(
         recordExposure(featureId)
     }
 
+    override fun recordEvent(eventId: String, count: Int) {
+        dbScope.launch {
+            recordEventOnThisThread(eventId, count)
+        }
+    }
+
+    @WorkerThread
+    @VisibleForTesting(otherwise = VisibleForTesting.PRIVATE)
+    internal fun recordEventOnThisThread(eventId: String, count: Int) = withCatchAll {
+        // Don't let a negative count wrap around to a huge one.
+        nimbusClient.recordEvent(eventId, count.coerceAtLeast(0).toUInt())
+    }
+
+    override fun clearEvents() {
+        dbScope.launch {
+            withCatchAll {
+                nimbusClient.clearEvents()
+            }
+        }
+    }
+
     @VisibleForTesting(otherwise = VisibleForTesting.PRIVATE)
     internal fun recordExperimentTelemetry(experiments: List<EnrolledExperiment>) {
         // Call Glean.setExperimentActive() for each active experiment.
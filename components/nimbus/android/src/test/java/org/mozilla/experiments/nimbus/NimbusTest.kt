@@ -251,7 +251,7 @@ class NimbusTest {
         assertNotNull("Experiment enrollment-id must not be null", enrollmentEventExtras["enrollment_id"])
     }
 
-    private fun Nimbus.setUpTestExperiments(appId: String, appInfo: NimbusAppInfo) {
+    private fun Nimbus.setUpTestExperiments(appId: String, appInfo: NimbusAppInfo, targeting: String = "true") {
         this.setExperimentsLocallyOnThisThread("""
                 {"data": [{
                   "schemaVersion": "1.0.0",
@@ -277,6 +277,7 @@ class NimbusTest {
                   "appName": "${appInfo.appName}",
                   "appId": "$appId",
                   "channel": "${appInfo.channel}",
+                  "targeting": "$targeting",
                   "bucketConfig": {
                     "count": 10000,
                     "start": 0,
@@ -296,6 +297,20 @@ class NimbusTest {
         this.applyPendingExperimentsOnThisThread()
     }
 
+    @Test
+    fun `recorded events can be targeted on`() {
+        nimbus.recordEventOnThisThread("app_opened", 2)
+        nimbus.setUpTestExperiments(packageName, appInfo, targeting = "'app_opened'|eventCount(7) >= 2")
+        assertEquals("test-branch", nimbus.getExperimentBranch("test-experiment"))
+    }
+
+    @Test
+    fun `negative event counts are recorded as 0`() {
+        nimbus.recordEventOnThisThread("app_opened", -1)
+        nimbus.setUpTestExperiments(packageName, appInfo, targeting = "'app_opened'|eventCount(7) == 0")
+        assertEquals("test-branch", nimbus.getExperimentBranch("test-experiment"))
+    }
+
     @Test
     fun `buildExperimentContext returns a valid context`() {
         val expContext = nimbus.buildExperimentContext(context, appInfo, deviceInfo)
@@ -320,6 +335,19 @@ class NimbusTest {
         assertNull(json2)
     }
 
+    @Test
+    fun `getFeatureConfigProvenance lists the experiment configuring the feature`() {
+        nimbus.setUpTestExperiments(packageName, appInfo)
+
+        val provenance = nimbus.getFeatureConfigProvenance("about_welcome")
+        assertEquals(1, provenance.size)
+        assertEquals("test-experiment", provenance.first().slug)
+        assertEquals("test-branch", provenance.first().branchSlug)
+        assertFalse(provenance.first().isRollout)
+
+        assertTrue(nimbus.getFeatureConfigProvenance("non-existent-feature").isEmpty())
+    }
+
     @Test
     fun `getAvailableExperiments returns experiments for this appName even if the channel and appId don't match`() {
         val appName = "TestApp"
@@ -359,6 +387,19 @@ class NimbusTest {
         val available = nimbus.getAvailableExperiments()
         assertTrue(available.isEmpty())
     }
+
+    @Test
+    fun `explainEnrollment explains why the client is enrolled`() {
+        nimbus.setUpTestExperiments(packageName, appInfo)
+
+        val explanation = nimbus.explainEnrollment("test-experiment")
+        assertNotNull(explanation)
+        assertTrue(explanation!!.appNameMatches)
+        assertTrue(explanation.channelMatches)
+        assertEquals("test-branch", explanation.branchSlug)
+
+        assertNull(nimbus.explainEnrollment("not-an-experiment"))
+    }
 }
 
 // Mocking utilities, from mozilla.components.support.test
@@ -2,6 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use chrono::Utc;
 use clap::{App, Arg, SubCommand};
 use env_logger::Env;
 use nimbus::TargetingAttributes;
@@ -193,12 +194,14 @@ fn main() -> Result<()> {
     let config = RemoteSettingsConfig {
         server_url: server_url.to_string(),
         collection_name: collection_name.to_string(),
+        signature_root_hash: None,
     };
 
     let aru = AvailableRandomizationUnits::with_client_id(&client_id);
 
     // Here we initialize our main `NimbusClient` struct
-    let nimbus_client = NimbusClient::new(context.clone(), "", Some(config), aru)?;
+    let nimbus_client =
+        NimbusClient::new(context.clone(), Default::default(), "", Some(config), aru)?;
 
     // Explicitly update experiments at least once for init purposes
     nimbus_client.fetch_experiments()?;
@@ -288,7 +291,13 @@ fn main() -> Result<()> {
                 let uuid = uuid::Uuid::new_v4();
                 let mut num_of_experiments_enrolled = 0;
                 for exp in &all_experiments {
-                    let enr = nimbus::evaluate_enrollment(&uuid, &aru, &targeting_attributes, exp)?;
+                    let enr = nimbus::evaluate_enrollment(
+                        &uuid,
+                        &aru,
+                        &targeting_attributes,
+                        exp,
+                        Utc::now(),
+                    )?;
                     if enr.status.is_enrolled() {
                         num_of_experiments_enrolled += 1;
                         if num_of_experiments_enrolled >= num {
@@ -346,8 +355,13 @@ fn main() -> Result<()> {
                     app_context: context.clone(),
                     ..Default::default()
                 };
-                let enrollment =
-                    nimbus::evaluate_enrollment(&uuid, &aru, &targeting_attributes, &exp)?;
+                let enrollment = nimbus::evaluate_enrollment(
+                    &uuid,
+                    &aru,
+                    &targeting_attributes,
+                    &exp,
+                    Utc::now(),
+                )?;
                 let key = match enrollment.status.clone() {
                     EnrollmentStatus::Enrolled { .. } => "Enrolled",
                     EnrollmentStatus::NotEnrolled { .. } => "NotEnrolled",
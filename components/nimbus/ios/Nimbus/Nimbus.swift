@@ -174,6 +174,10 @@ internal extension Nimbus {
         try nimbusClient.setExperimentsLocally(experimentsJson: experimentsJson)
     }
 
+    func setFeatureManifestOnThisThread(_ manifestJson: String) throws {
+        try nimbusClient.setFeatureManifest(manifestJson: manifestJson)
+    }
+
     func optOutOnThisThread(_ experimentId: String) throws {
         let changes = try nimbusClient.optOut(experimentSlug: experimentId)
         postEnrollmentCalculation(changes)
@@ -188,6 +192,10 @@ internal extension Nimbus {
         let changes = try nimbusClient.resetTelemetryIdentifiers(newRandomizationUnits: identifiers)
         postEnrollmentCalculation(changes)
     }
+
+    func recordEventOnThisThread(_ eventId: String, count: Int) throws {
+        try nimbusClient.recordEvent(eventId: eventId, count: UInt32(clamping: count))
+    }
 }
 
 extension Nimbus: NimbusFeatureConfiguration {
@@ -220,6 +228,12 @@ extension Nimbus: NimbusFeatureConfiguration {
 
         return JSONVariables(with: json, in: resourceBundles)
     }
+
+    public func getFeatureConfigProvenance(featureId: String) -> [FeatureProvenance] {
+        return catchAll {
+            try nimbusClient.getFeatureConfigProvenance(featureId: featureId)
+        } ?? []
+    }
 }
 
 extension Nimbus: NimbusUserConfiguration {
@@ -252,6 +266,12 @@ extension Nimbus: NimbusUserConfiguration {
         }
     }
 
+    public func explainEnrollment(_ experimentId: String) -> EnrollmentExplanation? {
+        return catchAll {
+            try nimbusClient.explainEnrollment(experimentSlug: experimentId)
+        }
+    }
+
     public func optOut(_ experimentId: String) {
         catchAll(dbQueue) {
             try self.optOutOnThisThread(experimentId)
@@ -305,6 +325,26 @@ extension Nimbus: NimbusStartup {
             try self.setExperimentsLocallyOnThisThread(experimentsJson)
         }
     }
+
+    public func setFeatureManifest(_ manifestJson: String) {
+        catchAll(dbQueue) {
+            try self.setFeatureManifestOnThisThread(manifestJson)
+        }
+    }
+}
+
+extension Nimbus: NimbusEventStore {
+    public func recordEvent(_ eventId: String, count: Int) {
+        catchAll(dbQueue) {
+            try self.recordEventOnThisThread(eventId, count: count)
+        }
+    }
+
+    public func clearEvents() {
+        catchAll(dbQueue) {
+            try self.nimbusClient.clearEvents()
+        }
+    }
 }
 
 public class NimbusDisabled: NimbusApi {
@@ -330,6 +370,10 @@ public extension NimbusDisabled {
         return NilVariables.instance
     }
 
+    func getFeatureConfigProvenance(featureId _: String) -> [FeatureProvenance] {
+        return []
+    }
+
     func initialize() {}
 
     func fetchExperiments() {}
@@ -340,6 +384,8 @@ public extension NimbusDisabled {
 
     func setExperimentsLocally(_: String) {}
 
+    func setFeatureManifest(_: String) {}
+
     func optOut(_: String) {}
 
     func optIn(_: String, branch _: String) {}
@@ -348,7 +394,15 @@ public extension NimbusDisabled {
 
     func recordExposureEvent(featureId _: String) {}
 
+    func recordEvent(_: String, count _: Int) {}
+
+    func clearEvents() {}
+
     func getExperimentBranches(_: String) -> [Branch]? {
         return nil
     }
+
+    func explainEnrollment(_: String) -> EnrollmentExplanation? {
+        return nil
+    }
 }
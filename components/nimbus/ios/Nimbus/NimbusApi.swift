@@ -12,11 +12,13 @@ import Foundation
 /// Feature developers configuring their features shoiuld use the methods in `NimbusFeatureConfiguration`.
 /// These are safe to call from any thread. Developers building UI tools for the user or QA to modify experiment
 /// enrollment will mostly use `NimbusUserConfiguration` methods. Application developers integrating
-/// `Nimbus` into their app should use the methods in `NimbusStartup`.
+/// `Nimbus` into their app should use the methods in `NimbusStartup`, and record the behavioral events
+/// experiments can target on with `NimbusEventStore`.
 ///
 public protocol NimbusApi: AnyObject,
     NimbusStartup, NimbusFeatureConfiguration,
-    NimbusUserConfiguration, NimbusTelemetryConfiguration {}
+    NimbusUserConfiguration, NimbusTelemetryConfiguration,
+    NimbusEventStore {}
 
 public protocol NimbusFeatureConfiguration {
     /// Get the currently enrolled branch for the given experiment
@@ -39,6 +41,14 @@ public protocol NimbusFeatureConfiguration {
     ///
     /// - Returns a `Variables` object used to configure the feature.
     func getVariables(featureId: String, sendExposureEvent: Bool) -> Variables
+
+    /// Get the experiments and rollouts the variables for the given feature came from, highest precedence first.
+    /// There's only more than one experiment for features listed in `NimbusAppSettings.coenrollingFeatureIds`.
+    ///
+    /// - Parameter featureId The string feature id that identifies to the feature under experiment.
+    ///
+    /// - Returns a list of `FeatureProvenance`s, which is empty if the feature isn't being configured.
+    func getFeatureConfigProvenance(featureId: String) -> [FeatureProvenance]
 }
 
 public extension NimbusFeatureConfiguration {
@@ -84,6 +94,35 @@ public protocol NimbusTelemetryConfiguration {
     func recordExposureEvent(featureId: String)
 }
 
+public protocol NimbusEventStore {
+    /// Records that a behavioral event happened `count` times, so that experiments can target on it with the
+    /// `eventCount` JEXL transform, eg, `'app_opened'|eventCount(7) >= 3`.
+    ///
+    /// This is performed on a background thread.
+    ///
+    /// - Parameters:
+    ///     - eventId The string id of the event, eg, `"app_opened"`.
+    ///     - count The number of times the event happened. Negative counts are recorded as 0.
+    ///
+    func recordEvent(_ eventId: String, count: Int)
+
+    /// Forgets all the events recorded with `recordEvent`.
+    ///
+    /// This is performed on a background thread.
+    ///
+    func clearEvents()
+}
+
+public extension NimbusEventStore {
+    /// Records that a behavioral event happened once.
+    ///
+    /// - Parameter eventId The string id of the event, eg, `"app_opened"`.
+    ///
+    func recordEvent(_ eventId: String) {
+        recordEvent(eventId, count: 1)
+    }
+}
+
 public protocol NimbusStartup {
     /// Open the database and populate the SDK so as make it usable by feature developers.
     ///
@@ -136,6 +175,17 @@ public protocol NimbusStartup {
     /// - Parameter fileURL the URL of a JSON document in the app `Bundle`.
     ///
     func setExperimentsLocally(_ fileURL: URL)
+
+    /// Set the app's feature manifest, as the JSON of its intermediate representation (ie, the output of
+    /// `nimbus-fml --language ir`). Once it's set, branches whose feature values don't match the manifest
+    /// aren't enrolled in.
+    ///
+    /// Like `setExperimentsLocally`, this takes effect the next time `applyPendingExperiments` is called,
+    /// and is performed on the same background thread.
+    ///
+    /// - Parameter manifestJson string representation of the feature manifest's JSON.
+    ///
+    func setFeatureManifest(_ manifestJson: String)
 }
 
 public protocol NimbusUserConfiguration {
@@ -179,6 +229,14 @@ public protocol NimbusUserConfiguration {
     /// - Returns  A list of `AvailableExperiment`s
     ///
     func getAvailableExperiments() -> [AvailableExperiment]
+
+    /// Explain how the given experiment is evaluated for this client: whether the app name and channel match,
+    /// which part of the targeting failed, and the bucket and branch the client falls in. This doesn't change
+    /// any enrollments, so is safe to call from debugging tools.
+    ///
+    /// - Parameter experimentId the id or slug of the experiment to explain.
+    /// - Returns an `EnrollmentExplanation`, or `nil` if no such experiment exists.
+    func explainEnrollment(_ experimentId: String) -> EnrollmentExplanation?
 }
 
 /// Notifications emitted by the `NotificationCenter`.
@@ -190,14 +248,19 @@ public extension Notification.Name {
 
 /// This struct is used during in the `create` method to point `Nimbus` at the given `RemoteSettings` server.
 ///
+/// `signatureRootHash` is the SHA-256 fingerprint of the root certificate the server's content signatures
+/// chain up to. It defaults to the production root, so needs to be set when pointing at the stage or dev servers.
+///
 public struct NimbusServerSettings {
-    public init(url: URL, collection: String = remoteSettingsCollection) {
+    public init(url: URL, collection: String = remoteSettingsCollection, signatureRootHash: String? = nil) {
         self.url = url
         self.collection = collection
+        self.signatureRootHash = signatureRootHash
     }
 
     public let url: URL
     public let collection: String
+    public let signatureRootHash: String?
 }
 
 public let remoteSettingsCollection = "nimbus-mobile-experiments"
@@ -206,15 +269,23 @@ public let remoteSettingsCollection = "nimbus-mobile-experiments"
 /// The specifc context is there to capture any context that the SDK doesn't need to be explictly aware of.
 ///
 public struct NimbusAppSettings {
-    public init(appName: String, channel: String, customTargetingAttributes: [String: String] = [String: String]()) {
+    public init(
+        appName: String,
+        channel: String,
+        customTargetingAttributes: [String: String] = [String: String](),
+        coenrollingFeatureIds: [String] = []
+    ) {
         self.appName = appName
         self.channel = channel
         self.customTargetingAttributes = customTargetingAttributes
+        self.coenrollingFeatureIds = coenrollingFeatureIds
     }
 
     public let appName: String
     public let channel: String
     public let customTargetingAttributes: [String: String]
+    /// Features which more than one experiment may configure at the same time.
+    public let coenrollingFeatureIds: [String]
 }
 
 /// This error reporter is passed to `Nimbus` and any errors that are caught are reported via this type.
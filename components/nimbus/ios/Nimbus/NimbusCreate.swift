@@ -46,11 +46,13 @@ public extension Nimbus {
         let remoteSettings = server.map { server -> RemoteSettingsConfig in
             RemoteSettingsConfig(
                 serverUrl: server.url.absoluteString,
-                collectionName: server.collection
+                collectionName: server.collection,
+                signatureRootHash: server.signatureRootHash
             )
         }
         let nimbusClient = try NimbusClient(
             appCtx: context,
+            coenrollingFeatureIds: appSettings.coenrollingFeatureIds,
             dbpath: dbPath,
             remoteSettingsConfig: remoteSettings,
             // The "dummy" field here is required for obscure reasons when generating code on desktop,
@@ -15,12 +15,14 @@
 
 use std::time::{Duration, Instant};
 
+use super::signatures::SignatureVerifier;
+use super::RecordCache;
 use crate::config::RemoteSettingsConfig;
 use crate::error::{NimbusError, Result};
 use crate::{Experiment, SettingsClient, SCHEMA_VERSION};
 use std::cell::Cell;
 use url::Url;
-use viaduct::{status_codes, Request, Response};
+use viaduct::{header_names, status_codes, Request, Response};
 
 const HEADER_BACKOFF: &str = "Backoff";
 const HEADER_RETRY_AFTER: &str = "Retry-After";
@@ -29,6 +31,8 @@ pub struct Client {
     base_url: Url,
     collection_name: String,
     remote_state: Cell<RemoteState>,
+    // `None` only in tests which don't care about signatures.
+    signature_verifier: Option<SignatureVerifier>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -44,10 +48,15 @@ impl Client {
     #[allow(unused)]
     pub fn new(config: RemoteSettingsConfig) -> Result<Self> {
         let base_url = Url::parse(&config.server_url)?;
+        let signature_verifier = match config.signature_root_hash {
+            Some(root_hash) => SignatureVerifier::with_root_hash(&root_hash),
+            None => SignatureVerifier::default(),
+        };
         Ok(Self {
             base_url,
             collection_name: config.collection_name,
             remote_state: Cell::new(RemoteState::Ok),
+            signature_verifier: Some(signature_verifier),
         })
     }
 
@@ -101,6 +110,88 @@ impl Client {
         }
         Ok(())
     }
+
+    /// Fetch the records in the collection, or if `since` is given, only
+    /// those which changed after that timestamp. In the latter case the
+    /// response may be a 304 if nothing has changed.
+    fn fetch_records(&self, since: Option<&str>) -> Result<Response> {
+        let path = format!(
+            "v1/buckets/main/collections/{}/records",
+            &self.collection_name
+        );
+        let mut url = self.base_url.join(&path)?;
+        let req = match since {
+            Some(timestamp) => {
+                url.query_pairs_mut().append_pair("_since", timestamp);
+                Request::get(url)
+                    .header(header_names::IF_NONE_MATCH, format!("\"{}\"", timestamp))?
+            }
+            None => Request::get(url),
+        };
+        self.make_request(req)
+    }
+
+    /// Bring `cache` up to date with the server. The cache is only modified
+    /// if everything succeeds, including signature verification.
+    fn sync_records(&self, cache: &mut RecordCache) -> Result<()> {
+        let resp = self.fetch_records(cache.timestamp.as_deref())?;
+        if resp.status == status_codes::NOT_MODIFIED {
+            log::info!("Experiments collection hasn't changed since it was last fetched");
+            return Ok(());
+        }
+        let mut updated = cache.clone();
+        updated.apply_changes(parse_records(&resp)?, parse_timestamp(&resp));
+        if let Some(verifier) = &self.signature_verifier {
+            match self.verify_signature(verifier, &updated) {
+                Ok(()) => (),
+                // If our copy of the records has somehow diverged from the
+                // server's, the merged collection won't verify - so we try
+                // again with the whole collection before giving up.
+                Err(NimbusError::SignatureError(e)) if cache.timestamp.is_some() => {
+                    log::warn!(
+                        "Signature verification failed after merging changes ({}); fetching everything",
+                        e
+                    );
+                    let resp = self.fetch_records(None)?;
+                    updated = RecordCache::default();
+                    updated.apply_changes(parse_records(&resp)?, parse_timestamp(&resp));
+                    self.verify_signature(verifier, &updated)?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        *cache = updated;
+        Ok(())
+    }
+
+    /// Fetch the signature from the collection metadata and check that it
+    /// matches the records in `cache`.
+    fn verify_signature(&self, verifier: &SignatureVerifier, cache: &RecordCache) -> Result<()> {
+        // The collection timestamp is part of what's signed.
+        let timestamp = cache
+            .timestamp
+            .as_ref()
+            .ok_or_else(|| NimbusError::SignatureError("Records response has no ETag".into()))?;
+        let records: Vec<serde_json::Value> = cache.records.values().cloned().collect();
+
+        let path = format!("v1/buckets/main/collections/{}", &self.collection_name);
+        let metadata_resp = self.make_request(Request::get(self.base_url.join(&path)?))?;
+        let metadata: serde_json::Value = serde_json::from_str(metadata_resp.text().as_ref())?;
+        let signature_field = |name: &str| -> Result<String> {
+            metadata
+                .pointer(&format!("/data/signature/{}", name))
+                .and_then(|v| v.as_str())
+                .map(ToString::to_string)
+                .ok_or_else(|| {
+                    NimbusError::SignatureError(format!("Collection metadata has no {}", name))
+                })
+        };
+        let signature = signature_field("signature")?;
+        let x5u = signature_field("x5u")?;
+
+        let chain_resp = self.make_request(Request::get(Url::parse(&x5u)?))?;
+        verifier.verify(&records, timestamp, &signature, &chain_resp.body)
+    }
 }
 
 impl SettingsClient for Client {
@@ -109,14 +200,29 @@ impl SettingsClient for Client {
     }
 
     fn fetch_experiments(&self) -> Result<Vec<Experiment>> {
-        let path = format!(
-            "v1/buckets/main/collections/{}/records",
-            &self.collection_name
-        );
-        let url = self.base_url.join(&path)?;
-        let req = Request::get(url);
-        let resp = self.make_request(req)?;
-        parse_experiments(&resp.text())
+        self.fetch_experiments_with_cache(&mut RecordCache::default())
+    }
+
+    fn fetch_experiments_with_cache(&self, cache: &mut RecordCache) -> Result<Vec<Experiment>> {
+        self.sync_records(cache)?;
+        Ok(parse_experiment_records(cache.records.values()))
+    }
+}
+
+// The collection timestamp is handed back to us as the (quoted) ETag of the
+// records response.
+fn parse_timestamp(response: &Response) -> Option<String> {
+    response
+        .headers
+        .get(header_names::ETAG)
+        .map(|etag| etag.trim_matches('"').to_string())
+}
+
+fn parse_records(response: &Response) -> Result<Vec<serde_json::Value>> {
+    let mut payload: serde_json::Value = serde_json::from_str(response.text().as_ref())?;
+    match payload.get_mut("data").map(serde_json::Value::take) {
+        Some(serde_json::Value::Array(records)) => Ok(records),
+        _ => Err(NimbusError::InvalidExperimentFormat),
     }
 }
 
@@ -128,11 +234,17 @@ pub fn parse_experiments(payload: &str) -> Result<Vec<Experiment>> {
     let data = value
         .get("data")
         .ok_or(NimbusError::InvalidExperimentFormat)?;
+    Ok(parse_experiment_records(
+        data.as_array()
+            .ok_or(NimbusError::InvalidExperimentFormat)?,
+    ))
+}
+
+fn parse_experiment_records<'a>(
+    records: impl IntoIterator<Item = &'a serde_json::Value>,
+) -> Vec<Experiment> {
     let mut res = Vec::new();
-    for exp in data
-        .as_array()
-        .ok_or(NimbusError::InvalidExperimentFormat)?
-    {
+    for exp in records {
         // Validate the schema major version matches the supported version
         let exp_schema_version = match exp.get("schemaVersion") {
             Some(ver) => {
@@ -168,20 +280,137 @@ pub fn parse_experiments(payload: &str) -> Result<Vec<Experiment>> {
             }
         }
     }
-    Ok(res)
+    res
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{Branch, BucketConfig, FeatureConfig, RandomizationUnit};
-    use mockito::mock;
+    use mockito::{mock, Matcher};
+
+    // The certificate chain and signature used by the `rc_crypto` tests,
+    // which is a valid signature of an empty collection.
+    const TEST_ROOT_HASH: &str = "3C:01:44:6A:BE:90:36:CE:A9:A0:9A:CA:A3:A5:20:AC:62:8F:20:A7:AE:32:CE:86:1C:B2:EF:B7:0F:A0:C7:45";
+    const TEST_CERT_CHAIN: &[u8] = b"\
+-----BEGIN CERTIFICATE-----
+MIIDBjCCAougAwIBAgIIFml6g0ldRGowCgYIKoZIzj0EAwMwgaMxCzAJBgNVBAYT
+AlVTMRwwGgYDVQQKExNNb3ppbGxhIENvcnBvcmF0aW9uMS8wLQYDVQQLEyZNb3pp
+bGxhIEFNTyBQcm9kdWN0aW9uIFNpZ25pbmcgU2VydmljZTFFMEMGA1UEAww8Q29u
+dGVudCBTaWduaW5nIEludGVybWVkaWF0ZS9lbWFpbEFkZHJlc3M9Zm94c2VjQG1v
+emlsbGEuY29tMB4XDTIxMDIwMzE1MDQwNVoXDTIxMDQyNDE1MDQwNVowgakxCzAJ
+BgNVBAYTAlVTMRMwEQYDVQQIEwpDYWxpZm9ybmlhMRYwFAYDVQQHEw1Nb3VudGFp
+biBWaWV3MRwwGgYDVQQKExNNb3ppbGxhIENvcnBvcmF0aW9uMRcwFQYDVQQLEw5D
+bG91ZCBTZXJ2aWNlczE2MDQGA1UEAxMtcmVtb3RlLXNldHRpbmdzLmNvbnRlbnQt
+c2lnbmF0dXJlLm1vemlsbGEub3JnMHYwEAYHKoZIzj0CAQYFK4EEACIDYgAE8pKb
+HX4IiD0SCy+NO7gwKqRRZ8IhGd8PTaIHIBgM6RDLRyDeswXgV+2kGUoHyzkbNKZt
+zlrS3AhqeUCtl1g6ECqSmZBbRTjCpn/UCpCnMLL0T0goxtAB8Rmi3CdM0cBUo4GD
+MIGAMA4GA1UdDwEB/wQEAwIHgDATBgNVHSUEDDAKBggrBgEFBQcDAzAfBgNVHSME
+GDAWgBQlZawrqt0eUz/t6OdN45oKfmzy6DA4BgNVHREEMTAvgi1yZW1vdGUtc2V0
+dGluZ3MuY29udGVudC1zaWduYXR1cmUubW96aWxsYS5vcmcwCgYIKoZIzj0EAwMD
+aQAwZgIxAPh43Bxl4MxPT6Ra1XvboN5O2OvIn2r8rHvZPWR/jJ9vcTwH9X3F0aLJ
+9FiresnsLAIxAOoAcREYB24gFBeWxbiiXaG7TR/yM1/MXw4qxbN965FFUaoB+5Bc
+fS8//SQGTlCqKQ==
+-----END CERTIFICATE-----
+-----BEGIN CERTIFICATE-----
+MIIF2jCCA8KgAwIBAgIEAQAAADANBgkqhkiG9w0BAQsFADCBqTELMAkGA1UEBhMC
+VVMxCzAJBgNVBAgTAkNBMRYwFAYDVQQHEw1Nb3VudGFpbiBWaWV3MRwwGgYDVQQK
+ExNBZGRvbnMgVGVzdCBTaWduaW5nMSQwIgYDVQQDExt0ZXN0LmFkZG9ucy5zaWdu
+aW5nLnJvb3QuY2ExMTAvBgkqhkiG9w0BCQEWInNlY29wcytzdGFnZXJvb3RhZGRv
+bnNAbW96aWxsYS5jb20wHhcNMjEwMTExMDAwMDAwWhcNMjQxMTE0MjA0ODU5WjCB
+ozELMAkGA1UEBhMCVVMxHDAaBgNVBAoTE01vemlsbGEgQ29ycG9yYXRpb24xLzAt
+BgNVBAsTJk1vemlsbGEgQU1PIFByb2R1Y3Rpb24gU2lnbmluZyBTZXJ2aWNlMUUw
+QwYDVQQDDDxDb250ZW50IFNpZ25pbmcgSW50ZXJtZWRpYXRlL2VtYWlsQWRkcmVz
+cz1mb3hzZWNAbW96aWxsYS5jb20wdjAQBgcqhkjOPQIBBgUrgQQAIgNiAARw1dyE
+xV5aNiHJPa/fVHO6kxJn3oZLVotJ0DzFZA9r1sQf8i0+v78Pg0/c3nTAyZWfkULz
+vOpKYK/GEGBtisxCkDJ+F3NuLPpSIg3fX25pH0LE15fvASBVcr8tKLVHeOmjggG6
+MIIBtjAMBgNVHRMEBTADAQH/MA4GA1UdDwEB/wQEAwIBBjAWBgNVHSUBAf8EDDAK
+BggrBgEFBQcDAzAdBgNVHQ4EFgQUJWWsK6rdHlM/7ejnTeOaCn5s8ugwgdkGA1Ud
+IwSB0TCBzoAUhtg0HE5Y0RNcmV/YQpjtFA8Z8l2hga+kgawwgakxCzAJBgNVBAYT
+AlVTMQswCQYDVQQIEwJDQTEWMBQGA1UEBxMNTW91bnRhaW4gVmlldzEcMBoGA1UE
+ChMTQWRkb25zIFRlc3QgU2lnbmluZzEkMCIGA1UEAxMbdGVzdC5hZGRvbnMuc2ln
+bmluZy5yb290LmNhMTEwLwYJKoZIhvcNAQkBFiJzZWNvcHMrc3RhZ2Vyb290YWRk
+b25zQG1vemlsbGEuY29tggRgJZg7MDMGCWCGSAGG+EIBBAQmFiRodHRwOi8vYWRk
+b25zLmFsbGl6b20ub3JnL2NhL2NybC5wZW0wTgYDVR0eBEcwRaBDMCCCHi5jb250
+ZW50LXNpZ25hdHVyZS5tb3ppbGxhLm9yZzAfgh1jb250ZW50LXNpZ25hdHVyZS5t
+b3ppbGxhLm9yZzANBgkqhkiG9w0BAQsFAAOCAgEAtGTTzcPzpcdf07kIeRs9vPMx
+qiF8ylW5L/IQ2NzT3sFFAvPW1vW1wZC0xAHMsuVyo+BTGrv+4mlD0AUR9acRfiTZ
+9qyZ3sJbyhQwJAXLKU4YpnzuFOf58T/yOnOdwpH2ky/0FuHskMyfXaAz2Az4JXJH
+TCgggqfdZNvsZ5eOnQlKoC5NadMa8oTI5sd4SyR5ANUPAtYok931MvVSz3IMbwTr
+v4PPWXdl9SGXuOknSqdY6/bS1LGvC2KprsT+PBlvVtS6YgZOH0uCgTTLpnrco87O
+ErzC2PJBA1Ftn3Mbaou6xy7O+YX+reJ6soNUV+0JHOuKj0aTXv0c+lXEAh4Y8nea
+UGhW6+MRGYMOP2NuKv8s2+CtNH7asPq3KuTQpM5RerjdouHMIedX7wpNlNk0CYbg
+VMJLxZfAdwcingLWda/H3j7PxMoAm0N+eA24TGDQPC652ZakYk4MQL/45lm0A5f0
+xLGKEe6JMZcTBQyO7ANWcrpVjKMiwot6bY6S2xU17mf/h7J32JXZJ23OPOKpMS8d
+mljj4nkdoYDT35zFuS1z+5q6R5flLca35vRHzC3XA0H/XJvgOKUNLEW/IiJIqLNi
+ab3Ao0RubuX+CAdFML5HaJmkyuJvL3YtwIOwe93RGcGRZSKZsnMS+uY5QN8+qKQz
+LC4GzWQGSCGDyD+JCVw=
+-----END CERTIFICATE-----
+-----BEGIN CERTIFICATE-----
+MIIHbDCCBVSgAwIBAgIEYCWYOzANBgkqhkiG9w0BAQwFADCBqTELMAkGA1UEBhMC
+VVMxCzAJBgNVBAgTAkNBMRYwFAYDVQQHEw1Nb3VudGFpbiBWaWV3MRwwGgYDVQQK
+ExNBZGRvbnMgVGVzdCBTaWduaW5nMSQwIgYDVQQDExt0ZXN0LmFkZG9ucy5zaWdu
+aW5nLnJvb3QuY2ExMTAvBgkqhkiG9w0BCQEWInNlY29wcytzdGFnZXJvb3RhZGRv
+bnNAbW96aWxsYS5jb20wHhcNMjEwMjExMjA0ODU5WhcNMjQxMTE0MjA0ODU5WjCB
+qTELMAkGA1UEBhMCVVMxCzAJBgNVBAgTAkNBMRYwFAYDVQQHEw1Nb3VudGFpbiBW
+aWV3MRwwGgYDVQQKExNBZGRvbnMgVGVzdCBTaWduaW5nMSQwIgYDVQQDExt0ZXN0
+LmFkZG9ucy5zaWduaW5nLnJvb3QuY2ExMTAvBgkqhkiG9w0BCQEWInNlY29wcytz
+dGFnZXJvb3RhZGRvbnNAbW96aWxsYS5jb20wggIiMA0GCSqGSIb3DQEBAQUAA4IC
+DwAwggIKAoICAQDKRVty/FRsO4Ech6EYleyaKgAueaLYfMSsAIyPC/N8n/P8QcH8
+rjoiMJrKHRlqiJmMBSmjUZVzZAP0XJku0orLKWPKq7cATt+xhGY/RJtOzenMMsr5
+eN02V3GzUd1jOShUpERjzXdaO3pnfZqhdqNYqP9ocqQpyno7bZ3FZQ2vei+bF52k
+51uPioTZo+1zduoR/rT01twGtZm3QpcwU4mO74ysyxxgqEy3kpojq8Nt6haDwzrj
+khV9M6DGPLHZD71QaUiz5lOhD9CS8x0uqXhBhwMUBBkHsUDSxbN4ZhjDDWpCmwaD
+OtbJMUJxDGPCr9qj49QESccb367OeXLrfZ2Ntu/US2Bw9EDfhyNsXr9dg9NHj5yf
+4sDUqBHG0W8zaUvJx5T2Ivwtno1YZLyJwQW5pWeWn8bEmpQKD2KS/3y2UjlDg+YM
+NdNASjFe0fh6I5NCFYmFWA73DpDGlUx0BtQQU/eZQJ+oLOTLzp8d3dvenTBVnKF+
+uwEmoNfZwc4TTWJOhLgwxA4uK+Paaqo4Ap2RGS2ZmVkPxmroB3gL5n3k3QEXvULh
+7v8Psk4+MuNWnxudrPkN38MGJo7ju7gDOO8h1jLD4tdfuAqbtQLduLXzT4DJPA4y
+JBTFIRMIpMqP9CovaS8VPtMFLTrYlFh9UnEGpCeLPanJr+VEj7ae5sc8YwIDAQAB
+o4IBmDCCAZQwDAYDVR0TBAUwAwEB/zAOBgNVHQ8BAf8EBAMCAQYwFgYDVR0lAQH/
+BAwwCgYIKwYBBQUHAwMwLAYJYIZIAYb4QgENBB8WHU9wZW5TU0wgR2VuZXJhdGVk
+IENlcnRpZmljYXRlMDMGCWCGSAGG+EIBBAQmFiRodHRwOi8vYWRkb25zLm1vemls
+bGEub3JnL2NhL2NybC5wZW0wHQYDVR0OBBYEFIbYNBxOWNETXJlf2EKY7RQPGfJd
+MIHZBgNVHSMEgdEwgc6AFIbYNBxOWNETXJlf2EKY7RQPGfJdoYGvpIGsMIGpMQsw
+CQYDVQQGEwJVUzELMAkGA1UECBMCQ0ExFjAUBgNVBAcTDU1vdW50YWluIFZpZXcx
+HDAaBgNVBAoTE0FkZG9ucyBUZXN0IFNpZ25pbmcxJDAiBgNVBAMTG3Rlc3QuYWRk
+b25zLnNpZ25pbmcucm9vdC5jYTExMC8GCSqGSIb3DQEJARYic2Vjb3BzK3N0YWdl
+cm9vdGFkZG9uc0Btb3ppbGxhLmNvbYIEYCWYOzANBgkqhkiG9w0BAQwFAAOCAgEA
+nowyJv8UaIV7NA0B3wkWratq6FgA1s/PzetG/ZKZDIW5YtfUvvyy72HDAwgKbtap
+Eog6zGI4L86K0UGUAC32fBjE5lWYEgsxNM5VWlQjbgTG0dc3dYiufxfDFeMbAPmD
+DzpIgN3jHW2uRqa/MJ+egHhv7kGFL68uVLboqk/qHr+SOCc1LNeSMCuQqvHwwM0+
+AU1GxhzBWDkealTS34FpVxF4sT5sKLODdIS5HXJr2COHHfYkw2SW/Sfpt6fsOwaF
+2iiDaK4LPWHWhhIYa6yaynJ+6O6KPlpvKYCChaTOVdc+ikyeiSO6AakJykr5Gy7d
+PkkK7MDCxuY6psHj7iJQ59YK7ujQB8QYdzuXBuLLo5hc5gBcq3PJs0fLT2YFcQHA
+dj+olGaDn38T0WI8ycWaFhQfKwATeLWfiQepr8JfoNlC2vvSDzGUGfdAfZfsJJZ8
+5xZxahHoTFGS0mDRfXqzKH5uD578GgjOZp0fULmzkcjWsgzdpDhadGjExRZFKlAy
+iKv8cXTONrGY0fyBDKennuX0uAca3V0Qm6v2VRp+7wG/pywWwc5n+04qgxTQPxgO
+6pPB9UUsNbaLMDR5QPYAWrNhqJ7B07XqIYJZSwGP5xB9NqUZLF4z+AOMYgWtDpmg
+IKdcFKAt3fFrpyMhlfIKkLfmm0iDjmfmIXbDGBJw9SE=
+-----END CERTIFICATE-----";
+    const TEST_TIMESTAMP: &str = "1603992731957";
+    const TEST_SIGNATURE: &str = "fJJcOpwdnkjEWFeHXfdOJN6GaGLuDTPGzQOxA2jn6ldIleIk6KqMhZcy2GZv2uYiGwl6DERWwpaoUfQFLyCAOcVjck1qlaaEFZGY1BQba9p99xEc9FNQ3YPPfvSSZqsw";
+    const TEST_VERIFICATION_TIME: u64 = 1615559719; // March 12, 2021
+
+    // Most tests don't care about signatures, and would otherwise need to
+    // sign their fixtures.
+    fn unverified_client(collection_name: &str) -> Client {
+        let config = RemoteSettingsConfig {
+            server_url: mockito::server_url(),
+            collection_name: collection_name.to_string(),
+            signature_root_hash: None,
+        };
+        let mut client = Client::new(config).unwrap();
+        client.signature_verifier = None;
+        client
+    }
 
     fn response_body() -> String {
         format!(
             r#"
         {{ "data": [
             {{
+                "id": "mobile-a-a-example",
                 "schemaVersion": "{current_version}.0.0",
                 "slug": "mobile-a-a-example",
                 "appName": "reference-browser",
@@ -222,6 +451,7 @@ mod tests {
                 ]
             }},
             {{
+                "id": "mobile-a-a-example-newer",
                 "schemaVersion": "{newer_version}.0.0",
                 "slug": "mobile-a-a-example",
                 "appName": "reference-browser",
@@ -254,6 +484,7 @@ mod tests {
                 ]
             }},
             {{
+                "id": "schema-version-missing",
                 "slug": "schema-version-missing",
                 "appName": "reference-browser",
                 "userFacingName": "Schema Version Missing",
@@ -279,11 +510,7 @@ mod tests {
         .with_status(200)
         .with_header("content-type", "application/json")
         .create();
-        let config = RemoteSettingsConfig {
-            server_url: mockito::server_url(),
-            collection_name: "messaging-experiments".to_string(),
-        };
-        let http_client = Client::new(config).unwrap();
+        let http_client = unverified_client("messaging-experiments");
         let resp = http_client.fetch_experiments().unwrap();
 
         m.expect(1).assert();
@@ -346,11 +573,7 @@ mod tests {
         .with_header("content-type", "application/json")
         .with_header("Backoff", "60")
         .create();
-        let config = RemoteSettingsConfig {
-            server_url: mockito::server_url(),
-            collection_name: "messaging-experiments".to_string(),
-        };
-        let http_client = Client::new(config).unwrap();
+        let http_client = unverified_client("messaging-experiments");
         assert!(http_client.fetch_experiments().is_ok());
         let second_request = http_client.fetch_experiments();
         assert!(matches!(second_request, Err(NimbusError::BackoffError(_))));
@@ -368,11 +591,7 @@ mod tests {
         .with_status(500)
         .with_header("Retry-After", "60")
         .create();
-        let config = RemoteSettingsConfig {
-            server_url: mockito::server_url(),
-            collection_name: "messaging-experiments".to_string(),
-        };
-        let http_client = Client::new(config).unwrap();
+        let http_client = unverified_client("messaging-experiments");
         assert!(http_client.fetch_experiments().is_err());
         let second_request = http_client.fetch_experiments();
         assert!(matches!(second_request, Err(NimbusError::BackoffError(_))));
@@ -390,11 +609,7 @@ mod tests {
         .with_status(200)
         .with_header("content-type", "application/json")
         .create();
-        let config = RemoteSettingsConfig {
-            server_url: mockito::server_url(),
-            collection_name: "messaging-experiments".to_string(),
-        };
-        let mut http_client = Client::new(config).unwrap();
+        let mut http_client = unverified_client("messaging-experiments");
         // First, sanity check that manipulating the remote state does something.
         http_client.remote_state.replace(RemoteState::Backoff {
             observed_at: Instant::now(),
@@ -412,4 +627,247 @@ mod tests {
         assert!(http_client.fetch_experiments().is_ok());
         m.expect(1).assert();
     }
+
+    fn mock_signed_collection(
+        collection_name: &str,
+        timestamp: &str,
+        signature: &str,
+    ) -> Vec<mockito::Mock> {
+        let records = mock(
+            "GET",
+            format!("/v1/buckets/main/collections/{}/records", collection_name).as_str(),
+        )
+        .with_body(r#"{"data":[]}"#)
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_header("etag", &format!("\"{}\"", timestamp))
+        .create();
+        let metadata = mock(
+            "GET",
+            format!("/v1/buckets/main/collections/{}", collection_name).as_str(),
+        )
+        .with_body(
+            serde_json::json!({
+                "data": {
+                    "id": collection_name,
+                    "signature": {
+                        "signature": signature,
+                        "x5u": format!("{}/chains/{}.pem", mockito::server_url(), collection_name),
+                    }
+                }
+            })
+            .to_string(),
+        )
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .create();
+        let chain = mock("GET", format!("/chains/{}.pem", collection_name).as_str())
+            .with_body(TEST_CERT_CHAIN)
+            .with_status(200)
+            .create();
+        vec![records, metadata, chain]
+    }
+
+    fn signed_client(collection_name: &str, root_hash: Option<&str>) -> Client {
+        let config = RemoteSettingsConfig {
+            server_url: mockito::server_url(),
+            collection_name: collection_name.to_string(),
+            signature_root_hash: root_hash.map(str::to_string),
+        };
+        let mut client = Client::new(config).unwrap();
+        client.signature_verifier = client
+            .signature_verifier
+            .map(|verifier| verifier.at_time(TEST_VERIFICATION_TIME));
+        client
+    }
+
+    #[test]
+    fn test_valid_signature() {
+        viaduct_reqwest::use_reqwest_backend();
+        let mocks = mock_signed_collection("signed-experiments", TEST_TIMESTAMP, TEST_SIGNATURE);
+        let http_client = signed_client("signed-experiments", Some(TEST_ROOT_HASH));
+        let resp = http_client.fetch_experiments().unwrap();
+        assert!(resp.is_empty());
+        for m in mocks {
+            m.expect(1).assert();
+        }
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        viaduct_reqwest::use_reqwest_backend();
+        // The timestamp is part of the signed data, so changing it should
+        // invalidate the signature.
+        let _mocks =
+            mock_signed_collection("tampered-experiments", "1603992731958", TEST_SIGNATURE);
+        let http_client = signed_client("tampered-experiments", Some(TEST_ROOT_HASH));
+        assert!(matches!(
+            http_client.fetch_experiments(),
+            Err(NimbusError::SignatureError(_))
+        ));
+    }
+
+    #[test]
+    fn test_signature_wrong_root() {
+        viaduct_reqwest::use_reqwest_backend();
+        // Our fixtures don't chain up to the production root, which is what
+        // we use unless the config says otherwise.
+        let _mocks = mock_signed_collection("stage-experiments", TEST_TIMESTAMP, TEST_SIGNATURE);
+        let http_client = signed_client("stage-experiments", None);
+        assert!(matches!(
+            http_client.fetch_experiments(),
+            Err(NimbusError::SignatureError(_))
+        ));
+    }
+
+    #[test]
+    fn test_missing_signature() {
+        viaduct_reqwest::use_reqwest_backend();
+        let _records = mock(
+            "GET",
+            "/v1/buckets/main/collections/unsigned-experiments/records",
+        )
+        .with_body(r#"{"data":[]}"#)
+        .with_status(200)
+        .with_header("etag", "\"1234\"")
+        .create();
+        let _metadata = mock("GET", "/v1/buckets/main/collections/unsigned-experiments")
+            .with_body(r#"{"data":{"id":"unsigned-experiments"}}"#)
+            .with_status(200)
+            .create();
+        let http_client = Client::new(RemoteSettingsConfig {
+            server_url: mockito::server_url(),
+            collection_name: "unsigned-experiments".to_string(),
+            signature_root_hash: None,
+        })
+        .unwrap();
+        assert!(matches!(
+            http_client.fetch_experiments(),
+            Err(NimbusError::SignatureError(_))
+        ));
+    }
+
+    fn experiment_record(id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "schemaVersion": format!("{}.0.0", SCHEMA_VERSION),
+            "slug": id,
+            "userFacingName": id,
+            "userFacingDescription": id,
+            "isEnrollmentPaused": false,
+            "bucketConfig": {
+                "randomizationUnit": "nimbus_id",
+                "namespace": id,
+                "start": 0,
+                "count": 5000,
+                "total": 10000
+            },
+            "proposedEnrollment": 7,
+            "featureIds": ["some_feature"],
+            "branches": [{
+                "slug": "control",
+                "ratio": 1,
+                "feature": {"featureId": "some_feature", "enabled": true}
+            }]
+        })
+    }
+
+    fn slugs(experiments: &[Experiment]) -> Vec<&str> {
+        let mut slugs: Vec<&str> = experiments.iter().map(|e| e.slug.as_str()).collect();
+        slugs.sort_unstable();
+        slugs
+    }
+
+    #[test]
+    fn test_incremental_fetch() {
+        viaduct_reqwest::use_reqwest_backend();
+        let records_path =
+            Matcher::Regex("^/v1/buckets/main/collections/incremental-experiments/records".into());
+        let full = mock("GET", records_path.clone())
+            .match_header("if-none-match", Matcher::Missing)
+            .with_body(
+                serde_json::json!({
+                    "data": [experiment_record("first"), experiment_record("second")]
+                })
+                .to_string(),
+            )
+            .with_status(200)
+            .with_header("etag", "\"100\"")
+            .create();
+        let changes = mock("GET", records_path)
+            .match_header("if-none-match", "\"100\"")
+            .match_query(Matcher::UrlEncoded("_since".into(), "100".into()))
+            .with_body(
+                serde_json::json!({
+                    "data": [
+                        {"id": "first", "deleted": true, "last_modified": 200},
+                        experiment_record("third"),
+                    ]
+                })
+                .to_string(),
+            )
+            .with_status(200)
+            .with_header("etag", "\"200\"")
+            .create();
+
+        let http_client = unverified_client("incremental-experiments");
+        let mut cache = RecordCache::default();
+        let experiments = http_client
+            .fetch_experiments_with_cache(&mut cache)
+            .unwrap();
+        assert_eq!(slugs(&experiments), vec!["first", "second"]);
+        assert_eq!(cache.timestamp.as_deref(), Some("100"));
+
+        let experiments = http_client
+            .fetch_experiments_with_cache(&mut cache)
+            .unwrap();
+        assert_eq!(slugs(&experiments), vec!["second", "third"]);
+        assert_eq!(cache.timestamp.as_deref(), Some("200"));
+        full.expect(1).assert();
+        changes.expect(1).assert();
+    }
+
+    #[test]
+    fn test_not_modified() {
+        viaduct_reqwest::use_reqwest_backend();
+        let m = mock(
+            "GET",
+            Matcher::Regex("^/v1/buckets/main/collections/unchanged-experiments/records".into()),
+        )
+        .match_header("if-none-match", "\"100\"")
+        .with_status(304)
+        .create();
+
+        let http_client = unverified_client("unchanged-experiments");
+        let mut cache = RecordCache::default();
+        cache.apply_changes(vec![experiment_record("cached")], Some("100".into()));
+        let before = cache.clone();
+        let experiments = http_client
+            .fetch_experiments_with_cache(&mut cache)
+            .unwrap();
+        assert_eq!(slugs(&experiments), vec!["cached"]);
+        assert_eq!(cache, before);
+        m.expect(1).assert();
+    }
+
+    #[test]
+    fn test_failed_fetch_keeps_cache() {
+        viaduct_reqwest::use_reqwest_backend();
+        let _m = mock(
+            "GET",
+            Matcher::Regex("^/v1/buckets/main/collections/broken-experiments/records".into()),
+        )
+        .with_body("Boom!")
+        .with_status(500)
+        .create();
+
+        let http_client = unverified_client("broken-experiments");
+        let mut cache = RecordCache::default();
+        cache.apply_changes(vec![experiment_record("cached")], Some("100".into()));
+        let before = cache.clone();
+        assert!(http_client
+            .fetch_experiments_with_cache(&mut cache)
+            .is_err());
+        assert_eq!(cache, before);
+    }
 }
@@ -5,12 +5,15 @@
 mod fs_client;
 mod http_client;
 mod null_client;
+mod signatures;
 use crate::error::{NimbusError, Result};
 use crate::Experiment;
 use crate::RemoteSettingsConfig;
 use fs_client::FileSystemClient;
 use http_client::Client;
 use null_client::NullClient;
+use serde_json::Value;
+use std::collections::BTreeMap;
 use url::Url;
 
 pub use http_client::parse_experiments;
@@ -42,8 +45,51 @@ pub(crate) fn create_client(
     })
 }
 
+/// Our copy of the records in the experiments collection, which lets clients
+/// that support it fetch only what has changed since last time.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct RecordCache {
+    /// The collection timestamp the records are current as of, if known.
+    pub timestamp: Option<String>,
+    /// The raw records, keyed by their id.
+    pub records: BTreeMap<String, Value>,
+}
+
+impl RecordCache {
+    /// Merge records received from the server into the cache, removing any
+    /// which have since been deleted.
+    pub fn apply_changes(&mut self, changes: Vec<Value>, timestamp: Option<String>) {
+        for record in changes {
+            let id = match record.get("id").and_then(Value::as_str) {
+                Some(id) => id.to_string(),
+                None => {
+                    log::warn!("Ignoring a record without an id");
+                    continue;
+                }
+            };
+            let deleted = record
+                .get("deleted")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            if deleted {
+                self.records.remove(&id);
+            } else {
+                self.records.insert(id, record);
+            }
+        }
+        self.timestamp = timestamp;
+    }
+}
+
 // The trait used to fetch experiments.
 pub(crate) trait SettingsClient {
     fn get_experiments_metadata(&self) -> Result<String>;
     fn fetch_experiments(&self) -> Result<Vec<Experiment>>;
+
+    /// Fetch experiments, using `cache` to avoid downloading records we
+    /// already have and updating it with what was received. Clients which
+    /// don't support this just fetch everything.
+    fn fetch_experiments_with_cache(&self, _cache: &mut RecordCache) -> Result<Vec<Experiment>> {
+        self.fetch_experiments()
+    }
 }
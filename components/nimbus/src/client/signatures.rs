@@ -0,0 +1,241 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Verification of the content signature Remote Settings attaches to a
+//! collection.
+//!
+//! The server signs a canonical JSON serialization of the whole collection,
+//! ie, `{"data":[<records sorted by id>],"last_modified":"<timestamp>"}`, and
+//! publishes the signature and the URL of the certificate chain used to make
+//! it in the collection's metadata. We rebuild that serialization from the
+//! records we received and check it against the signature, so that experiments
+//! which didn't come from Mozilla never make it into the database.
+
+use crate::error::{NimbusError, Result};
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// SHA-256 fingerprint of the root certificate used by the production
+/// Remote Settings server.
+const PRODUCTION_ROOT_HASH: &str = "97:E8:BA:9C:F1:2F:B3:DE:53:CC:42:A4:E6:57:7E:D6:4D:F4:93:C2:47:B4:14:FE:A0:36:81:8D:38:23:56:0E";
+
+/// The subject name the leaf certificate must have.
+const SIGNER_HOSTNAME: &str = "remote-settings.content-signature.mozilla.org";
+
+const SIGNATURE_PREFIX: &[u8] = b"Content-Signature:\x00";
+
+#[derive(Debug, Clone)]
+pub(crate) struct SignatureVerifier {
+    root_hash: String,
+    hostname: String,
+    // Only set by tests, because the certificates we have fixtures for have
+    // long expired.
+    fixed_time: Option<u64>,
+}
+
+impl Default for SignatureVerifier {
+    fn default() -> Self {
+        Self {
+            root_hash: PRODUCTION_ROOT_HASH.to_string(),
+            hostname: SIGNER_HOSTNAME.to_string(),
+            fixed_time: None,
+        }
+    }
+}
+
+impl SignatureVerifier {
+    /// A verifier for signatures which chain up to the root certificate with
+    /// the given SHA-256 fingerprint, rather than the production one.
+    pub(crate) fn with_root_hash(root_hash: &str) -> Self {
+        Self {
+            root_hash: root_hash.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn at_time(self, seconds_since_epoch: u64) -> Self {
+        Self {
+            fixed_time: Some(seconds_since_epoch),
+            ..self
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.fixed_time.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        })
+    }
+
+    /// Check that `signature` (as found in the collection metadata) is a
+    /// valid signature of `records` at `timestamp`, made by a certificate in
+    /// the PEM encoded `cert_chain`.
+    pub(crate) fn verify(
+        &self,
+        records: &[Value],
+        timestamp: &str,
+        signature: &str,
+        cert_chain: &[u8],
+    ) -> Result<()> {
+        rc_crypto::ensure_initialized();
+        let mut message = SIGNATURE_PREFIX.to_vec();
+        message.extend_from_slice(serialize_collection(records, timestamp)?.as_bytes());
+        rc_crypto::contentsignature::verify(
+            &message,
+            signature.as_bytes(),
+            cert_chain,
+            self.now(),
+            &self.root_hash,
+            &self.hostname,
+        )?;
+        Ok(())
+    }
+}
+
+/// Produce the exact string the server signed for this set of records.
+fn serialize_collection(records: &[Value], timestamp: &str) -> Result<String> {
+    let mut sorted = records.to_vec();
+    sorted.sort_by(|a, b| record_id(a).cmp(record_id(b)));
+    let collection = serde_json::json!({
+        "data": sorted,
+        "last_modified": timestamp,
+    });
+    let mut out = String::new();
+    write_canonical_json(&collection, &mut out)?;
+    Ok(out)
+}
+
+fn record_id(record: &Value) -> &str {
+    record.get("id").and_then(Value::as_str).unwrap_or_default()
+}
+
+/// Write `value` as canonical JSON - object keys sorted, no insignificant
+/// whitespace, everything outside of printable ASCII escaped, and floats
+/// without a fractional part written as integers. This matches what the
+/// signer (and the Desktop implementation of the verifier) does.
+fn write_canonical_json(value: &Value, out: &mut String) -> Result<()> {
+    match value {
+        Value::Null | Value::Bool(_) => out.push_str(&value.to_string()),
+        Value::Number(n) => {
+            if n.is_f64() {
+                let f = n.as_f64().unwrap_or_default();
+                if !f.is_finite() {
+                    return Err(NimbusError::SignatureError(format!(
+                        "Can't serialize {} as canonical JSON",
+                        f
+                    )));
+                }
+                if f.fract() == 0.0 && f.abs() < 1e21 {
+                    out.push_str(&format!("{:.0}", f));
+                } else {
+                    out.push_str(&n.to_string());
+                }
+            } else {
+                out.push_str(&n.to_string());
+            }
+        }
+        Value::String(s) => write_canonical_string(s, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_json(item, out)?;
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical_string(key, out);
+                out.push(':');
+                write_canonical_json(&map[key], out)?;
+            }
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+fn write_canonical_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            ' '..='~' => out.push(c),
+            _ => {
+                // Characters outside the BMP are written as a surrogate pair.
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    out.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn canonical(value: Value) -> String {
+        let mut out = String::new();
+        write_canonical_json(&value, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_canonical_json_sorts_keys() {
+        assert_eq!(
+            canonical(json!({"b": 1, "a": {"d": [true, null], "c": "x"}})),
+            r#"{"a":{"c":"x","d":[true,null]},"b":1}"#
+        );
+    }
+
+    #[test]
+    fn test_canonical_json_numbers() {
+        assert_eq!(
+            canonical(json!([1.0, -2.0, 1.5, 10, -3])),
+            "[1,-2,1.5,10,-3]"
+        );
+    }
+
+    #[test]
+    fn test_canonical_json_escapes() {
+        assert_eq!(
+            canonical(json!("quote\" slash\\ nl\n tab\t nul\u{0} é 😀")),
+            r#""quote\" slash\\ nl\n tab\t nul\u0000 \u00e9 \ud83d\ude00""#
+        );
+    }
+
+    #[test]
+    fn test_serialize_collection_sorts_by_id() {
+        let records = vec![json!({"id": "b", "x": 1}), json!({"id": "a"})];
+        assert_eq!(
+            serialize_collection(&records, "1234").unwrap(),
+            r#"{"data":[{"id":"a"},{"id":"b","x":1}],"last_modified":"1234"}"#
+        );
+        assert_eq!(
+            serialize_collection(&[], "1603992731957").unwrap(),
+            r#"{"data":[],"last_modified":"1603992731957"}"#
+        );
+    }
+}
@@ -12,8 +12,12 @@
 /// Currently includes the following:
 /// - `server_url`: The url for the settings server that would be used to retrieve experiments
 /// - `bucket_name`: The name of the bucket containing the collection on the server
+/// - `signature_root_hash`: The SHA-256 fingerprint of the root certificate the
+///   server's content signatures chain up to. Defaults to the production root,
+///   so needs to be set when using the stage or dev servers.
 #[derive(Debug, Clone)]
 pub struct RemoteSettingsConfig {
     pub server_url: String,
     pub collection_name: String,
+    pub signature_root_hash: Option<String>,
 }
@@ -2,11 +2,14 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use crate::enrollment::{get_enrollments, map_features_by_feature_id, EnrolledFeatureConfig};
+use crate::enrollment::{
+    get_enrollments, map_features_by_feature_id, EnrolledFeatureConfig, FeatureProvenance,
+};
 use crate::error::{NimbusError, Result};
 use crate::persistence::{Database, StoreId, Writer};
 use crate::EnrolledExperiment;
 use crate::{enrollment::ExperimentEnrollment, Experiment};
+use chrono::Utc;
 use std::collections::HashMap;
 use std::sync::RwLock;
 
@@ -28,9 +31,18 @@ struct CachedData {
 #[derive(Default)]
 pub struct DatabaseCache {
     data: RwLock<Option<CachedData>>,
+    // Features which can be configured by more than one experiment at once.
+    coenrolling_feature_ids: Vec<String>,
 }
 
 impl DatabaseCache {
+    pub fn new(coenrolling_feature_ids: Vec<String>) -> Self {
+        Self {
+            coenrolling_feature_ids,
+            ..Default::default()
+        }
+    }
+
     // Call this function whenever it's possible that anything cached by this
     // struct (eg, our enrollments) might have changed.
     //
@@ -45,7 +57,8 @@ impl DatabaseCache {
     pub fn commit_and_update(&self, db: &Database, writer: Writer) -> Result<()> {
         // By passing in the active `writer` we read the state of enrollments
         // as written by the calling code, before it's committed to the db.
-        let enrollments = get_enrollments(db, &writer)?;
+        let now = Utc::now();
+        let enrollments = get_enrollments(db, &writer, now)?;
 
         // Build a lookup table for experiments by experiment slug.
         // This will be used for get_experiment_branch() and get_active_experiments()
@@ -56,10 +69,17 @@ impl DatabaseCache {
 
         let enrollments: Vec<ExperimentEnrollment> =
             db.get_store(StoreId::Enrollments).collect_all(&writer)?;
-        let experiments: Vec<Experiment> =
-            db.get_store(StoreId::Experiments).collect_all(&writer)?;
-
-        let features_by_feature_id = map_features_by_feature_id(&enrollments, &experiments);
+        // Features from experiments which have ended shouldn't be used, even
+        // if we haven't yet processed the end of the experiment.
+        let experiments: Vec<Experiment> = db
+            .get_store(StoreId::Experiments)
+            .collect_all::<Experiment, _>(&writer)?
+            .into_iter()
+            .filter(|e| !e.has_ended(now))
+            .collect();
+
+        let features_by_feature_id =
+            map_features_by_feature_id(&enrollments, &experiments, &self.coenrolling_feature_ids);
 
         // This is where testing tools would override i.e. replace experimental feature configurations.
         // i.e. testing tools would cause custom feature configs to be stored in a Store.
@@ -134,6 +154,19 @@ impl DatabaseCache {
         })
     }
 
+    // The recipes which the feature's variables came from, highest precedence first.
+    pub fn get_feature_config_provenance(
+        &self,
+        feature_id: &str,
+    ) -> Result<Vec<FeatureProvenance>> {
+        self.get_data(|data| {
+            data.features_by_feature_id
+                .get(feature_id)
+                .map(|enrolled_feature| enrolled_feature.provenance.clone())
+                .unwrap_or_default()
+        })
+    }
+
     pub fn get_active_experiments(&self) -> Result<Vec<EnrolledExperiment>> {
         self.get_data(|data| {
             data.experiments_by_slug
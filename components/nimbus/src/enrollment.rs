@@ -4,18 +4,23 @@ use crate::defaults::Defaults;
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 use crate::error::{NimbusError, Result};
 use crate::evaluator::TargetingAttributes;
+use crate::events::EventStore;
 use crate::persistence::{Database, StoreId, Writer};
 use crate::{evaluator::evaluate_enrollment, persistence::Readable};
 use crate::{AvailableRandomizationUnits, EnrolledExperiment, Experiment, FeatureConfig};
 
 use ::uuid::Uuid;
+use chrono::{DateTime, Utc};
+use nimbus_fml::intermediate_representation::FeatureManifest;
 use serde_derive::*;
+use serde_json::Value;
 use std::{
     collections::{HashMap, HashSet},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 const DB_KEY_GLOBAL_USER_PARTICIPATION: &str = "user-opt-in";
+const DB_KEY_EXPERIMENTS_FIRST_SEEN: &str = "experiments-first-seen";
 const DEFAULT_GLOBAL_USER_PARTICIPATION: bool = true;
 const PREVIOUS_ENROLLMENTS_GC_TIME: Duration = Duration::from_secs(30 * 24 * 3600);
 
@@ -35,6 +40,7 @@ pub enum EnrolledReason {
 // ⚠️ Attention : Changes to this type should be accompanied by a new test  ⚠️
 // ⚠️ in `mod test_schema_bw_compat` below, and may require a DB migration. ⚠️
 #[derive(Deserialize, Serialize, Debug, Clone, Hash, Eq, PartialEq)]
+#[serde(from = "PersistedNotEnrolledReason")]
 pub enum NotEnrolledReason {
     /// The user opted-out of experiments before we ever got enrolled to this one.
     OptOut,
@@ -45,9 +51,68 @@ pub enum NotEnrolledReason {
     /// The experiment enrollment is paused.
     EnrollmentsPaused,
     /// The experiment used a feature that was already under experiment.
+    /// `blocking_slug` is the experiment or rollout holding the feature; it's
+    /// `None` for enrollments recorded before we kept track of it.
+    FeatureConflict { blocking_slug: Option<String> },
+    /// The experiment's end date has passed.
+    ExperimentEnded,
+    /// The branch we were bucketed into has a value for `feature_id` which
+    /// doesn't match the app's feature manifest.
+    InvalidFeatureValue { feature_id: String },
+}
+
+// `FeatureConflict` used to be a unit variant, so older databases have it
+// stored as a bare string, which serde won't read as a struct variant.
+// `NotEnrolledReason` is deserialized via this type so we can still read them.
+// Keep `CurrentNotEnrolledReason` in sync with `NotEnrolledReason`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PersistedNotEnrolledReason {
+    Current(CurrentNotEnrolledReason),
+    Legacy(LegacyNotEnrolledReason),
+}
+
+#[derive(Deserialize)]
+enum CurrentNotEnrolledReason {
+    OptOut,
+    NotSelected,
+    NotTargeted,
+    EnrollmentsPaused,
+    FeatureConflict { blocking_slug: Option<String> },
+    ExperimentEnded,
+    InvalidFeatureValue { feature_id: String },
+}
+
+#[derive(Deserialize)]
+enum LegacyNotEnrolledReason {
     FeatureConflict,
 }
 
+impl From<PersistedNotEnrolledReason> for NotEnrolledReason {
+    fn from(reason: PersistedNotEnrolledReason) -> Self {
+        match reason {
+            PersistedNotEnrolledReason::Current(reason) => match reason {
+                CurrentNotEnrolledReason::OptOut => Self::OptOut,
+                CurrentNotEnrolledReason::NotSelected => Self::NotSelected,
+                CurrentNotEnrolledReason::NotTargeted => Self::NotTargeted,
+                CurrentNotEnrolledReason::EnrollmentsPaused => Self::EnrollmentsPaused,
+                CurrentNotEnrolledReason::FeatureConflict { blocking_slug } => {
+                    Self::FeatureConflict { blocking_slug }
+                }
+                CurrentNotEnrolledReason::ExperimentEnded => Self::ExperimentEnded,
+                CurrentNotEnrolledReason::InvalidFeatureValue { feature_id } => {
+                    Self::InvalidFeatureValue { feature_id }
+                }
+            },
+            PersistedNotEnrolledReason::Legacy(LegacyNotEnrolledReason::FeatureConflict) => {
+                Self::FeatureConflict {
+                    blocking_slug: None,
+                }
+            }
+        }
+    }
+}
+
 // These are types we use internally for managing disqualifications.
 
 // ⚠️ Attention : Changes to this type should be accompanied by a new test  ⚠️
@@ -60,6 +125,8 @@ pub enum DisqualifiedReason {
     OptOut,
     /// The targeting has changed for an experiment.
     NotTargeted,
+    /// The experiment's end date has passed.
+    ExperimentEnded,
 }
 
 // Every experiment has an ExperimentEnrollment, even when we aren't enrolled.
@@ -75,12 +142,16 @@ pub struct ExperimentEnrollment {
 impl ExperimentEnrollment {
     /// Evaluate an experiment enrollment for an experiment
     /// we are seeing for the first time.
+    #[allow(clippy::too_many_arguments)]
     fn from_new_experiment(
         is_user_participating: bool,
         nimbus_id: &Uuid,
         available_randomization_units: &AvailableRandomizationUnits,
         targeting_attributes: &TargetingAttributes,
         experiment: &Experiment,
+        feature_manifest: Option<&FeatureManifest>,
+        first_seen: DateTime<Utc>,
+        now: DateTime<Utc>,
         out_enrollment_events: &mut Vec<EnrollmentChangeEvent>,
     ) -> Result<Self> {
         Ok(if !is_user_participating {
@@ -90,7 +161,9 @@ impl ExperimentEnrollment {
                     reason: NotEnrolledReason::OptOut,
                 },
             }
-        } else if experiment.is_enrollment_paused {
+        } else if experiment.is_enrollment_paused
+            || experiment.is_enrollment_period_over(first_seen, now)
+        {
             Self {
                 slug: experiment.slug.clone(),
                 status: EnrollmentStatus::NotEnrolled {
@@ -103,7 +176,9 @@ impl ExperimentEnrollment {
                 available_randomization_units,
                 targeting_attributes,
                 experiment,
-            )?;
+                now,
+            )?
+            .check_feature_values(experiment, feature_manifest);
             log::debug!(
                 "Experiment '{}' is new - enrollment status is {:?}",
                 &enrollment.slug,
@@ -116,6 +191,51 @@ impl ExperimentEnrollment {
         })
     }
 
+    /// Check the feature values of the branch a freshly evaluated enrollment
+    /// is enrolled in against the app's feature manifest, if we have one. An
+    /// invalid value would be silently replaced with the app's defaults, so
+    /// we don't enroll rather than report an enrollment which has no effect.
+    /// Features which the manifest doesn't describe aren't checked.
+    fn check_feature_values(
+        self,
+        experiment: &Experiment,
+        feature_manifest: Option<&FeatureManifest>,
+    ) -> Self {
+        let (manifest, branch) = match (feature_manifest, &self.status) {
+            (Some(manifest), EnrollmentStatus::Enrolled { branch, .. }) => (manifest, branch),
+            _ => return self,
+        };
+        let feature_configs = match experiment.get_branch(branch) {
+            Some(branch) => branch.get_feature_configs(),
+            None => return self,
+        };
+        for config in feature_configs {
+            if manifest.get_feature(&config.feature_id).is_none() {
+                continue;
+            }
+            if let Err(e) =
+                manifest.validate_feature_config(&config.feature_id, &Value::Object(config.value))
+            {
+                log::warn!(
+                    "Not enrolling in '{}': branch '{}' has an invalid value for feature '{}': {}",
+                    &self.slug,
+                    branch,
+                    &config.feature_id,
+                    e
+                );
+                return Self {
+                    slug: self.slug,
+                    status: EnrollmentStatus::NotEnrolled {
+                        reason: NotEnrolledReason::InvalidFeatureValue {
+                            feature_id: config.feature_id,
+                        },
+                    },
+                };
+            }
+        }
+        self
+    }
+
     /// Force enroll ourselves in an experiment.
     fn from_explicit_opt_in(
         experiment: &Experiment,
@@ -137,6 +257,7 @@ impl ExperimentEnrollment {
     }
 
     /// Update our enrollment to an experiment we have seen before.
+    #[allow(clippy::too_many_arguments)]
     fn on_experiment_updated(
         &self,
         is_user_participating: bool,
@@ -144,11 +265,17 @@ impl ExperimentEnrollment {
         available_randomization_units: &AvailableRandomizationUnits,
         targeting_attributes: &TargetingAttributes,
         updated_experiment: &Experiment,
+        feature_manifest: Option<&FeatureManifest>,
+        first_seen: DateTime<Utc>,
+        now: DateTime<Utc>,
         out_enrollment_events: &mut Vec<EnrollmentChangeEvent>,
     ) -> Result<Self> {
         Ok(match self.status {
             EnrollmentStatus::NotEnrolled { .. } | EnrollmentStatus::Error { .. } => {
-                if !is_user_participating || updated_experiment.is_enrollment_paused {
+                if !is_user_participating
+                    || updated_experiment.is_enrollment_paused
+                    || updated_experiment.is_enrollment_period_over(first_seen, now)
+                {
                     self.clone()
                 } else {
                     let updated_enrollment = evaluate_enrollment(
@@ -156,7 +283,9 @@ impl ExperimentEnrollment {
                         available_randomization_units,
                         targeting_attributes,
                         updated_experiment,
-                    )?;
+                        now,
+                    )?
+                    .check_feature_values(updated_experiment, feature_manifest);
                     log::debug!(
                         "Experiment '{}' with enrollment {:?} is now {:?}",
                         &self.slug,
@@ -199,6 +328,7 @@ impl ExperimentEnrollment {
                         available_randomization_units,
                         targeting_attributes,
                         updated_experiment,
+                        now,
                     )?;
                     match evaluated_enrollment.status {
                         EnrollmentStatus::Error { .. } => {
@@ -216,6 +346,18 @@ impl ExperimentEnrollment {
                             out_enrollment_events.push(updated_enrollment.get_change_event());
                             updated_enrollment
                         }
+                        EnrollmentStatus::NotEnrolled {
+                            reason: NotEnrolledReason::ExperimentEnded,
+                        } => {
+                            log::debug!(
+                                "Existing experiment enrollment '{}' is now disqualified (experiment ended)",
+                                &self.slug
+                            );
+                            let updated_enrollment =
+                                self.disqualify_from_enrolled(DisqualifiedReason::ExperimentEnded);
+                            out_enrollment_events.push(updated_enrollment.get_change_event());
+                            updated_enrollment
+                        }
                         EnrollmentStatus::NotEnrolled { .. }
                         | EnrollmentStatus::Enrolled { .. }
                         | EnrollmentStatus::Disqualified { .. }
@@ -404,6 +546,7 @@ impl ExperimentEnrollment {
                     DisqualifiedReason::NotTargeted => Some("targeting"),
                     DisqualifiedReason::OptOut => Some("optout"),
                     DisqualifiedReason::Error => Some("error"),
+                    DisqualifiedReason::ExperimentEnded => Some("ended"),
                 },
                 EnrollmentChangeEventType::Disqualification,
             ),
@@ -504,10 +647,12 @@ impl EnrollmentStatus {
 }
 
 /// Return information about all enrolled experiments.
-/// Note this does not include rollouts
-pub fn get_enrollments<'r>(
+/// Note this does not include rollouts, or experiments whose end date has
+/// passed since we last evolved our enrollments.
+pub fn get_enrollments(
     db: &Database,
-    reader: &'r impl Readable<'r>,
+    reader: &impl Readable,
+    now: DateTime<Utc>,
 ) -> Result<Vec<EnrolledExperiment>> {
     let enrollments: Vec<ExperimentEnrollment> =
         db.get_store(StoreId::Enrollments).collect_all(reader)?;
@@ -524,6 +669,12 @@ pub fn get_enrollments<'r>(
                 .get_store(StoreId::Experiments)
                 .get::<Experiment, _>(reader, &enrollment.slug)?
             {
+                Some(experiment) if experiment.has_ended(now) => {
+                    log::info!(
+                        "Ignoring enrollment in ended experiment '{}'",
+                        experiment.slug
+                    );
+                }
                 Some(experiment) => {
                     if !experiment.is_rollout() {
                         result.push(EnrolledExperiment {
@@ -552,6 +703,16 @@ pub(crate) struct EnrollmentsEvolver<'a> {
     nimbus_id: &'a Uuid,
     available_randomization_units: &'a AvailableRandomizationUnits,
     targeting_attributes: &'a TargetingAttributes,
+    now: DateTime<Utc>,
+    // When we first saw each experiment. Experiments which aren't in here
+    // are being seen for the first time now.
+    first_seen: HashMap<String, DateTime<Utc>>,
+    // Features which the app allows more than one experiment (or rollout) to
+    // configure at once.
+    coenrolling_feature_ids: &'a [String],
+    // The app's feature manifest, which the feature values of branches are
+    // checked against before enrolling.
+    feature_manifest: Option<&'a FeatureManifest>,
 }
 
 impl<'a> EnrollmentsEvolver<'a> {
@@ -564,9 +725,52 @@ impl<'a> EnrollmentsEvolver<'a> {
             nimbus_id,
             available_randomization_units,
             targeting_attributes,
+            now: Utc::now(),
+            first_seen: HashMap::new(),
+            coenrolling_feature_ids: &[],
+            feature_manifest: None,
         }
     }
 
+    /// Allow experiments to coenroll on the given features, rather than
+    /// conflicting with each other.
+    pub(crate) fn with_coenrolling_feature_ids(
+        self,
+        coenrolling_feature_ids: &'a [String],
+    ) -> Self {
+        Self {
+            coenrolling_feature_ids,
+            ..self
+        }
+    }
+
+    /// Don't enroll in branches with feature values which don't match the
+    /// app's feature manifest.
+    pub(crate) fn with_feature_manifest(
+        self,
+        feature_manifest: Option<&'a FeatureManifest>,
+    ) -> Self {
+        Self {
+            feature_manifest,
+            ..self
+        }
+    }
+
+    fn is_coenrolling(&self, feature_id: &str) -> bool {
+        self.coenrolling_feature_ids
+            .iter()
+            .any(|id| id == feature_id)
+    }
+
+    /// Evolve enrollments as if the current time was `now`.
+    pub(crate) fn at(self, now: DateTime<Utc>) -> Self {
+        Self { now, ..self }
+    }
+
+    fn first_seen(&self, slug: &str) -> DateTime<Utc> {
+        self.first_seen.get(slug).copied().unwrap_or(self.now)
+    }
+
     /// Convenient wrapper around `evolve_enrollments` that fetches the current state of experiments,
     /// enrollments and user participation from the database.
     pub(crate) fn evolve_enrollments_in_db(
@@ -579,10 +783,21 @@ impl<'a> EnrollmentsEvolver<'a> {
         let is_user_participating = get_global_user_participation(db, writer)?;
         let experiments_store = db.get_store(StoreId::Experiments);
         let enrollments_store = db.get_store(StoreId::Enrollments);
+        let meta_store = db.get_store(StoreId::Meta);
         let prev_experiments: Vec<Experiment> = experiments_store.collect_all(writer)?;
         let prev_enrollments: Vec<ExperimentEnrollment> = enrollments_store.collect_all(writer)?;
+        let mut targeting_attributes = self.targeting_attributes.clone();
+        targeting_attributes
+            .update_from_db(&prev_enrollments, EventStore::read(db, writer, self.now)?);
+        let evolver = EnrollmentsEvolver {
+            first_seen: meta_store
+                .get(writer, DB_KEY_EXPERIMENTS_FIRST_SEEN)?
+                .unwrap_or_default(),
+            targeting_attributes: &targeting_attributes,
+            ..*self
+        };
         // Calculate the changes.
-        let (next_enrollments, enrollments_change_events) = self.evolve_enrollments(
+        let (next_enrollments, enrollments_change_events) = evolver.evolve_enrollments(
             is_user_participating,
             &prev_experiments,
             next_experiments,
@@ -603,9 +818,52 @@ impl<'a> EnrollmentsEvolver<'a> {
             }
             experiments_store.put(writer, &experiment.slug, experiment)?;
         }
+        let first_seen: HashMap<String, DateTime<Utc>> = next_experiments
+            .iter()
+            .map(|e| (e.slug.clone(), evolver.first_seen(&e.slug)))
+            .collect();
+        meta_store.put(writer, DB_KEY_EXPERIMENTS_FIRST_SEEN, &first_seen)?;
         Ok(enrollments_change_events)
     }
 
+    /// The enrollments which applying the experiments in the database again
+    /// would give, without writing anything. Experiments which don't have an
+    /// enrollment yet are evolved as new ones.
+    pub(crate) fn preview_enrollments_in_db(
+        &self,
+        db: &Database,
+        reader: &impl Readable,
+    ) -> Result<Vec<ExperimentEnrollment>> {
+        let is_user_participating = get_global_user_participation(db, reader)?;
+        let experiments: Vec<Experiment> =
+            db.get_store(StoreId::Experiments).collect_all(reader)?;
+        let enrollments: Vec<ExperimentEnrollment> =
+            db.get_store(StoreId::Enrollments).collect_all(reader)?;
+        let enrolled_slugs: HashSet<&str> = enrollments.iter().map(|e| e.slug.as_str()).collect();
+        let prev_experiments: Vec<Experiment> = experiments
+            .iter()
+            .filter(|e| enrolled_slugs.contains(e.slug.as_str()))
+            .cloned()
+            .collect();
+        let mut targeting_attributes = self.targeting_attributes.clone();
+        targeting_attributes.update_from_db(&enrollments, EventStore::read(db, reader, self.now)?);
+        let evolver = EnrollmentsEvolver {
+            first_seen: db
+                .get_store(StoreId::Meta)
+                .get(reader, DB_KEY_EXPERIMENTS_FIRST_SEEN)?
+                .unwrap_or_default(),
+            targeting_attributes: &targeting_attributes,
+            ..*self
+        };
+        let (next_enrollments, _) = evolver.evolve_enrollments(
+            is_user_participating,
+            &prev_experiments,
+            &experiments,
+            &enrollments,
+        )?;
+        Ok(next_enrollments)
+    }
+
     pub(crate) fn evolve_enrollments(
         &self,
         is_user_participating: bool,
@@ -675,7 +933,8 @@ impl<'a> EnrollmentsEvolver<'a> {
         let prev_enrollments = map_enrollments(prev_enrollments);
 
         // Step 1. Build an initial active_features to keep track of
-        // the features that are being experimented upon.
+        // the features that are being experimented upon. Coenrolling features
+        // can't conflict, so they're left out of it.
         let mut enrolled_features = HashMap::with_capacity(next_experiments.len());
 
         let mut next_enrollments = Vec::with_capacity(next_experiments.len());
@@ -690,7 +949,7 @@ impl<'a> EnrollmentsEvolver<'a> {
             if matches!(
                 prev_enrollment.status,
                 EnrollmentStatus::NotEnrolled {
-                    reason: NotEnrolledReason::FeatureConflict
+                    reason: NotEnrolledReason::FeatureConflict { .. }
                 }
             ) {
                 continue;
@@ -737,6 +996,7 @@ impl<'a> EnrollmentsEvolver<'a> {
             let needed_features_in_use: Vec<&EnrolledFeatureConfig> = next_experiment
                 .get_feature_ids()
                 .iter()
+                .filter(|id| !self.is_coenrolling(id))
                 .filter_map(|id| enrolled_features.get(id))
                 .collect();
             if !needed_features_in_use.is_empty() {
@@ -751,10 +1011,19 @@ impl<'a> EnrollmentsEvolver<'a> {
                 } else {
                     // At least one feature needed for this experiment is already in use by another experiment.
                     // Thus, we cannot proceed with an enrollment other than as a `FeatureConflict`.
+                    let blocking_slug = needed_features_in_use[0].slug.clone();
+                    log::info!(
+                        "Not enrolling in '{}': feature '{}' is already held by '{}'",
+                        slug,
+                        needed_features_in_use[0].feature_id,
+                        blocking_slug
+                    );
                     next_enrollments.push(ExperimentEnrollment {
                         slug: slug.clone(),
                         status: EnrollmentStatus::NotEnrolled {
-                            reason: NotEnrolledReason::FeatureConflict,
+                            reason: NotEnrolledReason::FeatureConflict {
+                                blocking_slug: Some(blocking_slug),
+                            },
                         },
                     });
                 }
@@ -776,7 +1045,7 @@ impl<'a> EnrollmentsEvolver<'a> {
                 || matches!(
                     prev_enrollment.unwrap().status,
                     EnrollmentStatus::NotEnrolled {
-                        reason: NotEnrolledReason::FeatureConflict
+                        reason: NotEnrolledReason::FeatureConflict { .. }
                     }
                 )
             {
@@ -811,7 +1080,12 @@ impl<'a> EnrollmentsEvolver<'a> {
         // Check that we generate the enrolled feature map from the new
         // enrollments and new experiments.  Perhaps this should just be an
         // assert.
-        let updated_enrolled_features = map_features(&next_enrollments, &next_experiments);
+        let mut updated_enrolled_features = map_features(
+            &next_enrollments,
+            &next_experiments,
+            self.coenrolling_feature_ids,
+        );
+        updated_enrolled_features.retain(|feature_id, _| !self.is_coenrolling(feature_id));
         if enrolled_features != updated_enrolled_features {
             Err(NimbusError::InternalError(
                 "Next enrollment calculation error",
@@ -834,7 +1108,9 @@ impl<'a> EnrollmentsEvolver<'a> {
             // If it's an enrolled enrollment, then get the FeatureConfigs
             // from the experiment and store them in the active_features map.
             for enrolled_feature in get_enrolled_feature_configs(&enrollment, experiments) {
-                enrolled_features.insert(enrolled_feature.feature_id.clone(), enrolled_feature);
+                if !self.is_coenrolling(&enrolled_feature.feature_id) {
+                    enrolled_features.insert(enrolled_feature.feature_id.clone(), enrolled_feature);
+                }
             }
             // Also, record the enrollment for our return value
             enrollments.push(enrollment);
@@ -875,6 +1151,9 @@ impl<'a> EnrollmentsEvolver<'a> {
                 self.available_randomization_units,
                 &targeting_attributes,
                 experiment,
+                self.feature_manifest,
+                self.first_seen(&experiment.slug),
+                self.now,
                 out_enrollment_events,
             )?),
             // Experiment deleted remotely.
@@ -889,6 +1168,9 @@ impl<'a> EnrollmentsEvolver<'a> {
                     self.available_randomization_units,
                     &targeting_attributes,
                     experiment,
+                    self.feature_manifest,
+                    self.first_seen(&experiment.slug),
+                    self.now,
                     out_enrollment_events,
                 )?)
             }
@@ -959,35 +1241,61 @@ fn filter_experiments(
 
 /// Take a list of enrollments and a map of experiments, and generate mapping of `feature_id` to
 /// `EnrolledFeatureConfig` structs.
+///
+/// A coenrolling feature can be configured by several of the enrollments, in which case their
+/// configs are merged in order of slug, so the first slug's values take precedence.
 fn map_features(
     enrollments: &[ExperimentEnrollment],
     experiments: &HashMap<String, &Experiment>,
+    coenrolling_feature_ids: &[String],
 ) -> HashMap<String, EnrolledFeatureConfig> {
-    let mut map = HashMap::with_capacity(enrollments.len());
-    for enrolled_feature_config in enrollments
+    let mut configs: Vec<EnrolledFeatureConfig> = enrollments
         .iter()
         .flat_map(|e| get_enrolled_feature_configs(e, experiments))
-    {
-        map.insert(
-            enrolled_feature_config.feature_id.clone(),
-            enrolled_feature_config,
-        );
+        .collect();
+    configs.sort_by(|a, b| a.slug.cmp(&b.slug));
+
+    let mut map: HashMap<String, EnrolledFeatureConfig> = HashMap::with_capacity(configs.len());
+    for enrolled_feature_config in configs {
+        let feature_id = enrolled_feature_config.feature_id.clone();
+        let config = match map.remove(&feature_id) {
+            Some(existing) if coenrolling_feature_ids.contains(&feature_id) => {
+                // Both configs are for the same feature, so this can't fail.
+                existing.defaults(&enrolled_feature_config).unwrap()
+            }
+            _ => enrolled_feature_config,
+        };
+        map.insert(feature_id, config);
     }
 
     map
 }
 
+/// Resolve the configuration of each feature from the recipes we're enrolled in.
+///
+/// Experiments are layered over rollouts: a feature's config is the experiment's config, with
+/// any values it doesn't set coming from the rollout. The `provenance` of each resolved config
+/// lists the recipes it came from, in order of precedence.
 pub fn map_features_by_feature_id(
     enrollments: &[ExperimentEnrollment],
     experiments: &[Experiment],
+    coenrolling_feature_ids: &[String],
 ) -> HashMap<String, EnrolledFeatureConfig> {
     let (rollouts, ro_enrollments) =
         filter_experiments_and_enrollments(experiments, enrollments, Experiment::is_rollout);
     let (experiments, exp_enrollments) =
         filter_experiments_and_enrollments(experiments, enrollments, |e| !e.is_rollout());
 
-    let features_under_rollout = map_features(&ro_enrollments, &map_experiments(&rollouts));
-    let features_under_experiment = map_features(&exp_enrollments, &map_experiments(&experiments));
+    let features_under_rollout = map_features(
+        &ro_enrollments,
+        &map_experiments(&rollouts),
+        coenrolling_feature_ids,
+    );
+    let features_under_experiment = map_features(
+        &exp_enrollments,
+        &map_experiments(&experiments),
+        coenrolling_feature_ids,
+    );
 
     features_under_experiment
         .defaults(&features_under_rollout)
@@ -1050,6 +1358,11 @@ fn get_enrolled_feature_configs(
                 None
             },
             feature_id: f.feature_id.clone(),
+            provenance: vec![FeatureProvenance {
+                slug: experiment_slug.clone(),
+                branch_slug: branch_slug.clone(),
+                is_rollout: experiment.is_rollout(),
+            }],
         })
         .collect()
 }
@@ -1063,6 +1376,16 @@ pub struct EnrolledFeatureConfig {
     pub slug: String,
     pub branch: Option<String>,
     pub feature_id: String,
+    /// The recipes this config was built from, highest precedence first.
+    pub provenance: Vec<FeatureProvenance>,
+}
+
+/// One of the recipes contributing to a feature's configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureProvenance {
+    pub slug: String,
+    pub branch_slug: String,
+    pub is_rollout: bool,
 }
 
 impl Defaults for EnrolledFeatureConfig {
@@ -1079,9 +1402,16 @@ impl Defaults for EnrolledFeatureConfig {
                 // Merge the actual feature config.
                 feature: self.feature.defaults(&fallback.feature)?,
                 // If this is an experiment, then this will be Some(_).
-                // The feature is involved in zero or one experiments, and 0 or more rollouts.
-                // So we can clone this Option safely.
+                // Unless the feature is coenrolling, it's involved in zero or one experiments,
+                // and 0 or more rollouts, so we can clone this Option safely. Otherwise, we
+                // report the branch of the experiment which takes precedence.
                 branch: self.branch.to_owned(),
+                provenance: self
+                    .provenance
+                    .iter()
+                    .chain(fallback.provenance.iter())
+                    .cloned()
+                    .collect(),
             })
         }
     }
@@ -1160,10 +1490,7 @@ pub fn opt_out(
     Ok(events)
 }
 
-pub fn get_global_user_participation<'r>(
-    db: &Database,
-    reader: &'r impl Readable<'r>,
-) -> Result<bool> {
+pub fn get_global_user_participation(db: &Database, reader: &impl Readable) -> Result<bool> {
     let store = db.get_store(StoreId::Meta);
     let opted_in = store.get::<bool, _>(reader, DB_KEY_GLOBAL_USER_PARTICIPATION)?;
     if let Some(opted_in) = opted_in {
@@ -1677,9 +2004,9 @@ mod tests {
         .unwrap()
     }
 
-    fn get_experiment_enrollments<'r>(
+    fn get_experiment_enrollments(
         db: &Database,
-        reader: &'r impl Readable<'r>,
+        reader: &impl Readable,
     ) -> Result<Vec<ExperimentEnrollment>> {
         db.get_store(StoreId::Enrollments).collect_all(reader)
     }
@@ -2269,7 +2596,7 @@ mod tests {
 
         // Test to ensure that features are being de-serialized and copied into EnrolledFeatureConfig and mapped
         // properly to the feature id.
-        let features = map_features_by_feature_id(&enrollments, &updated_experiments);
+        let features = map_features_by_feature_id(&enrollments, &updated_experiments, &[]);
         assert_eq!(features.len(), 1);
         assert!(features.contains_key("about_welcome"));
 
@@ -2409,22 +2736,42 @@ mod tests {
             "There should be exactly 3 ExperimentEnrollments returned"
         );
 
-        let not_enrolleds = enrollments
+        let not_enrolleds: Vec<&ExperimentEnrollment> = enrollments
             .iter()
             .filter(|&e| {
                 matches!(
                     e.status,
                     EnrollmentStatus::NotEnrolled {
-                        reason: NotEnrolledReason::FeatureConflict
+                        reason: NotEnrolledReason::FeatureConflict { .. }
                     }
                 )
             })
-            .count();
+            .collect();
         assert_eq!(
-            1, not_enrolleds,
+            1,
+            not_enrolleds.len(),
             "exactly one enrollment should have NotEnrolled status"
         );
 
+        // The conflict should name the enrolled experiment holding the feature.
+        let blocking_slug = match &not_enrolleds[0].status {
+            EnrollmentStatus::NotEnrolled {
+                reason: NotEnrolledReason::FeatureConflict { blocking_slug },
+            } => blocking_slug.clone().unwrap(),
+            _ => unreachable!(),
+        };
+        assert_ne!(blocking_slug, not_enrolleds[0].slug);
+        let blocking_experiment = test_experiments
+            .iter()
+            .find(|e| e.slug == blocking_slug)
+            .unwrap();
+        assert!(blocking_experiment
+            .get_feature_ids()
+            .contains(&"some_control".to_string()));
+        assert!(enrollments
+            .iter()
+            .any(|e| e.slug == blocking_slug && e.status.is_enrolled()));
+
         let enrolled_count = enrollments
             .iter()
             .filter(|&e| matches!(e.status, EnrollmentStatus::Enrolled { .. }))
@@ -2563,7 +2910,7 @@ mod tests {
 
         let (enrollments, _) = evolver.evolve_enrollments(true, &[], &next_experiments, &[])?;
 
-        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments);
+        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments, &[]);
         assert_eq!(feature_map.len(), 2);
         assert_eq!(
             feature_map.get("about_welcome").unwrap().slug,
@@ -2606,7 +2953,7 @@ mod tests {
 
         assert_eq!(events.len(), 0);
 
-        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments);
+        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments, &[]);
         assert_eq!(feature_map.len(), 2);
         assert_eq!(
             feature_map.get("about_welcome").unwrap().slug,
@@ -2643,7 +2990,7 @@ mod tests {
             &prev_enrollments,
         )?;
 
-        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments);
+        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments, &[]);
         assert_eq!(feature_map.len(), 1);
         assert!(feature_map.get("about_welcome").is_none());
         assert_eq!(
@@ -2674,7 +3021,7 @@ mod tests {
             &prev_enrollments,
         )?;
 
-        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments);
+        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments, &[]);
         assert_eq!(feature_map.len(), 2);
         assert_eq!(
             feature_map.get("about_welcome").unwrap().slug,
@@ -2725,7 +3072,7 @@ mod tests {
         )?;
 
         assert_eq!(events.len(), 0);
-        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments);
+        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments, &[]);
         assert_eq!(feature_map.len(), 2);
         assert_eq!(
             feature_map.get("about_welcome").unwrap().slug,
@@ -2761,7 +3108,7 @@ mod tests {
         )?;
 
         assert_eq!(events.len(), 0);
-        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments);
+        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments, &[]);
         assert_eq!(feature_map.len(), 2);
         assert_eq!(
             feature_map.get("about_welcome").unwrap().slug,
@@ -2795,7 +3142,7 @@ mod tests {
             &prev_enrollments,
         )?;
 
-        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments);
+        let feature_map = map_features_by_feature_id(&enrollments, &next_experiments, &[]);
         assert_eq!(feature_map.len(), 3);
         assert_eq!(
             feature_map.get("about_welcome").unwrap().slug,
@@ -3340,6 +3687,11 @@ mod tests {
             slug: "exp".to_string(),
             branch: Some("treatment".to_string()),
             feature_id: exp_bob.feature_id,
+            provenance: vec![FeatureProvenance {
+                slug: "exp".to_string(),
+                branch_slug: "treatment".to_string(),
+                is_rollout: false,
+            }],
         };
 
         let ro_bob = EnrolledFeatureConfig {
@@ -3347,9 +3699,23 @@ mod tests {
             slug: "ro".to_string(),
             branch: None,
             feature_id: exp_bob.feature_id.clone(),
+            provenance: vec![FeatureProvenance {
+                slug: "ro".to_string(),
+                branch_slug: "control".to_string(),
+                is_rollout: true,
+            }],
         };
 
-        let bob = exp_bob.defaults(&ro_bob)?.feature;
+        let merged = exp_bob.defaults(&ro_bob)?;
+        assert_eq!(
+            merged
+                .provenance
+                .iter()
+                .map(|p| p.slug.as_str())
+                .collect::<Vec<_>>(),
+            vec!["exp", "ro"]
+        );
+        let bob = merged.feature;
         assert_eq!(bob.feature_id, "bob".to_string());
 
         assert_eq!(
@@ -3495,12 +3861,206 @@ mod tests {
         };
         let enrollments = &[ro_enrollment, exp_enrollment];
         let experiments = &[experiment, rollout];
-        let features = map_features_by_feature_id(enrollments, experiments);
+        let features = map_features_by_feature_id(enrollments, experiments, &[]);
 
         assert_alice_bob_charlie(&features);
         Ok(())
     }
 
+    fn get_coenrolling_experiments() -> (Experiment, Experiment) {
+        let experiment = |slug: &str, value: Value| Experiment {
+            slug: slug.to_string(),
+            branches: vec![Branch {
+                slug: "treatment".to_string(),
+                features: Some(vec![FeatureConfig {
+                    feature_id: "bob".into(),
+                    value: value.as_object().unwrap().to_owned(),
+                }]),
+                ratio: 1,
+                ..Default::default()
+            }],
+            bucket_config: BucketConfig::always(),
+            ..Default::default()
+        };
+        (
+            experiment(
+                "experiment1",
+                json!({"name": "Bob", "specified": "Experiment 1"}),
+            ),
+            experiment(
+                "experiment2",
+                json!({"specified": "Experiment 2", "color": "green"}),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_evolver_exclusive_features_conflict() -> Result<()> {
+        let (exp1, exp2) = get_coenrolling_experiments();
+        let recipes = &[exp1, exp2];
+
+        let (nimbus_id, app_ctx, aru) = local_ctx();
+        let targeting_attributes = app_ctx.into();
+        let evolver = enrollment_evolver(&nimbus_id, &targeting_attributes, &aru);
+        let (enrollments, _events) = evolver.evolve_enrollments(true, &[], recipes, &[])?;
+
+        let enrolled: Vec<&ExperimentEnrollment> = enrollments
+            .iter()
+            .filter(|e| e.status.is_enrolled())
+            .collect();
+        assert_eq!(enrolled.len(), 1);
+        let conflicted: Vec<&ExperimentEnrollment> = enrollments
+            .iter()
+            .filter(|e| !e.status.is_enrolled())
+            .collect();
+        assert_eq!(conflicted.len(), 1);
+        assert_eq!(
+            conflicted[0].status,
+            EnrollmentStatus::NotEnrolled {
+                reason: NotEnrolledReason::FeatureConflict {
+                    blocking_slug: Some(enrolled[0].slug.clone())
+                }
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_evolver_coenrolling_features() -> Result<()> {
+        let (exp1, exp2) = get_coenrolling_experiments();
+        let rollout = Experiment {
+            slug: "rollout1".to_string(),
+            is_rollout: true,
+            branches: vec![Branch {
+                slug: "control".to_string(),
+                features: Some(vec![FeatureConfig {
+                    feature_id: "bob".into(),
+                    value: json!({"name": "Rollout Bob", "size": 3})
+                        .as_object()
+                        .unwrap()
+                        .to_owned(),
+                }]),
+                ratio: 1,
+                ..Default::default()
+            }],
+            ..exp1.clone()
+        };
+        let recipes = &[exp2, rollout, exp1];
+        let coenrolling_feature_ids = vec!["bob".to_string()];
+
+        let (nimbus_id, app_ctx, aru) = local_ctx();
+        let targeting_attributes = app_ctx.into();
+        let evolver = enrollment_evolver(&nimbus_id, &targeting_attributes, &aru)
+            .with_coenrolling_feature_ids(&coenrolling_feature_ids);
+        let (enrollments, events) = evolver.evolve_enrollments(true, &[], recipes, &[])?;
+        assert_eq!(enrollments.len(), 3);
+        assert!(enrollments.iter().all(|e| e.status.is_enrolled()));
+        assert_eq!(events.len(), 3);
+
+        // Evolving again keeps everyone enrolled.
+        let (next_enrollments, events) =
+            evolver.evolve_enrollments(true, recipes, recipes, &enrollments)?;
+        assert!(next_enrollments.iter().all(|e| e.status.is_enrolled()));
+        assert!(events.is_empty());
+
+        let features = map_features_by_feature_id(&enrollments, recipes, &coenrolling_feature_ids);
+        let bob = &features["bob"];
+        // The experiments are merged in slug order, and layered over the rollout.
+        assert_eq!(
+            Value::Object(bob.feature.value.clone()),
+            json!({
+                "name": "Bob",
+                "specified": "Experiment 1",
+                "color": "green",
+                "size": 3,
+            })
+        );
+        assert_eq!(bob.branch.as_deref(), Some("treatment"));
+        assert_eq!(
+            bob.provenance,
+            vec![
+                FeatureProvenance {
+                    slug: "experiment1".to_string(),
+                    branch_slug: "treatment".to_string(),
+                    is_rollout: false,
+                },
+                FeatureProvenance {
+                    slug: "experiment2".to_string(),
+                    branch_slug: "treatment".to_string(),
+                    is_rollout: false,
+                },
+                FeatureProvenance {
+                    slug: "rollout1".to_string(),
+                    branch_slug: "control".to_string(),
+                    is_rollout: true,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_evolver_invalid_feature_values() -> Result<()> {
+        let manifest: FeatureManifest = serde_json::from_value(json!({
+            "enum_defs": [],
+            "obj_defs": [],
+            "hints": {},
+            "feature_defs": ["bob", "carol"].iter().map(|name| json!({
+                "name": name,
+                "doc": "",
+                "props": [
+                    { "name": "name", "doc": "", "typ": "String", "default": "" },
+                    { "name": "size", "doc": "", "typ": "Int", "default": 1 },
+                ],
+                "default": null
+            })).collect::<Vec<_>>(),
+        }))?;
+        let experiment = |slug: &str, feature_id: &str, value: Value| Experiment {
+            slug: slug.to_string(),
+            branches: vec![Branch {
+                slug: "treatment".to_string(),
+                features: Some(vec![FeatureConfig {
+                    feature_id: feature_id.into(),
+                    value: value.as_object().unwrap().to_owned(),
+                }]),
+                ratio: 1,
+                ..Default::default()
+            }],
+            bucket_config: BucketConfig::always(),
+            ..Default::default()
+        };
+        let recipes = &[
+            experiment("valid", "bob", json!({"name": "Bob", "size": 2})),
+            experiment("invalid", "carol", json!({"size": "big"})),
+            // Features the manifest doesn't know about aren't checked.
+            experiment("unknown", "dave", json!({"anything": true})),
+        ];
+
+        let (nimbus_id, app_ctx, aru) = local_ctx();
+        let targeting_attributes = app_ctx.into();
+        let evolver = enrollment_evolver(&nimbus_id, &targeting_attributes, &aru)
+            .with_feature_manifest(Some(&manifest));
+        let (enrollments, events) = evolver.evolve_enrollments(true, &[], recipes, &[])?;
+        let enrollments = map_enrollments(&enrollments);
+        assert!(enrollments["valid"].status.is_enrolled());
+        assert!(enrollments["unknown"].status.is_enrolled());
+        assert_eq!(
+            enrollments["invalid"].status,
+            EnrollmentStatus::NotEnrolled {
+                reason: NotEnrolledReason::InvalidFeatureValue {
+                    feature_id: "carol".to_string()
+                }
+            }
+        );
+        assert_eq!(events.len(), 2);
+
+        // Without a manifest, we enroll as usual.
+        let evolver = enrollment_evolver(&nimbus_id, &targeting_attributes, &aru);
+        let (enrollments, _) = evolver.evolve_enrollments(true, &[], recipes, &[])?;
+        assert!(enrollments.iter().all(|e| e.status.is_enrolled()));
+        Ok(())
+    }
+
     #[test]
     fn test_rollouts_end_to_end() -> Result<()> {
         let (rollout, experiment) = get_rollout_and_experiment();
@@ -3512,7 +4072,7 @@ mod tests {
 
         let (enrollments, _events) = evolver.evolve_enrollments(true, &[], recipes, &[])?;
 
-        let features = map_features_by_feature_id(&enrollments, recipes);
+        let features = map_features_by_feature_id(&enrollments, recipes, &[]);
 
         assert_alice_bob_charlie(&features);
 
@@ -3636,12 +4196,12 @@ mod tests {
             ..Default::default()
         }
         .into();
-        assert_eq!(get_enrollments(&db, &writer)?.len(), 0);
+        assert_eq!(get_enrollments(&db, &writer, Utc::now())?.len(), 0);
 
         let evolver = EnrollmentsEvolver::new(&nimbus_id, &aru, &targeting_attributes);
         let events = evolver.evolve_enrollments_in_db(&db, &mut writer, &[exp1])?;
 
-        let enrollments = get_enrollments(&db, &writer)?;
+        let enrollments = get_enrollments(&db, &writer, Utc::now())?;
         assert_eq!(enrollments.len(), 1);
         let enrollment = &enrollments[0];
         assert_eq!(enrollment.slug, "secure-gold");
@@ -3676,7 +4236,7 @@ mod tests {
 
         // Now opt-out.
         opt_out(&db, &mut writer, "secure-gold")?;
-        assert_eq!(get_enrollments(&db, &writer)?.len(), 0);
+        assert_eq!(get_enrollments(&db, &writer, Utc::now())?.len(), 0);
         // check we recorded the "why" correctly.
         let ee: ExperimentEnrollment = db
             .get_store(StoreId::Enrollments)
@@ -3692,7 +4252,7 @@ mod tests {
 
         // Opt in to a specific branch.
         opt_in_with_branch(&db, &mut writer, "secure-gold", "treatment")?;
-        let enrollments = get_enrollments(&db, &writer)?;
+        let enrollments = get_enrollments(&db, &writer, Utc::now())?;
         assert_eq!(enrollments.len(), 1);
         let enrollment = &enrollments[0];
         assert_eq!(enrollment.slug, "secure-gold");
@@ -3717,13 +4277,13 @@ mod tests {
             ..Default::default()
         }
         .into();
-        assert_eq!(get_enrollments(&db, &writer)?.len(), 0);
+        assert_eq!(get_enrollments(&db, &writer, Utc::now())?.len(), 0);
         let exps = get_test_experiments();
 
         let evolver = EnrollmentsEvolver::new(&nimbus_id, &aru, &targeting_attributes);
         let events = evolver.evolve_enrollments_in_db(&db, &mut writer, &exps)?;
 
-        let enrollments = get_enrollments(&db, &writer)?;
+        let enrollments = get_enrollments(&db, &writer, Utc::now())?;
         assert_eq!(enrollments.len(), 2);
         assert_eq!(events.len(), 2);
 
@@ -3733,7 +4293,7 @@ mod tests {
         let events = evolver.evolve_enrollments_in_db(&db, &mut writer, exps)?;
 
         // should only have 1 now.
-        let enrollments = get_enrollments(&db, &writer)?;
+        let enrollments = get_enrollments(&db, &writer, Utc::now())?;
         assert_eq!(enrollments.len(), 1);
         // Check that the un-enrolled event was emitted.
         assert_eq!(events.len(), 1);
@@ -3748,6 +4308,177 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_evolver_new_experiment_ended() -> Result<()> {
+        let mut exp = get_test_experiments()[0].clone();
+        exp.end_date = Some(Utc::now() - chrono::Duration::days(1));
+        let (nimbus_id, app_ctx, aru) = local_ctx();
+        let targeting_attributes = app_ctx.into();
+        let evolver = enrollment_evolver(&nimbus_id, &targeting_attributes, &aru);
+        let mut events = vec![];
+        let enrollment = evolver
+            .evolve_enrollment(true, None, Some(&exp), None, &mut events)?
+            .unwrap();
+        assert_eq!(
+            enrollment.status,
+            EnrollmentStatus::NotEnrolled {
+                reason: NotEnrolledReason::ExperimentEnded
+            }
+        );
+        assert!(events.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evolver_experiment_update_enrolled_then_experiment_ended() -> Result<()> {
+        let now = Utc::now();
+        let mut exp = get_test_experiments()[0].clone();
+        exp.end_date = Some(now + chrono::Duration::days(1));
+        let (nimbus_id, app_ctx, aru) = local_ctx();
+        let targeting_attributes = app_ctx.into();
+        let existing_enrollment = ExperimentEnrollment {
+            slug: exp.slug.clone(),
+            status: EnrollmentStatus::new_enrolled(EnrolledReason::Qualified, "control"),
+        };
+
+        // Before the end date, nothing changes.
+        let evolver = enrollment_evolver(&nimbus_id, &targeting_attributes, &aru).at(now);
+        let mut events = vec![];
+        let enrollment = evolver
+            .evolve_enrollment(
+                true,
+                Some(&exp),
+                Some(&exp),
+                Some(&existing_enrollment),
+                &mut events,
+            )?
+            .unwrap();
+        assert_eq!(enrollment, existing_enrollment);
+        assert!(events.is_empty());
+
+        // After it, we're disqualified.
+        let evolver = enrollment_evolver(&nimbus_id, &targeting_attributes, &aru)
+            .at(now + chrono::Duration::days(2));
+        let enrollment = evolver
+            .evolve_enrollment(
+                true,
+                Some(&exp),
+                Some(&exp),
+                Some(&existing_enrollment),
+                &mut events,
+            )?
+            .unwrap();
+        assert!(matches!(
+            enrollment.status,
+            EnrollmentStatus::Disqualified {
+                reason: DisqualifiedReason::ExperimentEnded,
+                ..
+            }
+        ));
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].change,
+            EnrollmentChangeEventType::Disqualification
+        );
+        assert_eq!(events[0].reason.as_deref(), Some("ended"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_evolver_experiment_not_started() -> Result<()> {
+        let now = Utc::now();
+        let mut exp = get_test_experiments()[0].clone();
+        exp.start_date = Some(now + chrono::Duration::days(1));
+        let (nimbus_id, app_ctx, aru) = local_ctx();
+        let targeting_attributes = app_ctx.into();
+        let mut events = vec![];
+
+        let evolver = enrollment_evolver(&nimbus_id, &targeting_attributes, &aru).at(now);
+        let enrollment = evolver
+            .evolve_enrollment(true, None, Some(&exp), None, &mut events)?
+            .unwrap();
+        assert_eq!(
+            enrollment.status,
+            EnrollmentStatus::NotEnrolled {
+                reason: NotEnrolledReason::EnrollmentsPaused
+            }
+        );
+
+        // Once the experiment starts we can enroll.
+        let evolver = enrollment_evolver(&nimbus_id, &targeting_attributes, &aru)
+            .at(now + chrono::Duration::days(2));
+        let enrollment = evolver
+            .evolve_enrollment(true, Some(&exp), Some(&exp), Some(&enrollment), &mut events)?
+            .unwrap();
+        assert!(matches!(
+            enrollment.status,
+            EnrollmentStatus::Enrolled { .. }
+        ));
+        assert_eq!(events.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_enrollment_period_counts_from_first_seen() -> Result<()> {
+        let _ = env_logger::try_init();
+        let first_seen = Utc::now();
+        let (nimbus_id, app_ctx, aru) = local_ctx();
+        let targeting_attributes = app_ctx.into();
+        let mut exp = get_test_experiments()[0].clone();
+        assert_eq!(exp.proposed_enrollment, 7);
+
+        // Evolve while enrollment is paused, so we see the experiment but
+        // don't enroll, then unpause it `days_later`.
+        let mut enroll_after = |days_later: i64| -> Result<EnrollmentStatus> {
+            let tmp_dir = TempDir::new("test_enrollment_period")?;
+            let db = Database::new(&tmp_dir)?;
+            let mut writer = db.write()?;
+            exp.is_enrollment_paused = true;
+            EnrollmentsEvolver::new(&nimbus_id, &aru, &targeting_attributes)
+                .at(first_seen)
+                .evolve_enrollments_in_db(&db, &mut writer, &[exp.clone()])?;
+            exp.is_enrollment_paused = false;
+            EnrollmentsEvolver::new(&nimbus_id, &aru, &targeting_attributes)
+                .at(first_seen + chrono::Duration::days(days_later))
+                .evolve_enrollments_in_db(&db, &mut writer, &[exp.clone()])?;
+            let enrollments = get_experiment_enrollments(&db, &writer)?;
+            Ok(enrollments[0].status.clone())
+        };
+
+        assert!(matches!(
+            enroll_after(3)?,
+            EnrollmentStatus::Enrolled { .. }
+        ));
+        assert_eq!(
+            enroll_after(8)?,
+            EnrollmentStatus::NotEnrolled {
+                reason: NotEnrolledReason::EnrollmentsPaused
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_enrollments_ignores_ended_experiments() -> Result<()> {
+        let tmp_dir = TempDir::new("test_get_enrollments_ended")?;
+        let db = Database::new(&tmp_dir)?;
+        let mut writer = db.write()?;
+        let now = Utc::now();
+        let (nimbus_id, app_ctx, aru) = local_ctx();
+        let targeting_attributes = app_ctx.into();
+        let mut exps = get_test_experiments();
+        exps[0].end_date = Some(now + chrono::Duration::days(1));
+        EnrollmentsEvolver::new(&nimbus_id, &aru, &targeting_attributes)
+            .at(now)
+            .evolve_enrollments_in_db(&db, &mut writer, &exps)?;
+
+        assert_eq!(get_enrollments(&db, &writer, now)?.len(), 2);
+        let enrollments = get_enrollments(&db, &writer, now + chrono::Duration::days(2))?;
+        assert_eq!(enrollments.len(), 1);
+        assert_eq!(enrollments[0].slug, exps[1].slug);
+        Ok(())
+    }
+
     #[test]
     fn test_global_opt_out() -> Result<()> {
         let _ = env_logger::try_init();
@@ -3763,7 +4494,7 @@ mod tests {
         }
         .into();
         let aru = Default::default();
-        assert_eq!(get_enrollments(&db, &writer)?.len(), 0);
+        assert_eq!(get_enrollments(&db, &writer, Utc::now())?.len(), 0);
         let exps = get_test_experiments();
 
         // User has opted out of new experiments.
@@ -3772,7 +4503,7 @@ mod tests {
         let evolver = EnrollmentsEvolver::new(&nimbus_id, &aru, &targeting_attributes);
         let events = evolver.evolve_enrollments_in_db(&db, &mut writer, &exps)?;
 
-        let enrollments = get_enrollments(&db, &writer)?;
+        let enrollments = get_enrollments(&db, &writer, Utc::now())?;
         assert_eq!(enrollments.len(), 0);
         assert!(events.is_empty());
         // We should see the experiment non-enrollments.
@@ -3796,7 +4527,7 @@ mod tests {
         let evolver = EnrollmentsEvolver::new(&nimbus_id, &aru, &targeting_attributes);
         let events = evolver.evolve_enrollments_in_db(&db, &mut writer, &exps)?;
 
-        let enrollments = get_enrollments(&db, &writer)?;
+        let enrollments = get_enrollments(&db, &writer, Utc::now())?;
         assert_eq!(enrollments.len(), 2);
         assert_eq!(events.len(), 2);
         // We should see 2 experiment enrollments.
@@ -3813,7 +4544,7 @@ mod tests {
         let evolver = EnrollmentsEvolver::new(&nimbus_id, &aru, &targeting_attributes);
         let events = evolver.evolve_enrollments_in_db(&db, &mut writer, &exps)?;
 
-        let enrollments = get_enrollments(&db, &writer)?;
+        let enrollments = get_enrollments(&db, &writer, Utc::now())?;
         assert_eq!(enrollments.len(), 0);
         assert_eq!(events.len(), 2);
         // We should see 2 experiment enrolments, this time they're both opt outs
@@ -3841,7 +4572,7 @@ mod tests {
         let evolver = EnrollmentsEvolver::new(&nimbus_id, &aru, &targeting_attributes);
         let events = evolver.evolve_enrollments_in_db(&db, &mut writer, &exps)?;
 
-        let enrollments = get_enrollments(&db, &writer)?;
+        let enrollments = get_enrollments(&db, &writer, Utc::now())?;
         assert_eq!(enrollments.len(), 0);
         assert!(events.is_empty());
 
@@ -4083,7 +4814,81 @@ mod test_schema_bw_compat {
         }))
         .unwrap();
         assert!(
-            matches!(non_enrollment.status, EnrollmentStatus::NotEnrolled{ ref reason, ..} if reason == &NotEnrolledReason::FeatureConflict)
+            matches!(non_enrollment.status, EnrollmentStatus::NotEnrolled{ ref reason, ..} if reason == &NotEnrolledReason::FeatureConflict { blocking_slug: None })
+        );
+    }
+
+    // We added the slug of the blocking experiment to `FeatureConflict`, which
+    // made it a struct variant.
+    #[test]
+    fn test_not_enrolled_reason_schema_with_blocking_slug() {
+        // ⚠️ Warning : Do not change the JSON data used by this test. ⚠️
+        let non_enrollment: ExperimentEnrollment = serde_json::from_value(json!({
+            "slug": "secure-gold",
+            "status": {"NotEnrolled": {
+                "reason": {"FeatureConflict": {"blocking_slug": "secure-silver"}},
+            }}
+        }))
+        .unwrap();
+        assert!(
+            matches!(non_enrollment.status, EnrollmentStatus::NotEnrolled{ ref reason, ..} if reason == &NotEnrolledReason::FeatureConflict { blocking_slug: Some("secure-silver".to_string()) })
+        );
+        // Check we still read the other variants, too.
+        let non_enrollment: ExperimentEnrollment = serde_json::from_value(json!({
+            "slug": "secure-gold",
+            "status": {"NotEnrolled": {
+                "reason": "NotSelected",
+            }}
+        }))
+        .unwrap();
+        assert!(
+            matches!(non_enrollment.status, EnrollmentStatus::NotEnrolled{ ref reason, ..} if reason == &NotEnrolledReason::NotSelected)
+        );
+    }
+
+    // We added `ExperimentEnded` variants to `NotEnrolledReason` and
+    // `DisqualifiedReason` when we started enforcing experiment end dates.
+    #[test]
+    fn test_enrollment_schema_with_experiment_ended() {
+        // ⚠️ Warning : Do not change the JSON data used by this test. ⚠️
+        let non_enrollment: ExperimentEnrollment = serde_json::from_value(json!({
+            "slug": "secure-gold",
+            "status": {"NotEnrolled": {
+                "reason": "ExperimentEnded",
+            }}
+        }))
+        .unwrap();
+        assert!(
+            matches!(non_enrollment.status, EnrollmentStatus::NotEnrolled{ ref reason, ..} if reason == &NotEnrolledReason::ExperimentEnded)
+        );
+        let disqualified: ExperimentEnrollment = serde_json::from_value(json!({
+            "slug": "secure-gold",
+            "status": {"Disqualified": {
+                "enrollment_id": "b6d6f532-e219-4b5a-8ddf-66700dd47d68",
+                "reason": "ExperimentEnded",
+                "branch": "hello",
+            }}
+        }))
+        .unwrap();
+        assert!(
+            matches!(disqualified.status, EnrollmentStatus::Disqualified{ ref reason, ..} if reason == &DisqualifiedReason::ExperimentEnded)
+        );
+    }
+
+    // We added `InvalidFeatureValue` to `NotEnrolledReason` when we started
+    // checking feature values against the app's feature manifest.
+    #[test]
+    fn test_not_enrolled_reason_schema_with_invalid_feature_value() {
+        // ⚠️ Warning : Do not change the JSON data used by this test. ⚠️
+        let non_enrollment: ExperimentEnrollment = serde_json::from_value(json!({
+            "slug": "secure-gold",
+            "status": {"NotEnrolled": {
+                "reason": {"InvalidFeatureValue": {"feature_id": "homescreen"}},
+            }}
+        }))
+        .unwrap();
+        assert!(
+            matches!(non_enrollment.status, EnrollmentStatus::NotEnrolled{ ref reason, ..} if reason == &NotEnrolledReason::InvalidFeatureValue { feature_id: "homescreen".to_string() })
         );
     }
 }
@@ -13,6 +13,10 @@ pub enum NimbusError {
     InvalidPersistedData,
     #[error("Rkv error: {0}")]
     RkvError(#[from] rkv::StoreError),
+    #[error("Error executing SQL: {0}")]
+    SqlError(#[from] rusqlite::Error),
+    #[error("Error opening database: {0}")]
+    OpenDatabaseError(#[from] sql_support::open_database::Error),
     #[error("IO error: {0}")]
     IOError(#[from] std::io::Error),
     #[error("JSON Error: {0}")]
@@ -51,6 +55,10 @@ pub enum NimbusError {
     BackoffError(u64),
     #[error("Initialization of the database is not yet complete")]
     DatabaseNotReady,
+    #[error("Experiments failed signature verification: {0}")]
+    SignatureError(String),
+    #[error("Invalid feature manifest: {0}")]
+    InvalidFeatureManifest(String),
 }
 
 impl<'a> From<jexl_eval::error::EvaluationError<'a>> for NimbusError {
@@ -59,4 +67,10 @@ impl<'a> From<jexl_eval::error::EvaluationError<'a>> for NimbusError {
     }
 }
 
+impl From<rc_crypto::Error> for NimbusError {
+    fn from(error: rc_crypto::Error) -> Self {
+        NimbusError::SignatureError(error.to_string())
+    }
+}
+
 pub type Result<T, E = NimbusError> = std::result::Result<T, E>;
@@ -6,14 +6,18 @@
 use crate::enrollment::{
     EnrolledReason, EnrollmentStatus, ExperimentEnrollment, NotEnrolledReason,
 };
+use crate::events::EventStore;
 use crate::{
     error::{NimbusError, Result},
     AvailableRandomizationUnits,
 };
 use crate::{matcher::AppContext, sampling};
 use crate::{Branch, Experiment};
+use chrono::{DateTime, Utc};
 use jexl_eval::Evaluator;
 use serde_derive::*;
+use std::collections::HashSet;
+use std::sync::Arc;
 use uuid::Uuid;
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Bucket {}
@@ -32,6 +36,44 @@ pub struct TargetingAttributes {
     pub is_already_enrolled: bool,
     pub days_since_install: Option<i32>,
     pub days_since_update: Option<i32>,
+    /// The slugs of the experiments this client is currently enrolled in.
+    #[serde(default)]
+    pub active_experiments: HashSet<String>,
+    /// The slugs of every experiment this client has been enrolled in,
+    /// including the ones it has since left.
+    #[serde(default)]
+    pub enrollments: HashSet<String>,
+    /// The events recorded by the app, queried with the `eventCount` transform.
+    #[serde(skip)]
+    pub events: Arc<EventStore>,
+}
+
+impl TargetingAttributes {
+    /// Fill in the attributes which come from our own database.
+    pub(crate) fn update_from_db(
+        &mut self,
+        enrollments: &[ExperimentEnrollment],
+        events: EventStore,
+    ) {
+        self.active_experiments = enrollments
+            .iter()
+            .filter(|e| e.status.is_enrolled())
+            .map(|e| e.slug.clone())
+            .collect();
+        self.enrollments = enrollments
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e.status,
+                    EnrollmentStatus::Enrolled { .. }
+                        | EnrollmentStatus::Disqualified { .. }
+                        | EnrollmentStatus::WasEnrolled { .. }
+                )
+            })
+            .map(|e| e.slug.clone())
+            .collect();
+        self.events = Arc::new(events);
+    }
 }
 
 /// Determine the enrolment status for an experiment.
@@ -41,6 +83,7 @@ pub struct TargetingAttributes {
 /// - `available_randomization_units` The app provded available randomization units
 /// - `targeting_attributes` The attributes to use when evaluating targeting
 /// - `exp` The `Experiment` to evaluate.
+/// - `now` The current time, which is checked against the experiment's start and end dates.
 ///
 /// # Returns:
 /// An `ExperimentEnrollment` -  you need to inspect the EnrollmentStatus to
@@ -57,6 +100,7 @@ pub fn evaluate_enrollment(
     available_randomization_units: &AvailableRandomizationUnits,
     targeting_attributes: &TargetingAttributes,
     exp: &Experiment,
+    now: DateTime<Utc>,
 ) -> Result<ExperimentEnrollment> {
     if !is_experiment_available(&targeting_attributes.app_context, exp, true) {
         return Ok(ExperimentEnrollment {
@@ -67,6 +111,24 @@ pub fn evaluate_enrollment(
         });
     }
 
+    if exp.has_ended(now) {
+        return Ok(ExperimentEnrollment {
+            slug: exp.slug.clone(),
+            status: EnrollmentStatus::NotEnrolled {
+                reason: NotEnrolledReason::ExperimentEnded,
+            },
+        });
+    }
+    // Enrollment doesn't open until the experiment starts.
+    if exp.has_not_started(now) {
+        return Ok(ExperimentEnrollment {
+            slug: exp.slug.clone(),
+            status: EnrollmentStatus::NotEnrolled {
+                reason: NotEnrolledReason::EnrollmentsPaused,
+            },
+        });
+    }
+
     // Get targeting out of the way - "if let chains" are experimental,
     // otherwise we could improve this.
     if let Some(expr) = &exp.targeting {
@@ -135,34 +197,40 @@ pub fn is_experiment_available(
 ) -> bool {
     // Verify the app_name matches the application being targeted
     // by the experiment.
-    match &exp.app_name {
-        Some(app_name) => {
-            if !app_name.eq(&app_context.app_name) {
-                return false;
-            }
-        }
-        None => log::debug!("Experiment missing app_name, skipping it as a targeting parameter"),
+    if !app_name_matches(app_context, exp) {
+        return false;
     }
 
     if !is_release {
         return true;
     }
 
-    // Verify the channel matches the application being targeted
-    // by the experiment.  Note, we are intentionally comparing in a case-insensitive way.
-    // See https://jira.mozilla.com/browse/SDK-246 for more info.
+    channel_matches(app_context, exp)
+}
+
+fn app_name_matches(app_context: &AppContext, exp: &Experiment) -> bool {
+    match &exp.app_name {
+        Some(app_name) => app_name.eq(&app_context.app_name),
+        None => {
+            log::debug!("Experiment missing app_name, skipping it as a targeting parameter");
+            true
+        }
+    }
+}
+
+// Verify the channel matches the application being targeted
+// by the experiment.  Note, we are intentionally comparing in a case-insensitive way.
+// See https://jira.mozilla.com/browse/SDK-246 for more info.
+fn channel_matches(app_context: &AppContext, exp: &Experiment) -> bool {
     match &exp.channel {
-        Some(channel) => {
-            if !channel
-                .to_lowercase()
-                .eq(&app_context.channel.to_lowercase())
-            {
-                return false;
-            }
+        Some(channel) => channel
+            .to_lowercase()
+            .eq(&app_context.channel.to_lowercase()),
+        None => {
+            log::debug!("Experiment missing channel, skipping it as a targeting parameter");
+            true
         }
-        None => log::debug!("Experiment missing channel, skipping it as a targeting parameter"),
     }
-    true
 }
 
 /// Chooses a branch randomly from a set of branches
@@ -219,7 +287,10 @@ fn targeting(
     expression_statement: &str,
     targeting_attributes: &TargetingAttributes,
 ) -> Option<EnrollmentStatus> {
-    match Evaluator::new().eval_in_context(expression_statement, targeting_attributes) {
+    let events = &targeting_attributes.events;
+    let evaluator =
+        Evaluator::new().with_transform("eventCount", move |args| events.count_transform(args));
+    match evaluator.eval_in_context(expression_statement, targeting_attributes) {
         Ok(res) => match res.as_bool() {
             Some(true) => None,
             Some(false) => Some(EnrollmentStatus::NotEnrolled {
@@ -235,6 +306,222 @@ fn targeting(
     }
 }
 
+/// A trace of how an experiment was evaluated for this client, intended to
+/// help people debugging why a client is (or isn't) enrolled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnrollmentExplanation {
+    pub experiment_slug: String,
+    pub app_name_matches: bool,
+    pub channel_matches: bool,
+    /// `None` if the experiment has no targeting expression.
+    pub targeting: Option<TargetingExplanation>,
+    /// `None` if the client doesn't have the experiment's randomization unit.
+    pub bucket: Option<BucketExplanation>,
+    /// The branch the client would be in if it were enrolled.
+    pub branch_slug: Option<String>,
+    /// The result of evaluating the enrollment, eg, `NotEnrolled (NotTargeted)`.
+    pub status: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetingExplanation {
+    pub expression: String,
+    pub matches: bool,
+    /// The narrowest `&&` clause of the expression which didn't evaluate to
+    /// `true`, if we could find one.
+    pub failed_clause: Option<String>,
+    /// Set if the expression couldn't be evaluated, or didn't evaluate to a bool.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BucketExplanation {
+    pub randomization_unit: String,
+    pub bucket: u32,
+    pub start: u32,
+    pub count: u32,
+    pub total: u32,
+    pub selected: bool,
+}
+
+/// Explain how an experiment is evaluated, step by step. Unlike
+/// `evaluate_enrollment`, this doesn't stop at the first check which fails,
+/// so that every step can be reported.
+///
+/// The other arguments are the same as `evaluate_enrollment`'s, and `status`
+/// is the enrollment the enrollments evolver gives the experiment, which also
+/// accounts for paused enrollment and feature conflicts. It's `None` if the
+/// evolver discarded the experiment.
+pub fn explain_enrollment(
+    nimbus_id: &Uuid,
+    available_randomization_units: &AvailableRandomizationUnits,
+    targeting_attributes: &TargetingAttributes,
+    exp: &Experiment,
+    status: Option<&EnrollmentStatus>,
+) -> Result<EnrollmentExplanation> {
+    let app_context = &targeting_attributes.app_context;
+    let targeting = exp
+        .targeting
+        .as_ref()
+        .map(|expr| explain_targeting(expr, targeting_attributes));
+
+    let bucket_config = &exp.bucket_config;
+    let (bucket, branch_slug) = match available_randomization_units
+        .get_value(&nimbus_id.to_string(), &bucket_config.randomization_unit)
+    {
+        Some(id) => {
+            let input = vec![id.to_owned(), bucket_config.namespace.clone()];
+            let bucket = BucketExplanation {
+                randomization_unit: serde_json::to_value(&bucket_config.randomization_unit)?
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                bucket: sampling::bucket_for(&input, bucket_config.total)?,
+                start: bucket_config.start,
+                count: bucket_config.count,
+                total: bucket_config.total,
+                selected: sampling::bucket_sample(
+                    &input,
+                    bucket_config.start,
+                    bucket_config.count,
+                    bucket_config.total,
+                )?,
+            };
+            let branch = choose_branch(&exp.slug, &exp.branches, id)?;
+            (Some(bucket), Some(branch.slug.clone()))
+        }
+        None => (None, None),
+    };
+
+    let status = match status {
+        Some(EnrollmentStatus::Enrolled { reason, branch, .. }) => {
+            format!("Enrolled ({:?}) in {}", reason, branch)
+        }
+        Some(EnrollmentStatus::NotEnrolled { reason }) => format!("NotEnrolled ({:?})", reason),
+        Some(EnrollmentStatus::Error { reason }) => format!("Error ({})", reason),
+        Some(status) => format!("{:?}", status),
+        None => "Discarded".to_string(),
+    };
+
+    Ok(EnrollmentExplanation {
+        experiment_slug: exp.slug.clone(),
+        app_name_matches: app_name_matches(app_context, exp),
+        channel_matches: channel_matches(app_context, exp),
+        targeting,
+        bucket,
+        branch_slug,
+        status,
+    })
+}
+
+fn explain_targeting(
+    expression_statement: &str,
+    targeting_attributes: &TargetingAttributes,
+) -> TargetingExplanation {
+    let (matches, error) = match targeting(expression_statement, targeting_attributes) {
+        None => (true, None),
+        Some(EnrollmentStatus::Error { reason }) => (false, Some(reason)),
+        Some(_) => (false, None),
+    };
+    let failed_clause = if matches {
+        None
+    } else {
+        find_failed_clause(expression_statement, targeting_attributes)
+            .or_else(|| Some(expression_statement.trim().to_string()))
+    };
+    TargetingExplanation {
+        expression: expression_statement.to_string(),
+        matches,
+        failed_clause,
+        error,
+    }
+}
+
+/// Find the first clause of a chain of `&&`s which doesn't evaluate to
+/// `true`, descending into parenthesized `&&` chains to narrow it down.
+fn find_failed_clause(
+    expression_statement: &str,
+    targeting_attributes: &TargetingAttributes,
+) -> Option<String> {
+    let clauses = top_level_clauses(expression_statement);
+    if clauses.len() < 2 {
+        // Strip redundant parentheses around the whole expression, if any.
+        let inner = strip_outer_parens(expression_statement)?;
+        return find_failed_clause(inner, targeting_attributes)
+            .or_else(|| Some(inner.trim().to_string()));
+    }
+    let failed = clauses
+        .into_iter()
+        .find(|clause| targeting(clause, targeting_attributes).is_some())?;
+    find_failed_clause(failed, targeting_attributes).or_else(|| Some(failed.to_string()))
+}
+
+/// Split an expression on the `&&`s which aren't nested in brackets or
+/// strings. If the expression has a top level `||`, it's returned whole,
+/// because `&&` binds tighter and splitting it wouldn't be meaningful.
+fn top_level_clauses(expression_statement: &str) -> Vec<&str> {
+    let bytes = expression_statement.as_bytes();
+    let mut clauses = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        match quote {
+            Some(q) => {
+                if c == b'\\' {
+                    i += 1;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                b'"' | b'\'' => quote = Some(c),
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' | b'}' => depth -= 1,
+                b'|' if depth == 0 && bytes.get(i + 1) == Some(&b'|') => {
+                    return vec![expression_statement.trim()];
+                }
+                b'&' if depth == 0 && bytes.get(i + 1) == Some(&b'&') => {
+                    clauses.push(expression_statement[start..i].trim());
+                    i += 1;
+                    start = i + 1;
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    clauses.push(expression_statement[start..].trim());
+    clauses
+}
+
+/// Returns the contents of `expression_statement` if it's entirely wrapped
+/// in a single pair of parentheses.
+fn strip_outer_parens(expression_statement: &str) -> Option<&str> {
+    let trimmed = expression_statement.trim();
+    let inner = trimmed.strip_prefix('(')?.strip_suffix(')')?;
+    // Make sure the parentheses we stripped match each other, ie, this isn't
+    // `(a) && (b)`, by checking the inner brackets balance without going
+    // negative. Brackets inside strings are rare enough in targeting that we
+    // don't worry about them here; the worst case is a less precise answer.
+    let mut depth = 0i32;
+    for c in inner.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth < 0 {
+                    return None;
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(inner)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -435,6 +722,131 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_top_level_clauses() {
+        assert_eq!(
+            top_level_clauses("a == 1 && (b || c && d) && e == 'x && y'"),
+            vec!["a == 1", "(b || c && d)", "e == 'x && y'"]
+        );
+        assert_eq!(top_level_clauses("a && b || c"), vec!["a && b || c"]);
+        assert_eq!(top_level_clauses(" a "), vec!["a"]);
+        assert_eq!(strip_outer_parens("(a && b)"), Some("a && b"));
+        assert_eq!(strip_outer_parens("(a) && (b)"), None);
+    }
+
+    #[test]
+    fn test_explain_targeting() {
+        let targeting_attributes: TargetingAttributes = AppContext {
+            app_id: "1010".to_string(),
+            app_version: Some("4.4".to_string()),
+            locale: Some("en-US".to_string()),
+            ..Default::default()
+        }
+        .into();
+
+        let explanation = explain_targeting(
+            "app_id == '1010' && (locale == 'en-US' && (app_version == '4.5'))",
+            &targeting_attributes,
+        );
+        assert!(!explanation.matches);
+        assert_eq!(explanation.error, None);
+        assert_eq!(
+            explanation.failed_clause.as_deref(),
+            Some("app_version == '4.5'")
+        );
+
+        let explanation = explain_targeting("app_id == '1010'", &targeting_attributes);
+        assert!(explanation.matches);
+        assert_eq!(explanation.failed_clause, None);
+
+        let explanation = explain_targeting("app_id == '1011'", &targeting_attributes);
+        assert!(!explanation.matches);
+        assert_eq!(
+            explanation.failed_clause.as_deref(),
+            Some("app_id == '1011'")
+        );
+
+        let explanation = explain_targeting(
+            "app_id == '1010' && not_a_field == 1",
+            &targeting_attributes,
+        );
+        assert!(!explanation.matches);
+        assert!(explanation.error.is_some());
+        assert_eq!(
+            explanation.failed_clause.as_deref(),
+            Some("not_a_field == 1")
+        );
+    }
+
+    #[test]
+    fn test_targeting_enrollments() {
+        let enrollments = vec![
+            ExperimentEnrollment {
+                slug: "enrolled".to_string(),
+                status: EnrollmentStatus::new_enrolled(EnrolledReason::Qualified, "control"),
+            },
+            ExperimentEnrollment {
+                slug: "was-enrolled".to_string(),
+                status: EnrollmentStatus::WasEnrolled {
+                    enrollment_id: Uuid::new_v4(),
+                    branch: "control".to_string(),
+                    experiment_ended_at: 0,
+                },
+            },
+            ExperimentEnrollment {
+                slug: "not-enrolled".to_string(),
+                status: EnrollmentStatus::NotEnrolled {
+                    reason: NotEnrolledReason::NotSelected,
+                },
+            },
+        ];
+        let mut targeting_attributes = TargetingAttributes::default();
+        targeting_attributes.update_from_db(&enrollments, Default::default());
+
+        let matches = |expr: &str| targeting(expr, &targeting_attributes).is_none();
+        assert!(matches("'enrolled' in active_experiments"));
+        assert!(!matches("'was-enrolled' in active_experiments"));
+        assert!(matches("'was-enrolled' in enrollments"));
+        assert!(matches("'enrolled' in enrollments"));
+        assert!(!matches("'not-enrolled' in enrollments"));
+    }
+
+    #[cfg(feature = "rkv-safe-mode")]
+    #[test]
+    fn test_targeting_event_count() -> Result<()> {
+        use crate::persistence::Database;
+        use tempdir::TempDir;
+
+        let tmp_dir = TempDir::new("test_targeting_event_count")?;
+        let db = Database::new(&tmp_dir)?;
+        let mut writer = db.write()?;
+        crate::events::record_event(&db, &mut writer, "app_opened", 3, Utc::now())?;
+        writer.commit()?;
+
+        let mut targeting_attributes = TargetingAttributes::default();
+        targeting_attributes.update_from_db(&[], EventStore::read(&db, &db.read()?, Utc::now())?);
+
+        assert_eq!(
+            targeting("'app_opened'|eventCount(7) >= 3", &targeting_attributes),
+            None
+        );
+        assert_eq!(
+            targeting("'app_opened'|eventCount > 3", &targeting_attributes),
+            Some(EnrollmentStatus::NotEnrolled {
+                reason: NotEnrolledReason::NotTargeted
+            })
+        );
+        assert_eq!(
+            targeting("'app_closed'|eventCount(7) == 0", &targeting_attributes),
+            None
+        );
+        assert!(matches!(
+            targeting("'app_opened'|eventCount('forever')", &targeting_attributes),
+            Some(EnrollmentStatus::Error { .. })
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_expression() {
         // This expression doesn't return a bool
@@ -598,9 +1010,14 @@ mod tests {
 
         let id = uuid::Uuid::new_v4();
 
-        let enrollment =
-            evaluate_enrollment(&id, &Default::default(), &targeting_attributes, &experiment)
-                .unwrap();
+        let enrollment = evaluate_enrollment(
+            &id,
+            &Default::default(),
+            &targeting_attributes,
+            &experiment,
+            Utc::now(),
+        )
+        .unwrap();
         println!("Uh oh!  {:#?}", enrollment.status);
         assert!(matches!(
             enrollment.status,
@@ -615,9 +1032,14 @@ mod tests {
         targeting_attributes.app_context.channel = "Nightly".to_string();
 
         // Now we will be enrolled in the experiment because we have the right channel, but with different capitalization
-        let enrollment =
-            evaluate_enrollment(&id, &Default::default(), &targeting_attributes, &experiment)
-                .unwrap();
+        let enrollment = evaluate_enrollment(
+            &id,
+            &Default::default(),
+            &targeting_attributes,
+            &experiment,
+            Utc::now(),
+        )
+        .unwrap();
         assert!(matches!(
             enrollment.status,
             EnrollmentStatus::Enrolled {
@@ -680,6 +1102,7 @@ mod tests {
             &Default::default(),
             &targeting_attributes,
             &experiment,
+            Utc::now(),
         )
         .unwrap();
         // The status should be `Error`
@@ -693,6 +1116,7 @@ mod tests {
             &available_randomization_units,
             &targeting_attributes,
             &experiment,
+            Utc::now(),
         )
         .unwrap();
         assert!(matches!(
@@ -752,9 +1176,14 @@ mod tests {
         .into();
 
         // We won't be enrolled in the experiment because we don't have the right app_name
-        let enrollment =
-            evaluate_enrollment(&id, &Default::default(), &targeting_attributes, &experiment)
-                .unwrap();
+        let enrollment = evaluate_enrollment(
+            &id,
+            &Default::default(),
+            &targeting_attributes,
+            &experiment,
+            Utc::now(),
+        )
+        .unwrap();
         assert!(matches!(
             enrollment.status,
             EnrollmentStatus::NotEnrolled {
@@ -768,9 +1197,14 @@ mod tests {
 
         // Now we won't be enrolled in the experiment because we don't have the right channel, but with the same
         // `NotTargeted` reason
-        let enrollment =
-            evaluate_enrollment(&id, &Default::default(), &targeting_attributes, &experiment)
-                .unwrap();
+        let enrollment = evaluate_enrollment(
+            &id,
+            &Default::default(),
+            &targeting_attributes,
+            &experiment,
+            Utc::now(),
+        )
+        .unwrap();
         assert!(matches!(
             enrollment.status,
             EnrollmentStatus::NotEnrolled {
@@ -820,6 +1254,7 @@ mod tests {
             &available_randomization_units,
             &targeting_attributes,
             &experiment,
+            Utc::now(),
         )
         .unwrap();
         assert!(matches!(
@@ -0,0 +1,224 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Behavioral events recorded by the application, so that experiments can
+//! target clients based on what they've done, eg, "opened the app on at least
+//! 3 of the last 7 days".
+//!
+//! We only keep a count per event per day, for the last `MAX_DAYS` days.
+
+use crate::error::Result;
+use crate::persistence::{Database, Readable, StoreId, Writer};
+use chrono::{DateTime, Utc};
+use serde_derive::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+/// Events older than this many days are forgotten.
+const MAX_DAYS: i64 = 365;
+
+/// The number of times an event was recorded on each day, keyed by the
+/// number of days since the Unix epoch.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EventCounts {
+    days: BTreeMap<i64, u64>,
+}
+
+impl EventCounts {
+    fn record(&mut self, count: u64, now: DateTime<Utc>) {
+        let today = day_number(now);
+        *self.days.entry(today).or_default() += count;
+        self.days = self.days.split_off(&(today - MAX_DAYS + 1));
+    }
+
+    /// The number of times the event was recorded in the `days` days up to
+    /// and including today, or ever if `days` is `None`.
+    fn count(&self, days: Option<i64>, now: DateTime<Utc>) -> u64 {
+        let today = day_number(now);
+        let first_day = days.map_or(i64::MIN, |days| today - days + 1);
+        self.days.range(first_day..=today).map(|(_, c)| c).sum()
+    }
+}
+
+fn day_number(time: DateTime<Utc>) -> i64 {
+    time.timestamp().div_euclid(24 * 60 * 60)
+}
+
+/// A snapshot of all the recorded events, used when evaluating targeting.
+#[derive(Debug, Clone)]
+pub struct EventStore {
+    events: HashMap<String, EventCounts>,
+    /// The time targeting is evaluated at, which the `eventCount` transform
+    /// counts back from.
+    now: DateTime<Utc>,
+}
+
+impl Default for EventStore {
+    fn default() -> Self {
+        Self {
+            events: HashMap::new(),
+            now: Utc::now(),
+        }
+    }
+}
+
+impl EventStore {
+    /// Read the events to evaluate targeting at `now`.
+    pub(crate) fn read(db: &Database, reader: &impl Readable, now: DateTime<Utc>) -> Result<Self> {
+        let events = db
+            .get_store(StoreId::Events)
+            .collect_all_with_keys::<EventCounts, _>(reader)?
+            .into_iter()
+            .collect();
+        Ok(Self { events, now })
+    }
+
+    pub fn count(&self, event_id: &str, days: Option<i64>, now: DateTime<Utc>) -> u64 {
+        self.events
+            .get(event_id)
+            .map_or(0, |counts| counts.count(days, now))
+    }
+
+    /// The `eventCount` JEXL transform: `'app_opened'|eventCount(7)` is the
+    /// number of times `app_opened` was recorded in the last 7 days, and
+    /// `'app_opened'|eventCount` the number of times it was ever recorded.
+    pub(crate) fn count_transform(&self, args: &[Value]) -> anyhow::Result<Value> {
+        let event_id = args
+            .get(0)
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("eventCount must be applied to an event id"))?;
+        let days = match args.get(1) {
+            None => None,
+            Some(days) => Some(days.as_f64().filter(|days| *days >= 1.0).ok_or_else(|| {
+                anyhow::anyhow!("eventCount takes a positive number of days, not {}", days)
+            })? as i64),
+        };
+        Ok(Value::from(self.count(event_id, days, self.now)))
+    }
+}
+
+pub(crate) fn record_event(
+    db: &Database,
+    writer: &mut Writer,
+    event_id: &str,
+    count: u64,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let store = db.get_store(StoreId::Events);
+    let mut counts: EventCounts = store.get(writer, event_id)?.unwrap_or_default();
+    counts.record(count, now);
+    store.put(writer, event_id, &counts)
+}
+
+pub(crate) fn clear_events(db: &Database, writer: &mut Writer) -> Result<()> {
+    db.get_store(StoreId::Events).clear(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use serde_json::json;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_event_counts() {
+        let now = Utc::now();
+        let mut counts = EventCounts::default();
+        counts.record(1, now - Duration::days(10));
+        counts.record(2, now - Duration::days(3));
+        counts.record(1, now);
+        counts.record(1, now);
+
+        assert_eq!(counts.count(Some(1), now), 2);
+        assert_eq!(counts.count(Some(7), now), 4);
+        assert_eq!(counts.count(None, now), 5);
+        // Nothing is counted from the future.
+        assert_eq!(counts.count(None, now - Duration::days(5)), 1);
+    }
+
+    #[test]
+    fn test_event_counts_forget_old_days() {
+        let now = Utc::now();
+        let mut counts = EventCounts::default();
+        counts.record(1, now - Duration::days(MAX_DAYS + 1));
+        counts.record(1, now - Duration::days(MAX_DAYS - 1));
+        counts.record(1, now);
+        assert_eq!(counts.days.len(), 2);
+        assert_eq!(counts.count(None, now), 2);
+    }
+
+    #[test]
+    fn test_count_transform() {
+        let now = Utc::now();
+        let mut counts = EventCounts::default();
+        counts.record(3, now);
+        counts.record(2, now - Duration::days(30));
+        let mut events = HashMap::new();
+        events.insert("app_opened".to_string(), counts);
+        let store = EventStore { events, now };
+
+        assert_eq!(
+            store
+                .count_transform(&[json!("app_opened"), json!(7)])
+                .unwrap(),
+            json!(3)
+        );
+        assert_eq!(
+            store.count_transform(&[json!("app_opened")]).unwrap(),
+            json!(5)
+        );
+        assert_eq!(
+            store
+                .count_transform(&[json!("never_seen"), json!(7)])
+                .unwrap(),
+            json!(0)
+        );
+        assert!(store.count_transform(&[json!(1)]).is_err());
+        assert!(store
+            .count_transform(&[json!("app_opened"), json!("week")])
+            .is_err());
+        assert!(store
+            .count_transform(&[json!("app_opened"), json!(0)])
+            .is_err());
+
+        // The days are counted back from when the targeting is evaluated, not
+        // from when the transform happens to run.
+        let store = EventStore {
+            now: now + Duration::days(7),
+            ..store
+        };
+        assert_eq!(
+            store
+                .count_transform(&[json!("app_opened"), json!(7)])
+                .unwrap(),
+            json!(0)
+        );
+    }
+
+    #[cfg(feature = "rkv-safe-mode")]
+    #[test]
+    fn test_record_and_read_events() -> Result<()> {
+        let tmp_dir = TempDir::new("test_record_and_read_events")?;
+        let db = Database::new(&tmp_dir)?;
+        let now = Utc::now();
+
+        let mut writer = db.write()?;
+        record_event(&db, &mut writer, "app_opened", 1, now)?;
+        record_event(&db, &mut writer, "app_opened", 2, now)?;
+        record_event(&db, &mut writer, "sync_login", 1, now)?;
+        writer.commit()?;
+
+        let store = EventStore::read(&db, &db.read()?, now)?;
+        assert_eq!(store.count("app_opened", Some(1), now), 3);
+        assert_eq!(store.count("sync_login", None, now), 1);
+
+        let mut writer = db.write()?;
+        clear_events(&db, &mut writer)?;
+        writer.commit()?;
+        let store = EventStore::read(&db, &db.read()?, now)?;
+        assert_eq!(store.count("app_opened", None, now), 0);
+        Ok(())
+    }
+}
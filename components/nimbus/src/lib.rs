@@ -6,7 +6,8 @@ mod dbcache;
 mod enrollment;
 pub mod error;
 mod evaluator;
-use chrono::{DateTime, NaiveDateTime, Utc};
+mod events;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use defaults::Defaults;
 pub use error::{NimbusError, Result};
 mod client;
@@ -23,14 +24,17 @@ use client::{create_client, parse_experiments, SettingsClient};
 pub use config::RemoteSettingsConfig;
 use dbcache::DatabaseCache;
 pub use enrollment::EnrollmentStatus;
+pub use enrollment::FeatureProvenance;
 use enrollment::{
     get_global_user_participation, opt_in_with_branch, opt_out, set_global_user_participation,
-    EnrollmentChangeEvent, EnrollmentsEvolver,
+    EnrollmentChangeEvent, EnrollmentsEvolver, ExperimentEnrollment,
 };
-use evaluator::is_experiment_available;
+use evaluator::{explain_enrollment, is_experiment_available};
+pub use evaluator::{BucketExplanation, EnrollmentExplanation, TargetingExplanation};
 
 // Exposed for Example only
 pub use evaluator::TargetingAttributes;
+pub use events::EventStore;
 
 // We only use this in a test, and with --no-default-features, we don't use it
 // at all
@@ -38,6 +42,7 @@ pub use evaluator::TargetingAttributes;
 use enrollment::EnrollmentChangeEventType;
 
 pub use matcher::AppContext;
+use nimbus_fml::intermediate_representation::FeatureManifest;
 use once_cell::sync::OnceCell;
 use persistence::{Database, StoreId, Writer};
 use serde_derive::*;
@@ -45,7 +50,10 @@ use serde_json::{Map, Value};
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use updating::{read_and_remove_pending_experiments, write_pending_experiments};
+use updating::{
+    read_and_remove_pending_experiments, read_record_cache, write_pending_experiments,
+    write_record_cache,
+};
 use uuid::Uuid;
 
 const DEFAULT_TOTAL_BUCKETS: u32 = 10000;
@@ -71,6 +79,9 @@ struct InternalMutableState {
     available_randomization_units: AvailableRandomizationUnits,
     // Application level targeting attributes
     targeting_attributes: TargetingAttributes,
+    // The app's feature manifest, if it gave us one, which feature values
+    // are validated against before enrolling.
+    feature_manifest: Option<FeatureManifest>,
 }
 
 /// Nimbus is the main struct representing the experiments state
@@ -80,6 +91,8 @@ pub struct NimbusClient {
     settings_client: Mutex<Box<dyn SettingsClient + Send>>,
     mutable_state: Mutex<InternalMutableState>,
     app_context: AppContext,
+    // Features which more than one experiment (or rollout) can configure at once.
+    coenrolling_feature_ids: Vec<String>,
     db: OnceCell<Database>,
     // Manages an in-memory cache so that we can answer certain requests
     // without doing (or waiting for) IO.
@@ -92,6 +105,7 @@ impl NimbusClient {
     // thread in the gecko Javascript stack, hence the use of OnceCell for the db.
     pub fn new<P: Into<PathBuf>>(
         app_context: AppContext,
+        coenrolling_feature_ids: Vec<String>,
         db_path: P,
         config: Option<RemoteSettingsConfig>,
         available_randomization_units: AvailableRandomizationUnits,
@@ -101,12 +115,14 @@ impl NimbusClient {
         let mutable_state = Mutex::new(InternalMutableState {
             available_randomization_units,
             targeting_attributes: app_context.clone().into(),
+            feature_manifest: None,
         });
         Ok(Self {
             settings_client,
             mutable_state,
             app_context,
-            database_cache: Default::default(),
+            database_cache: DatabaseCache::new(coenrolling_feature_ids.clone()),
+            coenrolling_feature_ids,
             db_path: db_path.into(),
             db: OnceCell::default(),
         })
@@ -142,6 +158,14 @@ impl NimbusClient {
             .get_feature_config_variables(&feature_id)
     }
 
+    pub fn get_feature_config_provenance(
+        &self,
+        feature_id: String,
+    ) -> Result<Vec<FeatureProvenance>> {
+        self.database_cache
+            .get_feature_config_provenance(&feature_id)
+    }
+
     pub fn get_experiment_branches(&self, slug: String) -> Result<Vec<ExperimentBranch>> {
         self.get_all_experiments()?
             .into_iter()
@@ -174,7 +198,9 @@ impl NimbusClient {
             &nimbus_id,
             &state.available_randomization_units,
             &state.targeting_attributes,
-        );
+        )
+        .with_coenrolling_feature_ids(&self.coenrolling_feature_ids)
+        .with_feature_manifest(state.feature_manifest.as_ref());
         let events = evolver.evolve_enrollments_in_db(db, &mut writer, &existing_experiments)?;
         self.database_cache.commit_and_update(db, writer)?;
         Ok(events)
@@ -200,6 +226,78 @@ impl NimbusClient {
             .collect())
     }
 
+    /// Explain how the experiment with the given slug is evaluated for this
+    /// client, using the current targeting attributes and randomization units.
+    /// Nothing is written to the database, so this can be called freely when
+    /// debugging why a client is, or isn't, enrolled.
+    pub fn explain_enrollment(&self, experiment_slug: String) -> Result<EnrollmentExplanation> {
+        let db = self.db()?;
+        let reader = db.read()?;
+        let experiment = db
+            .get_store(StoreId::Experiments)
+            .get::<Experiment, _>(&reader, &experiment_slug)?
+            .ok_or_else(|| NimbusError::NoSuchExperiment(experiment_slug.clone()))?;
+        // We don't want to create an id here, because then we'd be persisting
+        // it. It's created when experiments are first applied.
+        let nimbus_id: Uuid = db
+            .get_store(StoreId::Meta)
+            .get(&reader, DB_KEY_NIMBUS_ID)?
+            .ok_or(NimbusError::InternalError(
+                "No nimbus_id yet - experiments haven't been applied",
+            ))?;
+        let enrollments: Vec<ExperimentEnrollment> =
+            db.get_store(StoreId::Enrollments).collect_all(&reader)?;
+
+        // The explanation and the evolver's status are evaluated at the same time.
+        let now = Utc::now();
+        let state = self.mutable_state.lock().unwrap();
+        let mut targeting_attributes = state.targeting_attributes.clone();
+        targeting_attributes.update_from_db(&enrollments, EventStore::read(db, &reader, now)?);
+        targeting_attributes.is_already_enrolled = enrollments
+            .iter()
+            .any(|e| e.slug == experiment_slug && e.status.is_enrolled());
+        // The status comes from the evolver, so that it's the one applying
+        // the experiments would give.
+        let evolver = EnrollmentsEvolver::new(
+            &nimbus_id,
+            &state.available_randomization_units,
+            &state.targeting_attributes,
+        )
+        .with_coenrolling_feature_ids(&self.coenrolling_feature_ids)
+        .with_feature_manifest(state.feature_manifest.as_ref())
+        .at(now);
+        let enrollment = evolver
+            .preview_enrollments_in_db(db, &reader)?
+            .into_iter()
+            .find(|e| e.slug == experiment_slug);
+        explain_enrollment(
+            &nimbus_id,
+            &state.available_randomization_units,
+            &targeting_attributes,
+            &experiment,
+            enrollment.as_ref().map(|e| &e.status),
+        )
+    }
+
+    /// Record that a behavioral event happened `count` times, so that
+    /// experiments can target on it with the `eventCount` JEXL transform.
+    pub fn record_event(&self, event_id: String, count: u32) -> Result<()> {
+        let db = self.db()?;
+        let mut writer = db.write()?;
+        events::record_event(db, &mut writer, &event_id, count.into(), Utc::now())?;
+        writer.commit()?;
+        Ok(())
+    }
+
+    /// Forget all the recorded events.
+    pub fn clear_events(&self) -> Result<()> {
+        let db = self.db()?;
+        let mut writer = db.write()?;
+        events::clear_events(db, &mut writer)?;
+        writer.commit()?;
+        Ok(())
+    }
+
     pub fn opt_in_with_branch(
         &self,
         experiment_slug: String,
@@ -228,9 +326,11 @@ impl NimbusClient {
     pub fn fetch_experiments(&self) -> Result<()> {
         log::info!("fetching experiments");
         let settings_client = self.settings_client.lock().unwrap();
-        let new_experiments = settings_client.fetch_experiments()?;
         let db = self.db()?;
+        let mut cache = read_record_cache(db, &db.read()?)?;
+        let new_experiments = settings_client.fetch_experiments_with_cache(&mut cache)?;
         let mut writer = db.write()?;
+        write_record_cache(db, &mut writer, &cache)?;
         write_pending_experiments(db, &mut writer, new_experiments)?;
         writer.commit()?;
         Ok(())
@@ -276,7 +376,9 @@ impl NimbusClient {
                     &nimbus_id,
                     &state.available_randomization_units,
                     &state.targeting_attributes,
-                );
+                )
+                .with_coenrolling_feature_ids(&self.coenrolling_feature_ids)
+                .with_feature_manifest(state.feature_manifest.as_ref());
                 evolver.evolve_enrollments_in_db(db, &mut writer, &new_experiments)?
             }
             None => vec![],
@@ -387,6 +489,21 @@ impl NimbusClient {
         Ok(())
     }
 
+    /// Set the app's feature manifest, as the JSON of the intermediate
+    /// representation generated by `nimbus-fml`. From the next time the
+    /// experiments are applied, branches with feature values which don't
+    /// match the manifest won't be enrolled in.
+    pub fn set_feature_manifest(&self, manifest_json: String) -> Result<()> {
+        let manifest: FeatureManifest = serde_json::from_str(&manifest_json)
+            .map_err(|e| NimbusError::InvalidFeatureManifest(e.to_string()))?;
+        manifest
+            .validate_manifest()
+            .map_err(|e| NimbusError::InvalidFeatureManifest(e.to_string()))?;
+        let mut state = self.mutable_state.lock().unwrap();
+        state.feature_manifest = Some(manifest);
+        Ok(())
+    }
+
     /// Reset internal state in response to application-level telemetry reset.
     ///
     /// When the user resets their telemetry state in the consuming application, we need learn
@@ -495,9 +612,13 @@ pub struct Experiment {
     #[serde(default)]
     pub feature_ids: Vec<String>,
     pub targeting: Option<String>,
-    pub start_date: Option<String>, // TODO: Use a date format here
-    pub end_date: Option<String>,   // TODO: Use a date format here
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub start_date: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub end_date: Option<DateTime<Utc>>,
     pub proposed_duration: Option<u32>,
+    // The number of days enrollment stays open for, counted from when we first
+    // saw the experiment.
     pub proposed_enrollment: u32,
     pub reference_branch: Option<String>,
     #[serde(default)]
@@ -506,7 +627,52 @@ pub struct Experiment {
     // but we ignore them because they're for internal use by RemoteSettings.
 }
 
+// Dates have been sent both as RFC 3339 timestamps and as plain `YYYY-MM-DD`
+// dates, which we take to mean midnight UTC. A date we can't parse is treated
+// as missing rather than making the whole experiment invalid.
+fn deserialize_optional_date<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let date: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(date.and_then(|date| {
+        let parsed = DateTime::parse_from_rfc3339(&date)
+            .map(|d| d.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                    .ok()
+                    .map(|d| DateTime::from_utc(d.and_hms(0, 0, 0), Utc))
+            });
+        if parsed.is_none() {
+            log::warn!("Ignoring invalid experiment date {:?}", date);
+        }
+        parsed
+    }))
+}
+
 impl Experiment {
+    /// Whether the experiment's `end_date` has passed.
+    fn has_ended(&self, now: DateTime<Utc>) -> bool {
+        matches!(self.end_date, Some(end_date) if end_date <= now)
+    }
+
+    /// Whether the experiment's `start_date` is still to come.
+    fn has_not_started(&self, now: DateTime<Utc>) -> bool {
+        matches!(self.start_date, Some(start_date) if start_date > now)
+    }
+
+    /// Whether the `proposed_enrollment` period, counted from `first_seen`,
+    /// is over, meaning we should stop enrolling new clients.
+    fn is_enrollment_period_over(&self, first_seen: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        // A `proposed_enrollment` of 0 means it wasn't specified, not that
+        // there's no enrollment period at all.
+        self.proposed_enrollment > 0
+            && now - first_seen >= chrono::Duration::days(self.proposed_enrollment.into())
+    }
+
     fn has_branch(&self, branch_slug: &str) -> bool {
         self.branches
             .iter()
@@ -709,7 +875,7 @@ mod tests {
 
     use super::*;
     use chrono::Duration;
-    use enrollment::{EnrolledReason, EnrollmentStatus, ExperimentEnrollment};
+    use enrollment::{EnrolledReason, EnrollmentStatus, NotEnrolledReason};
     use serde_json::json;
     use tempdir::TempDir;
 
@@ -722,6 +888,7 @@ mod tests {
         let tmp_dir = TempDir::new("test_telemetry_reset")?;
         let client = NimbusClient::new(
             AppContext::default(),
+            Default::default(),
             tmp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -784,6 +951,251 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_explain_enrollment() -> Result<()> {
+        let tmp_dir = TempDir::new("test_explain_enrollment")?;
+        let client = NimbusClient::new(
+            AppContext {
+                app_name: "fenix".to_string(),
+                app_id: "org.mozilla.fenix".to_string(),
+                channel: "nightly".to_string(),
+                ..Default::default()
+            },
+            Default::default(),
+            tmp_dir.path(),
+            None,
+            Default::default(),
+        )?;
+        let experiment: Experiment = serde_json::from_value(json!({
+            "schemaVersion": "1.0.0",
+            "slug": "secure-gold",
+            "appName": "fenix",
+            "channel": "nightly",
+            "branches": [
+                {"slug": "control", "ratio": 1},
+                {"slug": "treatment", "ratio": 1}
+            ],
+            "bucketConfig": {
+                "count": 10000,
+                "start": 0,
+                "total": 10000,
+                "namespace": "secure-gold",
+                "randomizationUnit": "nimbus_id"
+            },
+            "targeting": "app_id == 'org.mozilla.fenix' && (is_already_enrolled || app_id == 'org.mozilla.firefox')",
+            "userFacingName": "",
+            "userFacingDescription": "",
+            "isEnrollmentPaused": false,
+            "featureIds": ["some-feature"]
+        }))?;
+
+        // Unknown experiments are an error.
+        assert!(matches!(
+            client.explain_enrollment("secure-gold".to_string()),
+            Err(NimbusError::NoSuchExperiment(_))
+        ));
+
+        let db = client.db()?;
+        let mut writer = db.write()?;
+        db.get_store(StoreId::Experiments)
+            .put(&mut writer, "secure-gold", &experiment)?;
+        writer.commit()?;
+        client.set_nimbus_id(&Uuid::parse_str("29686b11-00c0-4905-b5e4-f5f945eda60a")?)?;
+
+        let explanation = client.explain_enrollment("secure-gold".to_string())?;
+        assert_eq!(explanation.experiment_slug, "secure-gold");
+        assert!(explanation.app_name_matches);
+        assert!(explanation.channel_matches);
+        let targeting = explanation.targeting.unwrap();
+        assert!(!targeting.matches);
+        assert_eq!(targeting.error, None);
+        assert_eq!(
+            targeting.failed_clause.as_deref(),
+            Some("is_already_enrolled || app_id == 'org.mozilla.firefox'")
+        );
+        let bucket = explanation.bucket.unwrap();
+        assert_eq!(bucket.randomization_unit, "nimbus_id");
+        assert!(bucket.selected);
+        assert!(bucket.bucket < 10000);
+        assert!(explanation.branch_slug.is_some());
+        assert_eq!(explanation.status, "NotEnrolled (NotTargeted)");
+
+        // Nothing should have been written.
+        let reader = db.read()?;
+        let enrollment: Option<ExperimentEnrollment> = db
+            .get_store(StoreId::Enrollments)
+            .get(&reader, "secure-gold")?;
+        assert!(enrollment.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_enrollment_paused_and_conflicting() -> Result<()> {
+        let tmp_dir = TempDir::new("test_explain_enrollment_paused_and_conflicting")?;
+        let client = NimbusClient::new(
+            AppContext {
+                app_name: "fenix".to_string(),
+                app_id: "org.mozilla.fenix".to_string(),
+                channel: "nightly".to_string(),
+                ..Default::default()
+            },
+            Default::default(),
+            tmp_dir.path(),
+            None,
+            Default::default(),
+        )?;
+        let experiment = |slug: &str, feature_id: &str, paused: bool| -> Result<Experiment> {
+            Ok(serde_json::from_value(json!({
+                "schemaVersion": "1.0.0",
+                "slug": slug,
+                "appName": "fenix",
+                "channel": "nightly",
+                "branches": [
+                    {"slug": "control", "ratio": 1, "feature": {"featureId": feature_id, "enabled": true, "value": {}}},
+                ],
+                "bucketConfig": {
+                    "count": 10000,
+                    "start": 0,
+                    "total": 10000,
+                    "namespace": slug,
+                    "randomizationUnit": "nimbus_id"
+                },
+                "userFacingName": "",
+                "userFacingDescription": "",
+                "isEnrollmentPaused": paused,
+                "featureIds": [feature_id]
+            }))?)
+        };
+
+        let db = client.db()?;
+        let mut writer = db.write()?;
+        let experiments = db.get_store(StoreId::Experiments);
+        experiments.put(
+            &mut writer,
+            "blocker",
+            &experiment("blocker", "some-feature", false)?,
+        )?;
+        experiments.put(
+            &mut writer,
+            "blocked",
+            &experiment("blocked", "some-feature", false)?,
+        )?;
+        experiments.put(
+            &mut writer,
+            "paused",
+            &experiment("paused", "other-feature", true)?,
+        )?;
+        db.get_store(StoreId::Enrollments).put(
+            &mut writer,
+            "blocker",
+            &ExperimentEnrollment {
+                slug: "blocker".to_string(),
+                status: EnrollmentStatus::new_enrolled(EnrolledReason::Qualified, "control"),
+            },
+        )?;
+        writer.commit()?;
+        client.set_nimbus_id(&Uuid::parse_str("29686b11-00c0-4905-b5e4-f5f945eda60a")?)?;
+
+        // Both would be enrolled if only their targeting and bucketing counted.
+        let explanation = client.explain_enrollment("paused".to_string())?;
+        assert!(explanation.bucket.unwrap().selected);
+        assert_eq!(explanation.status, "NotEnrolled (EnrollmentsPaused)");
+
+        let explanation = client.explain_enrollment("blocked".to_string())?;
+        assert!(explanation.bucket.unwrap().selected);
+        assert_eq!(
+            explanation.status,
+            "NotEnrolled (FeatureConflict { blocking_slug: Some(\"blocker\") })"
+        );
+
+        let explanation = client.explain_enrollment("blocker".to_string())?;
+        assert_eq!(explanation.status, "Enrolled (Qualified) in control");
+        Ok(())
+    }
+
+    #[test]
+    fn test_feature_manifest() -> Result<()> {
+        let tmp_dir = TempDir::new("test_feature_manifest")?;
+        let client = NimbusClient::new(
+            AppContext {
+                app_name: "fenix".to_string(),
+                app_id: "org.mozilla.fenix".to_string(),
+                channel: "nightly".to_string(),
+                ..Default::default()
+            },
+            Default::default(),
+            tmp_dir.path(),
+            None,
+            Default::default(),
+        )?;
+        client.initialize()?;
+
+        assert!(matches!(
+            client.set_feature_manifest("not a manifest".to_string()),
+            Err(NimbusError::InvalidFeatureManifest(_))
+        ));
+        let manifest = json!({
+            "enum_defs": [],
+            "obj_defs": [],
+            "hints": {},
+            "feature_defs": [{
+                "name": "homescreen",
+                "doc": "The homescreen",
+                "props": [{
+                    "name": "row-count",
+                    "doc": "The number of rows",
+                    "typ": "Int",
+                    "default": 2
+                }],
+                "default": null
+            }]
+        });
+        client.set_feature_manifest(manifest.to_string())?;
+
+        let experiments = json!({"data": [{
+            "schemaVersion": "1.0.0",
+            "slug": "secure-gold",
+            "appName": "fenix",
+            "appId": "org.mozilla.fenix",
+            "channel": "nightly",
+            "branches": [{
+                "slug": "treatment",
+                "ratio": 1,
+                "feature": {"featureId": "homescreen", "value": {"row-count": "two"}}
+            }],
+            "bucketConfig": {
+                "count": 10000,
+                "start": 0,
+                "total": 10000,
+                "namespace": "secure-gold",
+                "randomizationUnit": "nimbus_id"
+            },
+            "userFacingName": "",
+            "userFacingDescription": "",
+            "isEnrollmentPaused": false,
+            "featureIds": ["homescreen"]
+        }]});
+        client.set_experiments_locally(experiments.to_string())?;
+        client.apply_pending_experiments()?;
+
+        assert!(client.get_active_experiments()?.is_empty());
+        let db = client.db()?;
+        let reader = db.read()?;
+        let enrollment: ExperimentEnrollment = db
+            .get_store(StoreId::Enrollments)
+            .get(&reader, "secure-gold")?
+            .unwrap();
+        assert_eq!(
+            enrollment.status,
+            EnrollmentStatus::NotEnrolled {
+                reason: NotEnrolledReason::InvalidFeatureValue {
+                    feature_id: "homescreen".to_string()
+                }
+            }
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_installation_date() -> Result<()> {
         let mock_client_id = "client-1".to_string();
@@ -799,6 +1211,7 @@ mod tests {
         };
         let client = NimbusClient::new(
             app_context.clone(),
+            Default::default(),
             tmp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -835,6 +1248,7 @@ mod tests {
         app_context.installation_date = None;
         let client = NimbusClient::new(
             app_context.clone(),
+            Default::default(),
             tmp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -858,6 +1272,7 @@ mod tests {
         // we wipe any non-persistent memory
         let client = NimbusClient::new(
             app_context.clone(),
+            Default::default(),
             tmp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -889,6 +1304,7 @@ mod tests {
         // Step 4: We test that if the storage is clear, we will fallback to the
         let client = NimbusClient::new(
             app_context,
+            Default::default(),
             tmp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -911,6 +1327,7 @@ mod tests {
         let tmp_dir = TempDir::new("test_days_since_update")?;
         let client = NimbusClient::new(
             AppContext::default(),
+            Default::default(),
             tmp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -932,6 +1349,7 @@ mod tests {
         };
         let client = NimbusClient::new(
             app_context.clone(),
+            Default::default(),
             tmp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -960,6 +1378,7 @@ mod tests {
         // the update_date should not change
         let client = NimbusClient::new(
             app_context.clone(),
+            Default::default(),
             tmp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -994,6 +1413,7 @@ mod tests {
         app_context.app_version = Some("v94.0.1".into()); // A different version
         let client = NimbusClient::new(
             app_context,
+            Default::default(),
             tmp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -1036,6 +1456,7 @@ mod tests {
         };
         let mut client = NimbusClient::new(
             app_context.clone(),
+            Default::default(),
             temp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -1048,6 +1469,7 @@ mod tests {
             days_since_install: Some(10),
             days_since_update: None,
             is_already_enrolled: false,
+            ..Default::default()
         };
         client.with_targeting_attributes(targeting_attributes);
         client.initialize()?;
@@ -1113,6 +1535,7 @@ mod tests {
         };
         let mut client = NimbusClient::new(
             app_context.clone(),
+            Default::default(),
             temp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -1125,6 +1548,7 @@ mod tests {
             days_since_install: Some(10),
             days_since_update: None,
             is_already_enrolled: false,
+            ..Default::default()
         };
         client.with_targeting_attributes(targeting_attributes);
         client.initialize()?;
@@ -1189,6 +1613,7 @@ mod tests {
         };
         let mut client = NimbusClient::new(
             app_context.clone(),
+            Default::default(),
             temp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -1201,6 +1626,7 @@ mod tests {
             days_since_install: None,
             days_since_update: Some(10),
             is_already_enrolled: false,
+            ..Default::default()
         };
         client.with_targeting_attributes(targeting_attributes);
         client.initialize()?;
@@ -1266,6 +1692,7 @@ mod tests {
         };
         let mut client = NimbusClient::new(
             app_context.clone(),
+            Default::default(),
             temp_dir.path(),
             None,
             AvailableRandomizationUnits {
@@ -1278,6 +1705,7 @@ mod tests {
             days_since_install: None,
             days_since_update: Some(10),
             is_already_enrolled: false,
+            ..Default::default()
         };
         client.with_targeting_attributes(targeting_attributes);
         client.initialize()?;
@@ -1865,6 +2293,52 @@ mod test_schema_bw_compat {
         assert_eq!(exp.app_id, Some("org.mozilla.fenix".to_string()));
         assert_eq!(exp.channel, Some("nightly".to_string()));
     }
+
+    // Experiment dates were stored as strings before we started parsing them,
+    // in whatever format the server sent.
+    #[test]
+    fn test_experiment_dates() {
+        // ⚠️ Warning : Do not change the JSON data used by this test. ⚠️
+        let exp_with_dates = |start_date: Value, end_date: Value| -> Experiment {
+            serde_json::from_value(json!({
+                "schemaVersion": "1.0.0",
+                "slug": "secure-gold",
+                "startDate": start_date,
+                "endDate": end_date,
+                "branches": [],
+                "bucketConfig": {
+                    "count": 10_000,
+                    "start": 0,
+                    "total": 10_000,
+                    "namespace": "secure-gold",
+                    "randomizationUnit": "nimbus_id"
+                },
+                "userFacingName": "Diagnostic test experiment",
+                "userFacingDescription": "This is a test experiment for diagnostic purposes.",
+                "isEnrollmentPaused": false,
+                "proposedEnrollment": 7
+            }))
+            .unwrap()
+        };
+        let exp = exp_with_dates(json!("2021-05-10T12:38:49.699091Z"), json!("2021-06-10"));
+        assert_eq!(
+            exp.start_date.unwrap().to_rfc3339(),
+            "2021-05-10T12:38:49.699091+00:00"
+        );
+        assert_eq!(
+            exp.end_date.unwrap().to_rfc3339(),
+            "2021-06-10T00:00:00+00:00"
+        );
+        // Dates we can't make sense of are ignored.
+        let exp = exp_with_dates(json!("last tuesday"), json!(null));
+        assert_eq!(exp.start_date, None);
+        assert_eq!(exp.end_date, None);
+        // And we can read back what we wrote.
+        let exp = exp_with_dates(json!("2021-05-10"), json!(null));
+        let roundtripped: Experiment =
+            serde_json::from_str(&serde_json::to_string(&exp).unwrap()).unwrap();
+        assert_eq!(roundtripped, exp);
+    }
 }
 
 #[cfg(test)]
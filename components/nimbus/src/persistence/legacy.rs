@@ -0,0 +1,109 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The rkv database which older versions of Nimbus kept their data in. We
+//! only ever read it, to import it into SQLite, and then remove it.
+
+use super::schema::STORE_NAMES;
+use crate::error::Result;
+use rkv::StoreOptions;
+use std::fs;
+use std::path::Path;
+
+// The directory, inside the one the app gives us, which rkv kept its files in.
+const RKV_DIR: &str = "db";
+
+// The number of stores we can open, which must be at least the number of
+// stores. rkv only allows 5 by default.
+const MAX_DBS: u32 = 8;
+
+// Inspired by Glean - use a feature to choose between the backends.
+// Select the LMDB-powered storage backend when the feature is not activated.
+#[cfg(not(feature = "rkv-safe-mode"))]
+mod backend {
+    use rkv::backend::{Lmdb, LmdbEnvironment};
+    use std::path::Path;
+
+    pub type Rkv = rkv::Rkv<LmdbEnvironment>;
+
+    pub fn rkv_new(path: &Path) -> Result<Rkv, rkv::StoreError> {
+        Rkv::with_capacity::<Lmdb>(path, super::MAX_DBS)
+    }
+}
+
+// Select the "safe mode" storage backend when the feature is activated.
+#[cfg(feature = "rkv-safe-mode")]
+mod backend {
+    use rkv::backend::{SafeMode, SafeModeEnvironment};
+    use std::path::Path;
+
+    pub type Rkv = rkv::Rkv<SafeModeEnvironment>;
+
+    pub fn rkv_new(path: &Path) -> Result<Rkv, rkv::StoreError> {
+        Rkv::with_capacity::<SafeMode>(path, super::MAX_DBS)
+    }
+}
+
+use backend::rkv_new;
+
+/// Read every item of every store of the rkv database in `path`, if there is
+/// one, as `(store name, key, JSON value)`.
+pub(super) fn read_rkv(path: &Path) -> Result<Vec<(&'static str, String, String)>> {
+    let rkv_path = path.join(RKV_DIR);
+    if !rkv_path.exists() {
+        return Ok(Vec::new());
+    }
+    log::info!("Reading the rkv database at '{}'", rkv_path.display());
+    let rkv = rkv_new(&rkv_path)?;
+    let mut result = Vec::new();
+    for store_name in STORE_NAMES {
+        // Older databases don't have all the stores, so we (harmlessly)
+        // create any which are missing.
+        let store = rkv.open_single(*store_name, StoreOptions::create())?;
+        let reader = rkv.read()?;
+        let mut iter = store.iter_start(&reader)?;
+        while let Some(Ok((key, data))) = iter.next() {
+            if let (Ok(key), rkv::Value::Json(data)) = (std::str::from_utf8(key), data) {
+                result.push((*store_name, key.to_string(), data.to_string()));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Remove the rkv database in `path`, if there is one. Once we've imported
+/// it, keeping it around would only waste space.
+pub(super) fn remove_rkv(path: &Path) {
+    let rkv_path = path.join(RKV_DIR);
+    if rkv_path.exists() {
+        if let Err(e) = fs::remove_dir_all(&rkv_path) {
+            log::warn!(
+                "Failed to remove the rkv database at '{}': {}",
+                rkv_path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Write `items` to the store `store_name` of an rkv database in `path`, as
+/// older versions of Nimbus did, to test that we import those databases.
+#[cfg(test)]
+pub(crate) fn write_rkv_store<T: serde::Serialize>(
+    path: &Path,
+    store_name: &str,
+    items: &[(&str, T)],
+) -> Result<()> {
+    let rkv_path = path.join(RKV_DIR);
+    fs::create_dir_all(&rkv_path)?;
+    let rkv = rkv_new(&rkv_path)?;
+    let store = rkv.open_single(store_name, StoreOptions::create())?;
+    let mut writer = rkv.write()?;
+    for (key, value) in items {
+        let json = serde_json::to_string(value)?;
+        store.put(&mut writer, key, &rkv::Value::Json(&json))?;
+    }
+    writer.commit()?;
+    Ok(())
+}
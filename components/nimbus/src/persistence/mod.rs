@@ -2,21 +2,24 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-//! Our storage abstraction, currently backed by Rkv.
+//! Our storage abstraction, backed by SQLite.
+//!
+//! Older versions of Nimbus kept their data in rkv, so the first time we open
+//! the database, we import the data of any rkv database we find.
+
+mod legacy;
+mod schema;
 
-use crate::error::{NimbusError, Result};
-// This uses the lmdb backend for rkv, which is unstable.
-// We use it for now since glean didn't seem to have trouble with it (although
-// it must be noted that the rkv documentation explicitly says "To use rkv in
-// production/release environments at Mozilla, you may do so with the "SafeMode"
-// backend", so we really should get more guidance here.)
 use crate::enrollment::ExperimentEnrollment;
+use crate::error::{NimbusError, Result};
 use crate::Experiment;
-use core::iter::Iterator;
-use rkv::{StoreError, StoreOptions};
+use rusqlite::{Connection, OpenFlags};
+use schema::NimbusConnectionInitializer;
+use sql_support::{open_database, ConnExt};
 use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
 
 // We use an incrementing integer to manage database migrations.
 // If you need to make a backwards-incompatible change to the data schema,
@@ -26,69 +29,14 @@ use std::path::Path;
 const DB_KEY_DB_VERSION: &str = "db_version";
 const DB_VERSION: u16 = 2;
 
-// Inspired by Glean - use a feature to choose between the backends.
-// Select the LMDB-powered storage backend when the feature is not activated.
-#[cfg(not(feature = "rkv-safe-mode"))]
-mod backend {
-    use rkv::backend::{
-        Lmdb, LmdbDatabase, LmdbEnvironment, LmdbRoCursor, LmdbRoTransaction, LmdbRwTransaction,
-    };
-    use std::path::Path;
-
-    pub type Rkv = rkv::Rkv<LmdbEnvironment>;
-    pub type RkvSingleStore = rkv::SingleStore<LmdbDatabase>;
-    pub type Reader<'t> = rkv::Reader<LmdbRoTransaction<'t>>;
-    pub type Writer<'t> = rkv::Writer<LmdbRwTransaction<'t>>;
-    pub trait Readable<'r>:
-        rkv::Readable<'r, Database = LmdbDatabase, RoCursor = LmdbRoCursor<'r>>
-    {
-    }
-    impl<'r, T: rkv::Readable<'r, Database = LmdbDatabase, RoCursor = LmdbRoCursor<'r>>>
-        Readable<'r> for T
-    {
-    }
-
-    pub fn rkv_new(path: &Path) -> Result<Rkv, rkv::StoreError> {
-        Rkv::new::<Lmdb>(path)
-    }
-}
-
-// Select the "safe mode" storage backend when the feature is activated.
-#[cfg(feature = "rkv-safe-mode")]
-mod backend {
-    use rkv::backend::{
-        SafeMode, SafeModeDatabase, SafeModeEnvironment, SafeModeRoCursor, SafeModeRoTransaction,
-        SafeModeRwTransaction,
-    };
-    use std::path::Path;
-
-    pub type Rkv = rkv::Rkv<SafeModeEnvironment>;
-    pub type RkvSingleStore = rkv::SingleStore<SafeModeDatabase>;
-    pub type Reader<'t> = rkv::Reader<SafeModeRoTransaction<'t>>;
-    pub type Writer<'t> = rkv::Writer<SafeModeRwTransaction<'t>>;
-    pub trait Readable<'r>:
-        rkv::Readable<'r, Database = SafeModeDatabase, RoCursor = SafeModeRoCursor<'r>>
-    {
-    }
-    impl<
-            'r,
-            T: rkv::Readable<'r, Database = SafeModeDatabase, RoCursor = SafeModeRoCursor<'r>>,
-        > Readable<'r> for T
-    {
-    }
-
-    pub fn rkv_new(path: &Path) -> Result<Rkv, rkv::StoreError> {
-        Rkv::new::<SafeMode>(path)
-    }
-}
-
-use backend::*;
-pub use backend::{Readable, Writer};
+// The name of the database file, in the directory the app gives us.
+const DB_FILENAME: &str = "nimbus.db";
 
 /// Enumeration of the different stores within our database.
 ///
-/// Our rkv database contains a number of different "stores", and the items
-/// in each store correspond to a particular type of object at the Rust level.
+/// Our database contains a number of different "stores", each a table of keys
+/// and JSON values, and the items in each store correspond to a particular type
+/// of object at the Rust level.
 pub enum StoreId {
     /// Store containing the set of known experiments, as read from the server.
     ///
@@ -119,6 +67,10 @@ pub enum StoreId {
     ///   * "update-date": a UTC DateTime string, defining the date the consuming app was
     ///                     last updated
     ///   * "app-version": String, the version of the app last persisted
+    ///   * "experiments-first-seen": a map of experiment slugs to the UTC DateTime
+    ///                     this client first saw them, used to end the enrollment period
+    ///   * "collection-timestamp": String, the Remote Settings timestamp of the
+    ///                     records in the `Collection` store.
     Meta,
     /// Store containing pending updates to experiment data.
     ///
@@ -126,151 +78,285 @@ pub enum StoreId {
     /// corresponding value is a serialized `Vec<Experiment>` of new experiment data
     /// that has been received from the server but not yet processed by the application.
     Updates,
+    /// Store containing our copy of the Remote Settings collection.
+    ///
+    /// Keys in the `Collection` store are Remote Settings record ids, and their
+    /// corresponding values are the records exactly as received from the server,
+    /// so that we only need to fetch records which have changed and can still
+    /// verify the signature of the whole collection.
+    Collection,
+    /// Store containing the behavioral events recorded by the application.
+    ///
+    /// Keys in the `Events` store are the event ids chosen by the application,
+    /// and their corresponding values are serialized `EventCounts` holding the
+    /// number of times the event was recorded on each recent day.
+    Events,
+}
+
+/// A transaction for reading the database, which sees the data as it was
+/// when the transaction started.
+pub struct Reader<'a> {
+    conn: MutexGuard<'a, Connection>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(conn: MutexGuard<'a, Connection>) -> Result<Self> {
+        conn.execute_batch("BEGIN DEFERRED")?;
+        Ok(Self { conn })
+    }
+}
+
+impl Drop for Reader<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.conn.execute_batch("ROLLBACK") {
+            log::warn!("Failed to end a read transaction: {}", e);
+        }
+    }
 }
 
-/// A wrapper for an Rkv store. Implemented to allow any value which supports
+/// A transaction for writing to the database. Unless `commit()` is called,
+/// its changes are rolled back when it's dropped.
+pub struct Writer<'a> {
+    conn: MutexGuard<'a, Connection>,
+    committed: bool,
+}
+
+impl<'a> Writer<'a> {
+    fn new(conn: MutexGuard<'a, Connection>) -> Result<Self> {
+        conn.execute_batch("BEGIN IMMEDIATE")?;
+        Ok(Self {
+            conn,
+            committed: false,
+        })
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        self.conn.execute_batch("COMMIT")?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for Writer<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            if let Err(e) = self.conn.execute_batch("ROLLBACK") {
+                log::warn!("Failed to roll back a write transaction: {}", e);
+            }
+        }
+    }
+}
+
+impl ConnExt for Reader<'_> {
+    fn conn(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl ConnExt for Writer<'_> {
+    fn conn(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+/// Anything the stores can be read from: a `Reader`, or a `Writer`, which
+/// also sees what's been written in its transaction before it's committed.
+pub trait Readable: ConnExt {}
+
+impl Readable for Reader<'_> {}
+
+impl Readable for Writer<'_> {}
+
+/// A wrapper for a store. Implemented to allow any value which supports
 /// serde to be used.
 pub struct SingleStore {
-    store: RkvSingleStore,
+    table: &'static str,
 }
 
 impl SingleStore {
-    pub fn new(store: RkvSingleStore) -> Self {
-        SingleStore { store }
+    fn new(table: &'static str) -> Self {
+        SingleStore { table }
     }
 
     pub fn put<T: serde::Serialize + for<'de> serde::Deserialize<'de>>(
         &self,
-        mut writer: &mut Writer,
+        writer: &mut Writer,
         key: &str,
         persisted_data: &T,
     ) -> Result<()> {
         let persisted_json = serde_json::to_string(persisted_data)?;
-        self.store
-            .put(&mut writer, key, &rkv::Value::Json(&persisted_json))?;
+        writer.execute_named_cached(
+            &format!(
+                "INSERT OR REPLACE INTO {} (key, value) VALUES (:key, :value)",
+                self.table
+            ),
+            &[(":key", &key), (":value", &persisted_json)],
+        )?;
         Ok(())
     }
 
     #[allow(dead_code)]
-    pub fn delete(&self, mut writer: &mut Writer, key: &str) -> Result<()> {
-        self.store.delete(&mut writer, key)?;
+    pub fn delete(&self, writer: &mut Writer, key: &str) -> Result<()> {
+        writer.execute_named_cached(
+            &format!("DELETE FROM {} WHERE key = :key", self.table),
+            &[(":key", &key)],
+        )?;
         Ok(())
     }
 
-    pub fn clear(&self, mut writer: &mut Writer) -> Result<()> {
-        self.store.clear(&mut writer)?;
+    pub fn clear(&self, writer: &mut Writer) -> Result<()> {
+        writer.execute_named_cached(&format!("DELETE FROM {}", self.table), &[])?;
         Ok(())
     }
 
-    // Some "get" functions that cooperate with transactions (ie, so we can
-    // get what we've written to the transaction before it's committed).
-    // It's unfortunate that these are duplicated with the DB itself, but the
-    // traits used by rkv make this tricky.
-    pub fn get<'r, T, R>(&self, reader: &'r R, key: &str) -> Result<Option<T>>
+    // These take either a `Reader` or a `Writer`, so that we can get what
+    // we've written to a transaction before it's committed.
+    pub fn get<T, R>(&self, reader: &R, key: &str) -> Result<Option<T>>
     where
-        R: Readable<'r>,
+        R: Readable,
         T: serde::Serialize + for<'de> serde::Deserialize<'de>,
     {
-        let persisted_data = self.store.get(reader, key)?;
+        let persisted_data: Option<String> = reader.try_query_row(
+            &format!("SELECT value FROM {} WHERE key = :key", self.table),
+            &[(":key", &key)],
+            |row| row.get(0),
+            true,
+        )?;
         match persisted_data {
-            Some(data) => {
-                if let rkv::Value::Json(data) = data {
-                    Ok(Some(serde_json::from_str::<T>(data)?))
-                } else {
-                    Err(NimbusError::InvalidPersistedData)
-                }
-            }
+            Some(data) => Ok(Some(serde_json::from_str::<T>(&data)?)),
             None => Ok(None),
         }
     }
 
+    // All the keys and values of the store, ordered by key, as rkv did.
+    fn get_all<R: Readable>(&self, reader: &R) -> Result<Vec<(String, String)>> {
+        Ok(
+            reader.query_rows_into_cached::<Vec<_>, _, rusqlite::Error, _>(
+                &format!("SELECT key, value FROM {} ORDER BY key", self.table),
+                &[],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?,
+        )
+    }
+
     /// Fork of collect_all that simply drops records that fail to read
     /// rather than simply returning an error up the stack.  This likely
     /// wants to be just a parameter to collect_all, but for now....
-    pub fn try_collect_all<'r, T, R>(&self, reader: &'r R) -> Result<Vec<T>>
+    pub fn try_collect_all<T, R>(&self, reader: &R) -> Result<Vec<T>>
     where
-        R: Readable<'r>,
+        R: Readable,
         T: serde::Serialize + for<'de> serde::Deserialize<'de>,
     {
         let mut result = Vec::new();
-        let mut iter = self.store.iter_start(reader)?;
-        while let Some(Ok((_, data))) = iter.next() {
-            if let rkv::Value::Json(data) = data {
-                let unserialized = serde_json::from_str::<T>(data);
-                match unserialized {
-                    Ok(value) => result.push(value),
-                    Err(e) => {
-                        // If there is an error, we won't push this onto the
-                        // result Vec, but we won't blow up the entire
-                        // deserialization either.
-                        log::warn!(
-                            "try_collect_all: discarded a record while deserializing with: {:?}",
-                            e
-                        );
-                        log::warn!(
-                            "try_collect_all:   data that failed to deserialize: {:?}",
-                            data
-                        );
-                    }
-                };
-            }
+        for (_, data) in self.get_all(reader)? {
+            let unserialized = serde_json::from_str::<T>(&data);
+            match unserialized {
+                Ok(value) => result.push(value),
+                Err(e) => {
+                    // If there is an error, we won't push this onto the
+                    // result Vec, but we won't blow up the entire
+                    // deserialization either.
+                    log::warn!(
+                        "try_collect_all: discarded a record while deserializing with: {:?}",
+                        e
+                    );
+                    log::warn!(
+                        "try_collect_all:   data that failed to deserialize: {:?}",
+                        data
+                    );
+                }
+            };
         }
         Ok(result)
     }
 
-    pub fn collect_all<'r, T, R>(&self, reader: &'r R) -> Result<Vec<T>>
+    pub fn collect_all<T, R>(&self, reader: &R) -> Result<Vec<T>>
     where
-        R: Readable<'r>,
+        R: Readable,
         T: serde::Serialize + for<'de> serde::Deserialize<'de>,
     {
-        let mut result = Vec::new();
-        let mut iter = self.store.iter_start(reader)?;
-        while let Some(Ok((_, data))) = iter.next() {
-            if let rkv::Value::Json(data) = data {
-                result.push(serde_json::from_str::<T>(data)?);
-            }
-        }
-        Ok(result)
+        Ok(self
+            .get_all(reader)?
+            .iter()
+            .map(|(_, data)| serde_json::from_str::<T>(data))
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// Like `collect_all`, but also returns the key of each item.
+    pub fn collect_all_with_keys<T, R>(&self, reader: &R) -> Result<Vec<(String, T)>>
+    where
+        R: Readable,
+        T: serde::Serialize + for<'de> serde::Deserialize<'de>,
+    {
+        Ok(self
+            .get_all(reader)?
+            .into_iter()
+            .map(|(key, data)| serde_json::from_str::<T>(&data).map(|value| (key, value)))
+            .collect::<Result<_, _>>()?)
     }
 }
 
 /// Database used to access persisted data
-/// This an abstraction around an Rkv database
+/// This an abstraction around a SQLite database
 /// An instance on this database is created each time the component is loaded
 /// if there is persisted data, the `get` functions should retrieve it
 pub struct Database {
-    rkv: Rkv,
+    // Writes are serialized through this connection.
+    writer: Mutex<Connection>,
+    // Readers get a connection of their own, so that, as with rkv, they only
+    // see committed data, and don't wait for writers.
+    reader: Mutex<Connection>,
     meta_store: SingleStore,
     experiment_store: SingleStore,
     enrollment_store: SingleStore,
     updates_store: SingleStore,
+    collection_store: SingleStore,
+    events_store: SingleStore,
 }
 
 impl Database {
     /// Main constructor for a database
-    /// Initiates the Rkv database to be used to retreive persisted data
+    /// Opens the SQLite database used to retrieve persisted data, importing
+    /// the data of any rkv database from an older version of Nimbus.
     /// # Arguments
     /// - `path`: A path to the persisted data, this is provided by the consuming application
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let rkv = Self::open_rkv(path)?;
-        let meta_store = rkv.open_single("meta", StoreOptions::create())?;
-        let experiment_store = rkv.open_single("experiments", StoreOptions::create())?;
-        let enrollment_store = rkv.open_single("enrollments", StoreOptions::create())?;
-        let updates_store = rkv.open_single("updates", StoreOptions::create())?;
+        let path = path.as_ref();
+        fs::create_dir_all(path)?;
+        let db_path = path.join(DB_FILENAME);
+        log::debug!("Database::new: path = {:?}", db_path.display());
+        let initializer = NimbusConnectionInitializer::new(path.to_path_buf());
+        // A corrupt database is deleted and recreated by `open_database`.
+        let writer = open_database::open_database(&db_path, &initializer)?;
+        // Now that the data we imported from the rkv database is committed,
+        // there's no reason to keep it. If we couldn't read it, we leave it
+        // alone rather than destroy data we didn't import.
+        if initializer.imported_rkv() {
+            legacy::remove_rkv(path);
+        }
+        let reader = open_database::open_database_with_flags(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            &initializer,
+        )?;
         let db = Self {
-            rkv,
-            meta_store: SingleStore::new(meta_store),
-            experiment_store: SingleStore::new(experiment_store),
-            enrollment_store: SingleStore::new(enrollment_store),
-            updates_store: SingleStore::new(updates_store),
+            writer: Mutex::new(writer),
+            reader: Mutex::new(reader),
+            meta_store: SingleStore::new("meta"),
+            experiment_store: SingleStore::new("experiments"),
+            enrollment_store: SingleStore::new("enrollments"),
+            updates_store: SingleStore::new("updates"),
+            collection_store: SingleStore::new("collection"),
+            events_store: SingleStore::new("events"),
         };
+        log::debug!("Database initialized");
         db.maybe_upgrade()?;
         Ok(db)
     }
 
     fn maybe_upgrade(&self) -> Result<()> {
         log::debug!("entered maybe upgrade");
-        let mut writer = self.rkv.write()?;
+        let mut writer = self.write()?;
         let db_version = self.meta_store.get::<u16, _>(&writer, DB_KEY_DB_VERSION)?;
         match db_version {
             Some(DB_VERSION) => {
@@ -416,52 +502,21 @@ impl Database {
             StoreId::Experiments => &self.experiment_store,
             StoreId::Enrollments => &self.enrollment_store,
             StoreId::Updates => &self.updates_store,
+            StoreId::Collection => &self.collection_store,
+            StoreId::Events => &self.events_store,
         }
     }
 
-    pub fn open_rkv<P: AsRef<Path>>(path: P) -> Result<Rkv> {
-        let path = std::path::Path::new(path.as_ref()).join("db");
-        log::debug!("open_rkv: path =  {:?}", path.display());
-        fs::create_dir_all(&path)?;
-        let rkv = match rkv_new(&path) {
-            Ok(rkv) => Ok(rkv),
-            Err(rkv_error) => {
-                match rkv_error {
-                    // For some errors we just delete the DB and start again.
-                    StoreError::DatabaseCorrupted | StoreError::FileInvalid => {
-                        // On one hand this seems a little dangerous, but on
-                        // the other hand avoids us knowing about the
-                        // underlying implementation (ie, how do we know what
-                        // files might exist in all cases?)
-                        log::warn!(
-                            "Database at '{}' appears corrupt - removing and recreating",
-                            path.display()
-                        );
-                        fs::remove_dir_all(&path)?;
-                        fs::create_dir_all(&path)?;
-                        // TODO: Once we have glean integration we want to
-                        // record telemetry here.
-                        rkv_new(&path)
-                    }
-                    // All other errors are fatal.
-                    _ => Err(rkv_error),
-                }
-            }
-        }?;
-        log::debug!("Database initialized");
-        Ok(rkv)
-    }
-
     /// Function used to obtain a "reader" which is used for read-only transactions.
     pub fn read(&self) -> Result<Reader> {
-        Ok(self.rkv.read()?)
+        Reader::new(self.reader.lock().unwrap())
     }
 
     /// Function used to obtain a "writer" which is used for transactions.
     /// The `writer.commit();` must be called to commit data added via the
     /// writer.
     pub fn write(&self) -> Result<Writer> {
-        Ok(self.rkv.write()?)
+        Writer::new(self.writer.lock().unwrap())
     }
 
     /// Function used to retrieve persisted data outside of a transaction.
@@ -477,43 +532,25 @@ impl Database {
         store_id: StoreId,
         key: &str,
     ) -> Result<Option<T>> {
-        let reader = self.rkv.read()?;
-        let persisted_data = self.get_store(store_id).store.get(&reader, key)?;
-        match persisted_data {
-            Some(data) => {
-                if let rkv::Value::Json(data) = data {
-                    Ok(Some(serde_json::from_str::<T>(data)?))
-                } else {
-                    Err(NimbusError::InvalidPersistedData)
-                }
-            }
-            None => Ok(None),
-        }
+        let reader = self.read()?;
+        self.get_store(store_id).get(&reader, key)
     }
 
     // Function for collecting all items in a store outside of a transaction.
     // Only available for tests; product code should always be using transactions.
-    // Iters are a bit tricky - would be nice to make them generic, but this will
-    // do for our use-case.
     #[cfg(test)]
     pub fn collect_all<T: serde::Serialize + for<'de> serde::Deserialize<'de>>(
         &self,
         store_id: StoreId,
     ) -> Result<Vec<T>> {
-        let mut result = Vec::new();
-        let reader = self.rkv.read()?;
-        let mut iter = self.get_store(store_id).store.iter_start(&reader)?;
-        while let Some(Ok((_, data))) = iter.next() {
-            if let rkv::Value::Json(data) = data {
-                result.push(serde_json::from_str::<T>(data)?);
-            }
-        }
-        Ok(result)
+        let reader = self.read()?;
+        self.get_store(store_id).collect_all(&reader)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::legacy::write_rkv_store;
     use super::*;
     use serde_json::json;
     use tempdir::TempDir;
@@ -523,16 +560,8 @@ mod tests {
         let path = "test_upgrade_1";
         let tmp_dir = TempDir::new(path)?;
 
-        let rkv = Database::open_rkv(&tmp_dir)?;
-        let _meta_store = rkv.open_single("meta", StoreOptions::create())?;
-        let experiment_store =
-            SingleStore::new(rkv.open_single("experiments", StoreOptions::create())?);
-        let enrollment_store =
-            SingleStore::new(rkv.open_single("enrollments", StoreOptions::create())?);
-        let mut writer = rkv.write()?;
-        enrollment_store.put(&mut writer, "foo", &"bar".to_owned())?;
-        experiment_store.put(&mut writer, "bobo", &"tron".to_owned())?;
-        writer.commit()?;
+        write_rkv_store(tmp_dir.path(), "enrollments", &[("foo", "bar")])?;
+        write_rkv_store(tmp_dir.path(), "experiments", &[("bobo", "tron")])?;
 
         let db = Database::new(&tmp_dir)?;
         assert_eq!(db.get(StoreId::Meta, DB_KEY_DB_VERSION)?, Some(DB_VERSION));
@@ -547,17 +576,9 @@ mod tests {
         let path = "test_upgrade_unknown";
         let tmp_dir = TempDir::new(path)?;
 
-        let rkv = Database::open_rkv(&tmp_dir)?;
-        let meta_store = SingleStore::new(rkv.open_single("meta", StoreOptions::create())?);
-        let experiment_store =
-            SingleStore::new(rkv.open_single("experiments", StoreOptions::create())?);
-        let enrollment_store =
-            SingleStore::new(rkv.open_single("enrollments", StoreOptions::create())?);
-        let mut writer = rkv.write()?;
-        meta_store.put(&mut writer, DB_KEY_DB_VERSION, &u16::MAX)?;
-        enrollment_store.put(&mut writer, "foo", &"bar".to_owned())?;
-        experiment_store.put(&mut writer, "bobo", &"tron".to_owned())?;
-        writer.commit()?;
+        write_rkv_store(tmp_dir.path(), "meta", &[(DB_KEY_DB_VERSION, u16::MAX)])?;
+        write_rkv_store(tmp_dir.path(), "enrollments", &[("foo", "bar")])?;
+        write_rkv_store(tmp_dir.path(), "experiments", &[("bobo", "tron")])?;
         let db = Database::new(&tmp_dir)?;
         assert_eq!(db.get(StoreId::Meta, DB_KEY_DB_VERSION)?, Some(DB_VERSION));
         assert!(db.collect_all::<String>(StoreId::Enrollments)?.is_empty());
@@ -566,20 +587,93 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_import_rkv_db() -> Result<()> {
+        let tmp_dir = TempDir::new("test_import_rkv_db")?;
+
+        write_rkv_store(
+            tmp_dir.path(),
+            "meta",
+            &[
+                (DB_KEY_DB_VERSION, json!(DB_VERSION)),
+                ("nimbus-id", json!("abc")),
+            ],
+        )?;
+        write_rkv_store(tmp_dir.path(), "events", &[("app_opened", json!([1, 2]))])?;
+        write_rkv_store(
+            tmp_dir.path(),
+            "collection",
+            &[("b", json!({"id": "b"})), ("a", json!({"id": "a"}))],
+        )?;
+
+        let db = Database::new(&tmp_dir)?;
+        assert_eq!(
+            db.get::<String>(StoreId::Meta, "nimbus-id")?.as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            db.get::<serde_json::Value>(StoreId::Events, "app_opened")?,
+            Some(json!([1, 2]))
+        );
+        // Items are in the order of their keys, as they were in rkv.
+        let reader = db.read()?;
+        assert_eq!(
+            db.get_store(StoreId::Collection)
+                .collect_all_with_keys::<serde_json::Value, _>(&reader)?,
+            vec![
+                ("a".to_string(), json!({"id": "a"})),
+                ("b".to_string(), json!({"id": "b"})),
+            ]
+        );
+        drop(reader);
+        // The rkv database is removed once it's imported...
+        assert!(!tmp_dir.path().join("db").exists());
+        drop(db);
+
+        // ... and we only ever import it once.
+        write_rkv_store(tmp_dir.path(), "meta", &[("nimbus-id", "def")])?;
+        let db = Database::new(&tmp_dir)?;
+        assert_eq!(
+            db.get::<String>(StoreId::Meta, "nimbus-id")?.as_deref(),
+            Some("abc")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_writer_rolls_back_unless_committed() -> Result<()> {
+        let tmp_dir = TempDir::new("test_writer_rolls_back_unless_committed")?;
+        let db = Database::new(&tmp_dir)?;
+        let store = db.get_store(StoreId::Meta);
+
+        let mut writer = db.write()?;
+        store.put(&mut writer, "foo", &"bar".to_string())?;
+        // The writer sees its own writes, but readers don't until they're
+        // committed.
+        assert_eq!(
+            store.get::<String, _>(&writer, "foo")?.as_deref(),
+            Some("bar")
+        );
+        assert_eq!(db.get::<String>(StoreId::Meta, "foo")?, None);
+        drop(writer);
+        assert_eq!(db.get::<String>(StoreId::Meta, "foo")?, None);
+
+        let mut writer = db.write()?;
+        store.put(&mut writer, "foo", &"bar".to_string())?;
+        writer.commit()?;
+        assert_eq!(
+            db.get::<String>(StoreId::Meta, "foo")?.as_deref(),
+            Some("bar")
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_corrupt_db() -> Result<()> {
         let path = "test_corrupt_db";
         let tmp_dir = TempDir::new(path)?;
 
-        let db_dir = tmp_dir.path().join("db");
-        fs::create_dir(db_dir.clone())?;
-
-        // The database filename differs depending on the rkv mode.
-        #[cfg(feature = "rkv-safe-mode")]
-        let db_file = db_dir.join("data.safe.bin");
-        #[cfg(not(feature = "rkv-safe-mode"))]
-        let db_file = db_dir.join("data.mdb");
-
+        let db_file = tmp_dir.path().join(DB_FILENAME);
         let garbage = b"Not a database!";
         let garbage_len = garbage.len() as u64;
         fs::write(&db_file, garbage)?;
@@ -591,6 +685,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_corrupt_rkv_db() -> Result<()> {
+        let path = "test_corrupt_rkv_db";
+        let tmp_dir = TempDir::new(path)?;
+
+        let rkv_dir = tmp_dir.path().join("db");
+        fs::create_dir(rkv_dir.clone())?;
+
+        // The database filename differs depending on the rkv mode.
+        #[cfg(feature = "rkv-safe-mode")]
+        let rkv_file = rkv_dir.join("data.safe.bin");
+        #[cfg(not(feature = "rkv-safe-mode"))]
+        let rkv_file = rkv_dir.join("data.mdb");
+
+        fs::write(&rkv_file, b"Not a database!")?;
+        // We can't import it, so we start afresh, but keep it.
+        let db = Database::new(&tmp_dir)?;
+        assert_eq!(db.get(StoreId::Meta, DB_KEY_DB_VERSION)?, Some(DB_VERSION));
+        assert!(db.collect_all::<String>(StoreId::Enrollments)?.is_empty());
+        assert_eq!(fs::read(&rkv_file)?, b"Not a database!");
+        drop(db);
+
+        // We don't try to import it again, or remove it, next time.
+        let db = Database::new(&tmp_dir)?;
+        assert_eq!(db.get(StoreId::Meta, DB_KEY_DB_VERSION)?, Some(DB_VERSION));
+        assert_eq!(fs::read(&rkv_file)?, b"Not a database!");
+        Ok(())
+    }
+
     // XXX secure-gold has some fields. Ideally, we would also have an
     // experiment with all current fields set, and another with almost no
     // optional fields set
@@ -1027,7 +1150,7 @@ mod tests {
         ]
     }
 
-    /// Create a database with an old database version number, and
+    /// Create an rkv database with an old database version number, and
     /// populate it with the given experiments and enrollments.
     fn create_old_database(
         tmp_dir: &TempDir,
@@ -1037,37 +1160,32 @@ mod tests {
     ) -> Result<()> {
         let _ = env_logger::try_init();
 
-        let rkv = Database::open_rkv(&tmp_dir)?;
-        let meta_store = SingleStore::new(rkv.open_single("meta", StoreOptions::create())?);
-        let experiment_store =
-            SingleStore::new(rkv.open_single("experiments", StoreOptions::create())?);
-        let enrollment_store =
-            SingleStore::new(rkv.open_single("enrollments", StoreOptions::create())?);
-        let mut writer = rkv.write()?;
-
-        meta_store.put(&mut writer, "db_version", &old_version)?;
-
-        // write out the experiments
-        for experiment_json in experiments_json {
-            // log::debug!("experiment_json = {:?}", experiment_json);
-            experiment_store.put(
-                &mut writer,
-                experiment_json["slug"].as_str().unwrap(),
-                experiment_json,
-            )?;
-        }
-
-        // write out the enrollments
-        for enrollment_json in enrollments_json {
-            // log::debug!("enrollment_json = {:?}", enrollment_json);
-            enrollment_store.put(
-                &mut writer,
-                enrollment_json["slug"].as_str().unwrap(),
-                enrollment_json,
-            )?;
-        }
-
-        writer.commit()?;
+        let keyed_by_slug = |values: &[serde_json::Value]| -> Vec<(String, serde_json::Value)> {
+            values
+                .iter()
+                .map(|value| (value["slug"].as_str().unwrap().to_string(), value.clone()))
+                .collect()
+        };
+        let experiments = keyed_by_slug(experiments_json);
+        let enrollments = keyed_by_slug(enrollments_json);
+
+        write_rkv_store(tmp_dir.path(), "meta", &[("db_version", old_version)])?;
+        write_rkv_store(
+            tmp_dir.path(),
+            "experiments",
+            &experiments
+                .iter()
+                .map(|(slug, value)| (slug.as_str(), value))
+                .collect::<Vec<_>>(),
+        )?;
+        write_rkv_store(
+            tmp_dir.path(),
+            "enrollments",
+            &enrollments
+                .iter()
+                .map(|(slug, value)| (slug.as_str(), value))
+                .collect::<Vec<_>>(),
+        )?;
         log::debug!("create_old_database committed");
 
         Ok(())
@@ -0,0 +1,92 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::legacy;
+use rusqlite::{Connection, Transaction};
+use sql_support::open_database::{self, ConnectionInitializer};
+use std::cell::Cell;
+use std::path::PathBuf;
+
+/// The names of the stores, each of which is a table of keys and JSON values.
+/// They're also the names of the stores in the rkv databases we import.
+pub(super) const STORE_NAMES: &[&str] = &[
+    "meta",
+    "experiments",
+    "enrollments",
+    "updates",
+    "collection",
+    "events",
+];
+
+pub(super) struct NimbusConnectionInitializer {
+    /// The directory the database is in, which may also have an rkv database
+    /// from an older version of Nimbus.
+    path: PathBuf,
+    /// Whether `init` read the rkv database, if there was one, into the
+    /// database it initialized.
+    imported_rkv: Cell<bool>,
+}
+
+impl NimbusConnectionInitializer {
+    pub(super) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            imported_rkv: Cell::new(false),
+        }
+    }
+
+    /// Whether we initialized a new database with the data of the rkv
+    /// database, so that it's safe to remove the rkv database once the
+    /// initialization is committed.
+    pub(super) fn imported_rkv(&self) -> bool {
+        self.imported_rkv.get()
+    }
+}
+
+impl ConnectionInitializer for NimbusConnectionInitializer {
+    const NAME: &'static str = "nimbus db";
+    const END_VERSION: u32 = 1;
+
+    fn prepare(&self, conn: &Connection) -> open_database::Result<()> {
+        // Readers have their own connection, so write-ahead-logging lets them
+        // read while we write.
+        conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+        Ok(())
+    }
+
+    fn init(&self, tx: &Transaction<'_>) -> open_database::Result<()> {
+        for store_name in STORE_NAMES {
+            tx.execute_batch(&format!(
+                "CREATE TABLE {} (
+                    key TEXT PRIMARY KEY NOT NULL,
+                    value TEXT NOT NULL
+                ) WITHOUT ROWID;",
+                store_name
+            ))?;
+        }
+        // This is the first time we've opened the database, so import the
+        // data of any rkv database, so that nobody loses their enrollments.
+        // If we can't read it, we start afresh, as we did with a corrupt rkv
+        // database, but leave it where it is.
+        self.imported_rkv.set(false);
+        match legacy::read_rkv(&self.path) {
+            Ok(items) => {
+                log::info!("Importing {} items from rkv", items.len());
+                for (store_name, key, value) in items {
+                    tx.execute(
+                        &format!("INSERT INTO {} (key, value) VALUES (?, ?)", store_name),
+                        &[&key, &value],
+                    )?;
+                }
+                self.imported_rkv.set(true);
+            }
+            Err(e) => log::warn!("Couldn't read the rkv database, not importing it: {}", e),
+        }
+        Ok(())
+    }
+
+    fn upgrade_from(&self, _tx: &Transaction<'_>, version: u32) -> open_database::Result<()> {
+        Err(open_database::Error::IncompatibleVersion(version))
+    }
+}
@@ -54,6 +54,35 @@ pub(crate) fn bucket_sample<T: serde::Serialize>(
     })
 }
 
+/// Find which of the `total` buckets the given input falls into. This is the
+/// bucket `bucket_sample` checks against its range, so an input is sampled
+/// iff the returned bucket is in `[start, start + count)` (modulo `total`).
+///
+/// # Arguments:
+/// - `input` What will be hashed to pick a bucket
+/// - `total` The total number of buckets to group inputs into
+///
+/// # Returns:
+/// Returns the index of the bucket, in the range `[0, total)`
+///
+/// # Errors:
+/// Could return an error if the input couldn't be hashed
+pub(crate) fn bucket_for<T: serde::Serialize>(input: T, total: u32) -> Result<u32> {
+    let input_hash = hex::encode(truncated_hash(input)?);
+    // The bucket boundaries are monotonic, so binary search for the last
+    // bucket whose lower bound is not above the hash.
+    let (mut low, mut high) = (0, total);
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if fraction_to_key(mid as f64 / total as f64)?.as_str() <= input_hash.as_str() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
 /// Sample over a list of ratios such that, over the input space, each
 /// ratio has a number of matches in correct proportion to the other ratios
 ///
@@ -243,4 +272,24 @@ mod tests {
         assert!(!bucket_sample(input.clone(), 0, 2000, 10000).unwrap());
         assert!(bucket_sample(input, 2000, 3000, 10000).unwrap());
     }
+
+    #[test]
+    fn test_bucket_for_agrees_with_bucket_sample() {
+        for id in &[
+            "299eed1e-be6d-457d-9e53-da7b1a03f10d",
+            "c590d3f5-fe9d-4820-97c9-f403535dd306",
+            "542213c0-9aef-47eb-bc6b-3b8529736ba2",
+        ] {
+            let input = serde_json::json!([id, "some-namespace"]);
+            let bucket = bucket_for(input.clone(), 10000).unwrap();
+            assert!(bucket < 10000);
+            assert!(bucket_sample(input.clone(), bucket, 1, 10000).unwrap());
+            assert!(!bucket_sample(input.clone(), bucket + 1, 9999, 10000).unwrap());
+        }
+        let input = serde_json::json!([
+            "299eed1e-be6d-457d-9e53-da7b1a03f10d",
+            "bug-1637316-message-aboutwelcome-pull-factor-reinforcement-76-rel-release-76-77"
+        ]);
+        assert!(bucket_for(input, 10000).unwrap() < 2000);
+    }
 }
@@ -5,11 +5,13 @@
 //! This module implements the primitive functions to implement
 //! safe updating from the server.
 
+use crate::client::RecordCache;
 use crate::error::Result;
-use crate::persistence::{Database, StoreId, Writer};
+use crate::persistence::{Database, Readable, StoreId, Writer};
 use crate::Experiment;
 
 const KEY_PENDING_UPDATES: &str = "pending-experiment-updates";
+const KEY_COLLECTION_TIMESTAMP: &str = "collection-timestamp";
 
 pub fn write_pending_experiments(
     db: &Database,
@@ -39,6 +41,32 @@ pub fn read_and_remove_pending_experiments(
     Ok(experiments)
 }
 
+pub fn read_record_cache<R>(db: &Database, reader: &R) -> Result<RecordCache>
+where
+    R: Readable,
+{
+    let timestamp = db
+        .get_store(StoreId::Meta)
+        .get::<String, _>(reader, KEY_COLLECTION_TIMESTAMP)?;
+    let records: Vec<serde_json::Value> = db.get_store(StoreId::Collection).collect_all(reader)?;
+    let mut cache = RecordCache::default();
+    cache.apply_changes(records, timestamp);
+    Ok(cache)
+}
+
+pub fn write_record_cache(db: &Database, writer: &mut Writer, cache: &RecordCache) -> Result<()> {
+    let store = db.get_store(StoreId::Collection);
+    store.clear(writer)?;
+    for (id, record) in &cache.records {
+        store.put(writer, id, record)?;
+    }
+    let meta = db.get_store(StoreId::Meta);
+    match &cache.timestamp {
+        Some(timestamp) => meta.put(writer, KEY_COLLECTION_TIMESTAMP, timestamp),
+        None => meta.delete(writer, KEY_COLLECTION_TIMESTAMP),
+    }
+}
+
 // This test crashes lmdb for reasons that make no sense, so only run it
 // in the "safe mode" backend.
 #[cfg(feature = "rkv-safe-mode")]
@@ -76,3 +104,42 @@ fn test_reading_writing_and_removing_experiments() -> Result<()> {
     writer.commit()?;
     Ok(())
 }
+
+#[cfg(feature = "rkv-safe-mode")]
+#[test]
+fn test_reading_and_writing_record_cache() -> Result<()> {
+    use serde_json::json;
+    use tempdir::TempDir;
+
+    let tmp_dir = TempDir::new("test_record_cache")?;
+    let db = Database::new(&tmp_dir)?;
+
+    // Nothing has been fetched yet.
+    assert_eq!(read_record_cache(&db, &db.read()?)?, RecordCache::default());
+
+    let mut cache = RecordCache::default();
+    cache.apply_changes(
+        vec![
+            json!({"id": "a", "slug": "a"}),
+            json!({"id": "b", "slug": "b"}),
+        ],
+        Some("100".to_string()),
+    );
+    let mut writer = db.write()?;
+    write_record_cache(&db, &mut writer, &cache)?;
+    writer.commit()?;
+    assert_eq!(read_record_cache(&db, &db.read()?)?, cache);
+
+    // Records which were deleted on the server are removed.
+    cache.apply_changes(
+        vec![json!({"id": "a", "deleted": true})],
+        Some("200".to_string()),
+    );
+    let mut writer = db.write()?;
+    write_record_cache(&db, &mut writer, &cache)?;
+    writer.commit()?;
+    let read = read_record_cache(&db, &db.read()?)?;
+    assert_eq!(read.timestamp.as_deref(), Some("200"));
+    assert_eq!(read.records.keys().collect::<Vec<_>>(), vec!["b"]);
+    Ok(())
+}
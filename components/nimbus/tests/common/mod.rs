@@ -31,6 +31,7 @@ fn new_test_client_internal(
     let config = RemoteSettingsConfig {
         server_url: url.as_str().to_string(),
         collection_name: "doesn't matter".to_string(),
+        signature_root_hash: None,
     };
     let aru = Default::default();
     let ctx = AppContext {
@@ -39,7 +40,7 @@ fn new_test_client_internal(
         channel: "nightly".to_string(),
         ..Default::default()
     };
-    NimbusClient::new(ctx, tmp_dir.path(), Some(config), aru)
+    NimbusClient::new(ctx, Default::default(), tmp_dir.path(), Some(config), aru)
 }
 
 #[allow(dead_code)] //  work around https://github.com/rust-lang/rust/issues/46379
@@ -380,9 +381,31 @@ pub fn no_test_experiments() -> String {
     .to_string()
 }
 
-use nimbus::persistence::{Database, SingleStore};
-use rkv::StoreOptions;
 use std::path::Path;
+
+// Write `items` to the store `store_name` of an rkv database in `path`, where
+// older versions of Nimbus kept their data.
+#[allow(dead_code)] //  work around https://github.com/rust-lang/rust/issues/46379
+fn write_rkv_store<T: serde::Serialize>(
+    path: &Path,
+    store_name: &str,
+    items: &[(&str, T)],
+) -> Result<()> {
+    use rkv::backend::{SafeMode, SafeModeEnvironment};
+    use rkv::StoreOptions;
+    let rkv_path = path.join("db");
+    std::fs::create_dir_all(&rkv_path)?;
+    let rkv = rkv::Rkv::<SafeModeEnvironment>::with_capacity::<SafeMode>(&rkv_path, 8)?;
+    let store = rkv.open_single(store_name, StoreOptions::create())?;
+    let mut writer = rkv.write()?;
+    for (key, value) in items {
+        let json = serde_json::to_string(value)?;
+        store.put(&mut writer, key, &rkv::Value::Json(&json))?;
+    }
+    writer.commit()?;
+    Ok(())
+}
+
 #[allow(dead_code)] //  work around https://github.com/rust-lang/rust/issues/46379
 pub fn create_database<P: AsRef<Path>>(
     path: P,
@@ -393,38 +416,24 @@ pub fn create_database<P: AsRef<Path>>(
     let _ = env_logger::try_init();
     log::debug!("create_database(): old_version = {:?}", old_version);
     log::debug!("create_database(): path = {:?}", path.as_ref());
-    let rkv = Database::open_rkv(path)?;
-    let meta_store = SingleStore::new(rkv.open_single("meta", StoreOptions::create())?);
-    let experiment_store =
-        SingleStore::new(rkv.open_single("experiments", StoreOptions::create())?);
-    let enrollment_store =
-        SingleStore::new(rkv.open_single("enrollments", StoreOptions::create())?);
-    let mut writer = rkv.write()?;
-
-    meta_store.put(&mut writer, "db_version", &old_version)?;
+    let path = path.as_ref();
+    // Older versions of Nimbus kept their data in rkv, which we import.
+    write_rkv_store(path, "meta", &[("db_version", old_version)])?;
 
     // write out the experiments
-    for experiment_json in experiments_json {
-        log::debug!("create_database(): experiment_json = {:?}", experiment_json);
-        experiment_store.put(
-            &mut writer,
-            experiment_json["slug"].as_str().unwrap(),
-            experiment_json,
-        )?;
-    }
+    let experiments: Vec<(&str, &serde_json::Value)> = experiments_json
+        .iter()
+        .map(|experiment_json| (experiment_json["slug"].as_str().unwrap(), experiment_json))
+        .collect();
+    write_rkv_store(path, "experiments", &experiments)?;
 
     // write out the enrollments
-    for enrollment_json in enrollments_json {
-        // log::debug!("enrollment_json = {:?}", enrollment_json);
-        enrollment_store.put(
-            &mut writer,
-            enrollment_json["slug"].as_str().unwrap(),
-            enrollment_json,
-        )?;
-    }
-
-    writer.commit()?;
-    log::debug!("create_database: writer committed");
+    let enrollments: Vec<(&str, &serde_json::Value)> = enrollments_json
+        .iter()
+        .map(|enrollment_json| (enrollment_json["slug"].as_str().unwrap(), enrollment_json))
+        .collect();
+    write_rkv_store(path, "enrollments", &enrollments)?;
+    log::debug!("create_database: rkv database written");
 
     Ok(())
 }
@@ -27,12 +27,19 @@ fn test_simple() -> Result<()> {
     let config = RemoteSettingsConfig {
         server_url: url.as_str().to_string(),
         collection_name: "doesn't matter".to_string(),
+        signature_root_hash: None,
     };
 
     let tmp_dir = TempDir::new("test_fs_client-test_simple")?;
 
     let aru = Default::default();
-    let client = NimbusClient::new(Default::default(), tmp_dir.path(), Some(config), aru)?;
+    let client = NimbusClient::new(
+        Default::default(),
+        Default::default(),
+        tmp_dir.path(),
+        Some(config),
+        aru,
+    )?;
     client.fetch_experiments()?;
     client.apply_pending_experiments()?;
 
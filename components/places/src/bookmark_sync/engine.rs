@@ -31,8 +31,9 @@ use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt;
 use sync15::{
-    telemetry, CollSyncIds, CollectionRequest, EngineSyncAssociation, IncomingChangeset,
-    OutgoingChangeset, Payload, ServerTimestamp, SyncEngine,
+    telemetry::{self, ReconcileReason},
+    CollSyncIds, CollectionRequest, EngineSyncAssociation, IncomingChangeset, OutgoingChangeset,
+    Payload, ServerTimestamp, SyncEngine,
 };
 use sync_guid::Guid as SyncGuid;
 use types::Timestamp;
@@ -1013,6 +1014,7 @@ impl<'a> SyncEngine for BookmarksEngine<'a> {
 #[derive(Default)]
 struct Driver {
     validation: RefCell<telemetry::Validation>,
+    reconciled: RefCell<Vec<(ReconcileReason, usize)>>,
 }
 
 impl dogear::Driver for Driver {
@@ -1021,23 +1023,40 @@ impl dogear::Driver for Driver {
     }
 
     fn record_telemetry_event(&self, event: TelemetryEvent) {
-        // Record validation telemetry for remote trees.
-        if let TelemetryEvent::FetchRemoteTree(stats) = event {
-            self.validation
-                .borrow_mut()
-                .problem("orphans", stats.problems.orphans)
-                .problem("misparentedRoots", stats.problems.misparented_roots)
-                .problem(
-                    "multipleParents",
-                    stats.problems.multiple_parents_by_children,
-                )
-                .problem("missingParents", stats.problems.missing_parent_guids)
-                .problem("nonFolderParents", stats.problems.non_folder_parent_guids)
-                .problem(
-                    "parentChildDisagreements",
-                    stats.problems.parent_child_disagreements,
-                )
-                .problem("missingChildren", stats.problems.missing_children);
+        match event {
+            // Record validation telemetry for remote trees.
+            TelemetryEvent::FetchRemoteTree(stats) => {
+                self.validation
+                    .borrow_mut()
+                    .problem("orphans", stats.problems.orphans)
+                    .problem("misparentedRoots", stats.problems.misparented_roots)
+                    .problem(
+                        "multipleParents",
+                        stats.problems.multiple_parents_by_children,
+                    )
+                    .problem("missingParents", stats.problems.missing_parent_guids)
+                    .problem("nonFolderParents", stats.problems.non_folder_parent_guids)
+                    .problem(
+                        "parentChildDisagreements",
+                        stats.problems.parent_child_disagreements,
+                    )
+                    .problem("missingChildren", stats.problems.missing_children);
+            }
+            // Record why items were reconciled during the merge.
+            TelemetryEvent::Merge(_, counts) => {
+                self.reconciled.borrow_mut().extend(vec![
+                    (ReconcileReason::Dedupe, counts.dupes),
+                    (
+                        ReconcileReason::RemoteWins,
+                        counts.remote_revives + counts.remote_deletes,
+                    ),
+                    (
+                        ReconcileReason::LocalWins,
+                        counts.local_revives + counts.local_deletes,
+                    ),
+                ]);
+            }
+            _ => {}
         }
     }
 }
@@ -1123,6 +1142,9 @@ impl<'a> Merger<'a> {
         // Record telemetry in all cases, even if the merge fails.
         if let Some(ref mut telem) = self.telem {
             telem.validation(driver.validation.into_inner());
+            for (reason, count) in driver.reconciled.into_inner() {
+                telem.reconciled_with(reason, count as u32);
+            }
         }
         result
     }
@@ -1780,6 +1802,20 @@ mod tests {
         conn: &PlacesDb,
         remote_time: ServerTimestamp,
         records_json: Value,
+    ) -> Vec<Guid> {
+        apply_incoming_with_telemetry(
+            conn,
+            remote_time,
+            records_json,
+            &mut telemetry::Engine::new("bookmarks"),
+        )
+    }
+
+    fn apply_incoming_with_telemetry(
+        conn: &PlacesDb,
+        remote_time: ServerTimestamp,
+        records_json: Value,
+        telem: &mut telemetry::Engine,
     ) -> Vec<Guid> {
         // suck records into the engine.
         let interrupt_scope = conn.begin_interrupt_scope();
@@ -1817,7 +1853,7 @@ mod tests {
         }
 
         engine
-            .apply_incoming(vec![incoming], &mut telemetry::Engine::new("bookmarks"))
+            .apply_incoming(vec![incoming], telem)
             .expect("Should apply incoming and stage outgoing records");
 
         let mut stmt = conn
@@ -3846,7 +3882,8 @@ mod tests {
         );
 
         // Add older remote dupes.
-        apply_incoming(
+        let mut telem = telemetry::Engine::new("bookmarks");
+        apply_incoming_with_telemetry(
             &syncer,
             ServerTimestamp(local_modified.as_millis() as i64),
             json!([{
@@ -3873,7 +3910,12 @@ mod tests {
                 "bmkUri": "http://example.com/a",
                 "modified": remote_modified,
             }]),
+            &mut telem,
         );
+        // The two records which were deduped are only counted as reconciled.
+        let telem = serde_json::to_value(&telem)?;
+        assert_eq!(telem["incoming"]["applied"], 1);
+        assert_eq!(telem["incoming"]["reconciled"], 2);
 
         assert_local_json_tree(
             &writer,
@@ -18,7 +18,7 @@ use crate::types::VisitTransition;
 use interrupt_support::Interruptee;
 use std::collections::HashSet;
 use std::time::{SystemTime, UNIX_EPOCH};
-use sync15::telemetry;
+use sync15::telemetry::{self, ReconcileReason};
 use sync15::{IncomingChangeset, OutgoingChangeset, Payload};
 use sync_guid::Guid as SyncGuid;
 use types::Timestamp;
@@ -244,7 +244,7 @@ pub fn apply_plan(
                 telem.applied(1);
             }
             IncomingPlan::Reconciled => {
-                telem.reconciled(1);
+                telem.reconciled_with(ReconcileReason::Dedupe, 1);
                 log::trace!("incoming: reconciled {:?}", guid);
                 apply_synced_reconciliation(db, &guid)?;
             }
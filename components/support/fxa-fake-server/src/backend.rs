@@ -0,0 +1,57 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::server::ServerState;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Once,
+    },
+};
+use viaduct::Backend;
+
+lazy_static::lazy_static! {
+    // The live servers, by host. Tests run in parallel, so each server gets
+    // its own host instead of sharing a single global one.
+    static ref SERVERS: Mutex<HashMap<String, Arc<Mutex<ServerState>>>> = Mutex::new(HashMap::new());
+}
+
+static INIT_FAKE_BACKEND: Once = Once::new();
+static NEXT_SERVER_ID: AtomicUsize = AtomicUsize::new(1);
+
+pub(crate) fn next_host() -> String {
+    format!(
+        "fxa-{}.fake-fxa.test",
+        NEXT_SERVER_ID.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+pub(crate) fn register(host: &str, state: Arc<Mutex<ServerState>>) {
+    INIT_FAKE_BACKEND.call_once(|| {
+        viaduct::set_backend(&FakeBackend).expect("Backend already set (FFI or reqwest)");
+    });
+    SERVERS.lock().unwrap().insert(host.to_owned(), state);
+}
+
+pub(crate) fn unregister(host: &str) {
+    SERVERS.lock().unwrap().remove(host);
+}
+
+struct FakeBackend;
+impl Backend for FakeBackend {
+    fn send(&self, request: viaduct::Request) -> Result<viaduct::Response, viaduct::Error> {
+        viaduct::note_backend("fxa-fake-server");
+        let host = request.url.host_str().unwrap_or_default();
+        // Don't hold the registry lock while handling the request.
+        let server = SERVERS.lock().unwrap().get(host).cloned();
+        match server {
+            Some(server) => Ok(server.lock().unwrap().handle(&request)),
+            None => Err(viaduct::Error::NetworkError(format!(
+                "No fake FxA server for {}",
+                request.url
+            ))),
+        }
+    }
+}
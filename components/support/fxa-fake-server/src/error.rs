@@ -0,0 +1,23 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FakeFxaServerError {
+    #[error("Missing parameter in authorization URL: {0}")]
+    MissingParameter(&'static str),
+    #[error("The account has no key for scope {0}")]
+    UnknownScope(String),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Error parsing URL: {0}")]
+    UrlParseError(#[from] url::ParseError),
+    #[error("Base64 decode error: {0}")]
+    Base64Decode(#[from] base64::DecodeError),
+    #[error("Crypto error: {0}")]
+    CryptoError(#[from] rc_crypto::Error),
+    #[error("JWE error: {0}")]
+    JwCryptoError(#[from] jwcrypto::JwCryptoError),
+}
@@ -0,0 +1,134 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A fake Firefox Accounts server, so that `fxa-client` flows can be tested
+//! end-to-end without a network connection or a real account.
+//!
+//! A [`FakeFxaServer`] implements the endpoints of the FxA auth, OAuth and
+//! profile servers that `fxa-client` talks to: the OAuth token endpoint,
+//! profile, devices, attached clients, device commands and scoped key data.
+//! Scoped keys are handed out encrypted to the `keys_jwk` of each OAuth flow,
+//! as the real server does, so the keys a client ends up with can be used
+//! for send-tab between two `FirefoxAccount` instances.
+//!
+//! Requests reach the server through a viaduct [`Backend`](viaduct::Backend)
+//! which is installed when the first server is created. Viaduct only allows
+//! a backend to be set once per process, so this can't be used alongside
+//! another backend such as `viaduct-reqwest`; tests using it should live in
+//! their own test binary.
+//!
+//! Each server has its own host and holds a single account. The server stands
+//! in for the web content too: where a user would sign in through the URL
+//! returned by `begin_oauth_flow`, tests hand that URL to
+//! [`FakeFxaServer::authorize`] and complete the flow with the code it returns.
+//!
+//! ```ignore
+//! let server = FakeFxaServer::new();
+//! let fxa = FirefoxAccount::new(&server.content_url(), "client", "https://redirect", &None);
+//! let url = fxa.begin_oauth_flow(&[OLD_SYNC_SCOPE], "test", None)?;
+//! let authorization = server.authorize(&url)?;
+//! fxa.complete_oauth_flow(&authorization.code, &authorization.state)?;
+//! ```
+//!
+//! There is no push service either. The push messages the server would have
+//! sent to a device are kept until [`FakeFxaServer::take_push_messages`] is
+//! called, and tests pass them to `handle_push_message` themselves.
+//!
+//! Not implemented: ID tokens (the `openid` scope is granted but no
+//! `id_token` is issued), Hawk request signature checks (the token id is
+//! enough to identify the session), rate limiting and backoff, and the
+//! sync token server.
+
+mod backend;
+mod error;
+mod server;
+
+pub use error::FakeFxaServerError;
+pub use server::OLD_SYNC_SCOPE;
+
+use server::ServerState;
+use std::sync::{Arc, Mutex};
+
+type Result<T> = std::result::Result<T, FakeFxaServerError>;
+
+/// An OAuth code issued by [`FakeFxaServer::authorize`], along with the
+/// state of the flow it was issued for.
+#[derive(Clone, Debug)]
+pub struct Authorization {
+    pub code: String,
+    pub state: String,
+}
+
+/// A fake FxA server holding a single account.
+///
+/// The server is reachable from the moment it's created until it's dropped.
+pub struct FakeFxaServer {
+    host: String,
+    state: Arc<Mutex<ServerState>>,
+}
+
+impl FakeFxaServer {
+    /// Create a server with a new account, whose keys are randomly generated.
+    pub fn new() -> Self {
+        rc_crypto::ensure_initialized();
+        let host = backend::next_host();
+        let state = ServerState::new(&host).expect("Failed to create the fake FxA account");
+        let state = Arc::new(Mutex::new(state));
+        backend::register(&host, state.clone());
+        Self { host, state }
+    }
+
+    /// The content URL to create `FirefoxAccount`s with.
+    pub fn content_url(&self) -> String {
+        format!("https://{}/", self.host)
+    }
+
+    pub fn uid(&self) -> String {
+        self.state.lock().unwrap().uid().to_owned()
+    }
+
+    pub fn email(&self) -> String {
+        self.state.lock().unwrap().email().to_owned()
+    }
+
+    /// Sign in to the account through an OAuth authorization URL, as the
+    /// user would do in the web content, and return the code to complete
+    /// the flow with.
+    ///
+    /// The code is only valid for the `client_id` and PKCE challenge in the
+    /// URL. If the URL has a `keys_jwk`, the account's keys for the requested
+    /// scopes are encrypted to it and returned when the code is exchanged.
+    pub fn authorize(&self, authorization_url: &str) -> Result<Authorization> {
+        self.state.lock().unwrap().authorize(authorization_url)
+    }
+
+    /// Rotate the account's key for `scope`. Clients that already have the
+    /// key will only find out when they next check its key data.
+    pub fn rotate_scoped_key(&self, scope: &str) -> Result<()> {
+        self.state.lock().unwrap().rotate_scoped_key(scope)
+    }
+
+    /// Reset the account's password, which changes all its keys and signs
+    /// out every session, refresh token and device.
+    pub fn reset_password(&self) -> Result<()> {
+        self.state.lock().unwrap().reset_password()
+    }
+
+    /// Take the push message payloads sent to a device since the last call.
+    pub fn take_push_messages(&self, device_id: &str) -> Vec<String> {
+        self.state.lock().unwrap().take_push_messages(device_id)
+    }
+}
+
+impl Default for FakeFxaServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for FakeFxaServer {
+    fn drop(&mut self) {
+        backend::unregister(&self.host);
+    }
+}
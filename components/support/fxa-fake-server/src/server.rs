@@ -0,0 +1,1017 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The account and token state of a fake server, and the handlers for the
+//! endpoints `fxa-client` uses.
+//!
+//! The server is laid out like production, minus the content server: the
+//! auth server lives under `/auth`, the OAuth server under `/oauth` and the
+//! profile server under `/profile`.
+
+use crate::{Authorization, FakeFxaServerError, Result};
+use jwcrypto::{EncryptionAlgorithm, EncryptionParameters, Jwk};
+use rc_crypto::{digest, hkdf, hmac, rand};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+use viaduct::{header_names, Header, Headers, Method, Request, Response};
+
+/// The only scope the fake account has a key for.
+pub const OLD_SYNC_SCOPE: &str = "https://identity.mozilla.com/apps/oldsync";
+const SESSION_SCOPE: &str = "https://identity.mozilla.com/tokens/session";
+const PROFILE_SCOPE: &str = "profile";
+
+const ACCESS_TOKEN_TTL_SECS: u64 = 3600;
+const DEFAULT_COMMANDS_LIMIT: usize = 100;
+const DEFAULT_DEVICE_NAME: &str = "Fake device";
+const SESSION_TOKEN_INFO: &[u8] = b"identity.mozilla.com/picl/v1/sessionToken";
+
+// The errnos the real servers use for the errors we return.
+const ERRNO_INVALID_PARAMETER: u32 = 107;
+const ERRNO_INVALID_TOKEN: u32 = 110;
+const ERRNO_DEVICE_UNKNOWN: u32 = 123;
+const ERRNO_UNAVAILABLE_DEVICE_COMMAND: u32 = 157;
+const ERRNO_INVALID_SCOPES: u32 = 163;
+const ERRNO_UNEXPECTED_ERROR: u32 = 999;
+
+pub(crate) struct ServerState {
+    base_url: Url,
+    uid: String,
+    email: String,
+    scoped_keys: HashMap<String, ScopedKeyMaterial>,
+    codes: HashMap<String, AuthorizationCode>,
+    // Keyed by token id, which is all a Hawk request tells us about its session token.
+    sessions: HashMap<String, Session>,
+    refresh_tokens: HashMap<String, RefreshToken>,
+    access_tokens: HashMap<String, AccessToken>,
+    devices: Vec<Device>,
+    commands: Vec<Command>,
+    push_messages: HashMap<String, Vec<String>>,
+}
+
+struct ScopedKeyMaterial {
+    key: Vec<u8>,
+    rotation_secret: Vec<u8>,
+    rotation_timestamp: u64,
+}
+
+struct AuthorizationCode {
+    client_id: String,
+    scopes: Vec<String>,
+    access_type: Option<String>,
+    code_challenge: Option<String>,
+    keys_jwe: Option<String>,
+}
+
+struct Session {
+    created_at: u64,
+    last_access_time: u64,
+}
+
+struct RefreshToken {
+    client_id: String,
+    scopes: Vec<String>,
+    created_at: u64,
+    last_access_time: u64,
+}
+
+struct AccessToken {
+    scopes: Vec<String>,
+}
+
+struct Device {
+    id: String,
+    // Like on the real server, the refresh token a device was registered with
+    // identifies it, and destroying one destroys the other.
+    refresh_token: String,
+    name: String,
+    device_type: String,
+    push_subscription: Option<PushSubscription>,
+    available_commands: serde_json::Map<String, Value>,
+    last_access_time: u64,
+}
+
+struct PushSubscription {
+    callback: String,
+    public_key: String,
+    auth_key: String,
+}
+
+struct Command {
+    index: u64,
+    target: String,
+    command: String,
+    payload: Value,
+    sender: Option<String>,
+}
+
+impl ServerState {
+    pub(crate) fn new(host: &str) -> Result<Self> {
+        let uid = random_hex(16)?;
+        let email = format!("{}@fake-fxa.test", &uid[..8]);
+        let mut scoped_keys = HashMap::new();
+        scoped_keys.insert(
+            OLD_SYNC_SCOPE.to_owned(),
+            ScopedKeyMaterial::generate(now())?,
+        );
+        Ok(Self {
+            base_url: Url::parse(&format!("https://{}/", host))?,
+            uid,
+            email,
+            scoped_keys,
+            codes: HashMap::new(),
+            sessions: HashMap::new(),
+            refresh_tokens: HashMap::new(),
+            access_tokens: HashMap::new(),
+            devices: Vec::new(),
+            commands: Vec::new(),
+            push_messages: HashMap::new(),
+        })
+    }
+
+    pub(crate) fn uid(&self) -> &str {
+        &self.uid
+    }
+
+    pub(crate) fn email(&self) -> &str {
+        &self.email
+    }
+
+    pub(crate) fn authorize(&mut self, authorization_url: &str) -> Result<Authorization> {
+        let url = Url::parse(authorization_url)?;
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        let param = |name: &'static str| {
+            params
+                .get(name)
+                .cloned()
+                .ok_or(FakeFxaServerError::MissingParameter(name))
+        };
+        let client_id = param("client_id")?;
+        let scopes = split_scopes(&param("scope")?);
+        let state = param("state")?;
+        let keys_jwe = match params.get("keys_jwk") {
+            Some(keys_jwk) => self.encrypt_scoped_keys(&scopes, keys_jwk)?,
+            None => None,
+        };
+        let code = self.issue_code(AuthorizationCode {
+            client_id,
+            scopes,
+            access_type: params.get("access_type").cloned(),
+            code_challenge: params.get("code_challenge").cloned(),
+            keys_jwe,
+        })?;
+        Ok(Authorization { code, state })
+    }
+
+    pub(crate) fn rotate_scoped_key(&mut self, scope: &str) -> Result<()> {
+        let previous = self
+            .scoped_keys
+            .get(scope)
+            .ok_or_else(|| FakeFxaServerError::UnknownScope(scope.to_owned()))?;
+        // Make sure the new key has a different `kid` even if we're fast.
+        let rotation_timestamp = now().max(previous.rotation_timestamp + 1);
+        self.scoped_keys.insert(
+            scope.to_owned(),
+            ScopedKeyMaterial::generate(rotation_timestamp)?,
+        );
+        Ok(())
+    }
+
+    pub(crate) fn reset_password(&mut self) -> Result<()> {
+        let scopes: Vec<String> = self.scoped_keys.keys().cloned().collect();
+        for scope in scopes {
+            self.rotate_scoped_key(&scope)?;
+        }
+        let device_ids: Vec<String> = self.devices.iter().map(|d| d.id.clone()).collect();
+        self.notify(&device_ids, "fxaccounts:password_reset", None);
+        self.codes.clear();
+        self.sessions.clear();
+        self.refresh_tokens.clear();
+        self.access_tokens.clear();
+        self.devices.clear();
+        Ok(())
+    }
+
+    pub(crate) fn take_push_messages(&mut self, device_id: &str) -> Vec<String> {
+        self.push_messages.remove(device_id).unwrap_or_default()
+    }
+
+    pub(crate) fn handle(&mut self, request: &Request) -> Response {
+        let reply = self.route(request).unwrap_or_else(|err| {
+            log::debug!(
+                "{} {} failed: {}",
+                request.method.as_str(),
+                request.url,
+                err.message
+            );
+            err.into_reply()
+        });
+        Response {
+            request_method: request.method,
+            url: request.url.clone(),
+            status: reply.status,
+            headers: reply.headers,
+            body: reply.body,
+        }
+    }
+
+    fn route(&mut self, request: &Request) -> ApiResult<Reply> {
+        match (request.method, request.url.path()) {
+            (Method::Get, "/.well-known/fxa-client-configuration") => {
+                Ok(Reply::json(self.client_configuration()))
+            }
+            (Method::Get, "/.well-known/openid-configuration") => {
+                Ok(Reply::json(self.openid_configuration()))
+            }
+            (Method::Post, "/auth/v1/oauth/token") => self.token(request),
+            (Method::Post, "/auth/v1/oauth/authorization") => self.authorization(request),
+            (Method::Post, "/auth/v1/session/duplicate") => self.duplicate_session(request),
+            (Method::Post, "/auth/v1/account/scoped-key-data") => self.scoped_key_data(request),
+            (Method::Get, "/auth/v1/account/attached_clients") => self.attached_clients(request),
+            (Method::Get, "/auth/v1/account/devices") => self.devices(request),
+            (Method::Post, "/auth/v1/account/device") => self.update_device(request),
+            (Method::Post, "/auth/v1/account/device/destroy") => self.destroy_device(request),
+            (Method::Post, "/auth/v1/account/devices/invoke_command") => {
+                self.invoke_command(request)
+            }
+            (Method::Get, "/auth/v1/account/device/commands") => self.device_commands(request),
+            (Method::Post, "/oauth/v1/destroy") => self.destroy_token(request),
+            (Method::Post, "/oauth/v1/introspect") => self.introspect(request),
+            // We don't issue ID tokens, so there's nothing to verify them with.
+            (Method::Get, "/oauth/v1/jwks") => Ok(Reply::json(json!({ "keys": [] }))),
+            (Method::Get, "/profile/v1/profile") => self.profile(request),
+            _ => Err(ApiError::not_found()),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        // `base_url` ends with a slash, and all our paths are relative to it.
+        format!("{}{}", self.base_url, path)
+    }
+
+    fn client_configuration(&self) -> Value {
+        json!({
+            "auth_server_base_url": self.url("auth"),
+            "oauth_server_base_url": self.url("oauth"),
+            "profile_server_base_url": self.url("profile"),
+            "sync_tokenserver_base_url": self.url("token"),
+        })
+    }
+
+    fn openid_configuration(&self) -> Value {
+        json!({
+            "authorization_endpoint": self.url("authorization"),
+            "introspection_endpoint": self.url("oauth/v1/introspect"),
+            "issuer": self.base_url.as_str().trim_end_matches('/'),
+            "jwks_uri": self.url("oauth/v1/jwks"),
+            "token_endpoint": self.url("auth/v1/oauth/token"),
+            "userinfo_endpoint": self.url("profile/v1/profile"),
+        })
+    }
+
+    fn token(&mut self, request: &Request) -> ApiResult<Reply> {
+        let body = json_body(request)?;
+        let grant = match str_param(&body, "grant_type")? {
+            "authorization_code" => self.grant_from_code(&body)?,
+            "refresh_token" => self.grant_from_refresh_token(&body)?,
+            "fxa-credentials" => {
+                self.hawk_session(request)?;
+                let client_id = str_param(&body, "client_id")?;
+                let scopes = split_scopes(str_param(&body, "scope")?);
+                let offline = body["access_type"].as_str() == Some("offline");
+                self.grant(client_id, scopes, offline, None)?
+            }
+            _ => return Err(ApiError::invalid_parameter("Unknown grant_type")),
+        };
+        Ok(Reply::json(grant))
+    }
+
+    fn grant_from_code(&mut self, body: &Value) -> ApiResult<Value> {
+        let code = self
+            .codes
+            .remove(str_param(body, "code")?)
+            .ok_or_else(|| ApiError::invalid_parameter("Unknown authorization code"))?;
+        if code.client_id != str_param(body, "client_id")? {
+            return Err(ApiError::invalid_parameter("Incorrect client_id"));
+        }
+        if let Some(ref code_challenge) = code.code_challenge {
+            let code_verifier = str_param(body, "code_verifier")?;
+            let digest = digest::digest(&digest::SHA256, code_verifier.as_bytes())?;
+            if &base64::encode_config(&digest, base64::URL_SAFE_NO_PAD) != code_challenge {
+                return Err(ApiError::invalid_parameter("Incorrect code_verifier"));
+            }
+        }
+        let offline = code.access_type.as_deref() == Some("offline");
+        self.grant(&code.client_id, code.scopes, offline, code.keys_jwe)
+    }
+
+    fn grant_from_refresh_token(&mut self, body: &Value) -> ApiResult<Value> {
+        let client_id = str_param(body, "client_id")?;
+        let token = self
+            .refresh_tokens
+            .get_mut(str_param(body, "refresh_token")?)
+            .ok_or_else(|| ApiError::invalid_token("Unknown refresh token"))?;
+        if token.client_id != client_id {
+            return Err(ApiError::invalid_parameter("Incorrect client_id"));
+        }
+        let scopes = match body["scope"].as_str() {
+            Some(scope) => split_scopes(scope),
+            None => token.scopes.clone(),
+        };
+        if let Some(scope) = scopes.iter().find(|s| !token.scopes.contains(s)) {
+            return Err(ApiError::new(
+                400,
+                ERRNO_INVALID_SCOPES,
+                format!("Requested scope {} is not allowed", scope),
+            ));
+        }
+        token.last_access_time = now();
+        let mut grant = self.grant(client_id, scopes, false, None)?;
+        if let Some(ttl) = body["ttl"].as_u64() {
+            grant["expires_in"] = ttl.min(ACCESS_TOKEN_TTL_SECS).into();
+        }
+        Ok(grant)
+    }
+
+    fn grant(
+        &mut self,
+        client_id: &str,
+        scopes: Vec<String>,
+        offline: bool,
+        keys_jwe: Option<String>,
+    ) -> ApiResult<Value> {
+        let access_token = random_hex(32)?;
+        let mut grant = json!({
+            "access_token": access_token,
+            "token_type": "bearer",
+            "scope": scopes.join(" "),
+            "expires_in": ACCESS_TOKEN_TTL_SECS,
+            "auth_at": now() / 1000,
+        });
+        if offline {
+            let refresh_token = random_hex(32)?;
+            self.refresh_tokens.insert(
+                refresh_token.clone(),
+                RefreshToken {
+                    client_id: client_id.to_owned(),
+                    scopes: scopes.clone(),
+                    created_at: now(),
+                    last_access_time: now(),
+                },
+            );
+            grant["refresh_token"] = refresh_token.into();
+        }
+        if scopes.iter().any(|s| s == SESSION_SCOPE) {
+            grant["session_token"] = self.create_session()?.into();
+        }
+        if let Some(keys_jwe) = keys_jwe {
+            grant["keys_jwe"] = keys_jwe.into();
+        }
+        self.access_tokens
+            .insert(access_token, AccessToken { scopes });
+        Ok(grant)
+    }
+
+    fn authorization(&mut self, request: &Request) -> ApiResult<Reply> {
+        self.hawk_session(request)?;
+        let body = json_body(request)?;
+        let client_id = str_param(&body, "client_id")?.to_owned();
+        let state = str_param(&body, "state")?.to_owned();
+        let code = self.issue_code(AuthorizationCode {
+            client_id: client_id.clone(),
+            scopes: split_scopes(str_param(&body, "scope")?),
+            access_type: body["access_type"].as_str().map(ToOwned::to_owned),
+            code_challenge: body["code_challenge"].as_str().map(ToOwned::to_owned),
+            keys_jwe: body["keys_jwe"].as_str().map(ToOwned::to_owned),
+        })?;
+        let mut redirect = Url::parse(&self.url("oauth/success/"))?.join(&client_id)?;
+        redirect
+            .query_pairs_mut()
+            .append_pair("code", &code)
+            .append_pair("state", &state);
+        Ok(Reply::json(json!({
+            "redirect": redirect.as_str(),
+            "code": code,
+            "state": state,
+        })))
+    }
+
+    fn duplicate_session(&mut self, request: &Request) -> ApiResult<Reply> {
+        self.hawk_session(request)?;
+        let session_token = self.create_session()?;
+        Ok(Reply::json(json!({
+            "uid": self.uid,
+            "sessionToken": session_token,
+            "verified": true,
+            "authAt": now() / 1000,
+        })))
+    }
+
+    fn scoped_key_data(&mut self, request: &Request) -> ApiResult<Reply> {
+        self.hawk_session(request)?;
+        let body = json_body(request)?;
+        let mut key_data = serde_json::Map::new();
+        for scope in split_scopes(str_param(&body, "scope")?) {
+            if let Some(key) = self.scoped_keys.get(&scope) {
+                key_data.insert(
+                    scope.clone(),
+                    json!({
+                        "identifier": scope,
+                        "keyRotationSecret": hex::encode(&key.rotation_secret),
+                        "keyRotationTimestamp": key.rotation_timestamp,
+                    }),
+                );
+            }
+        }
+        Ok(Reply::json(Value::Object(key_data)))
+    }
+
+    fn attached_clients(&mut self, request: &Request) -> ApiResult<Reply> {
+        let current_session = self.hawk_session(request)?;
+        let mut clients = Vec::new();
+        for (token, refresh_token) in &self.refresh_tokens {
+            let device = self.devices.iter().find(|d| &d.refresh_token == token);
+            clients.push(json!({
+                "clientId": refresh_token.client_id,
+                "refreshTokenId": token_id(token)?,
+                "deviceId": device.map(|d| &d.id),
+                "deviceType": device.map(|d| &d.device_type),
+                "isCurrentSession": false,
+                "name": device.map(|d| &d.name),
+                "createdTime": refresh_token.created_at,
+                "lastAccessTime": refresh_token.last_access_time,
+                "scope": refresh_token.scopes,
+                "userAgent": "",
+            }));
+        }
+        for (id, session) in &self.sessions {
+            clients.push(json!({
+                "sessionTokenId": id,
+                "isCurrentSession": id == &current_session,
+                "createdTime": session.created_at,
+                "lastAccessTime": session.last_access_time,
+                "userAgent": "",
+            }));
+        }
+        Ok(Reply::json(Value::Array(clients)))
+    }
+
+    fn devices(&mut self, request: &Request) -> ApiResult<Reply> {
+        let refresh_token = self.bearer_refresh_token(request)?;
+        let devices = self
+            .devices
+            .iter()
+            .map(|device| device.to_json(&refresh_token))
+            .collect();
+        Ok(Reply::json(Value::Array(devices)))
+    }
+
+    fn update_device(&mut self, request: &Request) -> ApiResult<Reply> {
+        let refresh_token = self.bearer_refresh_token(request)?;
+        let body = json_body(request)?;
+        let (index, is_new) = match self
+            .devices
+            .iter()
+            .position(|d| d.refresh_token == refresh_token)
+        {
+            Some(index) => (index, false),
+            None => {
+                self.devices.push(Device {
+                    id: random_hex(16)?,
+                    refresh_token: refresh_token.clone(),
+                    name: DEFAULT_DEVICE_NAME.to_owned(),
+                    device_type: "desktop".to_owned(),
+                    push_subscription: None,
+                    available_commands: serde_json::Map::new(),
+                    last_access_time: now(),
+                });
+                (self.devices.len() - 1, true)
+            }
+        };
+        let device = &mut self.devices[index];
+        match body.get("name") {
+            Some(Value::String(name)) => device.name = name.clone(),
+            Some(Value::Null) => device.name = DEFAULT_DEVICE_NAME.to_owned(),
+            _ => {}
+        }
+        if let Some(Value::String(device_type)) = body.get("type") {
+            device.device_type = device_type.clone();
+        }
+        if let Some(Value::String(callback)) = body.get("pushCallback") {
+            device.push_subscription = Some(PushSubscription {
+                callback: callback.clone(),
+                public_key: body["pushPublicKey"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_owned(),
+                auth_key: body["pushAuthKey"].as_str().unwrap_or_default().to_owned(),
+            });
+        }
+        match body.get("availableCommands") {
+            Some(Value::Object(commands)) => device.available_commands = commands.clone(),
+            Some(Value::Null) => device.available_commands.clear(),
+            _ => {}
+        }
+        device.last_access_time = now();
+        let response = device.to_json(&refresh_token);
+        if is_new {
+            let device_id = self.devices[index].id.clone();
+            let device_name = self.devices[index].name.clone();
+            let others = self.device_ids_except(&device_id);
+            self.notify(
+                &others,
+                "fxaccounts:device_connected",
+                Some(json!({ "deviceName": device_name })),
+            );
+        }
+        Ok(Reply::json(response))
+    }
+
+    fn destroy_device(&mut self, request: &Request) -> ApiResult<Reply> {
+        self.bearer_refresh_token(request)?;
+        let body = json_body(request)?;
+        let id = str_param(&body, "id")?;
+        let device = self
+            .devices
+            .iter()
+            .find(|d| d.id == id)
+            .ok_or_else(ApiError::unknown_device)?;
+        let refresh_token = device.refresh_token.clone();
+        self.destroy_refresh_token(&refresh_token);
+        Ok(Reply::json(json!({})))
+    }
+
+    fn invoke_command(&mut self, request: &Request) -> ApiResult<Reply> {
+        let refresh_token = self.bearer_refresh_token(request)?;
+        let body = json_body(request)?;
+        let command = str_param(&body, "command")?;
+        let target = self
+            .devices
+            .iter()
+            .find(|d| d.id == body["target"].as_str().unwrap_or_default())
+            .ok_or_else(ApiError::unknown_device)?;
+        if !target.available_commands.contains_key(command) {
+            return Err(ApiError::new(
+                400,
+                ERRNO_UNAVAILABLE_DEVICE_COMMAND,
+                "Unavailable device command",
+            ));
+        }
+        let sender = self
+            .devices
+            .iter()
+            .find(|d| d.refresh_token == refresh_token)
+            .map(|d| d.id.clone());
+        let index = self.commands.last().map_or(1, |c| c.index + 1);
+        let target = target.id.clone();
+        self.commands.push(Command {
+            index,
+            target: target.clone(),
+            command: command.to_owned(),
+            payload: body["payload"].clone(),
+            sender: sender.clone(),
+        });
+        let mut url = Url::parse(&self.url("auth/v1/account/device/commands"))?;
+        url.query_pairs_mut()
+            .append_pair("index", &index.to_string())
+            .append_pair("limit", "1");
+        self.notify(
+            &[target],
+            "fxaccounts:command_received",
+            Some(json!({
+                "command": command,
+                "index": index,
+                "sender": sender.unwrap_or_default(),
+                "url": url.as_str(),
+            })),
+        );
+        Ok(Reply::json(json!({})))
+    }
+
+    fn device_commands(&mut self, request: &Request) -> ApiResult<Reply> {
+        let refresh_token = self.bearer_refresh_token(request)?;
+        let device_id = self
+            .devices
+            .iter()
+            .find(|d| d.refresh_token == refresh_token)
+            .map(|d| d.id.clone())
+            .ok_or_else(ApiError::unknown_device)?;
+        let query: HashMap<_, _> = request.url.query_pairs().into_owned().collect();
+        let index = query_param(&query, "index")?.unwrap_or(0);
+        let limit = query_param(&query, "limit")?.unwrap_or(DEFAULT_COMMANDS_LIMIT as u64) as usize;
+        let pending: Vec<&Command> = self
+            .commands
+            .iter()
+            .filter(|c| c.target == device_id && c.index >= index)
+            .collect();
+        let messages: Vec<Value> = pending
+            .iter()
+            .take(limit)
+            .map(|c| {
+                json!({
+                    "index": c.index,
+                    "data": {
+                        "command": c.command,
+                        "payload": c.payload,
+                        "sender": c.sender,
+                    },
+                })
+            })
+            .collect();
+        let last_index = pending.iter().take(limit).last().map_or(index, |c| c.index);
+        Ok(Reply::json(json!({
+            "index": last_index,
+            "last": pending.len() <= limit,
+            "messages": messages,
+        })))
+    }
+
+    fn destroy_token(&mut self, request: &Request) -> ApiResult<Reply> {
+        let body = json_body(request)?;
+        let token = body["refresh_token"]
+            .as_str()
+            .or_else(|| body["token"].as_str())
+            .ok_or_else(|| ApiError::invalid_parameter("Missing token"))?;
+        if self.access_tokens.remove(token).is_none() {
+            self.destroy_refresh_token(token);
+        }
+        Ok(Reply::json(json!({})))
+    }
+
+    fn introspect(&mut self, request: &Request) -> ApiResult<Reply> {
+        let body = json_body(request)?;
+        let token = str_param(&body, "token")?;
+        let active = match body["token_type_hint"].as_str() {
+            Some("refresh_token") => self.refresh_tokens.contains_key(token),
+            _ => self.access_tokens.contains_key(token),
+        };
+        Ok(Reply::json(json!({ "active": active })))
+    }
+
+    fn profile(&mut self, request: &Request) -> ApiResult<Reply> {
+        let access_token = bearer_token(request)?;
+        let token = self
+            .access_tokens
+            .get(access_token)
+            .ok_or_else(|| ApiError::invalid_token("Unknown access token"))?;
+        if !token.scopes.iter().any(|s| s == PROFILE_SCOPE) {
+            return Err(ApiError::new(
+                403,
+                ERRNO_INVALID_SCOPES,
+                "The access token doesn't have the profile scope",
+            ));
+        }
+        let profile = json!({
+            "uid": self.uid,
+            "email": self.email,
+            "displayName": null,
+            "avatar": self.url(&format!("avatar/{}", self.uid)),
+            "avatarDefault": true,
+        });
+        let etag = token_id(&profile.to_string())?;
+        let if_none_match = request
+            .headers
+            .get(header_names::IF_NONE_MATCH)
+            .map(|v| v.trim_matches('"'));
+        if if_none_match == Some(etag.as_str()) {
+            return Ok(Reply::not_modified());
+        }
+        Ok(Reply::json(profile).with_header(header_names::ETAG, etag))
+    }
+
+    fn issue_code(&mut self, code: AuthorizationCode) -> Result<String> {
+        let code_value = random_hex(32)?;
+        self.codes.insert(code_value.clone(), code);
+        Ok(code_value)
+    }
+
+    fn encrypt_scoped_keys(&self, scopes: &[String], keys_jwk: &str) -> Result<Option<String>> {
+        let keys: serde_json::Map<String, Value> = scopes
+            .iter()
+            .filter_map(|scope| {
+                let key = self.scoped_keys.get(scope)?;
+                Some(key.to_jwk(scope).map(|jwk| (scope.clone(), jwk)))
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        if keys.is_empty() {
+            return Ok(None);
+        }
+        let keys_jwk = base64::decode_config(keys_jwk, base64::URL_SAFE_NO_PAD)?;
+        let jwk: Jwk = serde_json::from_slice(&keys_jwk)?;
+        Ok(Some(jwcrypto::encrypt_to_jwe(
+            Value::Object(keys).to_string().as_bytes(),
+            EncryptionParameters::ECDH_ES {
+                enc: EncryptionAlgorithm::A256GCM,
+                peer_jwk: &jwk,
+            },
+        )?))
+    }
+
+    fn create_session(&mut self) -> std::result::Result<String, rc_crypto::Error> {
+        let session_token = random_hex(32)?;
+        self.sessions.insert(
+            session_token_id(&session_token)?,
+            Session {
+                created_at: now(),
+                last_access_time: now(),
+            },
+        );
+        Ok(session_token)
+    }
+
+    /// Find the session a Hawk-authenticated request was made with, and
+    /// return its token id.
+    fn hawk_session(&mut self, request: &Request) -> ApiResult<String> {
+        let id = request
+            .headers
+            .get(header_names::AUTHORIZATION)
+            .and_then(|header| header.strip_prefix("Hawk "))
+            .and_then(|params| {
+                params.split(',').find_map(|param| {
+                    let (name, value) = param.trim().split_once('=')?;
+                    if name == "id" {
+                        Some(value.trim_matches('"').to_owned())
+                    } else {
+                        None
+                    }
+                })
+            })
+            .ok_or_else(|| ApiError::invalid_token("Missing Hawk credentials"))?;
+        let session = self
+            .sessions
+            .get_mut(&id)
+            .ok_or_else(|| ApiError::invalid_token("Unknown session token"))?;
+        session.last_access_time = now();
+        Ok(id)
+    }
+
+    /// The device endpoints are authenticated with the device's refresh token.
+    fn bearer_refresh_token(&mut self, request: &Request) -> ApiResult<String> {
+        let token = bearer_token(request)?;
+        let refresh_token = self
+            .refresh_tokens
+            .get_mut(token)
+            .ok_or_else(|| ApiError::invalid_token("Unknown refresh token"))?;
+        refresh_token.last_access_time = now();
+        Ok(token.to_owned())
+    }
+
+    fn destroy_refresh_token(&mut self, token: &str) {
+        self.refresh_tokens.remove(token);
+        if let Some(index) = self.devices.iter().position(|d| d.refresh_token == token) {
+            // Every device gets told, including the one that's going away.
+            let device_ids: Vec<String> = self.devices.iter().map(|d| d.id.clone()).collect();
+            let device = self.devices.remove(index);
+            self.notify(
+                &device_ids,
+                "fxaccounts:device_disconnected",
+                Some(json!({ "id": device.id })),
+            );
+        }
+    }
+
+    fn device_ids_except(&self, device_id: &str) -> Vec<String> {
+        self.devices
+            .iter()
+            .filter(|d| d.id != device_id)
+            .map(|d| d.id.clone())
+            .collect()
+    }
+
+    fn notify(&mut self, device_ids: &[String], command: &str, data: Option<Value>) {
+        let mut payload = json!({ "version": 1, "command": command });
+        if let Some(data) = data {
+            payload["data"] = data;
+        }
+        let payload = payload.to_string();
+        for device_id in device_ids {
+            self.push_messages
+                .entry(device_id.clone())
+                .or_default()
+                .push(payload.clone());
+        }
+    }
+}
+
+impl ScopedKeyMaterial {
+    fn generate(rotation_timestamp: u64) -> std::result::Result<Self, rc_crypto::Error> {
+        let mut key = vec![0u8; 64];
+        rand::fill(&mut key)?;
+        let mut rotation_secret = vec![0u8; 32];
+        rand::fill(&mut rotation_secret)?;
+        Ok(Self {
+            key,
+            rotation_secret,
+            rotation_timestamp,
+        })
+    }
+
+    fn to_jwk(&self, scope: &str) -> std::result::Result<Value, rc_crypto::Error> {
+        // The real server fingerprints the account's root key rather than the
+        // scoped key, but any fingerprint that changes with the key will do.
+        let fingerprint = digest::digest(&digest::SHA256, &self.key)?;
+        let fingerprint =
+            base64::encode_config(&fingerprint.as_ref()[..16], base64::URL_SAFE_NO_PAD);
+        Ok(json!({
+            "kty": "oct",
+            "scope": scope,
+            "k": base64::encode_config(&self.key, base64::URL_SAFE_NO_PAD),
+            "kid": format!("{}-{}", self.rotation_timestamp, fingerprint),
+        }))
+    }
+}
+
+impl Device {
+    fn to_json(&self, current_refresh_token: &str) -> Value {
+        let mut device = json!({
+            "id": self.id,
+            "name": self.name,
+            "type": self.device_type,
+            "availableCommands": self.available_commands,
+            "pushEndpointExpired": false,
+            "isCurrentDevice": self.refresh_token == current_refresh_token,
+            "location": {},
+            "lastAccessTime": self.last_access_time,
+        });
+        if let Some(ref push_subscription) = self.push_subscription {
+            device["pushCallback"] = push_subscription.callback.clone().into();
+            device["pushPublicKey"] = push_subscription.public_key.clone().into();
+            device["pushAuthKey"] = push_subscription.auth_key.clone().into();
+        }
+        device
+    }
+}
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// An error response, in the format all the FxA servers use.
+struct ApiError {
+    code: u16,
+    errno: u32,
+    message: String,
+}
+
+impl ApiError {
+    fn new(code: u16, errno: u32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            errno,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_parameter(message: &str) -> Self {
+        Self::new(400, ERRNO_INVALID_PARAMETER, message)
+    }
+
+    fn invalid_token(message: &str) -> Self {
+        Self::new(401, ERRNO_INVALID_TOKEN, message)
+    }
+
+    fn unknown_device() -> Self {
+        Self::new(400, ERRNO_DEVICE_UNKNOWN, "Unknown device")
+    }
+
+    fn not_found() -> Self {
+        Self::new(404, ERRNO_UNEXPECTED_ERROR, "Not Found")
+    }
+
+    fn into_reply(self) -> Reply {
+        let error = match self.code {
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            _ => "Internal Server Error",
+        };
+        let mut reply = Reply::json(json!({
+            "code": self.code,
+            "errno": self.errno,
+            "error": error,
+            "message": self.message,
+            "info": "",
+        }));
+        reply.status = self.code;
+        reply
+    }
+}
+
+impl From<rc_crypto::Error> for ApiError {
+    fn from(err: rc_crypto::Error) -> Self {
+        Self::new(500, ERRNO_UNEXPECTED_ERROR, err.to_string())
+    }
+}
+
+impl From<url::ParseError> for ApiError {
+    fn from(err: url::ParseError) -> Self {
+        Self::new(500, ERRNO_UNEXPECTED_ERROR, err.to_string())
+    }
+}
+
+impl From<FakeFxaServerError> for ApiError {
+    fn from(err: FakeFxaServerError) -> Self {
+        Self::new(500, ERRNO_UNEXPECTED_ERROR, err.to_string())
+    }
+}
+
+struct Reply {
+    status: u16,
+    headers: Headers,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(body: Value) -> Self {
+        let mut headers = Headers::new();
+        headers.insert_header(Header::new_unchecked(
+            header_names::CONTENT_TYPE,
+            "application/json",
+        ));
+        Self {
+            status: 200,
+            headers,
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    fn not_modified() -> Self {
+        Self {
+            status: 304,
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn with_header(mut self, name: viaduct::HeaderName, value: String) -> Self {
+        self.headers
+            .insert_header(Header::new_unchecked(name, value));
+        self
+    }
+}
+
+fn json_body(request: &Request) -> ApiResult<Value> {
+    let body = request.body.as_deref().unwrap_or_default();
+    serde_json::from_slice(body).map_err(|_| ApiError::invalid_parameter("Invalid JSON body"))
+}
+
+fn str_param<'a>(body: &'a Value, name: &str) -> ApiResult<&'a str> {
+    body[name]
+        .as_str()
+        .ok_or_else(|| ApiError::invalid_parameter(&format!("Missing parameter {}", name)))
+}
+
+fn query_param(query: &HashMap<String, String>, name: &str) -> ApiResult<Option<u64>> {
+    query
+        .get(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| ApiError::invalid_parameter(&format!("Invalid parameter {}", name)))
+        })
+        .transpose()
+}
+
+fn bearer_token(request: &Request) -> ApiResult<&str> {
+    request
+        .headers
+        .get(header_names::AUTHORIZATION)
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::invalid_token("Missing bearer token"))
+}
+
+fn split_scopes(scope: &str) -> Vec<String> {
+    scope.split_whitespace().map(ToOwned::to_owned).collect()
+}
+
+/// Derive the id of a session token, which is what identifies it in Hawk
+/// requests.
+fn session_token_id(session_token: &str) -> std::result::Result<String, rc_crypto::Error> {
+    let session_token = hex::decode(session_token).expect("We made this token");
+    let salt = hmac::SigningKey::new(&digest::SHA256, &[0u8; 32]);
+    let mut out = [0u8; 64];
+    hkdf::extract_and_expand(&salt, &session_token, SESSION_TOKEN_INFO, &mut out)?;
+    Ok(hex::encode(&out[..32]))
+}
+
+/// An opaque id for a token, or any other string.
+fn token_id(token: &str) -> std::result::Result<String, rc_crypto::Error> {
+    let digest = digest::digest(&digest::SHA256, token.as_bytes())?;
+    Ok(hex::encode(&digest.as_ref()[..16]))
+}
+
+fn random_hex(len: usize) -> std::result::Result<String, rc_crypto::Error> {
+    let mut bytes = vec![0u8; len];
+    rand::fill(&mut bytes)?;
+    Ok(hex::encode(bytes))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Current time before Unix Epoch.")
+        .as_millis() as u64
+}
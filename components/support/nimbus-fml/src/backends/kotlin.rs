@@ -0,0 +1,630 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Generates Kotlin accessors for the features of a manifest, which read the
+//! feature variables through `NimbusInterface.getVariables`.
+
+use super::{
+    doc_lines, find_enum, find_object, literal, unsupported, CodeGenOptions, LiteralSyntax,
+};
+use crate::error::{FMLError, Result};
+use crate::intermediate_representation::{
+    EnumDef, FeatureDef, FeatureManifest, ObjectDef, PropDef, TypeRef,
+};
+use heck::{CamelCase, MixedCase, ShoutySnakeCase};
+
+pub fn generate_struct(fm: &FeatureManifest, options: &CodeGenOptions) -> Result<String> {
+    let mut out = String::new();
+    out.push_str("// This file was autogenerated by nimbus-fml. Do not edit.\n\n");
+    if let Some(package_name) = &options.package_name {
+        out.push_str(&format!("package {}\n\n", package_name));
+    }
+    out.push_str("import org.mozilla.experiments.nimbus.NimbusInterface\n");
+    out.push_str("import org.mozilla.experiments.nimbus.Variables\n");
+
+    out.push('\n');
+    write_doc(
+        &mut out,
+        0,
+        "The features of the app which can be configured by Nimbus.",
+    );
+    out.push_str(&format!(
+        "class {}(private val nimbus: NimbusInterface) {{\n",
+        options.class_name
+    ));
+    for (i, feature_def) in fm.feature_defs.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        write_doc(&mut out, 1, feature_def.doc());
+        out.push_str(&format!(
+            "    fun {}(recordExposureEvent: Boolean = true) =\n        {}(nimbus.getVariables({}, recordExposureEvent))\n",
+            var_name(feature_def.name()),
+            class_name(feature_def.name()),
+            string_literal(feature_def.name()),
+        ));
+    }
+    out.push_str("}\n");
+
+    for feature_def in &fm.feature_defs {
+        out.push('\n');
+        write_feature(&mut out, fm, feature_def)?;
+    }
+    for obj_def in &fm.obj_defs {
+        out.push('\n');
+        write_object(&mut out, fm, obj_def)?;
+    }
+    for enum_def in &fm.enum_defs {
+        out.push('\n');
+        write_enum(&mut out, enum_def);
+    }
+    out.push('\n');
+    out.push_str(HELPERS);
+    Ok(out)
+}
+
+const HELPERS: &str = "\
+private fun <K, V, R> Map<K, V>.mapValuesNotNull(transform: (V) -> R?): Map<K, R> =
+    entries.mapNotNull { (k, v) -> transform(v)?.let { k to it } }.toMap()
+
+private fun <K, V, R> Map<K, V>.mapKeysNotNull(transform: (K) -> R?): Map<R, V> =
+    entries.mapNotNull { (k, v) -> transform(k)?.let { it to v } }.toMap()
+";
+
+fn write_feature(out: &mut String, fm: &FeatureManifest, feature_def: &FeatureDef) -> Result<()> {
+    write_doc(out, 0, feature_def.doc());
+    out.push_str(&format!(
+        "class {} internal constructor(private val variables: Variables) {{\n",
+        class_name(feature_def.name())
+    ));
+    for (i, prop) in feature_def.props().iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let typ = type_label(fm, &prop.typ)?;
+        write_doc(out, 1, &prop.doc);
+        out.push_str(&format!("    val {}: {}\n", var_name(&prop.name), typ));
+        out.push_str("        get() {\n");
+        out.push_str(&format!(
+            "            val defaultValue: {} = {}\n",
+            typ,
+            literal(fm, &Kotlin, &prop.typ, &prop.default)?
+        ));
+        out.push_str(&format!(
+            "            return {}\n",
+            value_expr(fm, prop, "defaultValue")?
+        ));
+        out.push_str("        }\n");
+    }
+    out.push_str("}\n");
+    Ok(())
+}
+
+fn write_object(out: &mut String, fm: &FeatureManifest, obj_def: &ObjectDef) -> Result<()> {
+    if obj_def.props().is_empty() {
+        return Err(FMLError::CodeGenError(format!(
+            "Object {} needs at least one field",
+            obj_def.name()
+        )));
+    }
+    let name = class_name(obj_def.name());
+    write_doc(out, 0, obj_def.doc());
+    out.push_str(&format!("data class {}(\n", name));
+    let fields = obj_def
+        .props()
+        .iter()
+        .map(|prop| {
+            let mut field = String::new();
+            write_doc(&mut field, 1, &prop.doc);
+            field.push_str(&format!(
+                "    val {}: {} = {}",
+                var_name(&prop.name),
+                type_label(fm, &prop.typ)?,
+                literal(fm, &Kotlin, &prop.typ, &prop.default)?
+            ));
+            Ok(field)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    out.push_str(&fields.join(",\n"));
+    out.push_str("\n) {\n");
+    out.push_str("    companion object {\n");
+    out.push_str(&format!(
+        "        internal fun create(variables: Variables, default: {name} = {name}()) = {name}(\n",
+        name = name
+    ));
+    let fields = obj_def
+        .props()
+        .iter()
+        .map(|prop| {
+            let default = format!("default.{}", var_name(&prop.name));
+            Ok(format!(
+                "            {} = {}",
+                var_name(&prop.name),
+                value_expr(fm, prop, &default)?
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    out.push_str(&fields.join(",\n"));
+    out.push_str("\n        )\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    Ok(())
+}
+
+fn write_enum(out: &mut String, enum_def: &EnumDef) {
+    write_doc(out, 0, &enum_def.doc);
+    out.push_str(&format!(
+        "enum class {}(val value: String) {{\n",
+        class_name(&enum_def.name)
+    ));
+    let variants = enum_def
+        .variants
+        .iter()
+        .map(|variant| {
+            let mut out = String::new();
+            write_doc(&mut out, 1, variant.doc());
+            out.push_str(&format!(
+                "    {}({})",
+                variant_name(variant.name()),
+                string_literal(variant.name())
+            ));
+            out
+        })
+        .collect::<Vec<_>>();
+    out.push_str(&variants.join(",\n"));
+    out.push_str(";\n\n");
+    out.push_str("    companion object {\n");
+    out.push_str("        fun enumValue(value: String) = values().find { it.value == value }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+}
+
+/// An expression for the value of `prop`, falling back to `default` for
+/// whatever the variables don't set.
+fn value_expr(fm: &FeatureManifest, prop: &PropDef, default: &str) -> Result<String> {
+    let getter = getter(fm, &prop.typ, &string_literal(&prop.name), default)?;
+    Ok(match prop.typ {
+        // Maps from the variables are merged into the default.
+        TypeRef::StringMap(_) | TypeRef::EnumMap(_, _) => {
+            format!("{} + ({} ?: emptyMap())", default, getter)
+        }
+        _ => format!("{} ?: {}", getter, default),
+    })
+}
+
+/// A nullable expression for the value of `key` in the variables.
+fn getter(fm: &FeatureManifest, typ: &TypeRef, key: &str, default: &str) -> Result<String> {
+    Ok(match typ {
+        TypeRef::String => format!("variables.getString({})", key),
+        TypeRef::Int => format!("variables.getInt({})", key),
+        TypeRef::Boolean => format!("variables.getBool({})", key),
+        TypeRef::Enum(name) => format!(
+            "variables.getString({})?.let({}::enumValue)",
+            key,
+            enum_type(fm, name)?
+        ),
+        TypeRef::Object(name) => format!(
+            "variables.getVariables({})?.let {{ {}.create(it, {}) }}",
+            key,
+            object_type(fm, name)?,
+            default
+        ),
+        TypeRef::Option(inner) => match inner.as_ref() {
+            TypeRef::Object(name) => {
+                let name = object_type(fm, name)?;
+                format!(
+                    "variables.getVariables({})?.let {{ {}.create(it, {} ?: {}()) }}",
+                    key, name, default, name
+                )
+            }
+            _ => getter(fm, inner, key, default)?,
+        },
+        TypeRef::List(item_type) => match item_type.as_ref() {
+            TypeRef::String => format!("variables.getStringList({})", key),
+            TypeRef::Int => format!("variables.getIntList({})", key),
+            TypeRef::Boolean => format!("variables.getBoolList({})", key),
+            TypeRef::Enum(name) => format!(
+                "variables.getStringList({})?.mapNotNull({}::enumValue)",
+                key,
+                enum_type(fm, name)?
+            ),
+            TypeRef::Object(name) => format!(
+                "variables.getVariablesList({})?.map {{ {}.create(it) }}",
+                key,
+                object_type(fm, name)?
+            ),
+            _ => return Err(unsupported(typ)),
+        },
+        TypeRef::StringMap(value_type) => map_getter(fm, typ, value_type, key)?,
+        TypeRef::EnumMap(key_type, value_type) => match key_type.as_ref() {
+            TypeRef::Enum(name) => format!(
+                "{}?.mapKeysNotNull({}::enumValue)",
+                map_getter(fm, typ, value_type, key)?,
+                enum_type(fm, name)?
+            ),
+            _ => return Err(unsupported(typ)),
+        },
+        TypeRef::BundleText(_) | TypeRef::BundleImage(_) => return Err(unsupported(typ)),
+    })
+}
+
+fn map_getter(
+    fm: &FeatureManifest,
+    typ: &TypeRef,
+    value_type: &TypeRef,
+    key: &str,
+) -> Result<String> {
+    Ok(match value_type {
+        TypeRef::String => format!("variables.getStringMap({})", key),
+        TypeRef::Int => format!("variables.getIntMap({})", key),
+        TypeRef::Boolean => format!("variables.getBoolMap({})", key),
+        TypeRef::Enum(name) => format!(
+            "variables.getStringMap({})?.mapValuesNotNull({}::enumValue)",
+            key,
+            enum_type(fm, name)?
+        ),
+        TypeRef::Object(name) => format!(
+            "variables.getVariablesMap({})?.mapValues {{ {}.create(it.value) }}",
+            key,
+            object_type(fm, name)?
+        ),
+        _ => return Err(unsupported(typ)),
+    })
+}
+
+fn type_label(fm: &FeatureManifest, typ: &TypeRef) -> Result<String> {
+    Ok(match typ {
+        TypeRef::String => "String".to_string(),
+        TypeRef::Int => "Int".to_string(),
+        TypeRef::Boolean => "Boolean".to_string(),
+        TypeRef::Enum(name) => enum_type(fm, name)?,
+        TypeRef::Object(name) => object_type(fm, name)?,
+        TypeRef::StringMap(value_type) => format!("Map<String, {}>", type_label(fm, value_type)?),
+        TypeRef::EnumMap(key_type, value_type) => format!(
+            "Map<{}, {}>",
+            type_label(fm, key_type)?,
+            type_label(fm, value_type)?
+        ),
+        TypeRef::List(item_type) => format!("List<{}>", type_label(fm, item_type)?),
+        TypeRef::Option(inner) => format!("{}?", type_label(fm, inner)?),
+        TypeRef::BundleText(_) | TypeRef::BundleImage(_) => return Err(unsupported(typ)),
+    })
+}
+
+fn enum_type(fm: &FeatureManifest, name: &str) -> Result<String> {
+    Ok(class_name(&find_enum(fm, name)?.name))
+}
+
+fn object_type(fm: &FeatureManifest, name: &str) -> Result<String> {
+    Ok(class_name(find_object(fm, name)?.name()))
+}
+
+struct Kotlin;
+
+impl LiteralSyntax for Kotlin {
+    fn null(&self) -> String {
+        "null".to_string()
+    }
+
+    fn string(&self, value: &str) -> String {
+        string_literal(value)
+    }
+
+    fn enum_variant(&self, enum_def: &EnumDef, variant: &str) -> String {
+        format!("{}.{}", class_name(&enum_def.name), variant_name(variant))
+    }
+
+    fn list(&self, items: Vec<String>) -> String {
+        format!("listOf({})", items.join(", "))
+    }
+
+    fn map(&self, entries: Vec<(String, String)>) -> String {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| format!("{} to {}", key, value))
+            .collect::<Vec<_>>();
+        format!("mapOf({})", entries.join(", "))
+    }
+
+    fn object(&self, obj_def: &ObjectDef, fields: Vec<(String, String)>) -> String {
+        let fields = fields
+            .into_iter()
+            .map(|(name, value)| format!("{} = {}", var_name(&name), value))
+            .collect::<Vec<_>>();
+        format!("{}({})", class_name(obj_def.name()), fields.join(", "))
+    }
+}
+
+fn string_literal(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '$' => out.push_str("\\$"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn write_doc(out: &mut String, indent: usize, doc: &str) {
+    let indent = "    ".repeat(indent);
+    out.push_str(&format!("{}/**\n", indent));
+    for line in doc_lines(doc) {
+        // Don't let the doc end the comment early.
+        let line = line.replace("*/", "*&#47;");
+        if line.is_empty() {
+            out.push_str(&format!("{} *\n", indent));
+        } else {
+            out.push_str(&format!("{} * {}\n", indent, line));
+        }
+    }
+    out.push_str(&format!("{} */\n", indent));
+}
+
+const KEYWORDS: &[&str] = &[
+    "as",
+    "break",
+    "class",
+    "continue",
+    "do",
+    "else",
+    "false",
+    "for",
+    "fun",
+    "if",
+    "in",
+    "interface",
+    "is",
+    "null",
+    "object",
+    "package",
+    "return",
+    "super",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typealias",
+    "typeof",
+    "val",
+    "var",
+    "when",
+    "while",
+];
+
+fn class_name(name: &str) -> String {
+    name.to_camel_case()
+}
+
+fn var_name(name: &str) -> String {
+    let name = name.to_mixed_case();
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("`{}`", name)
+    } else {
+        name
+    }
+}
+
+fn variant_name(name: &str) -> String {
+    name.to_shouty_snake_case()
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::fixtures::intermediate_representation::{
+        get_simple_homescreen_feature, get_simple_nimbus_validation_feature,
+    };
+    use crate::intermediate_representation::VariantDef;
+    use serde_json::json;
+
+    #[test]
+    fn test_simple_nimbus_validation() -> Result<()> {
+        let options = CodeGenOptions {
+            package_name: Some("org.mozilla.example".into()),
+            ..Default::default()
+        };
+        let code = generate_struct(&get_simple_nimbus_validation_feature(), &options)?;
+        assert!(code.contains("package org.mozilla.example\n"));
+        assert!(code.contains(
+            "    fun nimbusValidation(recordExposureEvent: Boolean = true) =\n        NimbusValidation(nimbus.getVariables(\"nimbus-validation\", recordExposureEvent))\n"
+        ));
+        assert!(code.contains(
+            "\
+    /**
+     * An example integer property
+     */
+    val rowCount: Int
+        get() {
+            val defaultValue: Int = 2
+            return variables.getInt(\"row-count\") ?: defaultValue
+        }
+"
+        ));
+        assert!(code.contains(
+            "            val defaultValue: String = \"deeplink://settings\"\n            return variables.getString(\"deeplink\") ?: defaultValue\n"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_enum_map_and_enum() -> Result<()> {
+        let code = generate_struct(&get_simple_homescreen_feature(), &Default::default())?;
+        assert!(code.contains(
+            "            val defaultValue: Map<SectionId, Boolean> = mapOf(SectionId.JUMP_BACK_IN to false, SectionId.RECENTLY_SAVED to false, SectionId.TOP_SITES to true)\n"
+        ));
+        assert!(code.contains(
+            "            return defaultValue + (variables.getBoolMap(\"sections-enabled\")?.mapKeysNotNull(SectionId::enumValue) ?: emptyMap())\n"
+        ));
+        assert!(code.contains(
+            "\
+enum class SectionId(val value: String) {
+    /**
+     * The original frecency sorted sites
+     */
+    TOP_SITES(\"top-sites\"),
+"
+        ));
+        assert!(code.contains("    RECENTLY_SAVED(\"recently-saved\");\n"));
+        Ok(())
+    }
+
+    fn manifest_with_objects() -> FeatureManifest {
+        let mut fm = get_simple_homescreen_feature();
+        fm.obj_defs = vec![ObjectDef::new(
+            "section",
+            "A section of the homescreen",
+            vec![
+                PropDef {
+                    name: "title".into(),
+                    doc: "The title of the section".into(),
+                    typ: TypeRef::String,
+                    default: json!("Untitled"),
+                },
+                PropDef {
+                    name: "id".into(),
+                    doc: "The id of the section".into(),
+                    typ: TypeRef::Enum("SectionId".into()),
+                    default: json!("top-sites"),
+                },
+            ],
+        )];
+        fm.feature_defs = vec![FeatureDef::new(
+            "homescreen",
+            "Represents the homescreen feature",
+            vec![
+                PropDef {
+                    name: "sections".into(),
+                    doc: "The sections".into(),
+                    typ: TypeRef::List(Box::new(TypeRef::Object("section".into()))),
+                    default: json!([{"title": "Top sites"}]),
+                },
+                PropDef {
+                    name: "hero".into(),
+                    doc: "The biggest section".into(),
+                    typ: TypeRef::Option(Box::new(TypeRef::Object("section".into()))),
+                    default: json!(null),
+                },
+                PropDef {
+                    name: "order".into(),
+                    doc: "The order of the sections".into(),
+                    typ: TypeRef::StringMap(Box::new(TypeRef::Enum("SectionId".into()))),
+                    default: json!({}),
+                },
+            ],
+            None,
+        )];
+        fm
+    }
+
+    #[test]
+    fn test_objects() -> Result<()> {
+        let code = generate_struct(&manifest_with_objects(), &Default::default())?;
+        assert!(code.contains(
+            "\
+data class Section(
+    /**
+     * The title of the section
+     */
+    val title: String = \"Untitled\",
+    /**
+     * The id of the section
+     */
+    val id: SectionId = SectionId.TOP_SITES
+) {
+    companion object {
+        internal fun create(variables: Variables, default: Section = Section()) = Section(
+            title = variables.getString(\"title\") ?: default.title,
+            id = variables.getString(\"id\")?.let(SectionId::enumValue) ?: default.id
+        )
+    }
+}
+"
+        ));
+        assert!(code.contains(
+            "            val defaultValue: List<Section> = listOf(Section(title = \"Top sites\"))\n            return variables.getVariablesList(\"sections\")?.map { Section.create(it) } ?: defaultValue\n"
+        ));
+        assert!(code.contains(
+            "            val defaultValue: Section? = null\n            return variables.getVariables(\"hero\")?.let { Section.create(it, defaultValue ?: Section()) } ?: defaultValue\n"
+        ));
+        assert!(code.contains(
+            "            return defaultValue + (variables.getStringMap(\"order\")?.mapValuesNotNull(SectionId::enumValue) ?: emptyMap())\n"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_defaults() {
+        let mut fm = get_simple_homescreen_feature();
+        fm.enum_defs[0].variants = vec![VariantDef::new("top-sites", "Top sites")];
+        generate_struct(&fm, &Default::default())
+            .expect_err("Should fail since the default map has a key which isn't a variant");
+
+        let with_sections_default = |default| {
+            let mut fm = manifest_with_objects();
+            fm.feature_defs = vec![FeatureDef::new(
+                "homescreen",
+                "Represents the homescreen feature",
+                vec![PropDef {
+                    name: "sections".into(),
+                    doc: "The sections".into(),
+                    typ: TypeRef::List(Box::new(TypeRef::Object("section".into()))),
+                    default,
+                }],
+                None,
+            )];
+            fm
+        };
+        generate_struct(
+            &with_sections_default(json!([{"titel": "Top sites"}])),
+            &Default::default(),
+        )
+        .expect_err("Should fail since the default object has an unknown field");
+        generate_struct(
+            &with_sections_default(json!([{"title": 1}])),
+            &Default::default(),
+        )
+        .expect_err("Should fail since the title isn't a string");
+        generate_struct(
+            &with_sections_default(json!({"title": "Top sites"})),
+            &Default::default(),
+        )
+        .expect_err("Should fail since the default isn't a list");
+    }
+
+    #[test]
+    fn test_nested_lists_are_unsupported() {
+        let mut fm = get_simple_homescreen_feature();
+        fm.feature_defs = vec![FeatureDef::new(
+            "grid",
+            "A grid",
+            vec![PropDef {
+                name: "cells".into(),
+                doc: "The cells".into(),
+                typ: TypeRef::List(Box::new(TypeRef::List(Box::new(TypeRef::Int)))),
+                default: json!([[1]]),
+            }],
+            None,
+        )];
+        assert!(matches!(
+            generate_struct(&fm, &Default::default()),
+            Err(FMLError::CodeGenError(_))
+        ));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(class_name("nimbus-validation"), "NimbusValidation");
+        assert_eq!(var_name("sections-enabled"), "sectionsEnabled");
+        assert_eq!(var_name("object"), "`object`");
+        assert_eq!(variant_name("jump-back-in"), "JUMP_BACK_IN");
+        assert_eq!(string_literal("$5 \"off\""), "\"\\$5 \\\"off\\\"\"");
+    }
+}
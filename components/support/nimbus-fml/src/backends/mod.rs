@@ -0,0 +1,171 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Code generators which turn a `FeatureManifest` into typed accessors for
+//! the features it describes, so that apps don't have to pick apart the JSON
+//! of each feature's variables by hand.
+//!
+//! Each accessor reads the feature's variables from Nimbus, and falls back to
+//! the defaults from the manifest for anything the variables don't set.
+
+use crate::error::{FMLError, Result};
+use crate::intermediate_representation::{EnumDef, FeatureManifest, Literal, ObjectDef, TypeRef};
+
+pub mod kotlin;
+pub mod swift;
+
+#[derive(Debug, Clone)]
+pub struct CodeGenOptions {
+    /// The name of the generated class the features are accessed through.
+    pub class_name: String,
+    /// The package of the generated Kotlin file.
+    pub package_name: Option<String>,
+}
+
+impl Default for CodeGenOptions {
+    fn default() -> Self {
+        Self {
+            class_name: "NimbusFeatures".to_string(),
+            package_name: None,
+        }
+    }
+}
+
+/// How a language writes the literals of the manifest's defaults.
+pub(crate) trait LiteralSyntax {
+    fn null(&self) -> String;
+    fn string(&self, value: &str) -> String;
+    fn enum_variant(&self, enum_def: &EnumDef, variant: &str) -> String;
+    fn list(&self, items: Vec<String>) -> String;
+    fn map(&self, entries: Vec<(String, String)>) -> String;
+    /// `fields` are the fields of the object which the literal sets, with
+    /// their values.
+    fn object(&self, obj_def: &ObjectDef, fields: Vec<(String, String)>) -> String;
+}
+
+/// Write `value` as a literal of type `typ`, failing if it isn't one.
+pub(crate) fn literal(
+    fm: &FeatureManifest,
+    syntax: &dyn LiteralSyntax,
+    typ: &TypeRef,
+    value: &Literal,
+) -> Result<String> {
+    let mismatch =
+        || FMLError::ValidationError(format!("Default {} isn't a valid {:?}", value, typ));
+    Ok(match typ {
+        TypeRef::String => syntax.string(value.as_str().ok_or_else(mismatch)?),
+        TypeRef::Int => value.as_i64().ok_or_else(mismatch)?.to_string(),
+        TypeRef::Boolean => value.as_bool().ok_or_else(mismatch)?.to_string(),
+        TypeRef::Enum(name) => {
+            let enum_def = find_enum(fm, name)?;
+            let variant = value.as_str().ok_or_else(mismatch)?;
+            check_variant(enum_def, variant)?;
+            syntax.enum_variant(enum_def, variant)
+        }
+        TypeRef::Object(name) => {
+            let obj_def = find_object(fm, name)?;
+            let values = value.as_object().ok_or_else(mismatch)?;
+            if let Some(key) = values
+                .keys()
+                .find(|key| !obj_def.props().iter().any(|prop| &prop.name == *key))
+            {
+                return Err(FMLError::ValidationError(format!(
+                    "{} isn't a field of {}",
+                    key, name
+                )));
+            }
+            let fields = obj_def
+                .props()
+                .iter()
+                .filter_map(|prop| {
+                    values.get(&prop.name).map(|value| {
+                        Ok((prop.name.clone(), literal(fm, syntax, &prop.typ, value)?))
+                    })
+                })
+                .collect::<Result<_>>()?;
+            syntax.object(obj_def, fields)
+        }
+        TypeRef::List(item_type) => syntax.list(
+            value
+                .as_array()
+                .ok_or_else(mismatch)?
+                .iter()
+                .map(|item| literal(fm, syntax, item_type, item))
+                .collect::<Result<_>>()?,
+        ),
+        TypeRef::StringMap(value_type) => syntax.map(
+            value
+                .as_object()
+                .ok_or_else(mismatch)?
+                .iter()
+                .map(|(key, value)| {
+                    Ok((syntax.string(key), literal(fm, syntax, value_type, value)?))
+                })
+                .collect::<Result<_>>()?,
+        ),
+        TypeRef::EnumMap(key_type, value_type) => syntax.map(
+            value
+                .as_object()
+                .ok_or_else(mismatch)?
+                .iter()
+                .map(|(key, value)| {
+                    Ok((
+                        literal(fm, syntax, key_type, &Literal::String(key.clone()))?,
+                        literal(fm, syntax, value_type, value)?,
+                    ))
+                })
+                .collect::<Result<_>>()?,
+        ),
+        TypeRef::Option(inner) => {
+            if value.is_null() {
+                syntax.null()
+            } else {
+                literal(fm, syntax, inner, value)?
+            }
+        }
+        TypeRef::BundleText(_) | TypeRef::BundleImage(_) => return Err(unsupported(typ)),
+    })
+}
+
+pub(crate) fn find_enum<'a>(fm: &'a FeatureManifest, name: &str) -> Result<&'a EnumDef> {
+    fm.enum_defs
+        .iter()
+        .find(|enum_def| enum_def.name == name)
+        .ok_or_else(|| {
+            FMLError::ValidationError(format!("Found enum reference {}, but no definition", name))
+        })
+}
+
+pub(crate) fn find_object<'a>(fm: &'a FeatureManifest, name: &str) -> Result<&'a ObjectDef> {
+    fm.obj_defs
+        .iter()
+        .find(|obj_def| obj_def.name() == name)
+        .ok_or_else(|| {
+            FMLError::ValidationError(format!(
+                "Found object reference {}, but no definition",
+                name
+            ))
+        })
+}
+
+fn check_variant(enum_def: &EnumDef, variant: &str) -> Result<()> {
+    if enum_def.variants.iter().any(|v| v.name() == variant) {
+        Ok(())
+    } else {
+        Err(FMLError::ValidationError(format!(
+            "{} isn't a variant of {}",
+            variant, enum_def.name
+        )))
+    }
+}
+
+/// Types which the `Variables` interfaces of the SDKs can't read.
+pub(crate) fn unsupported(typ: &TypeRef) -> FMLError {
+    FMLError::CodeGenError(format!("Can't generate code for {:?} yet", typ))
+}
+
+/// Split a doc string into the lines of a doc comment.
+pub(crate) fn doc_lines(doc: &str) -> impl Iterator<Item = &str> {
+    doc.trim().lines().map(str::trim_end)
+}
@@ -0,0 +1,611 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Generates Swift accessors for the features of a manifest, which read the
+//! feature variables through `NimbusFeatureConfiguration.getVariables`.
+
+use super::{
+    doc_lines, find_enum, find_object, literal, unsupported, CodeGenOptions, LiteralSyntax,
+};
+use crate::error::Result;
+use crate::intermediate_representation::{
+    EnumDef, FeatureDef, FeatureManifest, ObjectDef, PropDef, TypeRef,
+};
+use heck::{CamelCase, MixedCase};
+
+pub fn generate_struct(fm: &FeatureManifest, options: &CodeGenOptions) -> Result<String> {
+    let mut out = String::new();
+    out.push_str("// This file was autogenerated by nimbus-fml. Do not edit.\n\n");
+    out.push_str("import Foundation\n");
+    out.push_str("import MozillaAppServices\n");
+
+    out.push('\n');
+    write_doc(
+        &mut out,
+        0,
+        "The features of the app which can be configured by Nimbus.",
+    );
+    out.push_str(&format!("public class {} {{\n", options.class_name));
+    out.push_str("    private let api: NimbusFeatureConfiguration\n\n");
+    out.push_str("    public init(api: NimbusFeatureConfiguration) {\n");
+    out.push_str("        self.api = api\n");
+    out.push_str("    }\n");
+    for feature_def in &fm.feature_defs {
+        out.push('\n');
+        write_doc(&mut out, 1, feature_def.doc());
+        let name = class_name(feature_def.name());
+        out.push_str(&format!(
+            "    public func {}(sendExposureEvent: Bool = true) -> {} {{\n",
+            var_name(feature_def.name()),
+            name
+        ));
+        out.push_str(&format!(
+            "        return {}(api.getVariables(featureId: {}, sendExposureEvent: sendExposureEvent))\n",
+            name,
+            string_literal(feature_def.name())
+        ));
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+
+    for feature_def in &fm.feature_defs {
+        out.push('\n');
+        write_feature(&mut out, fm, feature_def)?;
+    }
+    for obj_def in &fm.obj_defs {
+        out.push('\n');
+        write_object(&mut out, fm, obj_def)?;
+    }
+    for enum_def in &fm.enum_defs {
+        out.push('\n');
+        write_enum(&mut out, enum_def);
+    }
+    Ok(out)
+}
+
+fn write_feature(out: &mut String, fm: &FeatureManifest, feature_def: &FeatureDef) -> Result<()> {
+    write_doc(out, 0, feature_def.doc());
+    out.push_str(&format!(
+        "public class {} {{\n",
+        class_name(feature_def.name())
+    ));
+    out.push_str("    private let variables: Variables\n\n");
+    out.push_str("    init(_ variables: Variables) {\n");
+    out.push_str("        self.variables = variables\n");
+    out.push_str("    }\n");
+    for prop in feature_def.props() {
+        let typ = type_label(fm, &prop.typ)?;
+        out.push('\n');
+        write_doc(out, 1, &prop.doc);
+        out.push_str(&format!(
+            "    public var {}: {} {{\n",
+            var_name(&prop.name),
+            typ
+        ));
+        out.push_str(&format!(
+            "        let defaultValue: {} = {}\n",
+            typ,
+            literal(fm, &Swift, &prop.typ, &prop.default)?
+        ));
+        out.push_str(&format!(
+            "        return {}\n",
+            value_expr(fm, prop, "defaultValue")?
+        ));
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+    Ok(())
+}
+
+fn write_object(out: &mut String, fm: &FeatureManifest, obj_def: &ObjectDef) -> Result<()> {
+    let name = class_name(obj_def.name());
+    write_doc(out, 0, obj_def.doc());
+    out.push_str(&format!("public struct {} {{\n", name));
+    for prop in obj_def.props() {
+        write_doc(out, 1, &prop.doc);
+        out.push_str(&format!(
+            "    public let {}: {}\n",
+            var_name(&prop.name),
+            type_label(fm, &prop.typ)?
+        ));
+    }
+
+    let params = obj_def
+        .props()
+        .iter()
+        .map(|prop| {
+            Ok(format!(
+                "{}: {} = {}",
+                param_name(&prop.name),
+                type_label(fm, &prop.typ)?,
+                literal(fm, &Swift, &prop.typ, &prop.default)?
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    out.push('\n');
+    out.push_str(&format!("    public init({}) {{\n", params.join(", ")));
+    for prop in obj_def.props() {
+        out.push_str(&format!(
+            "        self.{} = {}\n",
+            param_name(&prop.name),
+            var_name(&prop.name)
+        ));
+    }
+    out.push_str("    }\n\n");
+
+    out.push_str(&format!(
+        "    init(_ variables: Variables, _ defaults: {name} = {name}()) {{\n",
+        name = name
+    ));
+    for prop in obj_def.props() {
+        let default = format!("defaults.{}", param_name(&prop.name));
+        out.push_str(&format!(
+            "        self.{} = {}\n",
+            param_name(&prop.name),
+            value_expr(fm, prop, &default)?
+        ));
+    }
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    Ok(())
+}
+
+fn write_enum(out: &mut String, enum_def: &EnumDef) {
+    write_doc(out, 0, &enum_def.doc);
+    out.push_str(&format!(
+        "public enum {}: String, CaseIterable {{\n",
+        class_name(&enum_def.name)
+    ));
+    for variant in &enum_def.variants {
+        write_doc(out, 1, variant.doc());
+        out.push_str(&format!(
+            "    case {} = {}\n",
+            var_name(variant.name()),
+            string_literal(variant.name())
+        ));
+    }
+    out.push_str("}\n");
+}
+
+/// An expression for the value of `prop`, falling back to `default` for
+/// whatever the variables don't set.
+fn value_expr(fm: &FeatureManifest, prop: &PropDef, default: &str) -> Result<String> {
+    let getter = getter(fm, &prop.typ, &string_literal(&prop.name), default)?;
+    Ok(match prop.typ {
+        // Maps from the variables are merged into the default.
+        TypeRef::StringMap(_) | TypeRef::EnumMap(_, _) => format!(
+            "{}.merging({} ?? [:], uniquingKeysWith: {{ _, override in override }})",
+            default, getter
+        ),
+        _ => format!("{} ?? {}", getter, default),
+    })
+}
+
+/// An optional expression for the value of `key` in the variables.
+fn getter(fm: &FeatureManifest, typ: &TypeRef, key: &str, default: &str) -> Result<String> {
+    Ok(match typ {
+        TypeRef::String => format!("variables.getString({})", key),
+        TypeRef::Int => format!("variables.getInt({})", key),
+        TypeRef::Boolean => format!("variables.getBool({})", key),
+        TypeRef::Enum(name) => format!(
+            "variables.getString({}).flatMap({}.init(rawValue:))",
+            key,
+            enum_type(fm, name)?
+        ),
+        TypeRef::Object(name) => format!(
+            "variables.getVariables({}).map {{ {}($0, {}) }}",
+            key,
+            object_type(fm, name)?,
+            default
+        ),
+        TypeRef::Option(inner) => match inner.as_ref() {
+            TypeRef::Object(name) => {
+                let name = object_type(fm, name)?;
+                format!(
+                    "variables.getVariables({}).map {{ {}($0, {} ?? {}()) }}",
+                    key, name, default, name
+                )
+            }
+            _ => getter(fm, inner, key, default)?,
+        },
+        TypeRef::List(item_type) => match item_type.as_ref() {
+            TypeRef::String => format!("variables.getStringList({})", key),
+            TypeRef::Int => format!("variables.getIntList({})", key),
+            TypeRef::Boolean => format!("variables.getBoolList({})", key),
+            TypeRef::Enum(name) => format!(
+                "variables.getStringList({})?.compactMap({}.init(rawValue:))",
+                key,
+                enum_type(fm, name)?
+            ),
+            TypeRef::Object(name) => format!(
+                "variables.getVariablesList({})?.map {{ {}($0) }}",
+                key,
+                object_type(fm, name)?
+            ),
+            _ => return Err(unsupported(typ)),
+        },
+        TypeRef::StringMap(value_type) => map_getter(fm, typ, value_type, key)?,
+        TypeRef::EnumMap(key_type, value_type) => match key_type.as_ref() {
+            TypeRef::Enum(name) => {
+                // Mapping the values already unwrapped the optional map, so
+                // the rest of the chain applies to the map itself.
+                let chain = match value_type.as_ref() {
+                    TypeRef::Enum(_) | TypeRef::Object(_) => ".",
+                    _ => "?.",
+                };
+                format!(
+                    "{}{}compactMapKeys({}.init(rawValue:))",
+                    map_getter(fm, typ, value_type, key)?,
+                    chain,
+                    enum_type(fm, name)?
+                )
+            }
+            _ => return Err(unsupported(typ)),
+        },
+        TypeRef::BundleText(_) | TypeRef::BundleImage(_) => return Err(unsupported(typ)),
+    })
+}
+
+fn map_getter(
+    fm: &FeatureManifest,
+    typ: &TypeRef,
+    value_type: &TypeRef,
+    key: &str,
+) -> Result<String> {
+    Ok(match value_type {
+        TypeRef::String => format!("variables.getStringMap({})", key),
+        TypeRef::Int => format!("variables.getIntMap({})", key),
+        TypeRef::Boolean => format!("variables.getBoolMap({})", key),
+        TypeRef::Enum(name) => format!(
+            "variables.getStringMap({})?.compactMapValues({}.init(rawValue:))",
+            key,
+            enum_type(fm, name)?
+        ),
+        TypeRef::Object(name) => format!(
+            "variables.getVariablesMap({})?.mapValues {{ {}($0) }}",
+            key,
+            object_type(fm, name)?
+        ),
+        _ => return Err(unsupported(typ)),
+    })
+}
+
+fn type_label(fm: &FeatureManifest, typ: &TypeRef) -> Result<String> {
+    Ok(match typ {
+        TypeRef::String => "String".to_string(),
+        TypeRef::Int => "Int".to_string(),
+        TypeRef::Boolean => "Bool".to_string(),
+        TypeRef::Enum(name) => enum_type(fm, name)?,
+        TypeRef::Object(name) => object_type(fm, name)?,
+        TypeRef::StringMap(value_type) => format!("[String: {}]", type_label(fm, value_type)?),
+        TypeRef::EnumMap(key_type, value_type) => format!(
+            "[{}: {}]",
+            type_label(fm, key_type)?,
+            type_label(fm, value_type)?
+        ),
+        TypeRef::List(item_type) => format!("[{}]", type_label(fm, item_type)?),
+        TypeRef::Option(inner) => format!("{}?", type_label(fm, inner)?),
+        TypeRef::BundleText(_) | TypeRef::BundleImage(_) => return Err(unsupported(typ)),
+    })
+}
+
+fn enum_type(fm: &FeatureManifest, name: &str) -> Result<String> {
+    Ok(class_name(&find_enum(fm, name)?.name))
+}
+
+fn object_type(fm: &FeatureManifest, name: &str) -> Result<String> {
+    Ok(class_name(find_object(fm, name)?.name()))
+}
+
+struct Swift;
+
+impl LiteralSyntax for Swift {
+    fn null(&self) -> String {
+        "nil".to_string()
+    }
+
+    fn string(&self, value: &str) -> String {
+        string_literal(value)
+    }
+
+    fn enum_variant(&self, _enum_def: &EnumDef, variant: &str) -> String {
+        format!(".{}", var_name(variant))
+    }
+
+    fn list(&self, items: Vec<String>) -> String {
+        format!("[{}]", items.join(", "))
+    }
+
+    fn map(&self, entries: Vec<(String, String)>) -> String {
+        if entries.is_empty() {
+            return "[:]".to_string();
+        }
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect::<Vec<_>>();
+        format!("[{}]", entries.join(", "))
+    }
+
+    fn object(&self, obj_def: &ObjectDef, fields: Vec<(String, String)>) -> String {
+        let fields = fields
+            .into_iter()
+            .map(|(name, value)| format!("{}: {}", param_name(&name), value))
+            .collect::<Vec<_>>();
+        format!("{}({})", class_name(obj_def.name()), fields.join(", "))
+    }
+}
+
+fn string_literal(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn write_doc(out: &mut String, indent: usize, doc: &str) {
+    let indent = "    ".repeat(indent);
+    for line in doc_lines(doc) {
+        if line.is_empty() {
+            out.push_str(&format!("{}///\n", indent));
+        } else {
+            out.push_str(&format!("{}/// {}\n", indent, line));
+        }
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "associatedtype",
+    "class",
+    "deinit",
+    "enum",
+    "extension",
+    "fileprivate",
+    "func",
+    "import",
+    "init",
+    "inout",
+    "internal",
+    "let",
+    "open",
+    "operator",
+    "private",
+    "protocol",
+    "public",
+    "rethrows",
+    "static",
+    "struct",
+    "subscript",
+    "typealias",
+    "var",
+    "break",
+    "case",
+    "continue",
+    "default",
+    "defer",
+    "do",
+    "else",
+    "fallthrough",
+    "for",
+    "guard",
+    "if",
+    "in",
+    "repeat",
+    "return",
+    "switch",
+    "where",
+    "while",
+    "as",
+    "catch",
+    "false",
+    "is",
+    "nil",
+    "self",
+    "super",
+    "throw",
+    "throws",
+    "true",
+    "try",
+];
+
+fn class_name(name: &str) -> String {
+    name.to_camel_case()
+}
+
+/// The name of a property, function or enum case, escaped if it's a keyword.
+fn var_name(name: &str) -> String {
+    let name = name.to_mixed_case();
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("`{}`", name)
+    } else {
+        name
+    }
+}
+
+/// The name of a property where it's used as an argument label or after a
+/// `.`, which don't need keywords escaping.
+fn param_name(name: &str) -> String {
+    name.to_mixed_case()
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::error::FMLError;
+    use crate::fixtures::intermediate_representation::{
+        get_simple_homescreen_feature, get_simple_nimbus_validation_feature,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_simple_nimbus_validation() -> Result<()> {
+        let code = generate_struct(&get_simple_nimbus_validation_feature(), &Default::default())?;
+        assert!(code.contains(
+            "\
+    /// A simple validation feature
+    public func nimbusValidation(sendExposureEvent: Bool = true) -> NimbusValidation {
+        return NimbusValidation(api.getVariables(featureId: \"nimbus-validation\", sendExposureEvent: sendExposureEvent))
+    }
+"
+        ));
+        assert!(code.contains(
+            "\
+    /// An example integer property
+    public var rowCount: Int {
+        let defaultValue: Int = 2
+        return variables.getInt(\"row-count\") ?? defaultValue
+    }
+"
+        ));
+        assert!(code.contains(
+            "        let defaultValue: Bool = true\n        return variables.getBool(\"enabled\") ?? defaultValue\n"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_enum_map_and_enum() -> Result<()> {
+        let code = generate_struct(&get_simple_homescreen_feature(), &Default::default())?;
+        assert!(code.contains(
+            "        let defaultValue: [SectionId: Bool] = [.jumpBackIn: false, .recentlySaved: false, .topSites: true]\n"
+        ));
+        assert!(code.contains(
+            "        return defaultValue.merging(variables.getBoolMap(\"sections-enabled\")?.compactMapKeys(SectionId.init(rawValue:)) ?? [:], uniquingKeysWith: { _, override in override })\n"
+        ));
+        assert!(code.contains(
+            "\
+public enum SectionId: String, CaseIterable {
+    /// The original frecency sorted sites
+    case topSites = \"top-sites\"
+"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_objects() -> Result<()> {
+        let mut fm = get_simple_homescreen_feature();
+        fm.obj_defs = vec![ObjectDef::new(
+            "section",
+            "A section of the homescreen",
+            vec![
+                PropDef {
+                    name: "title".into(),
+                    doc: "The title of the section".into(),
+                    typ: TypeRef::String,
+                    default: json!("Untitled"),
+                },
+                PropDef {
+                    name: "default".into(),
+                    doc: "Whether the section is shown by default".into(),
+                    typ: TypeRef::Boolean,
+                    default: json!(true),
+                },
+            ],
+        )];
+        fm.feature_defs = vec![FeatureDef::new(
+            "homescreen",
+            "Represents the homescreen feature",
+            vec![
+                PropDef {
+                    name: "sections".into(),
+                    doc: "The sections, by id".into(),
+                    typ: TypeRef::EnumMap(
+                        Box::new(TypeRef::Enum("SectionId".into())),
+                        Box::new(TypeRef::Object("section".into())),
+                    ),
+                    default: json!({"top-sites": {"title": "Top sites"}}),
+                },
+                PropDef {
+                    name: "hero".into(),
+                    doc: "The biggest section".into(),
+                    typ: TypeRef::Option(Box::new(TypeRef::Object("section".into()))),
+                    default: json!(null),
+                },
+            ],
+            None,
+        )];
+        let code = generate_struct(&fm, &Default::default())?;
+        assert!(code.contains(
+            "\
+public struct Section {
+    /// The title of the section
+    public let title: String
+    /// Whether the section is shown by default
+    public let `default`: Bool
+
+    public init(title: String = \"Untitled\", default: Bool = true) {
+        self.title = title
+        self.default = `default`
+    }
+
+    init(_ variables: Variables, _ defaults: Section = Section()) {
+        self.title = variables.getString(\"title\") ?? defaults.title
+        self.default = variables.getBool(\"default\") ?? defaults.default
+    }
+}
+"
+        ));
+        assert!(code.contains(
+            "        let defaultValue: [SectionId: Section] = [.topSites: Section(title: \"Top sites\")]\n        return defaultValue.merging(variables.getVariablesMap(\"sections\")?.mapValues { Section($0) }.compactMapKeys(SectionId.init(rawValue:)) ?? [:], uniquingKeysWith: { _, override in override })\n"
+        ));
+        assert!(code.contains(
+            "        let defaultValue: Section? = nil\n        return variables.getVariables(\"hero\").map { Section($0, defaultValue ?? Section()) } ?? defaultValue\n"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_enum_map_of_enums() -> Result<()> {
+        let mut fm = get_simple_homescreen_feature();
+        fm.feature_defs = vec![FeatureDef::new(
+            "homescreen",
+            "Represents the homescreen feature",
+            vec![PropDef {
+                name: "next-section".into(),
+                doc: "The section after each section".into(),
+                typ: TypeRef::EnumMap(
+                    Box::new(TypeRef::Enum("SectionId".into())),
+                    Box::new(TypeRef::Enum("SectionId".into())),
+                ),
+                default: json!({}),
+            }],
+            None,
+        )];
+        let code = generate_struct(&fm, &Default::default())?;
+        assert!(code.contains(
+            "variables.getStringMap(\"next-section\")?.compactMapValues(SectionId.init(rawValue:)).compactMapKeys(SectionId.init(rawValue:))"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_maps_are_unsupported() {
+        let mut fm = get_simple_homescreen_feature();
+        fm.feature_defs = vec![FeatureDef::new(
+            "grid",
+            "A grid",
+            vec![PropDef {
+                name: "cells".into(),
+                doc: "The cells".into(),
+                typ: TypeRef::StringMap(Box::new(TypeRef::List(Box::new(TypeRef::Int)))),
+                default: json!({}),
+            }],
+            None,
+        )];
+        assert!(matches!(
+            generate_struct(&fm, &Default::default()),
+            Err(FMLError::CodeGenError(_))
+        ));
+    }
+}
@@ -13,12 +13,19 @@ pub enum FMLError {
     IOError(#[from] std::io::Error),
     #[error("JSON Error: {0}")]
     JSONError(#[from] serde_json::Error),
+    #[cfg(feature = "cli")]
+    #[error("YAML Error: {0}")]
+    YAMLError(#[from] serde_yaml::Error),
+    #[error("Parse Error: {0}")]
+    ParseError(String),
     #[error("Invalid path: {0}")]
     InvalidPath(String),
     #[error("Internal error: {0}")]
     InternalError(&'static str),
     #[error("Validation Error: {0}")]
     ValidationError(String),
+    #[error("Code generation Error: {0}")]
+    CodeGenError(String),
 }
 
 pub type Result<T, E = FMLError> = std::result::Result<T, E>;
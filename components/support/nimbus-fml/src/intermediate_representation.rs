@@ -55,8 +55,7 @@ pub struct FeatureManifest {
 }
 
 impl FeatureManifest {
-    #[allow(dead_code)]
-    fn validate_manifest(&self) -> Result<()> {
+    pub fn validate_manifest(&self) -> Result<()> {
         // We first validate that each enum_def has a unique name.
         // TODO: We repeat this check three times, it should be its
         // own generic helper
@@ -189,13 +188,148 @@ impl FeatureManifest {
     }
 
     fn validate_defaults(&self) -> Result<()> {
-        // TODO: Validate that the defaults of each feature
-        // in the feature manifest agree with the props
-        // There must be a default for each property
-        // either that's defined in the property itself
-        // or in the feature definition
+        // Every prop has a default, so we only need to check that they
+        // agree with the types of the props.
+        for feature_def in &self.feature_defs {
+            for prop in &feature_def.props {
+                let path = format!("{}.{}", feature_def.name, prop.name);
+                self.validate_prop_value(&path, &prop.typ, &prop.default)?;
+            }
+            if let Some(default) = &feature_def.default {
+                self.validate_feature_config(&feature_def.name, default)?;
+            }
+        }
+        for obj_def in &self.obj_defs {
+            for prop in &obj_def.props {
+                let path = format!("{}.{}", obj_def.name, prop.name);
+                self.validate_prop_value(&path, &prop.typ, &prop.default)?;
+            }
+        }
         Ok(())
     }
+
+    pub fn get_feature(&self, feature_id: &str) -> Option<&FeatureDef> {
+        self.feature_defs.iter().find(|f| f.name == feature_id)
+    }
+
+    /// Check the `value` of a feature's config in an experiment branch. It
+    /// can leave variables out, in which case the app uses their defaults,
+    /// but the variables it sets must be variables of the feature, and have
+    /// values of the right type.
+    pub fn validate_feature_config(&self, feature_id: &str, value: &Value) -> Result<()> {
+        let feature_def = self
+            .get_feature(feature_id)
+            .ok_or_else(|| FMLError::ValidationError(format!("Unknown feature: {}", feature_id)))?;
+        let values = value.as_object().ok_or_else(|| {
+            FMLError::ValidationError(format!(
+                "{}: the feature value must be an object, found: {}",
+                feature_id, value
+            ))
+        })?;
+        for (name, value) in values {
+            let prop = feature_def
+                .props
+                .iter()
+                .find(|prop| &prop.name == name)
+                .ok_or_else(|| {
+                    FMLError::ValidationError(format!(
+                        "{}: {} isn't one of the feature's variables",
+                        feature_id, name
+                    ))
+                })?;
+            self.validate_prop_value(&format!("{}.{}", feature_id, name), &prop.typ, value)?;
+        }
+        Ok(())
+    }
+
+    /// Check that `value` is a valid `type_ref`. Objects are allowed to leave
+    /// fields out, since those fall back to the object's defaults.
+    fn validate_prop_value(&self, path: &str, type_ref: &TypeRef, value: &Value) -> Result<()> {
+        match (type_ref, value) {
+            (TypeRef::String, Value::String(_))
+            | (TypeRef::BundleText(_), Value::String(_))
+            | (TypeRef::BundleImage(_), Value::String(_))
+            | (TypeRef::Boolean, Value::Bool(_))
+            | (TypeRef::Option(_), Value::Null) => Ok(()),
+            (TypeRef::Int, Value::Number(n)) if n.is_i64() || n.is_u64() => Ok(()),
+            (TypeRef::Enum(name), Value::String(variant)) => {
+                self.validate_variant(path, name, variant)
+            }
+            (TypeRef::Object(name), Value::Object(fields)) => {
+                let obj_def = self
+                    .obj_defs
+                    .iter()
+                    .find(|obj_def| &obj_def.name == name)
+                    .ok_or_else(|| {
+                        FMLError::ValidationError(format!(
+                            "{}: found object reference with name: {}, but no definition",
+                            path, name
+                        ))
+                    })?;
+                for (field, value) in fields {
+                    let prop = obj_def
+                        .props
+                        .iter()
+                        .find(|prop| &prop.name == field)
+                        .ok_or_else(|| {
+                            FMLError::ValidationError(format!(
+                                "{}: {} isn't a field of {}",
+                                path, field, name
+                            ))
+                        })?;
+                    self.validate_prop_value(&format!("{}.{}", path, field), &prop.typ, value)?;
+                }
+                Ok(())
+            }
+            (TypeRef::List(item_type), Value::Array(items)) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.validate_prop_value(&format!("{}[{}]", path, i), item_type, item)?;
+                }
+                Ok(())
+            }
+            (TypeRef::StringMap(value_type), Value::Object(entries)) => {
+                for (key, value) in entries {
+                    self.validate_prop_value(&format!("{}.{}", path, key), value_type, value)?;
+                }
+                Ok(())
+            }
+            (TypeRef::EnumMap(key_type, value_type), Value::Object(entries)) => {
+                for (key, value) in entries {
+                    if let TypeRef::Enum(name) = key_type.as_ref() {
+                        self.validate_variant(path, name, key)?;
+                    }
+                    self.validate_prop_value(&format!("{}.{}", path, key), value_type, value)?;
+                }
+                Ok(())
+            }
+            (TypeRef::Option(inner), _) => self.validate_prop_value(path, inner, value),
+            _ => Err(FMLError::ValidationError(format!(
+                "{}: {} isn't a valid {:?}",
+                path, value, type_ref
+            ))),
+        }
+    }
+
+    fn validate_variant(&self, path: &str, enum_name: &str, variant: &str) -> Result<()> {
+        let enum_def = self
+            .enum_defs
+            .iter()
+            .find(|enum_def| enum_def.name == enum_name)
+            .ok_or_else(|| {
+                FMLError::ValidationError(format!(
+                    "{}: found enum reference with name: {}, but no definition",
+                    path, enum_name
+                ))
+            })?;
+        if enum_def.variants.iter().any(|v| v.name == variant) {
+            Ok(())
+        } else {
+            Err(FMLError::ValidationError(format!(
+                "{}: {} isn't a variant of {}",
+                path, variant, enum_name
+            )))
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -206,7 +340,6 @@ pub struct FeatureDef {
     default: Option<Literal>,
 }
 impl FeatureDef {
-    #[allow(dead_code)]
     pub fn new(name: &str, doc: &str, props: Vec<PropDef>, default: Option<Literal>) -> Self {
         Self {
             name: name.into(),
@@ -215,6 +348,18 @@ impl FeatureDef {
             default,
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn doc(&self) -> &str {
+        &self.doc
+    }
+
+    pub fn props(&self) -> &[PropDef] {
+        &self.props
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -237,13 +382,20 @@ pub struct VariantDef {
     doc: String,
 }
 impl VariantDef {
-    #[allow(dead_code)]
     pub fn new(name: &str, doc: &str) -> Self {
         Self {
             name: name.into(),
             doc: doc.into(),
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn doc(&self) -> &str {
+        &self.doc
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -253,7 +405,6 @@ pub struct ObjectDef {
     props: Vec<PropDef>,
 }
 impl ObjectDef {
-    #[allow(dead_code)]
     pub fn new(name: &str, doc: &str, props: Vec<PropDef>) -> Self {
         Self {
             name: name.into(),
@@ -261,6 +412,18 @@ impl ObjectDef {
             props,
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn doc(&self) -> &str {
+        &self.doc
+    }
+
+    pub fn props(&self) -> &[PropDef] {
+        &self.props
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -271,7 +434,7 @@ pub struct PropDef {
     pub default: Literal,
 }
 
-type Literal = Value;
+pub type Literal = Value;
 
 #[cfg(test)]
 mod unit_tests {
@@ -562,5 +725,128 @@ mod unit_tests {
             .expect_err("Should fail since we can't have nested optionals");
         Ok(())
     }
-    // TODO: Add more tests for defaults
+
+    #[test]
+    fn validate_defaults_fail_on_wrong_type() -> Result<()> {
+        let mut fm = get_simple_homescreen_feature();
+        fm.feature_defs.push(FeatureDef::new(
+            "some_def",
+            "test doc",
+            vec![PropDef {
+                name: "prop name".into(),
+                doc: "prop doc".into(),
+                typ: TypeRef::Int,
+                default: json!("two"),
+            }],
+            None,
+        ));
+        fm.validate_manifest()
+            .expect_err("Should fail since the default isn't an Int");
+        Ok(())
+    }
+
+    #[test]
+    fn validate_defaults_fail_on_unknown_variant() -> Result<()> {
+        let mut fm = get_simple_homescreen_feature();
+        fm.feature_defs.push(FeatureDef::new(
+            "some_def",
+            "test doc",
+            vec![PropDef {
+                name: "prop name".into(),
+                doc: "prop doc".into(),
+                typ: TypeRef::EnumMap(
+                    Box::new(TypeRef::Enum("SectionId".into())),
+                    Box::new(TypeRef::Boolean),
+                ),
+                default: json!({ "top-sitez": true }),
+            }],
+            None,
+        ));
+        fm.validate_manifest()
+            .expect_err("Should fail since top-sitez isn't a SectionId");
+        Ok(())
+    }
+
+    #[test]
+    fn validate_feature_config_values() -> Result<()> {
+        let mut fm = get_simple_homescreen_feature();
+        fm.obj_defs = vec![ObjectDef::new(
+            "Section",
+            "A section",
+            vec![
+                PropDef {
+                    name: "title".into(),
+                    doc: "The title".into(),
+                    typ: TypeRef::String,
+                    default: json!("Untitled"),
+                },
+                PropDef {
+                    name: "rows".into(),
+                    doc: "The number of rows".into(),
+                    typ: TypeRef::Option(Box::new(TypeRef::Int)),
+                    default: json!(null),
+                },
+            ],
+        )];
+        fm.feature_defs.push(FeatureDef::new(
+            "sections",
+            "The sections",
+            vec![PropDef {
+                name: "list".into(),
+                doc: "A list of sections".into(),
+                typ: TypeRef::List(Box::new(TypeRef::Object("Section".into()))),
+                default: json!([]),
+            }],
+            None,
+        ));
+        fm.validate_manifest()?;
+
+        fm.validate_feature_config("homescreen", &json!({}))?;
+        fm.validate_feature_config(
+            "homescreen",
+            &json!({ "sections-enabled": { "jump-back-in": true } }),
+        )?;
+        fm.validate_feature_config(
+            "sections",
+            &json!({ "list": [{ "title": "Top" }, { "rows": 2 }, { "rows": null }] }),
+        )?;
+
+        let error = |feature_id, value| {
+            fm.validate_feature_config(feature_id, &value)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("homescreen", json!({ "sections-enabled": { "jump-back-inn": true } })),
+            "Validation Error: homescreen.sections-enabled: jump-back-inn isn't a variant of SectionId"
+        );
+        assert_eq!(
+            error("homescreen", json!({ "sections-enabled": { "jump-back-in": "yes" } })),
+            "Validation Error: homescreen.sections-enabled.jump-back-in: \"yes\" isn't a valid Boolean"
+        );
+        assert_eq!(
+            error("homescreen", json!({ "section-enabled": {} })),
+            "Validation Error: homescreen: section-enabled isn't one of the feature's variables"
+        );
+        assert_eq!(
+            error(
+                "sections",
+                json!({ "list": [{ "title": "Top" }, { "rows": 2.5 }] })
+            ),
+            "Validation Error: sections.list[1].rows: 2.5 isn't a valid Int"
+        );
+        assert_eq!(
+            error("sections", json!({ "list": [{ "titel": "Top" }] })),
+            "Validation Error: sections.list[0]: titel isn't a field of Section"
+        );
+        assert_eq!(
+            error("sections", json!([])),
+            "Validation Error: sections: the feature value must be an object, found: []"
+        );
+        assert_eq!(
+            error("search", json!({})),
+            "Validation Error: Unknown feature: search"
+        );
+        Ok(())
+    }
 }
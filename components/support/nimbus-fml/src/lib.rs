@@ -2,9 +2,15 @@
 * License, v. 2.0. If a copy of the MPL was not distributed with this
 * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+// The parser and the code generators are only needed by the command line
+// tool, not by the Nimbus SDK, which only validates feature values.
+#[cfg(feature = "cli")]
+pub mod backends;
 pub mod error;
 pub mod intermediate_representation;
+#[cfg(feature = "cli")]
 pub mod parser;
+pub mod validator;
 
 #[cfg(test)]
 #[allow(dead_code)]
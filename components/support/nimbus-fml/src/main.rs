@@ -5,9 +5,11 @@
 #[macro_use]
 extern crate clap;
 use clap::{App, ArgMatches};
+use nimbus_fml::backends::{self, CodeGenOptions};
 use nimbus_fml::error::{FMLError, Result};
 use nimbus_fml::intermediate_representation::FeatureManifest;
 use nimbus_fml::parser::Parser;
+use nimbus_fml::validator::validate_experiments;
 use std::path::Path;
 use std::{fs::File, path::PathBuf};
 
@@ -16,29 +18,55 @@ fn main() -> Result<()> {
     let matches = App::from_yaml(yaml).get_matches();
     let cwd = std::env::current_dir()?;
     if let Some(cmd) = matches.subcommand_matches("struct") {
-        let manifest_file_path = file_path("INPUT", cmd, &cwd)?;
-
-        let ir = if !cmd.is_present("ir") {
-            let file = File::open(manifest_file_path)?;
-            let _parser: Parser = Parser::new(file);
-            unimplemented!("No parser is available")
-        } else {
-            let string = slurp_file(&manifest_file_path)?;
-            serde_json::from_str::<FeatureManifest>(&string)?
-        };
+        let ir = load_manifest(cmd, &cwd)?;
 
         let output_path = file_path("output", cmd, &cwd)?;
-        match cmd.value_of("language") {
-            Some("ir") => {
-                let contents = serde_json::to_string_pretty(&ir)?;
-                std::fs::write(output_path, contents)?;
-            }
-            _ => unimplemented!("Language not implemented yet"),
+        let mut options = CodeGenOptions {
+            package_name: cmd.value_of("package").map(str::to_string),
+            ..Default::default()
         };
+        if let Some(class_name) = cmd.value_of("class") {
+            options.class_name = class_name.to_string();
+        }
+        let contents = match cmd.value_of("language") {
+            Some("kotlin") => backends::kotlin::generate_struct(&ir, &options)?,
+            Some("swift") => backends::swift::generate_struct(&ir, &options)?,
+            _ => serde_json::to_string_pretty(&ir)?,
+        };
+        std::fs::write(output_path, contents)?;
+    }
+    if let Some(cmd) = matches.subcommand_matches("validate") {
+        let ir = load_manifest(cmd, &cwd)?;
+        let experiments_file_path = file_path("EXPERIMENTS", cmd, &cwd)?;
+        let experiments = serde_json::from_str(&slurp_file(&experiments_file_path)?)?;
+        let errors = validate_experiments(&ir, &experiments);
+        if !errors.is_empty() {
+            for error in &errors {
+                eprintln!("{}", error);
+            }
+            return Err(FMLError::ValidationError(format!(
+                "Found {} invalid feature values",
+                errors.len()
+            )));
+        }
     }
     Ok(())
 }
 
+fn load_manifest(cmd: &ArgMatches, cwd: &Path) -> Result<FeatureManifest> {
+    let manifest_file_path = file_path("INPUT", cmd, cwd)?;
+    if !cmd.is_present("ir") {
+        let file = File::open(manifest_file_path)?;
+        let parser = Parser::new(file)?;
+        parser.get_intermediate_representation(cmd.value_of("channel"))
+    } else {
+        let string = slurp_file(&manifest_file_path)?;
+        let ir = serde_json::from_str::<FeatureManifest>(&string)?;
+        ir.validate_manifest()?;
+        Ok(ir)
+    }
+}
+
 fn file_path(name: &str, args: &ArgMatches, cwd: &Path) -> Result<PathBuf> {
     let mut abs = cwd.to_path_buf();
     match args.value_of(name) {
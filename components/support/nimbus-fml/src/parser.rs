@@ -2,12 +2,744 @@
 * License, v. 2.0. If a copy of the MPL was not distributed with this
 * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+//! The parser for the YAML front end of the Feature Manifest Language.
+//!
+//! A manifest looks like:
+//!
+//! ```yaml
+//! channels:
+//!   - release
+//!   - nightly
+//! enums:
+//!   SectionId:
+//!     description: The sections of the homescreen
+//!     variants:
+//!       top-sites:
+//!         description: The original frecency sorted sites
+//! objects:
+//!   ...
+//! features:
+//!   homescreen:
+//!     description: Represents the homescreen feature
+//!     variables:
+//!       sections-enabled:
+//!         description: A map of booleans
+//!         type: Map<SectionId, Boolean>
+//!         default:
+//!           top-sites: true
+//!     defaults:
+//!       - channel: nightly
+//!         value:
+//!           sections-enabled:
+//!             top-sites: false
+//! ```
+//!
+//! The `defaults` of a feature are merged into the defaults of its variables
+//! for the channel the intermediate representation is generated for, so the
+//! IR is specific to a channel.
+
+use crate::error::{FMLError, Result};
+use crate::intermediate_representation::{
+    EnumDef, FeatureDef, FeatureManifest, ObjectDef, PropDef, TypeRef, VariantDef,
+};
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
+use std::io::Read;
+use std::marker::PhantomData;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser as YamlParser};
+use yaml_rust::scanner::Marker;
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFrontEnd {
+    #[serde(default)]
+    channels: Vec<String>,
+    #[serde(default)]
+    enums: OrderedMap<EnumBody>,
+    #[serde(default)]
+    objects: OrderedMap<ObjectBody>,
+    #[serde(default)]
+    features: OrderedMap<FeatureBody>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnumBody {
+    description: String,
+    variants: OrderedMap<VariantBody>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VariantBody {
+    description: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectBody {
+    description: String,
+    #[serde(default)]
+    fields: OrderedMap<FieldBody>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FeatureBody {
+    description: String,
+    #[serde(default)]
+    variables: OrderedMap<FieldBody>,
+    #[serde(default)]
+    defaults: Vec<DefaultBlock>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldBody {
+    description: String,
+    #[serde(rename = "type")]
+    typ: String,
+    #[serde(default)]
+    default: Option<Value>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DefaultBlock {
+    #[serde(default)]
+    channel: Option<String>,
+    value: Value,
+}
+
+/// A YAML mapping which keeps its entries in the order they were written, so
+/// that the generated code follows the order of the manifest.
+#[derive(Debug)]
+struct OrderedMap<T>(Vec<(String, T)>);
+
+impl<T> Default for OrderedMap<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for OrderedMap<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct OrderedMapVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for OrderedMapVisitor<T> {
+            type Value = OrderedMap<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a mapping")
+            }
+
+            fn visit_unit<E>(self) -> std::result::Result<Self::Value, E> {
+                // An empty section, eg, `objects:` with nothing after it.
+                Ok(OrderedMap::default())
+            }
 
-pub struct Parser {}
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(OrderedMap(entries))
+            }
+        }
+
+        deserializer.deserialize_map(OrderedMapVisitor(PhantomData))
+    }
+}
+
+/// The line each key of the manifest, and each item of its sequences, is on,
+/// by the path to it, eg, `["features", "search", "defaults", "0"]`.
+///
+/// serde_yaml doesn't tell us where a value came from, so we walk the YAML
+/// events ourselves to find out.
+#[derive(Debug, Default)]
+struct Spans {
+    lines: HashMap<Vec<String>, usize>,
+    path: Vec<String>,
+    containers: Vec<Container>,
+}
+
+#[derive(Debug)]
+enum Container {
+    Mapping { expecting_key: bool },
+    Sequence { next_index: usize },
+}
+
+impl Spans {
+    fn from_source(source: &str) -> Self {
+        let mut spans = Spans::default();
+        // serde_yaml has already parsed this, so it can't fail.
+        let _ = YamlParser::new(source.chars()).load(&mut spans, false);
+        spans
+    }
+
+    /// The line of `path`, or of its closest ancestor we know the line of.
+    fn line_of(&self, path: &[&str]) -> Option<usize> {
+        let mut path: Vec<String> = path.iter().map(|s| s.to_string()).collect();
+        loop {
+            if let Some(line) = self.lines.get(&path) {
+                return Some(*line);
+            }
+            path.pop()?;
+        }
+    }
+
+    fn start_value(&mut self, mark: Marker) {
+        if let Some(Container::Sequence { next_index }) = self.containers.last_mut() {
+            self.path.push(next_index.to_string());
+            *next_index += 1;
+            self.lines.insert(self.path.clone(), mark.line());
+        }
+    }
+
+    fn end_value(&mut self) {
+        match self.containers.last_mut() {
+            Some(Container::Mapping { expecting_key }) => {
+                self.path.pop();
+                *expecting_key = true;
+            }
+            Some(Container::Sequence { .. }) => {
+                self.path.pop();
+            }
+            None => (),
+        }
+    }
+}
+
+impl MarkedEventReceiver for Spans {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        if let Some(Container::Mapping { expecting_key }) = self.containers.last_mut() {
+            // Manifests only have scalar keys, so we don't try to track
+            // anything else.
+            if let (true, Event::Scalar(key, ..)) = (*expecting_key, &ev) {
+                *expecting_key = false;
+                self.path.push(key.clone());
+                self.lines.insert(self.path.clone(), mark.line());
+                return;
+            }
+        }
+        match ev {
+            Event::Scalar(..) | Event::Alias(_) => {
+                self.start_value(mark);
+                self.end_value();
+            }
+            Event::MappingStart(_) => {
+                self.start_value(mark);
+                self.containers.push(Container::Mapping {
+                    expecting_key: true,
+                });
+            }
+            Event::SequenceStart(_) => {
+                self.start_value(mark);
+                self.containers.push(Container::Sequence { next_index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.containers.pop();
+                self.end_value();
+            }
+            _ => (),
+        }
+    }
+}
+
+pub struct Parser {
+    spans: Spans,
+    manifest: ManifestFrontEnd,
+}
 
 impl Parser {
-    pub fn new(_file: File) -> Self {
-        unimplemented!()
+    pub fn new(mut file: File) -> Result<Self> {
+        let mut source = String::new();
+        file.read_to_string(&mut source)?;
+        Self::from_source(source)
+    }
+
+    pub fn from_source(source: String) -> Result<Self> {
+        let manifest = serde_yaml::from_str(&source)?;
+        let spans = Spans::from_source(&source);
+        Ok(Self { spans, manifest })
+    }
+
+    /// Build and validate the intermediate representation of the manifest,
+    /// with the defaults for `channel` applied. With no channel, only the
+    /// defaults which don't name a channel are applied.
+    pub fn get_intermediate_representation(
+        &self,
+        channel: Option<&str>,
+    ) -> Result<FeatureManifest> {
+        if let Some(channel) = channel {
+            self.check_channel(channel, &["channels"])?;
+        }
+        let enum_defs = self
+            .manifest
+            .enums
+            .0
+            .iter()
+            .map(|(name, body)| EnumDef {
+                name: name.clone(),
+                doc: body.description.clone(),
+                variants: body
+                    .variants
+                    .0
+                    .iter()
+                    .map(|(name, body)| VariantDef::new(name, &body.description))
+                    .collect(),
+            })
+            .collect();
+        let obj_defs = self
+            .manifest
+            .objects
+            .0
+            .iter()
+            .map(|(name, body)| {
+                Ok(ObjectDef::new(
+                    name,
+                    &body.description,
+                    self.get_prop_defs(&["objects", name.as_str(), "fields"], &body.fields)?,
+                ))
+            })
+            .collect::<Result<_>>()?;
+        let feature_defs = self
+            .manifest
+            .features
+            .0
+            .iter()
+            .map(|(name, body)| self.get_feature_def(name, body, channel))
+            .collect::<Result<_>>()?;
+
+        let manifest = FeatureManifest {
+            enum_defs,
+            obj_defs,
+            hints: Default::default(),
+            feature_defs,
+        };
+        manifest.validate_manifest()?;
+        Ok(manifest)
+    }
+
+    /// Check that `channel`, found at `path`, is one of the manifest's channels.
+    fn check_channel(&self, channel: &str, path: &[&str]) -> Result<()> {
+        if self.manifest.channels.iter().any(|c| c == channel) {
+            Ok(())
+        } else {
+            Err(self.parse_error(
+                path,
+                format!(
+                    "Channel {} isn't one of the channels of the manifest: {:?}",
+                    channel, self.manifest.channels
+                ),
+            ))
+        }
+    }
+
+    fn get_feature_def(
+        &self,
+        name: &str,
+        body: &FeatureBody,
+        channel: Option<&str>,
+    ) -> Result<FeatureDef> {
+        let mut props = self.get_prop_defs(&["features", name, "variables"], &body.variables)?;
+        for (index, block) in body.defaults.iter().enumerate() {
+            let index = index.to_string();
+            let block_path = ["features", name, "defaults", index.as_str()];
+            if let Some(block_channel) = &block.channel {
+                self.check_channel(block_channel, &[&block_path[..], &["channel"][..]].concat())?;
+                if channel != Some(block_channel.as_str()) {
+                    continue;
+                }
+            }
+            let value_path = [&block_path[..], &["value"][..]].concat();
+            let values = block.value.as_object().ok_or_else(|| {
+                self.parse_error(
+                    &value_path,
+                    format!("The defaults of feature {} must be objects", name),
+                )
+            })?;
+            for (prop_name, value) in values {
+                let prop = props
+                    .iter_mut()
+                    .find(|prop| &prop.name == prop_name)
+                    .ok_or_else(|| {
+                        self.parse_error(
+                            &[&value_path[..], &[prop_name.as_str()][..]].concat(),
+                            format!(
+                                "The defaults of feature {} set {}, which isn't one of its variables",
+                                name, prop_name
+                            ),
+                        )
+                    })?;
+                merge_default(&mut prop.default, value);
+            }
+        }
+        Ok(FeatureDef::new(name, &body.description, props, None))
+    }
+
+    /// The definitions of the `fields` found at `path`.
+    fn get_prop_defs(&self, path: &[&str], fields: &OrderedMap<FieldBody>) -> Result<Vec<PropDef>> {
+        fields
+            .0
+            .iter()
+            .map(|(name, body)| {
+                let field_path = [path, &[name.as_str()][..]].concat();
+                let typ = self.get_type_ref(&body.typ).map_err(|message| {
+                    self.parse_error(
+                        &[&field_path[..], &["type"][..]].concat(),
+                        format!("{}: {}", name, message),
+                    )
+                })?;
+                let default = match (&body.default, &typ) {
+                    (Some(default), _) => default.clone(),
+                    (None, TypeRef::Option(_)) => Value::Null,
+                    (None, _) => {
+                        return Err(self.parse_error(
+                            &field_path,
+                            format!("{} has no default, and isn't optional", name),
+                        ))
+                    }
+                };
+                Ok(PropDef {
+                    name: name.clone(),
+                    doc: body.description.clone(),
+                    typ,
+                    default,
+                })
+            })
+            .collect()
+    }
+
+    /// Parse a type as written in the manifest, eg, `Option<List<SectionId>>`.
+    fn get_type_ref(&self, typ: &str) -> std::result::Result<TypeRef, String> {
+        let typ = typ.trim();
+        if let Some(inner) = generic_argument(typ, "Option") {
+            return Ok(TypeRef::Option(Box::new(self.get_type_ref(inner)?)));
+        }
+        if let Some(inner) = generic_argument(typ, "List") {
+            return Ok(TypeRef::List(Box::new(self.get_type_ref(inner)?)));
+        }
+        if let Some(inner) = generic_argument(typ, "Map") {
+            let (key, value) = split_top_level_comma(inner)
+                .ok_or_else(|| format!("{} should have a key and a value type", typ))?;
+            let key = self.get_type_ref(key)?;
+            let value = Box::new(self.get_type_ref(value)?);
+            return match key {
+                TypeRef::String => Ok(TypeRef::StringMap(value)),
+                TypeRef::Enum(_) => Ok(TypeRef::EnumMap(Box::new(key), value)),
+                _ => Err(format!("the keys of {} must be Strings or an enum", typ)),
+            };
+        }
+        match typ {
+            "String" => Ok(TypeRef::String),
+            "Int" => Ok(TypeRef::Int),
+            "Boolean" => Ok(TypeRef::Boolean),
+            _ if self.manifest.enums.0.iter().any(|(name, _)| name == typ) => {
+                Ok(TypeRef::Enum(typ.to_string()))
+            }
+            _ if self.manifest.objects.0.iter().any(|(name, _)| name == typ) => {
+                Ok(TypeRef::Object(typ.to_string()))
+            }
+            _ => Err(format!("unknown type {}", typ)),
+        }
+    }
+
+    /// An error which points at the line of the manifest the value at `path`
+    /// is on, eg, `["features", "search", "variables"]`.
+    fn parse_error(&self, path: &[&str], message: String) -> FMLError {
+        match self.spans.line_of(path) {
+            Some(line) => FMLError::ParseError(format!("{} at line {}", message, line)),
+            None => FMLError::ParseError(message),
+        }
+    }
+}
+
+/// `Some("T")` if `typ` is `name<T>`.
+fn generic_argument<'a>(typ: &'a str, name: &str) -> Option<&'a str> {
+    typ.strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('<')?
+        .strip_suffix('>')
+}
+
+/// Split `K, V` at the comma which isn't nested in another type's arguments.
+fn split_top_level_comma(args: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => return Some((&args[..i], &args[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Merge a default from a `defaults` block into the default of a variable.
+/// Objects are merged key by key, anything else is replaced.
+fn merge_default(default: &mut Value, value: &Value) {
+    match (default, value) {
+        (Value::Object(default), Value::Object(value)) => {
+            for (key, value) in value {
+                merge_default(default.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (default, value) => *default = value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::fixtures::intermediate_representation::{
+        get_simple_homescreen_feature, get_simple_nimbus_validation_feature,
+    };
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn parse_fixture(name: &str) -> Result<Parser> {
+        let root = std::env::var("CARGO_MANIFEST_DIR")
+            .expect("Missing $CARGO_MANIFEST_DIR, cannot find fixtures files");
+        let path: PathBuf = [&root, "fixtures", "fe", name].iter().collect();
+        Parser::new(File::open(path)?)
+    }
+
+    fn parse(source: &str) -> Result<FeatureManifest> {
+        Parser::from_source(source.to_string())?.get_intermediate_representation(None)
+    }
+
+    #[test]
+    fn test_parse_simple_nimbus_validation() -> Result<()> {
+        let parser = parse_fixture("simple_nimbus_validation.yaml")?;
+        assert_eq!(
+            parser.get_intermediate_representation(None)?,
+            get_simple_nimbus_validation_feature()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_simple_homescreen_with_channels() -> Result<()> {
+        let parser = parse_fixture("simple_homescreen.yaml")?;
+        assert_eq!(
+            parser.get_intermediate_representation(Some("release"))?,
+            get_simple_homescreen_feature()
+        );
+
+        let nightly = parser.get_intermediate_representation(Some("nightly"))?;
+        let mut expected = get_simple_homescreen_feature();
+        expected.feature_defs = vec![FeatureDef::new(
+            "homescreen",
+            "Represents the homescreen feature",
+            vec![PropDef {
+                name: "sections-enabled".into(),
+                doc: "A map of booleans".into(),
+                typ: TypeRef::EnumMap(
+                    Box::new(TypeRef::Enum("SectionId".into())),
+                    Box::new(TypeRef::Boolean),
+                ),
+                default: json!({
+                    "top-sites": true,
+                    "jump-back-in": false,
+                    "recently-saved": true,
+                }),
+            }],
+            None,
+        )];
+        assert_eq!(nightly, expected);
+
+        parser
+            .get_intermediate_representation(Some("beta"))
+            .expect_err("Should fail on an undeclared channel");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_types() {
+        let parser = Parser::from_source(
+            "
+enums:
+  Size:
+    description: A size
+    variants:
+      small:
+        description: Small
+objects:
+  Button:
+    description: A button
+    fields:
+      label:
+        description: The label
+        type: String
+        default: OK
+"
+            .to_string(),
+        )
+        .unwrap();
+        let parse = |typ| parser.get_type_ref(typ);
+        assert_eq!(parse("Int"), Ok(TypeRef::Int));
+        assert_eq!(parse("Size"), Ok(TypeRef::Enum("Size".into())));
+        assert_eq!(parse("Button"), Ok(TypeRef::Object("Button".into())));
+        assert_eq!(
+            parse("Option<List<Button>>"),
+            Ok(TypeRef::Option(Box::new(TypeRef::List(Box::new(
+                TypeRef::Object("Button".into())
+            )))))
+        );
+        assert_eq!(
+            parse("Map<String, List<Int>>"),
+            Ok(TypeRef::StringMap(Box::new(TypeRef::List(Box::new(
+                TypeRef::Int
+            )))))
+        );
+        assert_eq!(
+            parse("Map<Size, Map<String, Boolean>>"),
+            Ok(TypeRef::EnumMap(
+                Box::new(TypeRef::Enum("Size".into())),
+                Box::new(TypeRef::StringMap(Box::new(TypeRef::Boolean)))
+            ))
+        );
+        assert!(parse("Float").is_err());
+        assert!(parse("Map<Int, String>").is_err());
+        assert!(parse("Map<String>").is_err());
+        assert!(parse("List<Size").is_err());
+    }
+
+    #[test]
+    fn test_optional_variables_default_to_null() -> Result<()> {
+        let fm = parse(
+            "
+features:
+  search:
+    description: Search
+    variables:
+      engine:
+        description: The engine to use
+        type: Option<String>
+",
+        )?;
+        assert_eq!(
+            fm.feature_defs,
+            vec![FeatureDef::new(
+                "search",
+                "Search",
+                vec![PropDef {
+                    name: "engine".into(),
+                    doc: "The engine to use".into(),
+                    typ: TypeRef::Option(Box::new(TypeRef::String)),
+                    default: Value::Null,
+                }],
+                None
+            )]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_report_lines() {
+        let err = parse(
+            "
+features:
+  search:
+    description: Search
+    variables:
+      engine:
+        description: The engine to use
+        type: Lisst<String>
+        default: []
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parse Error: engine: unknown type Lisst<String> at line 8"
+        );
+
+        let err = parse(
+            "
+features:
+  search:
+    description: Search
+    variables:
+      engine:
+        type: String
+        default: google
+",
+        )
+        .unwrap_err();
+        assert!(matches!(err, FMLError::YAMLError(_)));
+        assert!(err.to_string().contains("line 7"), "{}", err);
+
+        let err = parse(
+            "
+features:
+  search:
+    description: Search
+    variables:
+      engine:
+        description: The engine to use
+        type: String
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parse Error: engine has no default, and isn't optional at line 6"
+        );
+
+        let err = parse(
+            "
+features:
+  search:
+    description: Search
+    variables:
+      engine:
+        description: The engine to use
+        type: String
+        default: google
+    defaults:
+      - value:
+          enigne: bing
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parse Error: The defaults of feature search set enigne, which isn't one of its variables at line 12"
+        );
+
+        // The line is that of the variable with the error, not the first
+        // line with its name on.
+        let err = parse(
+            "
+features:
+  search:
+    description: Search
+    variables:
+      engine:
+        description: The engine to use
+        type: String
+        default: google
+  suggest:
+    description: Suggestions
+    variables:
+      engine:
+        description: The engine to suggest from
+        type: String
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parse Error: engine has no default, and isn't optional at line 13"
+        );
     }
 }
@@ -0,0 +1,138 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+* License, v. 2.0. If a copy of the MPL was not distributed with this
+* file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Validation of the feature values of experiments against a manifest, so
+//! that a typo in an experiment is found before it's launched, rather than
+//! silently turning into the app's defaults.
+
+use crate::intermediate_representation::FeatureManifest;
+use serde_json::Value;
+
+/// Check every feature config of every branch of `experiments`, which can be
+/// a single experiment, a list of them, or the response of Remote Settings
+/// (ie, `{"data": [...]}`). Returns a description of each invalid config.
+pub fn validate_experiments(fm: &FeatureManifest, experiments: &Value) -> Vec<String> {
+    let experiments = match experiments {
+        Value::Array(experiments) => experiments.iter().collect(),
+        Value::Object(object) => match object.get("data") {
+            Some(Value::Array(experiments)) => experiments.iter().collect(),
+            _ => vec![experiments],
+        },
+        _ => return vec![format!("Expected experiments, found: {}", experiments)],
+    };
+    experiments
+        .into_iter()
+        .flat_map(|experiment| validate_experiment(fm, experiment))
+        .collect()
+}
+
+fn validate_experiment(fm: &FeatureManifest, experiment: &Value) -> Vec<String> {
+    let slug = experiment
+        .get("slug")
+        .and_then(Value::as_str)
+        .unwrap_or("<no slug>");
+    let branches = match experiment.get("branches").and_then(Value::as_array) {
+        Some(branches) => branches,
+        None => return vec![format!("{}: the experiment has no branches", slug)],
+    };
+    let mut errors = Vec::new();
+    for branch in branches {
+        let branch_slug = branch
+            .get("slug")
+            .and_then(Value::as_str)
+            .unwrap_or("<no slug>");
+        // Branches have either a single `feature`, or a list of `features`.
+        let feature_configs = match (branch.get("features"), branch.get("feature")) {
+            (Some(Value::Array(features)), _) => features.iter().collect(),
+            (_, Some(feature)) => vec![feature],
+            _ => vec![],
+        };
+        for config in feature_configs {
+            let feature_id = match config.get("featureId").and_then(Value::as_str) {
+                Some(feature_id) => feature_id,
+                None => {
+                    errors.push(format!("{}/{}: a feature has no id", slug, branch_slug));
+                    continue;
+                }
+            };
+            let value = config
+                .get("value")
+                .cloned()
+                .unwrap_or_else(|| Value::Object(Default::default()));
+            if let Err(e) = fm.validate_feature_config(feature_id, &value) {
+                errors.push(format!("{}/{}: {}", slug, branch_slug, e));
+            }
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::fixtures::intermediate_representation::get_simple_homescreen_feature;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_experiments() {
+        let fm = get_simple_homescreen_feature();
+        let experiments = json!({
+            "data": [
+                {
+                    "slug": "valid",
+                    "branches": [
+                        {
+                            "slug": "control",
+                            "feature": { "featureId": "homescreen", "enabled": true }
+                        },
+                        {
+                            "slug": "treatment",
+                            "features": [{
+                                "featureId": "homescreen",
+                                "value": { "sections-enabled": { "top-sites": false } }
+                            }]
+                        }
+                    ]
+                },
+                {
+                    "slug": "invalid",
+                    "branches": [
+                        {
+                            "slug": "control",
+                            "feature": { "featureId": "homescreen", "value": {} }
+                        },
+                        {
+                            "slug": "treatment",
+                            "feature": {
+                                "featureId": "homescreen",
+                                "value": { "sections-enabled": { "top-sitez": false } }
+                            }
+                        },
+                        {
+                            "slug": "other",
+                            "features": [{ "featureId": "search", "value": {} }]
+                        }
+                    ]
+                }
+            ]
+        });
+        assert_eq!(
+            validate_experiments(&fm, &experiments),
+            vec![
+                "invalid/treatment: Validation Error: homescreen.sections-enabled: top-sitez isn't a variant of SectionId",
+                "invalid/other: Validation Error: Unknown feature: search",
+            ]
+        );
+
+        // A single experiment works too.
+        assert_eq!(
+            validate_experiments(&fm, &experiments["data"][0]),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            validate_experiments(&fm, &json!({ "slug": "no-branches" })),
+            vec!["no-branches: the experiment has no branches"]
+        );
+    }
+}
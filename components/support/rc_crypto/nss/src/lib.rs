@@ -14,6 +14,7 @@ mod error;
 pub mod pbkdf2;
 pub mod pk11;
 pub mod pkixc;
+pub mod rsa;
 pub mod secport;
 pub use crate::error::{Error, ErrorKind, Result};
 pub use util::ensure_nss_initialized as ensure_initialized;
@@ -0,0 +1,92 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{
+    error::*,
+    pk11::{
+        context::HashAlgorithm,
+        types::{Pkcs11Object, PublicKey as PK11PublicKey},
+    },
+    util::{ensure_nss_initialized, map_nss_secstatus},
+};
+use std::{
+    convert::TryFrom,
+    os::raw::{c_uchar, c_uint},
+    ptr,
+};
+
+pub struct PublicKey {
+    wrapped: PK11PublicKey,
+}
+
+impl PublicKey {
+    /// Import an RSA public key from its big-endian modulus and public exponent.
+    pub fn from_components(modulus: &[u8], exponent: &[u8]) -> Result<PublicKey> {
+        // The following code is adapted from:
+        // https://searchfox.org/mozilla-central/rev/ec489aa170b6486891cf3625717d6fa12bcd11c1/dom/crypto/CryptoKey.cpp#940
+        ensure_nss_initialized();
+        let modulus = nss_sys::SECItem {
+            type_: nss_sys::SECItemType::siUnsignedInteger as u32,
+            data: modulus.as_ptr() as *mut c_uchar,
+            len: c_uint::try_from(modulus.len())?,
+        };
+        let exponent = nss_sys::SECItem {
+            type_: nss_sys::SECItemType::siUnsignedInteger as u32,
+            data: exponent.as_ptr() as *mut c_uchar,
+            len: c_uint::try_from(exponent.len())?,
+        };
+        let pub_key = nss_sys::SECKEYPublicKey {
+            arena: ptr::null_mut(),
+            keyType: nss_sys::KeyType::rsaKey as u32,
+            pkcs11Slot: ptr::null_mut(),
+            pkcs11ID: nss_sys::CK_INVALID_HANDLE.into(),
+            u: nss_sys::SECKEYPublicKeyStr_u {
+                rsa: nss_sys::SECKEYRSAPublicKey {
+                    arena: ptr::null_mut(),
+                    modulus,
+                    publicExponent: exponent,
+                },
+            },
+        };
+        Ok(Self {
+            wrapped: unsafe { PK11PublicKey::from_ptr(nss_sys::SECKEY_CopyPublicKey(&pub_key))? },
+        })
+    }
+
+    /// RSASSA-PKCS1-v1_5 verify operation.
+    pub fn verify(
+        &self,
+        message: &[u8],
+        signature: &[u8],
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<()> {
+        // Unlike for ECDSA, we let the token do the hashing: these mechanisms
+        // take care of wrapping the digest in a `DigestInfo` structure.
+        let mechanism = match hash_algorithm {
+            HashAlgorithm::SHA256 => nss_sys::CKM_SHA256_RSA_PKCS,
+            HashAlgorithm::SHA384 => nss_sys::CKM_SHA384_RSA_PKCS,
+        };
+        let signature = nss_sys::SECItem {
+            len: u32::try_from(signature.len())?,
+            data: signature.as_ptr() as *mut u8,
+            type_: 0,
+        };
+        let message = nss_sys::SECItem {
+            len: u32::try_from(message.len())?,
+            data: message.as_ptr() as *mut u8,
+            type_: 0,
+        };
+        map_nss_secstatus(|| unsafe {
+            nss_sys::PK11_VerifyWithMechanism(
+                self.wrapped.as_mut_ptr(),
+                mechanism.into(),
+                ptr::null(),
+                &signature,
+                &message,
+                ptr::null_mut(),
+            )
+        })?;
+        Ok(())
+    }
+}
@@ -19,8 +19,8 @@
 // OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF OR IN
 // CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use crate::Result;
-use nss::{ec::Curve, ec::PublicKey, pbkdf2::HashAlgorithm};
+use crate::{error::*, Result};
+use nss::{ec::Curve, ec::PublicKey, pbkdf2::HashAlgorithm, rsa};
 
 /// A signature verification algorithm.
 pub struct VerificationAlgorithm {
@@ -66,6 +66,54 @@ impl<'a> UnparsedPublicKey<'a> {
     }
 }
 
+/// Parameters for RSASSA-PKCS1-v1_5 signature verification.
+pub struct RsaParameters {
+    digest_alg: HashAlgorithm,
+    min_bits: usize,
+    max_bits: usize,
+}
+
+pub static RSA_PKCS1_2048_8192_SHA256: RsaParameters = RsaParameters {
+    digest_alg: HashAlgorithm::SHA256,
+    min_bits: 2048,
+    max_bits: 8192,
+};
+
+pub static RSA_PKCS1_2048_8192_SHA384: RsaParameters = RsaParameters {
+    digest_alg: HashAlgorithm::SHA384,
+    min_bits: 2048,
+    max_bits: 8192,
+};
+
+/// The components of an RSA public key, as big-endian unsigned integers.
+pub struct RsaPublicKeyComponents<B: AsRef<[u8]>> {
+    /// The public modulus.
+    pub n: B,
+    /// The public exponent.
+    pub e: B,
+}
+
+impl<B: AsRef<[u8]>> RsaPublicKeyComponents<B> {
+    pub fn verify(&self, params: &RsaParameters, message: &[u8], signature: &[u8]) -> Result<()> {
+        let n = strip_leading_zeros(self.n.as_ref());
+        let e = strip_leading_zeros(self.e.as_ref());
+        let n_bits = match n.first() {
+            Some(first) => n.len() * 8 - first.leading_zeros() as usize,
+            None => 0,
+        };
+        if n_bits < params.min_bits || n_bits > params.max_bits || e.is_empty() {
+            return Err(ErrorKind::InternalError.into());
+        }
+        let pub_key = rsa::PublicKey::from_components(n, e)?;
+        Ok(pub_key.verify(message, signature, params.digest_alg)?)
+    }
+}
+
+fn strip_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let first_non_zero = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[first_non_zero..]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,4 +156,64 @@ mod tests {
         // Happy case.
         assert!(public_key.verify(&message, &signature).is_ok());
     }
+
+    #[test]
+    fn test_rsa_pkcs1_sha256_verify() {
+        // Test generated with Python's `cryptography` package.
+        let n = base64::decode_config(
+            "vsnnuWpEX-ksEUzsqsWtNGM0D5b8P2m47AA2HTLvpNsEadIMZcR4KaAuODJTAYHStpSHWDytAW5bmHO2JUAoBLZoAK2MNdcE_WEURVG9ZRAR8AmyHxMik__KT9E--sGIl0-aUZMSO8gomLaklyvPKcFgq1nLgwHVbjIJ9I-v3zQO41sKQzclzqY-TqSuZzvyrpx86OUlFPb5hVutCvSDKW3aUIHjpaKXRc7tT5CwYENfDlJ2ZA27FxyuHxBCOAXc5E42PlgKKS4Wj-922CRMV1EvYncxXypxeKxlWoInbiR0xqML1oFTS-MgzxogfhLphP3d7pgTeKzqKtOGgU7kSw",
+            base64::URL_SAFE_NO_PAD,
+        )
+        .unwrap();
+        let e = base64::decode_config("AQAB", base64::URL_SAFE_NO_PAD).unwrap();
+        let message = b"The quick brown fox jumps over the lazy dog";
+        let signature = base64::decode_config(
+            "EJVM86UNsIg-ljJnhFfV8FDE6sK0NDUn0XsBEq1ruMf7-1L2K0jig6unhGFzXqn57K65AYOBp-ykqVeLS1y5ts1yhkJQaApOGvPjiL8_JgkxdeM5HIGmBGizQMIcYvzocXX00B1pCCwy_auXscgdk7iMurvIqHV9NLwAAID-JJugBcEUbzNe-NmTm-2iYVGMidduIKTPAOVrTxq4s4MbQy1D7dmWyHvpj3nsyVpOdE4_fQ0xJDKXnJk-YbCmMZkcftCtEsOi6d8ZhfrzUFVuhUFq6yO1rXlxP-DdhAGfYKGc_YrnUeBP8YOMhgPGWBXRKnQfA-riTbhI0hEHJkGZYA",
+            base64::URL_SAFE_NO_PAD,
+        )
+        .unwrap();
+        let public_key = RsaPublicKeyComponents { n: &n, e: &e };
+
+        // Failure case: Wrong hash algorithm.
+        assert!(public_key
+            .verify(&RSA_PKCS1_2048_8192_SHA384, message, &signature)
+            .is_err());
+
+        // Failure case: Flip a bit in signature.
+        let mut garbage_signature = signature.clone();
+        garbage_signature[42] ^= 1;
+        assert!(public_key
+            .verify(&RSA_PKCS1_2048_8192_SHA256, message, &garbage_signature)
+            .is_err());
+
+        // Failure case: Flip a bit in message.
+        let mut garbage_message = message.to_vec();
+        garbage_message[4] ^= 1;
+        assert!(public_key
+            .verify(&RSA_PKCS1_2048_8192_SHA256, &garbage_message, &signature)
+            .is_err());
+
+        // Failure case: Key too small.
+        let small_key = RsaPublicKeyComponents {
+            n: &n[..128],
+            e: &e[..],
+        };
+        assert!(small_key
+            .verify(&RSA_PKCS1_2048_8192_SHA256, message, &signature)
+            .is_err());
+
+        // Happy case, including with a leading zero on the modulus.
+        assert!(public_key
+            .verify(&RSA_PKCS1_2048_8192_SHA256, message, &signature)
+            .is_ok());
+        let mut padded_n = vec![0u8];
+        padded_n.extend_from_slice(&n);
+        let padded_key = RsaPublicKeyComponents {
+            n: &padded_n[..],
+            e: &e[..],
+        };
+        assert!(padded_key
+            .verify(&RSA_PKCS1_2048_8192_SHA256, message, &signature)
+            .is_ok());
+    }
 }
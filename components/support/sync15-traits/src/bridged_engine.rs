@@ -2,11 +2,15 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use std::{error::Error, fmt};
+use std::{borrow::Cow, error::Error, fmt};
 
 use serde::{Deserialize, Serialize};
 
-use super::{Guid, Payload, ServerTimestamp};
+use super::{
+    telemetry::{self, ReconcileReason},
+    CollSyncIds, CollectionRequest, EngineSyncAssociation, Guid, IncomingChangeset,
+    OutgoingChangeset, Payload, ServerTimestamp, SyncEngine,
+};
 
 /// A BridgedEngine acts as a bridge between application-services, rust
 /// implemented sync engines and sync engines as defined by Desktop Firefox.
@@ -49,6 +53,21 @@ pub trait BridgedEngine {
     /// sync.
     fn ensure_current_sync_id(&self, new_sync_id: &str) -> Result<String, Self::Error>;
 
+    /// Returns the global sync ID last passed to `set_global_sync_id`.
+    /// Desktop tracks the global sync ID itself, so this is only used when
+    /// the engine is synced via a [SyncEngineAdapter]. The default returns
+    /// `None`, which means the adapter resets the engine on every sync.
+    fn global_sync_id(&self) -> Result<Option<String>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Stores the global sync ID, alongside the sync ID for this engine's
+    /// collection. Like `global_sync_id`, this is only used by
+    /// [SyncEngineAdapter].
+    fn set_global_sync_id(&self, _global_sync_id: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     /// Indicates that the engine is about to start syncing. This is called
     /// once per sync, and always before `store_incoming`.
     fn sync_started(&self) -> Result<(), Self::Error>;
@@ -145,6 +164,20 @@ pub struct IncomingEnvelope {
 }
 
 impl IncomingEnvelope {
+    /// Builds an envelope from a payload fetched by `sync15`, which has
+    /// already been decrypted.
+    fn from_payload(mut payload: Payload, modified: ServerTimestamp) -> Self {
+        let ttl = payload.take_auto_field("ttl");
+        let sortindex = payload.take_auto_field("sortindex");
+        IncomingEnvelope {
+            id: payload.id.clone(),
+            modified,
+            sortindex,
+            ttl,
+            cleartext: payload.into_json_string(),
+        }
+    }
+
     /// Parses and returns the record payload from this envelope. Returns an
     /// error if the envelope's cleartext isn't valid JSON, or the payload is
     /// invalid.
@@ -174,6 +207,23 @@ pub struct OutgoingEnvelope {
     ttl: Option<u32>,
 }
 
+impl OutgoingEnvelope {
+    /// Parses the record payload from this envelope, putting the auto fields
+    /// back so that `sync15` can upload it.
+    fn into_payload(self) -> Result<Payload, PayloadError> {
+        let payload: Payload = serde_json::from_str(&self.cleartext)?;
+        if payload.id != self.id {
+            return Err(PayloadError::MismatchedId {
+                envelope: self.id,
+                payload: payload.id,
+            });
+        }
+        Ok(payload
+            .with_auto_field("ttl", self.ttl)
+            .with_auto_field("sortindex", self.sortindex))
+    }
+}
+
 impl From<Payload> for OutgoingEnvelope {
     fn from(mut payload: Payload) -> Self {
         let id = payload.id.clone();
@@ -189,6 +239,143 @@ impl From<Payload> for OutgoingEnvelope {
     }
 }
 
+/// Adapts a [BridgedEngine] to the [SyncEngine] trait, so that an engine
+/// written for Desktop can also be synced by `sync15` - and so, by the sync
+/// manager.
+///
+/// `SyncEngine`s are associated with both the global and collection sync IDs,
+/// but a `BridgedEngine` only needs to track the collection sync ID, so the
+/// adapter relies on the bridged engine implementing `global_sync_id` and
+/// `set_global_sync_id`.
+pub struct SyncEngineAdapter<B> {
+    collection: Cow<'static, str>,
+    engine: B,
+}
+
+impl<B> SyncEngineAdapter<B> {
+    pub fn new(collection: impl Into<Cow<'static, str>>, engine: B) -> Self {
+        SyncEngineAdapter {
+            collection: collection.into(),
+            engine,
+        }
+    }
+
+    pub fn engine(&self) -> &B {
+        &self.engine
+    }
+}
+
+impl<B> SyncEngine for SyncEngineAdapter<B>
+where
+    B: BridgedEngine,
+    B::Error: Error + Send + Sync + 'static,
+{
+    fn collection_name(&self) -> Cow<'static, str> {
+        self.collection.clone()
+    }
+
+    fn apply_incoming(
+        &self,
+        inbound: Vec<IncomingChangeset>,
+        telem: &mut telemetry::Engine,
+    ) -> anyhow::Result<OutgoingChangeset> {
+        let timestamp = inbound
+            .last()
+            .map(|changeset| changeset.timestamp)
+            .unwrap_or_default();
+        self.engine.sync_started()?;
+        let mut num_incoming = 0;
+        for changeset in inbound {
+            let envelopes = changeset
+                .changes
+                .into_iter()
+                .map(|(payload, modified)| IncomingEnvelope::from_payload(payload, modified))
+                .collect::<Vec<_>>();
+            num_incoming += envelopes.len();
+            self.engine.store_incoming(&envelopes)?;
+        }
+        let results = self.engine.apply()?;
+
+        let mut incoming_telemetry = telemetry::EngineIncoming::new();
+        let num_reconciled = results.num_reconciled.unwrap_or_default();
+        incoming_telemetry.applied(num_incoming.saturating_sub(num_reconciled) as u32);
+        if num_reconciled > 0 {
+            incoming_telemetry.reconciled_with(ReconcileReason::Merge, num_reconciled as u32);
+        }
+        telem.incoming(incoming_telemetry);
+
+        let mut outgoing = OutgoingChangeset::new(self.collection.clone(), timestamp);
+        outgoing.changes = results
+            .envelopes
+            .into_iter()
+            .map(OutgoingEnvelope::into_payload)
+            .collect::<Result<_, _>>()?;
+        Ok(outgoing)
+    }
+
+    fn sync_finished(
+        &self,
+        new_timestamp: ServerTimestamp,
+        records_synced: Vec<Guid>,
+    ) -> anyhow::Result<()> {
+        self.engine
+            .set_uploaded(new_timestamp.as_millis(), &records_synced)?;
+        self.engine.set_last_sync(new_timestamp.as_millis())?;
+        self.engine.sync_finished()?;
+        Ok(())
+    }
+
+    fn get_collection_requests(
+        &self,
+        server_timestamp: ServerTimestamp,
+    ) -> anyhow::Result<Vec<CollectionRequest>> {
+        let since = ServerTimestamp::from_millis(self.engine.last_sync()?);
+        Ok(if since == server_timestamp {
+            vec![]
+        } else {
+            vec![CollectionRequest::new(self.collection.clone())
+                .full()
+                .newer_than(since)]
+        })
+    }
+
+    fn get_sync_assoc(&self) -> anyhow::Result<EngineSyncAssociation> {
+        Ok(
+            match (self.engine.global_sync_id()?, self.engine.sync_id()?) {
+                (Some(global), Some(coll)) => EngineSyncAssociation::Connected(CollSyncIds {
+                    global: global.into(),
+                    coll: coll.into(),
+                }),
+                _ => EngineSyncAssociation::Disconnected,
+            },
+        )
+    }
+
+    fn reset(&self, assoc: &EngineSyncAssociation) -> anyhow::Result<()> {
+        // Bridged engines only reset when the collection sync ID changes, but
+        // we also need to when the global one does.
+        self.engine.reset()?;
+        if let EngineSyncAssociation::Connected(ids) = assoc {
+            let coll = self.engine.ensure_current_sync_id(ids.coll.as_str())?;
+            if coll != ids.coll.as_str() {
+                // We've no way to write the new ID back to meta/global, so the
+                // next sync will reset us again.
+                log::warn!(
+                    "Bridged engine for {} changed its sync ID during a reset",
+                    self.collection
+                );
+            }
+            self.engine.set_global_sync_id(ids.global.as_str())?;
+        }
+        Ok(())
+    }
+
+    fn wipe(&self) -> anyhow::Result<()> {
+        self.engine.wipe()?;
+        Ok(())
+    }
+}
+
 /// An error that indicates a payload is invalid.
 #[derive(Debug)]
 pub enum PayloadError {
@@ -218,3 +405,187 @@ impl fmt::Display for PayloadError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default)]
+    struct TestBridgedEngine {
+        last_sync: Cell<i64>,
+        sync_id: RefCell<Option<String>>,
+        global_sync_id: RefCell<Option<String>>,
+        staged: RefCell<Vec<IncomingEnvelope>>,
+        uploaded: RefCell<Vec<Guid>>,
+        resets: Cell<usize>,
+    }
+
+    impl BridgedEngine for TestBridgedEngine {
+        type Error = PayloadError;
+
+        fn last_sync(&self) -> Result<i64, Self::Error> {
+            Ok(self.last_sync.get())
+        }
+
+        fn set_last_sync(&self, last_sync_millis: i64) -> Result<(), Self::Error> {
+            self.last_sync.set(last_sync_millis);
+            Ok(())
+        }
+
+        fn sync_id(&self) -> Result<Option<String>, Self::Error> {
+            Ok(self.sync_id.borrow().clone())
+        }
+
+        fn reset_sync_id(&self) -> Result<String, Self::Error> {
+            let new_sync_id = "collBBBBBBBB".to_string();
+            self.sync_id.replace(Some(new_sync_id.clone()));
+            Ok(new_sync_id)
+        }
+
+        fn ensure_current_sync_id(&self, new_sync_id: &str) -> Result<String, Self::Error> {
+            self.sync_id.replace(Some(new_sync_id.to_string()));
+            Ok(new_sync_id.to_string())
+        }
+
+        fn global_sync_id(&self) -> Result<Option<String>, Self::Error> {
+            Ok(self.global_sync_id.borrow().clone())
+        }
+
+        fn set_global_sync_id(&self, global_sync_id: &str) -> Result<(), Self::Error> {
+            self.global_sync_id
+                .replace(Some(global_sync_id.to_string()));
+            Ok(())
+        }
+
+        fn sync_started(&self) -> Result<(), Self::Error> {
+            self.staged.borrow_mut().clear();
+            Ok(())
+        }
+
+        fn store_incoming(&self, envelopes: &[IncomingEnvelope]) -> Result<(), Self::Error> {
+            self.staged.borrow_mut().extend_from_slice(envelopes);
+            Ok(())
+        }
+
+        fn apply(&self) -> Result<ApplyResults, Self::Error> {
+            // Echo back everything we were given, as if we'd merged it all.
+            let envelopes = self
+                .staged
+                .borrow()
+                .iter()
+                .map(|envelope| envelope.payload().map(OutgoingEnvelope::from))
+                .collect::<Result<Vec<_>, _>>()?;
+            let num_reconciled = envelopes.len();
+            Ok(ApplyResults::new(envelopes, num_reconciled))
+        }
+
+        fn set_uploaded(
+            &self,
+            _server_modified_millis: i64,
+            ids: &[Guid],
+        ) -> Result<(), Self::Error> {
+            self.uploaded.borrow_mut().extend_from_slice(ids);
+            Ok(())
+        }
+
+        fn sync_finished(&self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn reset(&self) -> Result<(), Self::Error> {
+            self.resets.set(self.resets.get() + 1);
+            self.last_sync.set(0);
+            self.sync_id.replace(None);
+            self.global_sync_id.replace(None);
+            Ok(())
+        }
+
+        fn wipe(&self) -> Result<(), Self::Error> {
+            self.reset()
+        }
+    }
+
+    #[test]
+    fn test_sync_assoc() {
+        let adapter = SyncEngineAdapter::new("test", TestBridgedEngine::default());
+        assert_eq!(
+            adapter.get_sync_assoc().unwrap(),
+            EngineSyncAssociation::Disconnected
+        );
+
+        let ids = CollSyncIds {
+            global: "globalAAAAAA".into(),
+            coll: "collAAAAAAAA".into(),
+        };
+        adapter
+            .reset(&EngineSyncAssociation::Connected(ids.clone()))
+            .unwrap();
+        assert_eq!(adapter.engine().resets.get(), 1);
+        assert_eq!(
+            adapter.get_sync_assoc().unwrap(),
+            EngineSyncAssociation::Connected(ids)
+        );
+
+        adapter.reset(&EngineSyncAssociation::Disconnected).unwrap();
+        assert_eq!(adapter.engine().resets.get(), 2);
+        assert_eq!(
+            adapter.get_sync_assoc().unwrap(),
+            EngineSyncAssociation::Disconnected
+        );
+    }
+
+    #[test]
+    fn test_collection_requests() {
+        let adapter = SyncEngineAdapter::new("test", TestBridgedEngine::default());
+        adapter.engine().last_sync.set(1000);
+        assert!(adapter
+            .get_collection_requests(ServerTimestamp(1000))
+            .unwrap()
+            .is_empty());
+        let requests = adapter
+            .get_collection_requests(ServerTimestamp(2000))
+            .unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].collection, "test");
+        assert!(requests[0].full);
+        assert_eq!(requests[0].newer, Some(ServerTimestamp(1000)));
+    }
+
+    #[test]
+    fn test_apply_and_finish() {
+        let adapter = SyncEngineAdapter::new("test", TestBridgedEngine::default());
+        let mut inbound = IncomingChangeset::new("test", ServerTimestamp(2000));
+        let payload = Payload::from_json(json!({
+            "id": "recordAAAAAA",
+            "data": "hello",
+        }))
+        .unwrap()
+        .with_sortindex(5);
+        inbound
+            .changes
+            .push((payload.clone(), ServerTimestamp(1500)));
+
+        let mut telem = telemetry::Engine::new("test");
+        let outgoing = adapter.apply_incoming(vec![inbound], &mut telem).unwrap();
+        assert_eq!(outgoing.collection, "test");
+        assert_eq!(outgoing.timestamp, ServerTimestamp(2000));
+        // The sort index should survive the round trip through the envelopes.
+        assert_eq!(outgoing.changes, vec![payload]);
+        {
+            let staged = adapter.engine().staged.borrow();
+            assert_eq!(staged[0].sortindex, Some(5));
+            assert_eq!(staged[0].modified, ServerTimestamp(1500));
+        }
+
+        adapter
+            .sync_finished(ServerTimestamp(3000), vec!["recordAAAAAA".into()])
+            .unwrap();
+        assert_eq!(adapter.engine().last_sync.get(), 3000);
+        assert_eq!(
+            *adapter.engine().uploaded.borrow(),
+            vec![Guid::from("recordAAAAAA")]
+        );
+    }
+}
@@ -7,6 +7,7 @@ use crate::{
     ServerTimestamp,
 };
 use anyhow::Result;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct CollSyncIds {
@@ -95,6 +96,19 @@ pub trait SyncEngine {
         telem: &mut telemetry::Engine,
     ) -> Result<OutgoingChangeset>;
 
+    /// Called before `sync_finished` with the records we failed to upload,
+    /// which aren't in its `records_synced`, and why the server rejected
+    /// them where it told us. Engines which only mark the records synced as
+    /// uploaded don't need to do anything, as these are uploaded again next
+    /// time.
+    fn records_failed(
+        &self,
+        _failed_ids: Vec<Guid>,
+        _failure_reasons: HashMap<Guid, String>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     fn sync_finished(
         &self,
         new_timestamp: ServerTimestamp,
@@ -23,7 +23,9 @@ pub mod request;
 mod server_timestamp;
 pub mod telemetry;
 
-pub use bridged_engine::{ApplyResults, BridgedEngine, IncomingEnvelope, OutgoingEnvelope};
+pub use bridged_engine::{
+    ApplyResults, BridgedEngine, IncomingEnvelope, OutgoingEnvelope, SyncEngineAdapter,
+};
 pub use changeset::{IncomingChangeset, OutgoingChangeset, RecordChangeset};
 pub use engine::{CollSyncIds, EngineSyncAssociation, SyncEngine};
 pub use payload::Payload;
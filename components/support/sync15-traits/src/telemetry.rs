@@ -5,7 +5,7 @@
 //! Manage recording sync telemetry. Assumes some external telemetry
 //! library/code which manages submitting.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::time;
 
 use serde::{ser, Serialize, Serializer};
@@ -278,6 +278,35 @@ mod test {
     }
 }
 
+/// Why an incoming record was reconciled with a local one, rather than being
+/// applied as-is. Engines record these so we can tell how often conflicts
+/// actually happen, and how they are resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReconcileReason {
+    /// The incoming record was identical in content to an existing local one.
+    Dedupe,
+    /// Both sides had changes, which were merged field-by-field.
+    Merge,
+    /// Both sides had changes and the remote record was taken.
+    RemoteWins,
+    /// Both sides had changes and the local record was kept.
+    LocalWins,
+    /// Both sides had changes which couldn't be merged, so we kept both.
+    Fork,
+}
+
+impl ReconcileReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReconcileReason::Dedupe => "dedupe",
+            ReconcileReason::Merge => "merge",
+            ReconcileReason::RemoteWins => "remoteWins",
+            ReconcileReason::LocalWins => "localWins",
+            ReconcileReason::Fork => "fork",
+        }
+    }
+}
+
 /// Incoming record for an engine's sync
 #[derive(Debug, Default, Serialize)]
 pub struct EngineIncoming {
@@ -293,6 +322,11 @@ pub struct EngineIncoming {
 
     #[serde(skip_serializing_if = "crate::skip_if_default")]
     reconciled: u32,
+
+    // Not part of the "incoming" payload - these are reported as events
+    // in the ping.
+    #[serde(skip)]
+    reconcile_reasons: BTreeMap<ReconcileReason, u32>,
 }
 
 impl EngineIncoming {
@@ -334,6 +368,14 @@ impl EngineIncoming {
         self.reconciled += n;
     }
 
+    /// Increment the value of `reconciled` by `n`, recording `reason` as the
+    /// reason those records were reconciled.
+    #[inline]
+    pub fn reconciled_with(&mut self, reason: ReconcileReason, n: u32) {
+        self.reconciled += n;
+        *self.reconcile_reasons.entry(reason).or_default() += n;
+    }
+
     /// Get the value of `applied`. Mostly useful for testing.
     #[inline]
     pub fn get_applied(&self) -> u32 {
@@ -357,6 +399,16 @@ impl EngineIncoming {
     pub fn get_reconciled(&self) -> u32 {
         self.reconciled
     }
+
+    /// Get how many records were reconciled because of `reason`. Mostly
+    /// useful for testing.
+    #[inline]
+    pub fn get_reconciled_with(&self, reason: ReconcileReason) -> u32 {
+        self.reconcile_reasons
+            .get(&reason)
+            .copied()
+            .unwrap_or_default()
+    }
 }
 
 /// Outgoing record for an engine's sync
@@ -426,6 +478,18 @@ impl Engine {
         self.incoming = Some(inc);
     }
 
+    /// Record that `n` records, which were already counted as applied, were
+    /// reconciled because of `reason` instead. This is for engines which only
+    /// learn why records were reconciled after their incoming telemetry has
+    /// been recorded (eg, bookmarks, which reconciles as part of the merge).
+    pub fn reconciled_with(&mut self, reason: ReconcileReason, n: u32) {
+        if n > 0 {
+            let incoming = self.incoming.get_or_insert_with(EngineIncoming::new);
+            incoming.applied = incoming.applied.saturating_sub(n);
+            incoming.reconciled_with(reason, n);
+        }
+    }
+
     pub fn outgoing(&mut self, out: EngineOutgoing) {
         self.outgoing.push(out);
     }
@@ -453,6 +517,23 @@ impl Engine {
     fn finished(&mut self) {
         self.when_took = self.when_took.finished();
     }
+
+    // The events describing why records were reconciled, one per reason.
+    fn reconcile_events(&self) -> Vec<Event> {
+        match self.incoming {
+            Some(ref inc) => inc
+                .reconcile_reasons
+                .iter()
+                .map(|(reason, count)| {
+                    Event::new("sync", "reconcile")
+                        .value(reason.as_str())
+                        .extra("engine", self.name.clone())
+                        .extra("count", count.to_string())
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Default, Serialize)]
@@ -549,6 +630,29 @@ mod engine_tests {
         );
     }
 
+    #[test]
+    fn test_reconciled_with() {
+        let mut i = EngineIncoming::new();
+        i.applied(2);
+        i.reconciled_with(ReconcileReason::Dedupe, 2);
+        i.reconciled_with(ReconcileReason::RemoteWins, 1);
+        i.reconciled_with(ReconcileReason::Dedupe, 1);
+        assert_eq!(i.get_reconciled(), 4);
+        assert_eq!(i.get_reconciled_with(ReconcileReason::Dedupe), 3);
+        assert_eq!(i.get_reconciled_with(ReconcileReason::RemoteWins), 1);
+        assert_eq!(i.get_reconciled_with(ReconcileReason::LocalWins), 0);
+        let mut e = Engine::new("TestEngine");
+        e.incoming(i);
+        // The record is moved from `applied`.
+        e.reconciled_with(ReconcileReason::Merge, 1);
+        e.finished();
+        // The reasons aren't part of the engine payload.
+        assert_json(
+            &e,
+            serde_json::json!({"name": "TestEngine", "when": 0.0, "incoming": {"applied": 1, "reconciled": 5}}),
+        );
+    }
+
     #[test]
     fn test_raw() {
         let mut e = Engine::new("TestEngine");
@@ -734,6 +838,9 @@ impl SyncTelemetryPing {
 
     pub fn sync(&mut self, mut s: SyncTelemetry) {
         s.finished();
+        for engine in &s.engines {
+            self.events.extend(engine.reconcile_events());
+        }
         self.syncs.push(s);
     }
 
@@ -774,4 +881,43 @@ mod ping_tests {
             }),
         );
     }
+
+    #[test]
+    fn test_ping_reconcile_events() {
+        let mut inc = EngineIncoming::new();
+        inc.reconciled_with(ReconcileReason::LocalWins, 1);
+        inc.reconciled_with(ReconcileReason::Dedupe, 3);
+        let mut engine = Engine::new("test");
+        engine.incoming(inc);
+        let mut s = SyncTelemetry::new();
+        s.engine(engine);
+        let mut p = SyncTelemetryPing::new();
+        p.sync(s);
+        assert_json(
+            &p,
+            serde_json::json!({
+                "events": [{
+                    "object": "sync",
+                    "method": "reconcile",
+                    "value": "dedupe",
+                    "extra": {"engine": "test", "count": "3"}
+                }, {
+                    "object": "sync",
+                    "method": "reconcile",
+                    "value": "localWins",
+                    "extra": {"engine": "test", "count": "1"}
+                }],
+                "syncs": [{
+                    "engines": [{
+                        "name": "test",
+                        "when": 0.0,
+                        "incoming": {"reconciled": 4}
+                    }],
+                    "when": 0.0
+                }],
+                "uid": null,
+                "version": 1
+            }),
+        );
+    }
 }
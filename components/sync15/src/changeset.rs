@@ -9,7 +9,9 @@ use crate::key_bundle::KeyBundle;
 use crate::request::{CollectionRequest, NormalResponseHandler, UploadInfo};
 use crate::util::ServerTimestamp;
 use crate::CollState;
+use interrupt_support::Interruptee;
 use std::borrow::Cow;
+use sync_guid::Guid;
 
 pub use sync15_traits::{IncomingChangeset, OutgoingChangeset, RecordChangeset};
 
@@ -114,25 +116,37 @@ impl<'a> CollectionUpdate<'a> {
 
     /// Returns a list of the IDs that failed if allowed_dropped_records is true, otherwise
     /// returns an empty vec.
-    pub fn upload(self) -> error::Result<UploadInfo> {
-        let mut failed = vec![];
+    pub fn upload(self, interruptee: &dyn Interruptee) -> error::Result<UploadInfo> {
+        let mut failed: Vec<(Guid, &str)> = vec![];
         let mut q = self.client.new_post_queue(
             &self.collection,
             &self.state.config,
             self.xius,
             NormalResponseHandler::new(!self.fully_atomic),
+            interruptee,
         )?;
 
         for record in self.to_update.into_iter() {
             let enqueued = q.enqueue(&record)?;
-            if !enqueued && self.fully_atomic {
-                return Err(ErrorKind::RecordTooLargeError.into());
+            if !enqueued {
+                let (err, reason) = if q.is_paused() {
+                    (ErrorKind::StorageQuotaReached, "storage quota reached")
+                } else {
+                    (ErrorKind::RecordTooLargeError, "record too large")
+                };
+                if self.fully_atomic {
+                    return Err(err.into());
+                }
+                failed.push((record.id, reason));
             }
         }
 
         q.flush(true)?;
         let mut info = q.completed_upload_info();
-        info.failed_ids.append(&mut failed);
+        for (id, reason) in failed {
+            info.failure_reasons.insert(id.clone(), reason.into());
+            info.failed_ids.push(id);
+        }
         if self.fully_atomic {
             assert_eq!(
                 info.failed_ids.len(),
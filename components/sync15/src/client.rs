@@ -7,10 +7,11 @@ use crate::error::{self, ErrorKind, ErrorResponse};
 use crate::record_types::MetaGlobalRecord;
 use crate::request::{
     BatchPoster, CollectionRequest, InfoCollections, InfoConfiguration, PostQueue, PostResponse,
-    PostResponseHandler,
+    PostResponseHandler, QuotaSignals,
 };
 use crate::token;
 use crate::util::ServerTimestamp;
+use interrupt_support::Interruptee;
 use serde_json::Value;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU32, Ordering};
@@ -358,10 +359,12 @@ impl Sync15StorageClient {
         config: &InfoConfiguration,
         ts: ServerTimestamp,
         on_response: F,
+        interruptee: &'a dyn Interruptee,
     ) -> error::Result<PostQueue<PostWrapper<'a>, F>> {
         let pw = PostWrapper {
             client: self,
             coll: coll.into(),
+            interruptee,
         };
         Ok(PostQueue::new(config, ts, pw, on_response))
     }
@@ -414,6 +417,7 @@ impl Sync15StorageClient {
 pub struct PostWrapper<'a> {
     client: &'a Sync15StorageClient,
     coll: String,
+    interruptee: &'a dyn Interruptee,
 }
 
 impl<'a> BatchPoster for PostWrapper<'a> {
@@ -436,7 +440,23 @@ impl<'a> BatchPoster for PostWrapper<'a> {
             .header(header_names::CONTENT_TYPE, "application/json")?
             .header(header_names::X_IF_UNMODIFIED_SINCE, format!("{}", xius))?
             .body(bytes);
-        self.client.exec_request(req, false)
+        log::trace!("request: {} {}", req.method, req.url.path());
+        let resp = req.send()?;
+        // The quota signals are in the headers, which `from_response` drops.
+        let quota = QuotaSignals::from_headers(&resp.headers);
+        let mut result = PostResponse::from_response(resp, &self.client.backoff)?;
+        if let Sync15ClientResponse::Success { ref mut record, .. } = result {
+            record.quota = quota;
+        }
+        Ok(result)
+    }
+
+    fn required_wait(&self) -> Option<std::time::Duration> {
+        self.client.backoff.get_required_wait(false)
+    }
+
+    fn err_if_interrupted(&self) -> error::Result<()> {
+        Ok(self.interruptee.err_if_interrupted()?)
     }
 }
 
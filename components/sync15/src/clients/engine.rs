@@ -310,7 +310,7 @@ impl<'a> Engine<'a> {
         self.interruptee.err_if_interrupted()?;
         let upload_info =
             CollectionUpdate::new_from_changeset(storage_client, &coll_state, outgoing, true)?
-                .upload()?;
+                .upload(self.interruptee)?;
 
         log::info!(
             "Upload success ({} records success, {} records failed)",
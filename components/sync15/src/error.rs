@@ -41,6 +41,9 @@ pub enum ErrorKind {
     #[error("Outgoing record is too large to upload")]
     RecordTooLargeError,
 
+    #[error("The server reported that the storage quota for this account has been reached")]
+    StorageQuotaReached,
+
     // Do we want to record the concrete problems?
     #[error("Not all records were successfully uploaded")]
     RecordUploadFailed,
@@ -71,6 +74,9 @@ pub enum ErrorKind {
     #[error("Our storage needs setting up and we can't currently do it")]
     SetupRequired,
 
+    #[error("The persisted state couldn't be unsealed with the supplied key")]
+    UnsealPersistedStateError,
+
     #[error("Store error: {0}")]
     StoreError(#[from] anyhow::Error),
 
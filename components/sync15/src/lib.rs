@@ -16,6 +16,7 @@ mod key_bundle;
 mod migrate_state;
 mod record_types;
 mod request;
+mod sealed_state;
 mod state;
 mod status;
 mod sync;
@@ -36,10 +37,15 @@ pub use crate::error::{Error, ErrorKind, Result};
 pub use crate::key_bundle::KeyBundle;
 pub use crate::migrate_state::extract_v1_state;
 pub use crate::request::CollectionRequest;
+pub use crate::sealed_state::{
+    check_persisted_state_key, create_persisted_state_key, seal_persisted_state,
+    unseal_persisted_state, PERSISTED_STATE_KEY_LEN,
+};
 pub use crate::state::{GlobalState, SetupStateMachine};
 pub use crate::status::{ServiceStatus, SyncResult};
 pub use crate::sync::{synchronize, SyncEngine};
 pub use crate::sync_multiple::{
-    sync_multiple, sync_multiple_with_command_processor, MemoryCachedState, SyncRequestInfo,
+    set_engine_enabled, sync_multiple, sync_multiple_with_command_processor, MemoryCachedState,
+    SyncRequestInfo,
 };
 pub use crate::util::ServerTimestamp;
@@ -46,6 +46,7 @@ pub fn extract_v1_state(
     };
     let pgs = PersistedGlobalState::V2 {
         declined: Some(meta_global.declined),
+        pending_engine_changes: Default::default(),
     };
     let new_global_state = serde_json::to_string(&pgs).ok();
 
@@ -137,6 +138,7 @@ mod tests {
         // state reflects that.
         let expected_state = serde_json::to_string(&PersistedGlobalState::V2 {
             declined: Some(Vec::<String>::new()),
+            pending_engine_changes: Default::default(),
         })
         .expect("should stringify");
         assert_eq!(new_state, Some(expected_state));
@@ -162,6 +164,7 @@ mod tests {
         let s = get_state_with_engine_changes_and_declined("", "\\\"foo\\\"");
         let expected_state = serde_json::to_string(&PersistedGlobalState::V2 {
             declined: Some(vec!["foo".to_string()]),
+            pending_engine_changes: Default::default(),
         })
         .unwrap();
         assert_eq!(
@@ -4,15 +4,16 @@
 
 use crate::bso_record::EncryptedBso;
 use crate::client::Sync15ClientResponse;
-use crate::error::{self, ErrorKind, Result};
+use crate::error::{self, ErrorKind, ErrorResponse, Result};
 use crate::util::ServerTimestamp;
 use serde_derive::*;
 use std::collections::HashMap;
 use std::default::Default;
 use std::ops::Deref;
+use std::time::Duration;
 pub use sync15_traits::{CollectionRequest, RequestOrder};
 use sync_guid::Guid;
-use viaduct::status_codes;
+use viaduct::{header_names, status_codes, Headers};
 
 /// Manages a pair of (byte, count) limits for a PostQueue, such as
 /// (max_post_bytes, max_post_records) or (max_total_bytes, max_total_records).
@@ -138,6 +139,97 @@ pub struct UploadResult {
     /// Vec of ids
     #[serde(default = "Vec::new")]
     pub success: Vec<Guid>,
+    /// Quota information from the response headers, not the body.
+    #[serde(skip)]
+    pub quota: QuotaSignals,
+}
+
+/// Signals the server may send with the response to a POST telling us how
+/// much more it's willing to accept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaSignals {
+    /// `X-Weave-Quota-Remaining`: the remaining storage quota for the account,
+    /// in kilobytes. Can be negative if the account is already over quota.
+    pub quota_remaining_kb: Option<i64>,
+}
+
+impl QuotaSignals {
+    pub fn from_headers(headers: &Headers) -> QuotaSignals {
+        QuotaSignals {
+            quota_remaining_kb: headers
+                .get_as::<f64, _>(header_names::X_WEAVE_QUOTA_REMAINING)
+                .and_then(|r| r.ok())
+                .filter(|kb| kb.is_finite())
+                .map(|kb| kb.floor() as i64),
+        }
+    }
+}
+
+/// Controls how a `PostQueue` retries a POST which failed in a way that's
+/// likely to succeed if we try again - a transient 5xx. Retrying means we
+/// don't need to throw away the records already uploaded into the batch.
+///
+/// We don't retry a 412: the retry would be sent with the same
+/// X-If-Unmodified-Since, so it would fail the same way. The engine needs to
+/// fetch the new records and reconcile instead.
+///
+/// If the server asked us to back off, we wait at least as long as it asked
+/// before retrying, and don't retry at all if that's longer than `max_delay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of times we retry a single POST before giving up.
+    pub max_retries: u32,
+    /// The delay before the first retry. This doubles for each subsequent one.
+    pub base_delay: Duration,
+    /// The upper bound on the (pre-jitter) delay between retries.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before retry number `attempt` (starting at 1). We use
+    /// "equal jitter" - half the exponential delay, plus a random amount up to
+    /// the other half - so clients which failed together don't all retry
+    /// together.
+    fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        let delay = self
+            .base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        let mut bytes = [0u8; 4];
+        let jitter = match rc_crypto::rand::fill(&mut bytes) {
+            Ok(()) => f64::from(u32::from_le_bytes(bytes)) / f64::from(u32::MAX),
+            // Not worth failing the upload over.
+            Err(_) => 1.0,
+        };
+        delay / 2 + (delay / 2).mul_f64(jitter)
+    }
+}
+
+fn is_retryable(resp: &PostResponse) -> bool {
+    match resp {
+        Sync15ClientResponse::Error(ErrorResponse::ServerError { status, .. }) => matches!(
+            *status,
+            status_codes::INTERNAL_SERVER_ERROR
+                | status_codes::BAD_GATEWAY
+                | status_codes::SERVICE_UNAVAILABLE
+                | status_codes::GATEWAY_TIMEOUT
+        ),
+        _ => false,
+    }
 }
 
 pub type PostResponse = Sync15ClientResponse<UploadResult>;
@@ -160,6 +252,11 @@ pub struct PostQueue<Post, OnResponse> {
     queued: Vec<u8>,
     batch: BatchState,
     last_modified: ServerTimestamp,
+    retry_policy: RetryPolicy,
+    // The most recent quota signal from the server, reduced by what we've
+    // queued since.
+    bytes_remaining: Option<i64>,
+    paused: bool,
 }
 
 pub trait BatchPoster {
@@ -173,6 +270,23 @@ pub trait BatchPoster {
         commit: bool,
         queue: &PostQueue<P, O>,
     ) -> Result<PostResponse>;
+
+    /// Called to wait between retries of a failed POST. Overridden in tests
+    /// so they don't need to actually sleep.
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+
+    /// How long the server asked us to back off for, if it did.
+    fn required_wait(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Called before retrying a failed POST, so that we don't keep retrying
+    /// once the sync was interrupted.
+    fn err_if_interrupted(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 // We don't just use a FnMut here since we want to override it in mocking for RefCell<TestType>,
@@ -189,6 +303,8 @@ pub(crate) struct NormalResponseHandler {
     pub allow_failed: bool,
     pub pending_failed: Vec<Guid>,
     pub pending_success: Vec<Guid>,
+    /// Maps the ids of records the server rejected to why it rejected them.
+    pub failure_reasons: HashMap<Guid, String>,
 }
 
 impl NormalResponseHandler {
@@ -198,6 +314,7 @@ impl NormalResponseHandler {
             successful_ids: vec![],
             pending_failed: vec![],
             pending_success: vec![],
+            failure_reasons: HashMap::new(),
             allow_failed,
         }
     }
@@ -213,8 +330,9 @@ impl PostResponseHandler for NormalResponseHandler {
                 for id in record.success.iter() {
                     self.pending_success.push(id.clone());
                 }
-                for kv in record.failed.iter() {
-                    self.pending_failed.push(kv.0.clone());
+                for (id, reason) in record.failed.into_iter() {
+                    self.pending_failed.push(id.clone());
+                    self.failure_reasons.insert(id, reason);
                 }
                 if !mid_batch {
                     self.successful_ids.append(&mut self.pending_success);
@@ -248,6 +366,28 @@ where
             max_payload_bytes: config.max_record_payload_bytes,
             max_request_bytes: config.max_request_bytes,
             queued: Vec::new(),
+            retry_policy: RetryPolicy::default(),
+            bytes_remaining: None,
+            paused: false,
+        }
+    }
+
+    /// Returns true if the server told us it's out of quota, in which case
+    /// `enqueue()` won't accept any more records. Records which were already
+    /// queued are still uploaded by `flush()`.
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn quota_allows(&self, payload_length: usize) -> bool {
+        self.bytes_remaining
+            .map_or(true, |b| b >= payload_length as i64)
+    }
+
+    fn note_quota_signals(&mut self, quota: QuotaSignals) {
+        if let Some(kb) = quota.quota_remaining_kb {
+            self.bytes_remaining = Some(kb.saturating_mul(1024));
         }
     }
 
@@ -257,8 +397,17 @@ where
     }
 
     pub fn enqueue(&mut self, record: &EncryptedBso) -> Result<bool> {
+        if self.paused {
+            return Ok(false);
+        }
         let payload_length = record.payload.serialized_len();
 
+        if !self.quota_allows(payload_length) {
+            log::warn!("Server storage quota reached; pausing uploads");
+            self.paused = true;
+            return Ok(false);
+        }
+
         if self.post_limits.can_never_add(payload_length)
             || self.batch_limits.can_never_add(payload_length)
             || payload_length >= self.max_payload_bytes
@@ -330,6 +479,9 @@ where
 
         self.post_limits.record_added(payload_length);
         self.batch_limits.record_added(payload_length);
+        if let Some(bytes) = self.bytes_remaining.as_mut() {
+            *bytes -= payload_length as i64;
+        }
 
         Ok(true)
     }
@@ -361,14 +513,7 @@ where
         );
 
         let is_commit = want_commit && batch_id.is_some();
-        // Weird syntax for calling a function object that is a property.
-        let resp_or_error = self.poster.post(
-            self.queued.clone(),
-            self.last_modified,
-            batch_id,
-            is_commit,
-            self,
-        );
+        let resp_or_error = self.post_with_retry(batch_id, is_commit);
 
         self.queued.truncate(0);
 
@@ -385,7 +530,10 @@ where
                 last_modified,
                 ref record,
                 ..
-            } => (status, last_modified, record),
+            } => {
+                self.note_quota_signals(record.quota);
+                (status, last_modified, record)
+            }
             _ => {
                 self.on_response.handle_response(resp, !want_commit)?;
                 // on_response() should always fail!
@@ -450,12 +598,52 @@ where
 
         Ok(())
     }
+
+    fn post_with_retry(&self, batch_id: Option<String>, is_commit: bool) -> Result<PostResponse> {
+        let mut attempt = 0;
+        loop {
+            let resp = self.poster.post(
+                self.queued.clone(),
+                self.last_modified,
+                batch_id.clone(),
+                is_commit,
+                self,
+            )?;
+            if attempt >= self.retry_policy.max_retries || !is_retryable(&resp) {
+                return Ok(resp);
+            }
+            attempt += 1;
+            let mut delay = self.retry_policy.delay_for_attempt(attempt);
+            if let Some(wait) = self.poster.required_wait() {
+                if wait > self.retry_policy.max_delay {
+                    log::warn!(
+                        "POST failed and the server asked us to back off for {:?}; not retrying",
+                        wait
+                    );
+                    return Ok(resp);
+                }
+                delay = delay.max(wait);
+            }
+            log::warn!(
+                "POST failed with a retryable error; retry {} of {} in {:?}",
+                attempt,
+                self.retry_policy.max_retries,
+                delay
+            );
+            self.poster.err_if_interrupted()?;
+            self.poster.sleep(delay);
+            self.poster.err_if_interrupted()?;
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct UploadInfo {
     pub successful_ids: Vec<Guid>,
     pub failed_ids: Vec<Guid>,
+    /// Why records in `failed_ids` failed, where we know. Records which were
+    /// part of a batch that was never committed have no entry here.
+    pub failure_reasons: HashMap<Guid, String>,
     pub modified_timestamp: ServerTimestamp,
 }
 
@@ -469,6 +657,7 @@ impl<Poster> PostQueue<Poster, NormalResponseHandler> {
                     + self.on_response.pending_failed.len()
                     + self.on_response.pending_success.len(),
             ),
+            failure_reasons: std::mem::take(&mut self.on_response.failure_reasons),
             modified_timestamp: self.last_modified,
         };
 
@@ -581,6 +770,9 @@ mod test {
         batches: Vec<BatchInfo>,
         cur_batch: Option<BatchInfo>,
         cfg: InfoConfiguration,
+        sleeps: Vec<Duration>,
+        required_wait: Option<Duration>,
+        interrupted: bool,
     }
 
     type TestPosterRef = Rc<RefCell<TestPoster>>;
@@ -595,6 +787,9 @@ mod test {
                 batches: vec![],
                 cur_batch: None,
                 cfg: cfg.clone(),
+                sleeps: vec![],
+                required_wait: None,
+                interrupted: false,
             }))
         }
         // Adds &mut
@@ -647,9 +842,8 @@ mod test {
 
             let record = match response {
                 Sync15ClientResponse::Success { ref record, .. } => record,
-                _ => {
-                    panic!("only success codes are used in this test");
-                }
+                // Failed posts don't change the state of the batch.
+                Sync15ClientResponse::Error(_) => return response,
             };
 
             if self.cur_batch.is_none() {
@@ -707,10 +901,28 @@ mod test {
         ) -> Result<PostResponse> {
             Ok(self.borrow_mut().do_post(&body, xius, batch, commit, queue))
         }
+
+        fn sleep(&self, duration: Duration) {
+            self.borrow_mut().sleeps.push(duration);
+        }
+
+        fn required_wait(&self) -> Option<Duration> {
+            self.borrow().required_wait
+        }
+
+        fn err_if_interrupted(&self) -> Result<()> {
+            if self.borrow().interrupted {
+                return Err(interrupt_support::Interrupted.into());
+            }
+            Ok(())
+        }
     }
 
     impl PostResponseHandler for TestPosterRef {
         fn handle_response(&mut self, r: PostResponse, mid_batch: bool) -> Result<()> {
+            if let Sync15ClientResponse::Error(_) = r {
+                return Err(r.create_storage_error().into());
+            }
             self.borrow_mut().do_handle_response(r, mid_batch);
             Ok(())
         }
@@ -737,11 +949,28 @@ mod test {
                 batch: batch.into().map(Into::into),
                 failed: HashMap::new(),
                 success: vec![],
+                quota: QuotaSignals::default(),
             },
             route: "test/path".into(),
         }
     }
 
+    fn fake_error_response(status: u16) -> PostResponse {
+        let route = "test/path".to_string();
+        Sync15ClientResponse::Error(match status {
+            412 => ErrorResponse::PreconditionFailed { route },
+            500..=600 => ErrorResponse::ServerError { route, status },
+            _ => ErrorResponse::RequestFailed { route, status },
+        })
+    }
+
+    fn with_quota(mut resp: PostResponse, quota: QuotaSignals) -> PostResponse {
+        if let Sync15ClientResponse::Success { ref mut record, .. } = resp {
+            record.quota = quota;
+        }
+        resp
+    }
+
     lazy_static! {
         // ~40b
         static ref PAYLOAD_OVERHEAD: usize = {
@@ -1246,4 +1475,289 @@ mod test {
     // - mixed bytes/record limits
     //
     // A lot of these have good examples in test_postqueue.js on deskftop sync
+
+    #[test]
+    fn test_pq_retry_mid_batch() {
+        let cfg = InfoConfiguration {
+            max_post_records: 1,
+            ..InfoConfiguration::default()
+        };
+        let time = 11_111_111_000;
+        let (mut pq, tester) = pq_test_setup(
+            cfg,
+            time,
+            vec![
+                fake_response(status_codes::ACCEPTED, time, Some("1234")),
+                fake_error_response(status_codes::SERVICE_UNAVAILABLE),
+                fake_error_response(status_codes::BAD_GATEWAY),
+                fake_response(status_codes::ACCEPTED, time, Some("1234")),
+                fake_response(status_codes::ACCEPTED, time + 100_000, Some("1234")),
+            ],
+        );
+
+        pq.enqueue(&make_record(100)).unwrap();
+        // POST
+        pq.enqueue(&make_record(100)).unwrap();
+        // POST, fails twice and then succeeds.
+        pq.enqueue(&make_record(100)).unwrap();
+        pq.flush(true).unwrap(); // COMMIT
+
+        let t = tester.borrow();
+        assert!(t.cur_batch.is_none());
+        assert_eq!(t.all_posts.len(), 5);
+        assert_eq!(t.batches.len(), 1);
+        assert_eq!(t.batches[0].posts.len(), 3);
+        assert_eq!(t.batches[0].records, 3);
+        // The retries are the same request.
+        assert_eq!(t.all_posts[1].body, t.all_posts[2].body);
+        assert_eq!(t.all_posts[1].body, t.all_posts[3].body);
+        assert_eq!(t.all_posts[3].batch.as_ref().unwrap(), "1234");
+
+        let policy = RetryPolicy::default();
+        assert_eq!(t.sleeps.len(), 2);
+        assert!(t.sleeps[0] >= policy.base_delay / 2 && t.sleeps[0] <= policy.base_delay);
+        assert!(t.sleeps[1] >= policy.base_delay && t.sleeps[1] <= policy.base_delay * 2);
+        assert_eq!(pq.last_modified.0, time + 100_000);
+    }
+
+    #[test]
+    fn test_pq_retries_exhausted() {
+        let cfg = InfoConfiguration {
+            max_post_records: 1,
+            ..InfoConfiguration::default()
+        };
+        let time = 11_111_111_000;
+        let (mut pq, tester) = pq_test_setup(
+            cfg,
+            time,
+            vec![
+                fake_response(status_codes::ACCEPTED, time, Some("1234")),
+                fake_error_response(status_codes::INTERNAL_SERVER_ERROR),
+                fake_error_response(status_codes::BAD_GATEWAY),
+                fake_error_response(status_codes::GATEWAY_TIMEOUT),
+            ],
+        );
+        pq.retry_policy = RetryPolicy {
+            max_retries: 2,
+            ..RetryPolicy::default()
+        };
+
+        pq.enqueue(&make_record(100)).unwrap();
+        pq.enqueue(&make_record(100)).unwrap();
+        let err = pq.flush(true).unwrap_err();
+        match err.kind() {
+            ErrorKind::StorageHttpError(ErrorResponse::ServerError { status, .. }) => {
+                assert_eq!(*status, status_codes::GATEWAY_TIMEOUT)
+            }
+            e => panic!("Unexpected error {:?}", e),
+        }
+
+        let t = tester.borrow();
+        assert_eq!(t.all_posts.len(), 4);
+        assert_eq!(t.sleeps.len(), 2);
+    }
+
+    #[test]
+    fn test_pq_no_retry() {
+        let time = 11_111_111_000;
+        // A 412 means the collection changed under us.
+        let (mut pq, tester) = pq_test_setup(
+            InfoConfiguration::default(),
+            time,
+            vec![fake_error_response(status_codes::PRECONDITION_FAILED)],
+        );
+        pq.enqueue(&make_record(100)).unwrap();
+        pq.flush(true).unwrap_err();
+        assert_eq!(tester.borrow().all_posts.len(), 1);
+        assert!(tester.borrow().sleeps.is_empty());
+
+        // Even in the middle of a batch, since retrying would send the same
+        // X-If-Unmodified-Since.
+        let cfg = InfoConfiguration {
+            max_post_records: 1,
+            ..InfoConfiguration::default()
+        };
+        let (mut pq, tester) = pq_test_setup(
+            cfg,
+            time,
+            vec![
+                fake_response(status_codes::ACCEPTED, time, Some("1234")),
+                fake_error_response(status_codes::PRECONDITION_FAILED),
+            ],
+        );
+        pq.enqueue(&make_record(100)).unwrap();
+        pq.enqueue(&make_record(100)).unwrap();
+        let err = pq.flush(true).unwrap_err();
+        match err.kind() {
+            ErrorKind::StorageHttpError(ErrorResponse::PreconditionFailed { .. }) => {}
+            e => panic!("Unexpected error {:?}", e),
+        }
+        assert_eq!(tester.borrow().all_posts.len(), 2);
+        assert!(tester.borrow().sleeps.is_empty());
+
+        // And a 4xx is never retried.
+        let (mut pq, tester) = pq_test_setup(
+            InfoConfiguration::default(),
+            time,
+            vec![fake_error_response(status_codes::BAD_REQUEST)],
+        );
+        pq.enqueue(&make_record(100)).unwrap();
+        pq.flush(true).unwrap_err();
+        assert_eq!(tester.borrow().all_posts.len(), 1);
+        assert!(tester.borrow().sleeps.is_empty());
+    }
+
+    #[test]
+    fn test_pq_retry_backoff() {
+        let time = 11_111_111_000;
+        // The server asked us to wait longer than we're willing to, so we
+        // don't retry.
+        let (mut pq, tester) = pq_test_setup(
+            InfoConfiguration::default(),
+            time,
+            vec![fake_error_response(status_codes::SERVICE_UNAVAILABLE)],
+        );
+        tester.borrow_mut().required_wait = Some(Duration::from_secs(60));
+        pq.enqueue(&make_record(100)).unwrap();
+        pq.flush(true).unwrap_err();
+        assert_eq!(tester.borrow().all_posts.len(), 1);
+        assert!(tester.borrow().sleeps.is_empty());
+
+        // A shorter wait is honored.
+        let (mut pq, tester) = pq_test_setup(
+            InfoConfiguration::default(),
+            time,
+            vec![
+                fake_error_response(status_codes::SERVICE_UNAVAILABLE),
+                fake_response(status_codes::OK, time + 100_000, None),
+            ],
+        );
+        tester.borrow_mut().required_wait = Some(Duration::from_secs(5));
+        pq.enqueue(&make_record(100)).unwrap();
+        pq.flush(true).unwrap();
+        let t = tester.borrow();
+        assert_eq!(t.all_posts.len(), 2);
+        assert_eq!(t.sleeps, vec![Duration::from_secs(5)]);
+    }
+
+    #[test]
+    fn test_pq_retry_interrupted() {
+        let time = 11_111_111_000;
+        let (mut pq, tester) = pq_test_setup(
+            InfoConfiguration::default(),
+            time,
+            vec![fake_error_response(status_codes::SERVICE_UNAVAILABLE)],
+        );
+        tester.borrow_mut().interrupted = true;
+        pq.enqueue(&make_record(100)).unwrap();
+        let err = pq.flush(true).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Interrupted(_)));
+        assert_eq!(tester.borrow().all_posts.len(), 1);
+        assert!(tester.borrow().sleeps.is_empty());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+        };
+        assert!(policy.delay_for_attempt(1) <= Duration::from_secs(1));
+        assert!(policy.delay_for_attempt(3) >= Duration::from_secs(2));
+        assert!(policy.delay_for_attempt(3) <= Duration::from_secs(4));
+        for attempt in 4..40 {
+            let delay = policy.delay_for_attempt(attempt);
+            assert!(delay >= Duration::from_millis(2500) && delay <= policy.max_delay);
+        }
+    }
+
+    #[test]
+    fn test_pq_quota_bytes() {
+        let cfg = InfoConfiguration {
+            max_post_records: 1,
+            ..InfoConfiguration::default()
+        };
+        let time = 11_111_111_000;
+        let quota = QuotaSignals {
+            quota_remaining_kb: Some(1),
+        };
+        let (mut pq, tester) = pq_test_setup(
+            cfg,
+            time,
+            vec![
+                with_quota(
+                    fake_response(status_codes::ACCEPTED, time, Some("1234")),
+                    quota,
+                ),
+                fake_response(status_codes::ACCEPTED, time + 100_000, Some("1234")),
+            ],
+        );
+
+        assert!(pq.enqueue(&make_record(600)).unwrap());
+        // POST, and the server says we have 1KB left.
+        assert!(pq.enqueue(&make_record(400)).unwrap());
+        assert!(!pq.is_paused());
+        // 1024 - 400 bytes left, so this doesn't fit.
+        assert!(!pq.enqueue(&make_record(700)).unwrap());
+        assert!(pq.is_paused());
+        // Once paused, we stay paused.
+        assert!(!pq.enqueue(&make_record(10)).unwrap());
+        // But what's already queued is still committed.
+        pq.flush(true).unwrap();
+
+        let t = tester.borrow();
+        assert_eq!(t.batches.len(), 1);
+        assert_eq!(t.batches[0].records, 2);
+        assert!(t.batches[0].posts[1].commit);
+    }
+
+    #[test]
+    fn test_quota_signals_from_headers() {
+        let mut headers = Headers::new();
+        assert_eq!(
+            QuotaSignals::from_headers(&headers),
+            QuotaSignals::default()
+        );
+        // X-Weave-Records is the number of records in a GET response, so
+        // doesn't tell us anything about what we can upload.
+        headers
+            .insert(header_names::X_WEAVE_RECORDS, "10")
+            .unwrap()
+            .insert(header_names::X_WEAVE_QUOTA_REMAINING, "-1.5")
+            .unwrap();
+        assert_eq!(
+            QuotaSignals::from_headers(&headers),
+            QuotaSignals {
+                quota_remaining_kb: Some(-2),
+            }
+        );
+    }
+
+    #[test]
+    fn test_upload_info_failure_reasons() {
+        let time = 11_111_111_000;
+        let cfg = InfoConfiguration::default();
+        let mut resp = fake_response(status_codes::OK, time + 100_000, None);
+        if let Sync15ClientResponse::Success { ref mut record, .. } = resp {
+            record.success = vec!["good".into()];
+            record.failed.insert("bad".into(), "invalid bso".into());
+        }
+        let tester = TestPoster::new(&cfg, vec![resp]);
+        let mut pq = PostQueue::new(
+            &cfg,
+            ServerTimestamp(time),
+            tester,
+            NormalResponseHandler::new(true),
+        );
+        pq.enqueue(&make_record(100)).unwrap();
+        pq.enqueue(&make_record(100)).unwrap();
+        pq.flush(true).unwrap();
+
+        let info = pq.completed_upload_info();
+        assert_eq!(info.successful_ids, vec![Guid::from("good")]);
+        assert_eq!(info.failed_ids, vec![Guid::from("bad")]);
+        assert_eq!(info.failure_reasons.len(), 1);
+        assert_eq!(info.failure_reasons[&Guid::from("bad")], "invalid bso");
+    }
 }
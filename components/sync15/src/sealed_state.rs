@@ -0,0 +1,197 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Support for encrypting the persisted global state at rest.
+//!
+//! Apps are expected to store the string we hand back as the "persisted
+//! state" somewhere, typically in preferences. Rather than leaving that
+//! readable by anything which can read those preferences, apps can supply a
+//! key and store the "sealed" version returned by `seal_persisted_state()`
+//! instead, then `unseal_persisted_state()` it before handing it back to us.
+//!
+//! The sealed form is itself JSON with a version tag, so we can change the
+//! format in the future. Unsealing a string which was never sealed (ie, the
+//! plaintext JSON written by versions which didn't support sealing) returns it
+//! unchanged, so apps can migrate by just sealing whatever they get back from
+//! the next sync.
+
+use crate::error::{ErrorKind, Result};
+use rc_crypto::{aead, rand};
+use serde_derive::*;
+
+/// The AEAD used to seal the state. The key supplied by the app must be
+/// `PERSISTED_STATE_KEY_LEN` bytes, base64url encoded without padding.
+static ALGORITHM: &aead::Algorithm = &aead::AES_256_GCM;
+
+pub const PERSISTED_STATE_KEY_LEN: usize = 32;
+
+// Authenticated, but not encrypted - it binds the ciphertext to its purpose
+// so that it can't be confused with something else sealed using the same key.
+const SEALED_STATE_AAD: &[u8] = b"sync15-persisted-state";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "sealed_version")]
+enum SealedState {
+    /// AES-256-GCM, with the nonce and ciphertext (with its tag appended)
+    /// base64url encoded.
+    V1 { nonce: String, ciphertext: String },
+}
+
+fn decode_key(key: &str) -> Result<Vec<u8>> {
+    let key_bytes = base64::decode_config(key, base64::URL_SAFE_NO_PAD)?;
+    if key_bytes.len() != PERSISTED_STATE_KEY_LEN {
+        return Err(ErrorKind::BadKeyLength(
+            "persisted_state_key",
+            key_bytes.len(),
+            PERSISTED_STATE_KEY_LEN,
+        )
+        .into());
+    }
+    Ok(key_bytes)
+}
+
+/// Create a new random key suitable for passing to `seal_persisted_state()`.
+/// Apps should store this somewhere more secure than where they store the
+/// sealed state - eg, the Android keystore or the iOS keychain.
+pub fn create_persisted_state_key() -> Result<String> {
+    let mut key = [0u8; PERSISTED_STATE_KEY_LEN];
+    rand::fill(&mut key)?;
+    Ok(base64::encode_config(&key, base64::URL_SAFE_NO_PAD))
+}
+
+/// Check that `key` can be used to seal and unseal the persisted state,
+/// without sealing anything. Callers which only seal the state after doing
+/// other work can use this to fail before doing it.
+pub fn check_persisted_state_key(key: &str) -> Result<()> {
+    decode_key(key).map(|_| ())
+}
+
+/// Encrypt the persisted state string returned by a sync using `key`.
+pub fn seal_persisted_state(state: &str, key: &str) -> Result<String> {
+    // An empty state means "no state", so there's nothing worth protecting.
+    if state.is_empty() {
+        return Ok(String::new());
+    }
+    let key = aead::SealingKey::new(ALGORITHM, &decode_key(key)?)?;
+    let mut nonce_bytes = vec![0u8; ALGORITHM.nonce_len()];
+    rand::fill(&mut nonce_bytes)?;
+    let nonce = aead::Nonce::try_assume_unique_for_key(ALGORITHM, &nonce_bytes)?;
+    let ciphertext = aead::seal(
+        &key,
+        nonce,
+        aead::Aad::from(SEALED_STATE_AAD),
+        state.as_bytes(),
+    )?;
+    let sealed = SealedState::V1 {
+        nonce: base64::encode_config(&nonce_bytes, base64::URL_SAFE_NO_PAD),
+        ciphertext: base64::encode_config(&ciphertext, base64::URL_SAFE_NO_PAD),
+    };
+    Ok(serde_json::to_string(&sealed)?)
+}
+
+/// Decrypt a string previously returned by `seal_persisted_state()`, so it
+/// can be passed back to a sync. Strings which were never sealed are returned
+/// as-is.
+pub fn unseal_persisted_state(sealed: &str, key: &str) -> Result<String> {
+    if sealed.is_empty() {
+        return Ok(String::new());
+    }
+    let value: serde_json::Value = serde_json::from_str(sealed)?;
+    if value.get("sealed_version").is_none() {
+        // Not sealed, so this must be state persisted before the app started
+        // sealing it. There's nothing sensitive in it we need to check.
+        log::info!("Persisted state isn't sealed; using it as-is");
+        return Ok(sealed.to_string());
+    }
+    match serde_json::from_value::<SealedState>(value) {
+        Ok(SealedState::V1 { nonce, ciphertext }) => {
+            let key = aead::OpeningKey::new(ALGORITHM, &decode_key(key)?)?;
+            let nonce_bytes = base64::decode_config(&nonce, base64::URL_SAFE_NO_PAD)?;
+            let nonce = aead::Nonce::try_assume_unique_for_key(ALGORITHM, &nonce_bytes)?;
+            let ciphertext = base64::decode_config(&ciphertext, base64::URL_SAFE_NO_PAD)?;
+            // Don't log the underlying error - the most likely reason for
+            // this is the app giving us the wrong key.
+            let plaintext = aead::open(&key, nonce, aead::Aad::from(SEALED_STATE_AAD), &ciphertext)
+                .map_err(|_| ErrorKind::UnsealPersistedStateError)?;
+            Ok(String::from_utf8(plaintext)?)
+        }
+        Err(_) => {
+            log::error!("Persisted state has an unknown sealed_version");
+            Err(ErrorKind::UnsealPersistedStateError.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const STATE: &str = r#"{"schema_version":"V2","declined":["bookmarks"]}"#;
+
+    #[test]
+    fn test_roundtrip() {
+        rc_crypto::ensure_initialized();
+        let key = create_persisted_state_key().unwrap();
+        let sealed = seal_persisted_state(STATE, &key).unwrap();
+        assert!(!sealed.contains("bookmarks"));
+        assert_eq!(unseal_persisted_state(&sealed, &key).unwrap(), STATE);
+        // Nonces are random, so sealing twice gives different results.
+        assert_ne!(seal_persisted_state(STATE, &key).unwrap(), sealed);
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        rc_crypto::ensure_initialized();
+        let key = create_persisted_state_key().unwrap();
+        let other_key = create_persisted_state_key().unwrap();
+        let sealed = seal_persisted_state(STATE, &key).unwrap();
+        let err = unseal_persisted_state(&sealed, &other_key).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::UnsealPersistedStateError));
+    }
+
+    #[test]
+    fn test_bad_key_length() {
+        rc_crypto::ensure_initialized();
+        let key = base64::encode_config(&[0u8; 16], base64::URL_SAFE_NO_PAD);
+        let err = seal_persisted_state(STATE, &key).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::BadKeyLength("persisted_state_key", 16, 32)
+        ));
+    }
+
+    #[test]
+    fn test_check_key() {
+        rc_crypto::ensure_initialized();
+        let key = create_persisted_state_key().unwrap();
+        check_persisted_state_key(&key).unwrap();
+        // Sealing an empty state doesn't look at the key, but checking it does.
+        let short_key = base64::encode_config(&[0u8; 16], base64::URL_SAFE_NO_PAD);
+        let err = check_persisted_state_key(&short_key).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::BadKeyLength("persisted_state_key", 16, 32)
+        ));
+        let err = check_persisted_state_key("not base64!").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Base64Decode(_)));
+    }
+
+    #[test]
+    fn test_migrate_unsealed() {
+        rc_crypto::ensure_initialized();
+        let key = create_persisted_state_key().unwrap();
+        assert_eq!(unseal_persisted_state(STATE, &key).unwrap(), STATE);
+        assert_eq!(unseal_persisted_state("", &key).unwrap(), "");
+        assert_eq!(seal_persisted_state("", &key).unwrap(), "");
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        rc_crypto::ensure_initialized();
+        let key = create_persisted_state_key().unwrap();
+        let err =
+            unseal_persisted_state(r#"{"sealed_version":"V99","blob":"x"}"#, &key).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::UnsealPersistedStateError));
+    }
+}
@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use crate::bso_record::EncryptedBso;
 use crate::client::{SetupStorageClient, Sync15ClientResponse};
@@ -48,9 +48,13 @@ const DEFAULT_DECLINED: &[&str] = &[];
 /// allowing engines to be enabled or disabled per client rather than globally.
 ///
 /// Apps are expected to treat this as opaque, so we support serializing it.
-/// Note that this structure is *not* used to *change* the declined engines
-/// list - that will be done in the future, but the API exposed for that
-/// purpose will also take a mutable PersistedGlobalState.
+/// Apps which want the serialized form encrypted at rest should use
+/// `seal_persisted_state()` and `unseal_persisted_state()`.
+///
+/// This is also where we track engines the user has enabled or disabled
+/// (via `set_engine_enabled()`) but which we haven't yet reflected in
+/// meta/global - so a change made while offline, or which fails to upload,
+/// is retried on the next sync rather than being lost.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "schema_version")]
 pub enum PersistedGlobalState {
@@ -59,13 +63,22 @@ pub enum PersistedGlobalState {
     /// V2 is just tracking the globally declined list.
     /// None means "I've no idea" and theoretically should only happen on the
     /// very first sync for an app.
-    V2 { declined: Option<Vec<String>> },
+    V2 {
+        declined: Option<Vec<String>>,
+        /// Engines the user has enabled (true) or disabled (false) since
+        /// meta/global was last updated.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pending_engine_changes: BTreeMap<String, bool>,
+    },
 }
 
 impl Default for PersistedGlobalState {
     #[inline]
     fn default() -> PersistedGlobalState {
-        PersistedGlobalState::V2 { declined: None }
+        PersistedGlobalState::V2 {
+            declined: None,
+            pending_engine_changes: BTreeMap::new(),
+        }
     }
 }
 
@@ -146,13 +159,63 @@ fn compute_engine_states(input: EngineStateInput) -> EngineStateOutput {
 impl PersistedGlobalState {
     fn set_declined(&mut self, new_declined: Vec<String>) {
         match self {
-            Self::V2 { ref mut declined } => *declined = Some(new_declined),
+            Self::V2 {
+                ref mut declined, ..
+            } => *declined = Some(new_declined),
         }
     }
     pub(crate) fn get_declined(&self) -> &[String] {
         match self {
-            Self::V2 { declined: Some(d) } => d,
-            Self::V2 { declined: None } => &[],
+            Self::V2 {
+                declined: Some(d), ..
+            } => d,
+            Self::V2 { declined: None, .. } => &[],
+        }
+    }
+
+    /// Record that the user has enabled or disabled `engine`. The change is
+    /// applied to meta/global (and the engine reset or wiped as necessary)
+    /// on the next sync.
+    pub fn set_engine_enabled(&mut self, engine: &str, enabled: bool) {
+        match self {
+            Self::V2 {
+                pending_engine_changes,
+                ..
+            } => {
+                pending_engine_changes.insert(engine.to_string(), enabled);
+            }
+        }
+    }
+
+    /// Whether `engine` is enabled, taking into account changes which have
+    /// not yet been synced.
+    pub fn is_engine_enabled(&self, engine: &str) -> bool {
+        match self {
+            Self::V2 {
+                pending_engine_changes,
+                ..
+            } => match pending_engine_changes.get(engine) {
+                Some(enabled) => *enabled,
+                None => !self.get_declined().iter().any(|e| e == engine),
+            },
+        }
+    }
+
+    fn pending_engine_changes(&self) -> &BTreeMap<String, bool> {
+        match self {
+            Self::V2 {
+                pending_engine_changes,
+                ..
+            } => pending_engine_changes,
+        }
+    }
+
+    fn clear_pending_engine_changes(&mut self) {
+        match self {
+            Self::V2 {
+                pending_engine_changes,
+                ..
+            } => pending_engine_changes.clear(),
         }
     }
 }
@@ -190,7 +253,9 @@ fn new_global(pgs: &PersistedGlobalState) -> MetaGlobalRecord {
     // we previously saw a meta/global then we would have updated it with what
     // it was at the time.
     let declined = match pgs {
-        PersistedGlobalState::V2 { declined: Some(d) } => d.clone(),
+        PersistedGlobalState::V2 {
+            declined: Some(d), ..
+        } => d.clone(),
         _ => DEFAULT_DECLINED.iter().map(ToString::to_string).collect(),
     };
 
@@ -345,6 +410,22 @@ impl<'a> SetupStateMachine<'a> {
         }
     }
 
+    // The engines the user wants enabled or disabled - both those persisted
+    // from earlier calls to `set_engine_enabled()`, and those passed in for
+    // this sync (which take precedence).
+    fn user_engine_changes(&self) -> HashMap<String, bool> {
+        let mut changes: HashMap<String, bool> = self
+            .pgs
+            .pending_engine_changes()
+            .iter()
+            .map(|(k, v)| (k.clone(), *v))
+            .collect();
+        if let Some(updates) = self.engine_updates {
+            changes.extend(updates.iter().map(|(k, v)| (k.clone(), *v)));
+        }
+        changes
+    }
+
     fn advance(&mut self, from: SetupState) -> error::Result<SetupState> {
         match from {
             // Fetch `info/configuration` with current server limits, and
@@ -409,7 +490,7 @@ impl<'a> SetupStateMachine<'a> {
                                 global.declined.iter().cloned().collect();
                             let result = compute_engine_states(EngineStateInput {
                                 local_declined: self.pgs.get_declined().iter().cloned().collect(),
-                                user_changes: self.engine_updates.cloned().unwrap_or_default(),
+                                user_changes: self.user_engine_changes(),
                                 remote: Some(RemoteEngineState {
                                     declined: initial_global_declined.clone(),
                                     info_collections: collections.keys().cloned().collect(),
@@ -446,6 +527,8 @@ impl<'a> SetupStateMachine<'a> {
                                     self.client.put_meta_global(global_timestamp, &global)?;
                                 log::debug!("new global_timestamp: {:?}", global_timestamp);
                             }
+                            // meta/global now reflects the user's changes.
+                            self.pgs.clear_pending_engine_changes();
                             // Update the set of changes needed.
                             if self.changes_needed.is_some() {
                                 // Should never happen (we prevent state machine
@@ -513,6 +596,7 @@ impl<'a> SetupStateMachine<'a> {
                     ..
                 } => Ok(
                     if self.engine_updates.is_none()
+                        && self.pgs.pending_engine_changes().is_empty()
                         && is_same_timestamp(old_state.global_timestamp, &collections, "meta")
                         && is_same_timestamp(old_state.keys.modified, &collections, "crypto")
                     {
@@ -544,7 +628,7 @@ impl<'a> SetupStateMachine<'a> {
                 log::info!("Uploading meta/global");
                 let computed = compute_engine_states(EngineStateInput {
                     local_declined: self.pgs.get_declined().iter().cloned().collect(),
-                    user_changes: self.engine_updates.cloned().unwrap_or_default(),
+                    user_changes: self.user_engine_changes(),
                     remote: None,
                 });
                 self.pgs
@@ -556,6 +640,7 @@ impl<'a> SetupStateMachine<'a> {
 
                 self.client
                     .put_meta_global(ServerTimestamp::default(), &new_global)?;
+                self.pgs.clear_pending_engine_changes();
 
                 // ...And a fresh `crypto/keys`.
                 let new_keys = CollectionKeys::new_random()?.to_encrypted_bso(self.root_key)?;
@@ -833,7 +918,7 @@ mod tests {
                 888_000,
             ),
         };
-        let mut pgs = PersistedGlobalState::V2 { declined: None };
+        let mut pgs = PersistedGlobalState::default();
 
         let mut state_machine =
             SetupStateMachine::for_full_sync(&client, &root_key, &mut pgs, None, &NeverInterrupts);
@@ -936,7 +1021,7 @@ mod tests {
 
         // First a test where the "previous" global state is OK to reuse.
         {
-            let mut pgs = PersistedGlobalState::V2 { declined: None };
+            let mut pgs = PersistedGlobalState::default();
             // A "previous" global state.
             let old_state = GlobalState {
                 config: InfoConfiguration::default(),
@@ -959,7 +1044,7 @@ mod tests {
 
         // Now where the meta/global record on the server is later.
         {
-            let mut pgs = PersistedGlobalState::V2 { declined: None };
+            let mut pgs = PersistedGlobalState::default();
             // A "previous" global state.
             let old_state = GlobalState {
                 config: InfoConfiguration::default(),
@@ -982,7 +1067,7 @@ mod tests {
 
         // Where keys on the server is later.
         {
-            let mut pgs = PersistedGlobalState::V2 { declined: None };
+            let mut pgs = PersistedGlobalState::default();
             // A "previous" global state.
             let old_state = GlobalState {
                 config: InfoConfiguration::default(),
@@ -1003,9 +1088,40 @@ mod tests {
             );
         }
 
+        // Where there are engine-state changes persisted by an earlier call to
+        // `set_engine_enabled()`.
+        {
+            let mut pgs = PersistedGlobalState::default();
+            pgs.set_engine_enabled("logins", false);
+            assert!(!pgs.is_engine_enabled("logins"));
+            // A "previous" global state.
+            let old_state = GlobalState {
+                config: InfoConfiguration::default(),
+                collections: collections.clone(),
+                global: mg.clone(),
+                global_timestamp: ServerTimestamp(ts_metaglobal),
+                keys: keys
+                    .to_encrypted_bso_with_timestamp(&root_key, ServerTimestamp(ts_keys))
+                    .expect("should always work in this test"),
+            };
+            do_test(
+                &client,
+                &root_key,
+                &mut pgs,
+                None,
+                old_state,
+                &sm_seq_restarted,
+            );
+            // logins is now declined, and the change is no longer pending.
+            assert_eq!(pgs.get_declined(), &["logins".to_string()]);
+            assert!(pgs.pending_engine_changes().is_empty());
+            assert!(!pgs.is_engine_enabled("logins"));
+            assert!(pgs.is_engine_enabled("bookmarks"));
+        }
+
         // Where there are engine-state changes.
         {
-            let mut pgs = PersistedGlobalState::V2 { declined: None };
+            let mut pgs = PersistedGlobalState::default();
             // A "previous" global state.
             let old_state = GlobalState {
                 config: InfoConfiguration::default(),
@@ -1027,7 +1143,7 @@ mod tests {
                 &sm_seq_restarted,
             );
             let declined = match pgs {
-                PersistedGlobalState::V2 { declined: d } => d,
+                PersistedGlobalState::V2 { declined: d, .. } => d,
             };
             // and check we now consider logins as declined.
             assert_eq!(declined, Some(vec!["logins".to_string()]));
@@ -8,6 +8,7 @@ use crate::clients;
 use crate::coll_state::LocalCollStateMachine;
 use crate::error::Error;
 use crate::key_bundle::KeyBundle;
+use crate::request::UploadInfo;
 use crate::state::GlobalState;
 use crate::telemetry;
 use interrupt_support::Interruptee;
@@ -106,7 +107,7 @@ pub fn synchronize_with_clients_engine(
     log::info!("Uploading {} outgoing changes", outgoing.changes.len());
     let upload_info =
         CollectionUpdate::new_from_changeset(client, &coll_state, outgoing, fully_atomic)?
-            .upload()?;
+            .upload(interruptee)?;
 
     log::info!(
         "Upload success ({} records success, {} records failed)",
@@ -120,8 +121,120 @@ pub fn synchronize_with_clients_engine(
     telem_outgoing.failed(upload_info.failed_ids.len());
     telem_engine.outgoing(telem_outgoing);
 
-    engine.sync_finished(upload_info.modified_timestamp, upload_info.successful_ids)?;
+    finish_upload(engine, upload_info)?;
 
     log::info!("Sync finished!");
     Ok(())
 }
+
+/// Tell `engine` which records were uploaded, and which failed.
+fn finish_upload(engine: &dyn SyncEngine, upload_info: UploadInfo) -> Result<(), Error> {
+    if !upload_info.failed_ids.is_empty() {
+        for (id, reason) in &upload_info.failure_reasons {
+            log::debug!("Failed to upload record {}: {}", id, reason);
+        }
+        engine.records_failed(upload_info.failed_ids, upload_info.failure_reasons)?;
+    }
+    engine.sync_finished(upload_info.modified_timestamp, upload_info.successful_ids)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::changeset::OutgoingChangeset;
+    use crate::coll_state::EngineSyncAssociation;
+    use crate::request::CollectionRequest;
+    use crate::util::ServerTimestamp;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use sync_guid::Guid;
+
+    #[derive(Default)]
+    struct TestSyncEngine {
+        failed: RefCell<Vec<(Guid, Option<String>)>>,
+        synced: RefCell<Vec<Guid>>,
+    }
+
+    impl SyncEngine for TestSyncEngine {
+        fn collection_name(&self) -> std::borrow::Cow<'static, str> {
+            "test".into()
+        }
+
+        fn apply_incoming(
+            &self,
+            _inbound: Vec<IncomingChangeset>,
+            _telem: &mut telemetry::Engine,
+        ) -> anyhow::Result<OutgoingChangeset> {
+            unreachable!("these tests shouldn't call these");
+        }
+
+        fn records_failed(
+            &self,
+            failed_ids: Vec<Guid>,
+            mut failure_reasons: HashMap<Guid, String>,
+        ) -> anyhow::Result<()> {
+            let mut failed = self.failed.borrow_mut();
+            for id in failed_ids {
+                let reason = failure_reasons.remove(&id);
+                failed.push((id, reason));
+            }
+            Ok(())
+        }
+
+        fn sync_finished(
+            &self,
+            _new_timestamp: ServerTimestamp,
+            records_synced: Vec<Guid>,
+        ) -> anyhow::Result<()> {
+            // The failures were reported first.
+            assert!(!self.failed.borrow().is_empty());
+            self.synced.borrow_mut().extend(records_synced);
+            Ok(())
+        }
+
+        fn get_collection_requests(
+            &self,
+            _server_timestamp: ServerTimestamp,
+        ) -> anyhow::Result<Vec<CollectionRequest>> {
+            unreachable!("these tests shouldn't call these");
+        }
+
+        fn get_sync_assoc(&self) -> anyhow::Result<EngineSyncAssociation> {
+            unreachable!("these tests shouldn't call these");
+        }
+
+        fn reset(&self, _assoc: &EngineSyncAssociation) -> anyhow::Result<()> {
+            unreachable!("these tests shouldn't call these");
+        }
+
+        fn wipe(&self) -> anyhow::Result<()> {
+            unreachable!("these tests shouldn't call these");
+        }
+    }
+
+    #[test]
+    fn test_finish_upload_reports_failures() {
+        let engine = TestSyncEngine::default();
+        let mut failure_reasons = HashMap::new();
+        failure_reasons.insert(Guid::from("bad"), "invalid bso".to_string());
+        finish_upload(
+            &engine,
+            UploadInfo {
+                successful_ids: vec![Guid::from("good")],
+                failed_ids: vec![Guid::from("bad"), Guid::from("uncommitted")],
+                failure_reasons,
+                modified_timestamp: ServerTimestamp(1000),
+            },
+        )
+        .expect("should work");
+        assert_eq!(
+            *engine.failed.borrow(),
+            vec![
+                (Guid::from("bad"), Some("invalid bso".to_string())),
+                (Guid::from("uncommitted"), None),
+            ]
+        );
+        assert_eq!(*engine.synced.borrow(), vec![Guid::from("good")]);
+    }
+}
@@ -113,6 +113,33 @@ pub fn sync_multiple(
     )
 }
 
+/// Enable or disable an engine. The change is recorded in
+/// `persisted_global_state`, which the app should persist as it would after a
+/// sync, and is applied on the next sync - which updates the declined list in
+/// `meta/global`, and resets or wipes the engine as appropriate. Unlike
+/// `SyncRequestInfo::engines_to_state_change`, the change isn't lost if that
+/// next sync fails; it's retried until `meta/global` has been updated.
+pub fn set_engine_enabled(
+    persisted_global_state: &mut Option<String>,
+    engine: &str,
+    enabled: bool,
+) -> result::Result<(), Error> {
+    let mut pgs = match persisted_global_state {
+        Some(persisted_string) if !persisted_string.is_empty() => serde_json::from_str::<
+            PersistedGlobalState,
+        >(persisted_string)
+        .unwrap_or_else(|_| {
+            log::error!("Failed to parse PersistedGlobalState from JSON! Falling back to default");
+            PersistedGlobalState::default()
+        }),
+        _ => PersistedGlobalState::default(),
+    };
+    log::info!("Setting engine {} enabled={}", engine, enabled);
+    pgs.set_engine_enabled(engine, enabled);
+    *persisted_global_state = Some(serde_json::to_string(&pgs)?);
+    Ok(())
+}
+
 /// Like `sync_multiple`, but specifies an optional command processor to handle
 /// commands from the clients collection. This function is called by the sync
 /// manager, which provides its own processor.
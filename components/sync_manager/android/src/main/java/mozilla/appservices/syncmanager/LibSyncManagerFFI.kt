@@ -22,6 +22,16 @@ internal interface LibSyncManagerFFI : Library {
 
     fun sync_manager_sync(data: Pointer, len: Int, error: RustError.ByReference): RustBuffer.ByValue
 
+    fun sync_manager_set_engine_enabled(
+        persistedState: String?,
+        persistedStateKey: String?,
+        engine: String,
+        enabled: Byte,
+        error: RustError.ByReference
+    ): Pointer?
+
+    fun sync_manager_create_persisted_state_key(error: RustError.ByReference): Pointer?
+
     fun sync_manager_destroy_string(s: Pointer)
     fun sync_manager_destroy_bytebuffer(bb: RustBuffer.ByValue)
 }
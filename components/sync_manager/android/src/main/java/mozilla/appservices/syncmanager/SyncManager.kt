@@ -42,6 +42,57 @@ object SyncManager {
             LibSyncManagerFFI.INSTANCE.sync_manager_disconnect(err)
         }
     }
+    /**
+     * Enable or disable syncing an engine for this account. The change is applied to the
+     * server on the next sync, and is retried on later syncs if that one fails.
+     *
+     * @param persistedState The persisted state from the last `SyncResult`, if any.
+     * @param persistedStateKey The key `persistedState` is sealed with, if any.
+     * @param engine The name of the engine, as used in `SyncParams.engines`.
+     * @param enabled Whether the engine should be synced.
+     * @return The new persisted state, which should be stored in place of `persistedState`
+     * and passed as `SyncParams.persistedState` for the next sync.
+     */
+    fun setEngineEnabled(
+        persistedState: String?,
+        persistedStateKey: String?,
+        engine: String,
+        enabled: Boolean
+    ): String {
+        val cstring = rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_set_engine_enabled(
+                persistedState,
+                persistedStateKey,
+                engine,
+                (if (enabled) 1 else 0).toByte(),
+                err
+            )
+        }!!
+        try {
+            return cstring.getString(0, "utf8")
+        } finally {
+            LibSyncManagerFFI.INSTANCE.sync_manager_destroy_string(cstring)
+        }
+    }
+
+    /**
+     * Create a new random key to use as `SyncParams.persistedStateKey`. It should be
+     * stored somewhere more secure than the persisted state itself, such as the
+     * Android keystore.
+     *
+     * @return The new key.
+     */
+    fun createPersistedStateKey(): String {
+        val cstring = rustCall { err ->
+            LibSyncManagerFFI.INSTANCE.sync_manager_create_persisted_state_key(err)
+        }!!
+        try {
+            return cstring.getString(0, "utf8")
+        } finally {
+            LibSyncManagerFFI.INSTANCE.sync_manager_destroy_string(cstring)
+        }
+    }
+
     /**
      * Perform a sync.
      */
@@ -131,7 +131,17 @@ data class SyncParams(
     /**
      * The information used to populate a client record for this device.
      */
-    val deviceSettings: DeviceSettings
+    val deviceSettings: DeviceSettings,
+
+    /**
+     * If non-null, `persistedState` is expected to have been sealed with this
+     * key, and `SyncResult.persistedState` will be sealed with it, so it
+     * doesn't need to be stored in plaintext. The key must be 32 random bytes,
+     * base64url encoded without padding, and should be stored somewhere more
+     * secure than the persisted state itself. Unsealed state from before a key
+     * was first supplied is accepted and will be sealed in the result.
+     */
+    val persistedStateKey: String? = null
 ) {
     @Suppress("ComplexMethod")
     internal fun toProtobuf(): MsgTypes.SyncParams {
@@ -161,6 +171,7 @@ data class SyncParams(
         builder.acctKeyId = this.authInfo.kid
         builder.acctTokenserverUrl = this.authInfo.tokenserverURL
         this.persistedState?.let { builder.persistedState = it }
+        this.persistedStateKey?.let { builder.persistedStateKey = it }
 
         builder.fxaDeviceId = this.deviceSettings.fxaDeviceId
         builder.deviceName = this.deviceSettings.name
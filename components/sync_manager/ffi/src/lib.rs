@@ -8,7 +8,8 @@
 // the closure is small.
 #![allow(clippy::redundant_closure)]
 
-use ffi_support::{ExternError, HandleError};
+use ffi_support::{ExternError, FfiStr, HandleError};
+use std::os::raw::c_char;
 use sync_manager::Result as MgrResult;
 
 #[no_mangle]
@@ -58,5 +59,32 @@ pub unsafe extern "C" fn sync_manager_sync(
     })
 }
 
+#[no_mangle]
+pub extern "C" fn sync_manager_set_engine_enabled(
+    persisted_state: FfiStr<'_>,
+    persisted_state_key: FfiStr<'_>,
+    engine: FfiStr<'_>,
+    enabled: u8,
+    error: &mut ExternError,
+) -> *mut c_char {
+    ffi_support::call_with_result(error, || {
+        log::debug!("sync_manager_set_engine_enabled");
+        sync_manager::set_engine_enabled(
+            persisted_state.into_opt_string(),
+            persisted_state_key.as_opt_str(),
+            engine.as_str(),
+            enabled != 0,
+        )
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sync_manager_create_persisted_state_key(error: &mut ExternError) -> *mut c_char {
+    ffi_support::call_with_result(error, || {
+        log::debug!("sync_manager_create_persisted_state_key");
+        sync_manager::create_persisted_state_key()
+    })
+}
+
 ffi_support::define_string_destructor!(sync_manager_destroy_string);
 ffi_support::define_bytebuffer_destructor!(sync_manager_destroy_bytebuffer);
@@ -53,3 +53,34 @@ pub fn sync(params: msg_types::SyncParams) -> Result<msg_types::SyncResult> {
     let mut manager = MANAGER.lock().unwrap();
     manager.sync(params)
 }
+
+/// Create a new random key to pass as `SyncParams.persisted_state_key`. Apps
+/// should store it somewhere more secure than where they store the persisted
+/// state.
+pub fn create_persisted_state_key() -> Result<String> {
+    Ok(sync15::create_persisted_state_key()?)
+}
+
+/// Enable or disable `engine`, returning the new persisted state, which the
+/// app should store in place of `persisted_state` (the state from the last
+/// `SyncResult`). The change takes effect on the next sync. If
+/// `persisted_state_key` is supplied, `persisted_state` is expected to be
+/// sealed with it (as it is for `SyncParams.persisted_state_key`), and the
+/// returned state will be too.
+pub fn set_engine_enabled(
+    persisted_state: Option<String>,
+    persisted_state_key: Option<&str>,
+    engine: &str,
+    enabled: bool,
+) -> Result<String> {
+    let mut state = match (persisted_state, persisted_state_key) {
+        (Some(sealed), Some(key)) => Some(sync15::unseal_persisted_state(&sealed, key)?),
+        (state, _) => state,
+    };
+    sync15::set_engine_enabled(&mut state, engine, enabled)?;
+    let state = state.unwrap_or_default();
+    Ok(match persisted_state_key {
+        Some(key) => sync15::seal_persisted_state(&state, key)?,
+        None => state,
+    })
+}
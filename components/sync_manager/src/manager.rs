@@ -23,6 +23,7 @@ const BOOKMARKS_ENGINE: &str = "bookmarks";
 const TABS_ENGINE: &str = "tabs";
 const ADDRESSES_ENGINE: &str = "addresses";
 const CREDIT_CARDS_ENGINE: &str = "creditcards";
+const WEBEXT_STORAGE_ENGINE: &str = webext_storage::STORAGE_SYNC_COLLECTION;
 
 // Casts aren't allowed in `match` arms, so we can't directly match
 // `SyncParams.device_type`, which is an `i32`, against `DeviceType`
@@ -70,6 +71,10 @@ impl SyncManager {
         tabs::get_registered_sync_engine(engine)
     }
 
+    pub fn webext_storage_engine(engine: &str) -> Option<Box<dyn SyncEngine>> {
+        webext_storage::get_registered_sync_engine(engine)
+    }
+
     pub fn wipe(&mut self, engine: &str) -> Result<()> {
         match engine {
             "logins" => {
@@ -100,6 +105,12 @@ impl SyncManager {
                 }
                 Ok(())
             }
+            WEBEXT_STORAGE_ENGINE => {
+                if let Some(engine) = Self::webext_storage_engine(engine) {
+                    engine.wipe()?;
+                }
+                Ok(())
+            }
             _ => Err(ErrorKind::UnknownEngine(engine.into()).into()),
         }
     }
@@ -130,6 +141,12 @@ impl SyncManager {
                 }
                 Ok(())
             }
+            WEBEXT_STORAGE_ENGINE => {
+                if let Some(engine) = Self::webext_storage_engine(engine) {
+                    engine.reset(&EngineSyncAssociation::Disconnected)?;
+                }
+                Ok(())
+            }
             _ => Err(ErrorKind::UnknownEngine(engine.into()).into()),
         }
     }
@@ -145,6 +162,9 @@ impl SyncManager {
         if let Some(credit_cards) = Self::autofill_engine("creditcards") {
             credit_cards.reset(&EngineSyncAssociation::Disconnected)?;
         }
+        if let Some(webext_storage) = Self::webext_storage_engine(WEBEXT_STORAGE_ENGINE) {
+            webext_storage.reset(&EngineSyncAssociation::Disconnected)?;
+        }
         Ok(())
     }
 
@@ -180,6 +200,13 @@ impl SyncManager {
         } else {
             log::warn!("Unable to reset logins, be sure to call register_with_sync_manager before disconnect if this is surprising");
         }
+        if let Some(webext_storage) = Self::webext_storage_engine(WEBEXT_STORAGE_ENGINE) {
+            if let Err(e) = webext_storage.reset(&EngineSyncAssociation::Disconnected) {
+                log::error!("Failed to reset extension storage: {}", e);
+            }
+        } else {
+            log::warn!("Unable to reset extension storage, be sure to call register_with_sync_manager before disconnect if this is surprising");
+        }
     }
 
     pub fn sync(&mut self, params: SyncParams) -> Result<SyncResult> {
@@ -189,6 +216,7 @@ impl SyncManager {
         let logins = Self::logins_engine("logins");
         let addresses = Self::autofill_engine("addresses");
         let credit_cards = Self::autofill_engine("creditcards");
+        let webext_storage = Self::webext_storage_engine(WEBEXT_STORAGE_ENGINE);
         if places.is_some() {
             have_engines.push(HISTORY_ENGINE);
             have_engines.push(BOOKMARKS_ENGINE);
@@ -205,6 +233,9 @@ impl SyncManager {
         if credit_cards.is_some() {
             have_engines.push(CREDIT_CARDS_ENGINE);
         }
+        if webext_storage.is_some() {
+            have_engines.push(WEBEXT_STORAGE_ENGINE);
+        }
         check_engine_list(&params.engines_to_sync, &have_engines)?;
 
         let next_sync_after = self
@@ -239,9 +270,15 @@ impl SyncManager {
         let logins = Self::logins_engine("logins");
         let addresses = Self::autofill_engine("addresses");
         let credit_cards = Self::autofill_engine("creditcards");
+        let webext_storage = Self::webext_storage_engine(WEBEXT_STORAGE_ENGINE);
 
         let key_bundle = sync15::KeyBundle::from_ksync_base64(&params.acct_sync_key)?;
         let tokenserver_url = url::Url::parse(&params.acct_tokenserver_url)?;
+        // We only seal the new state once we've synced, so make sure we'll be
+        // able to before we start, instead of losing the result of the sync.
+        if let Some(ref key) = params.persisted_state_key {
+            sync15::check_persisted_state_key(key)?;
+        }
 
         let bookmarks_sync = should_sync(&params, BOOKMARKS_ENGINE) && places.is_some();
         let history_sync = should_sync(&params, HISTORY_ENGINE) && places.is_some();
@@ -249,6 +286,8 @@ impl SyncManager {
         let tabs_sync = should_sync(&params, TABS_ENGINE) && tabs.is_some();
         let addresses_sync = should_sync(&params, ADDRESSES_ENGINE) && addresses.is_some();
         let credit_cards_sync = should_sync(&params, CREDIT_CARDS_ENGINE) && credit_cards.is_some();
+        let webext_storage_sync =
+            should_sync(&params, WEBEXT_STORAGE_ENGINE) && webext_storage.is_some();
 
         let places_conn = if bookmarks_sync || history_sync {
             places
@@ -267,13 +306,32 @@ impl SyncManager {
         } else {
             None
         };
+        let ws = if webext_storage_sync {
+            webext_storage
+        } else {
+            None
+        };
 
         // TODO(issue 1684) this isn't ideal, we should have real support for interruption.
         let p = Arc::new(AtomicUsize::new(0));
         let interruptee = sql_support::SqlInterruptScope::new(p);
 
         let mut mem_cached_state = self.mem_cached_state.take().unwrap_or_default();
-        let mut disk_cached_state = params.persisted_state.take();
+        let mut disk_cached_state = match params.persisted_state_key {
+            Some(ref key) => params.persisted_state.take().and_then(|sealed| {
+                match sync15::unseal_persisted_state(&sealed, key) {
+                    Ok(state) => Some(state),
+                    Err(e) => {
+                        // As with state which fails to parse, we just start
+                        // again from scratch - the worst case is we lose track
+                        // of declined engines.
+                        log::error!("Failed to unseal persisted state: {}", e);
+                        None
+                    }
+                }
+            }),
+            None => params.persisted_state.take(),
+        };
         // `sync_multiple` takes a &[&dyn Engine], but we need something to hold
         // ownership of our engines.
         let mut engines: Vec<Box<dyn sync15::SyncEngine>> = vec![];
@@ -311,6 +369,11 @@ impl SyncManager {
             engines.push(cc);
         }
 
+        if let Some(ws) = ws {
+            assert!(webext_storage_sync, "Should have already checked");
+            engines.push(ws);
+        }
+
         // tell engines about the local encryption key.
         for engine in engines.iter_mut() {
             if let Some(key) = params.local_encryption_keys.get(&*engine.collection_name()) {
@@ -400,13 +463,19 @@ impl SyncManager {
         // unserializable type.
         let telemetry_json = serde_json::to_string(&result.telemetry).unwrap();
 
+        let persisted_state = disk_cached_state.unwrap_or_default();
+        let persisted_state = match params.persisted_state_key {
+            Some(ref key) => sync15::seal_persisted_state(&persisted_state, key)?,
+            None => persisted_state,
+        };
+
         Ok(SyncResult {
             status,
             results,
             have_declined: result.declined.is_some(),
             declined: result.declined.unwrap_or_default(),
             next_sync_allowed_at: system_time_to_millis(result.next_sync_after),
-            persisted_state: disk_cached_state.unwrap_or_default(),
+            persisted_state,
             telemetry_json: Some(telemetry_json),
         })
     }
@@ -481,6 +550,7 @@ fn check_engine_list(list: &[String], have_engines: &[&str]) -> Result<()> {
             HISTORY_ENGINE,
             LOGINS_ENGINE,
             TABS_ENGINE,
+            WEBEXT_STORAGE_ENGINE,
         ]
         .contains(&e.as_ref())
         {
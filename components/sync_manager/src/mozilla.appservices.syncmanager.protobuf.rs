@@ -28,6 +28,10 @@ pub struct SyncParams {
     pub device_type: i32,
     #[prost(map="string, string", tag="13")]
     pub local_encryption_keys: ::std::collections::HashMap<::prost::alloc::string::String, ::prost::alloc::string::String>,
+    /// If specified, `persisted_state` is expected to have been sealed with
+    /// this key, and the `persisted_state` in the result will be sealed with it.
+    #[prost(string, optional, tag="14")]
+    pub persisted_state_key: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SyncResult {
@@ -393,6 +393,7 @@ pub mod consts {
         (X_WEAVE_RECORDS, "x-weave-records"),
         (X_WEAVE_TIMESTAMP, "x-weave-timestamp"),
         (X_WEAVE_BACKOFF, "x-weave-backoff"),
+        (X_WEAVE_QUOTA_REMAINING, "x-weave-quota-remaining"),
     );
 
     #[test]
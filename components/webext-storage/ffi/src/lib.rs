@@ -3,20 +3,34 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::os::raw::c_char;
+use std::sync::{Arc, Mutex};
 
 use ffi_support::{define_handle_map_deleter, ConcurrentHandleMap, ExternError, FfiStr};
 use webext_storage::{error, store::Store};
 
 lazy_static::lazy_static! {
-    static ref STORES: ConcurrentHandleMap<Store> = ConcurrentHandleMap::new();
+    // The stores are shared with the sync manager once they're registered
+    // with it, which needs them in an `Arc<Mutex<_>>`.
+    static ref STORES: ConcurrentHandleMap<Arc<Mutex<Store>>> = ConcurrentHandleMap::new();
 }
 
 #[no_mangle]
 pub extern "C" fn webext_store_new(db_path: FfiStr<'_>, error: &mut ExternError) -> u64 {
     log::debug!("webext_store_new");
-    STORES.insert_with_result(error, || -> error::Result<Store> {
+    STORES.insert_with_result(error, || -> error::Result<_> {
         let path = db_path.as_str();
-        Store::new(path)
+        Ok(Arc::new(Mutex::new(Store::new(path)?)))
+    })
+}
+
+/// Makes the store available to the sync manager, which syncs it in the
+/// `extension-storage` collection. The sync manager only holds a weak
+/// reference, so destroying the store also unregisters it.
+#[no_mangle]
+pub extern "C" fn webext_store_register_with_sync_manager(handle: u64, error: &mut ExternError) {
+    log::debug!("webext_store_register_with_sync_manager");
+    STORES.call_with_output(error, handle, |store| {
+        webext_storage::register_with_sync_manager(Arc::clone(store))
     })
 }
 
@@ -29,6 +43,7 @@ pub extern "C" fn webext_store_set(
 ) -> *mut c_char {
     log::debug!("webext_store_set");
     STORES.call_with_result(error, handle, |store| -> error::Result<_> {
+        let store = store.lock().unwrap();
         let val = serde_json::from_str(json.as_str())?;
         let changes = store.set(ext_id.as_str(), val)?;
         Ok(serde_json::to_string(&changes)?)
@@ -44,6 +59,7 @@ pub extern "C" fn webext_store_get(
 ) -> *mut c_char {
     log::debug!("webext_store_get");
     STORES.call_with_result(error, handle, |store| -> error::Result<_> {
+        let store = store.lock().unwrap();
         let keys = serde_json::from_str(keys.as_str())?;
         let val = store.get(ext_id.as_str(), keys)?;
         Ok(serde_json::to_string(&val)?)
@@ -59,6 +75,7 @@ pub extern "C" fn webext_store_remove(
 ) -> *mut c_char {
     log::debug!("webext_store_remove");
     STORES.call_with_result(error, handle, |store| -> error::Result<_> {
+        let store = store.lock().unwrap();
         let keys = serde_json::from_str(keys.as_str())?;
         let changes = store.remove(ext_id.as_str(), keys)?;
         Ok(serde_json::to_string(&changes)?)
@@ -73,6 +90,7 @@ pub extern "C" fn webext_store_clear(
 ) -> *mut c_char {
     log::debug!("webext_store_clear");
     STORES.call_with_result(error, handle, |store| -> error::Result<_> {
+        let store = store.lock().unwrap();
         let changes = store.clear(ext_id.as_str())?;
         Ok(serde_json::to_string(&changes)?)
     })
@@ -24,3 +24,5 @@ pub use api::SYNC_QUOTA_BYTES;
 pub use api::SYNC_QUOTA_BYTES_PER_ITEM;
 
 pub use api::UsageInfo;
+
+pub use store::{get_registered_sync_engine, register_with_sync_manager, STORAGE_SYNC_COLLECTION};
@@ -9,9 +9,51 @@ use crate::migration::{migrate, MigrationInfo};
 use crate::sync;
 use std::path::Path;
 use std::result;
+use std::sync::{Arc, Mutex, Weak};
 
 use serde_json::Value as JsonValue;
 use sql_support::SqlInterruptHandle;
+use sync15_traits::{ApplyResults, BridgedEngine, IncomingEnvelope, SyncEngine, SyncEngineAdapter};
+use sync_guid::Guid as SyncGuid;
+
+/// The name of the collection `storage.sync` data is synced to.
+pub const STORAGE_SYNC_COLLECTION: &str = "extension-storage";
+
+// Our "sync manager" will use whatever is stashed here.
+lazy_static::lazy_static! {
+    // Mutex: just taken long enough to update the inner stuff. Unlike other
+    // components, the `Store` isn't `Sync`, so the app needs to give us one
+    // wrapped in a mutex.
+    static ref STORE_FOR_MANAGER: Mutex<Weak<Mutex<Store>>> = Mutex::new(Weak::new());
+}
+
+// This allows the embedding app to say "make this instance available to the
+// sync manager". The implementation is more like "offer to sync mgr" (thereby
+// avoiding us needing to link with the sync manager) but
+// `register_with_sync_manager()` is logically what's happening so that's the
+// name it gets.
+pub fn register_with_sync_manager(store: Arc<Mutex<Store>>) {
+    let mut state = STORE_FOR_MANAGER.lock().unwrap();
+    *state = Arc::downgrade(&store);
+}
+
+/// Called by the sync manager to get a sync engine via the store previously
+/// registered with the sync manager.
+pub fn get_registered_sync_engine(name: &str) -> Option<Box<dyn SyncEngine>> {
+    let weak = STORE_FOR_MANAGER.lock().unwrap();
+    match weak.upgrade() {
+        None => None,
+        Some(store) => match name {
+            STORAGE_SYNC_COLLECTION => Some(Box::new(SyncEngineAdapter::new(
+                STORAGE_SYNC_COLLECTION,
+                StoreBridgedEngine(store),
+            ))),
+            // panicing here seems reasonable - it's a static error if this
+            // it hit, not something that runtime conditions can influence.
+            _ => unreachable!("can't provide unknown engine: {}", name),
+        },
+    }
+}
 
 /// A store is used to access `storage.sync` data. It manages an underlying
 /// database connection, and exposes methods for reading and writing storage
@@ -159,6 +201,78 @@ impl Store {
     }
 }
 
+/// The bridged engine returned by `Store::bridged_engine` borrows the store,
+/// but the sync manager needs an engine which owns it. This forwards to a
+/// new bridged engine for each call.
+struct StoreBridgedEngine(Arc<Mutex<Store>>);
+
+impl StoreBridgedEngine {
+    fn with_engine<T>(&self, f: impl FnOnce(&sync::BridgedEngine<'_>) -> Result<T>) -> Result<T> {
+        let store = self.0.lock().unwrap();
+        f(&store.bridged_engine())
+    }
+}
+
+impl BridgedEngine for StoreBridgedEngine {
+    type Error = Error;
+
+    fn last_sync(&self) -> Result<i64> {
+        self.with_engine(|engine| engine.last_sync())
+    }
+
+    fn set_last_sync(&self, last_sync_millis: i64) -> Result<()> {
+        self.with_engine(|engine| engine.set_last_sync(last_sync_millis))
+    }
+
+    fn sync_id(&self) -> Result<Option<String>> {
+        self.with_engine(|engine| engine.sync_id())
+    }
+
+    fn reset_sync_id(&self) -> Result<String> {
+        self.with_engine(|engine| engine.reset_sync_id())
+    }
+
+    fn ensure_current_sync_id(&self, new_sync_id: &str) -> Result<String> {
+        self.with_engine(|engine| engine.ensure_current_sync_id(new_sync_id))
+    }
+
+    fn global_sync_id(&self) -> Result<Option<String>> {
+        self.with_engine(|engine| engine.global_sync_id())
+    }
+
+    fn set_global_sync_id(&self, global_sync_id: &str) -> Result<()> {
+        self.with_engine(|engine| engine.set_global_sync_id(global_sync_id))
+    }
+
+    fn sync_started(&self) -> Result<()> {
+        self.with_engine(|engine| engine.sync_started())
+    }
+
+    fn store_incoming(&self, incoming_envelopes: &[IncomingEnvelope]) -> Result<()> {
+        self.with_engine(|engine| engine.store_incoming(incoming_envelopes))
+    }
+
+    fn apply(&self) -> Result<ApplyResults> {
+        self.with_engine(|engine| engine.apply())
+    }
+
+    fn set_uploaded(&self, server_modified_millis: i64, ids: &[SyncGuid]) -> Result<()> {
+        self.with_engine(|engine| engine.set_uploaded(server_modified_millis, ids))
+    }
+
+    fn sync_finished(&self) -> Result<()> {
+        self.with_engine(|engine| engine.sync_finished())
+    }
+
+    fn reset(&self) -> Result<()> {
+        self.with_engine(|engine| engine.reset())
+    }
+
+    fn wipe(&self) -> Result<()> {
+        self.with_engine(|engine| engine.wipe())
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -174,4 +288,24 @@ pub mod test {
             db: crate::db::test::new_mem_db(),
         }
     }
+
+    #[test]
+    fn test_sync_manager_registration() {
+        let store = Arc::new(Mutex::new(new_mem_store()));
+        assert_eq!(Arc::strong_count(&store), 1);
+        assert_eq!(Arc::weak_count(&store), 0);
+        register_with_sync_manager(Arc::clone(&store));
+        assert_eq!(Arc::strong_count(&store), 1);
+        assert_eq!(Arc::weak_count(&store), 1);
+        let engine = get_registered_sync_engine(STORAGE_SYNC_COLLECTION).expect("should upgrade");
+        assert_eq!(engine.collection_name(), STORAGE_SYNC_COLLECTION);
+        assert_eq!(Arc::strong_count(&store), 2);
+        drop(engine);
+        // should be no new references
+        assert_eq!(Arc::strong_count(&store), 1);
+        assert_eq!(Arc::weak_count(&store), 1);
+        // dropping the registered object should drop the registration.
+        drop(store);
+        assert!(get_registered_sync_engine(STORAGE_SYNC_COLLECTION).is_none());
+    }
 }
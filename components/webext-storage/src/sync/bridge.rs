@@ -14,6 +14,7 @@ use crate::sync::outgoing::{get_outgoing, record_uploaded, stage_outgoing};
 
 const LAST_SYNC_META_KEY: &str = "last_sync_time";
 const SYNC_ID_META_KEY: &str = "sync_id";
+const GLOBAL_SYNC_ID_META_KEY: &str = "global_sync_id";
 
 /// A bridged engine implements all the methods needed to make the
 /// `storage.sync` store work with Desktop's Sync implementation.
@@ -79,6 +80,18 @@ impl<'a> sync15_traits::BridgedEngine for BridgedEngine<'a> {
         })
     }
 
+    fn global_sync_id(&self) -> Result<Option<String>> {
+        get_meta(self.db, GLOBAL_SYNC_ID_META_KEY)
+    }
+
+    fn set_global_sync_id(&self, global_sync_id: &str) -> Result<()> {
+        put_meta(
+            self.db,
+            GLOBAL_SYNC_ID_META_KEY,
+            &global_sync_id.to_string(),
+        )
+    }
+
     fn sync_started(&self) -> Result<()> {
         schema::create_empty_sync_temp_tables(self.db)?;
         Ok(())
@@ -137,6 +150,7 @@ impl<'a> sync15_traits::BridgedEngine for BridgedEngine<'a> {
         let tx = self.db.unchecked_transaction()?;
         self.do_reset(&tx)?;
         delete_meta(&tx, SYNC_ID_META_KEY)?;
+        delete_meta(&tx, GLOBAL_SYNC_ID_META_KEY)?;
         tx.commit()?;
         Ok(())
     }
@@ -244,10 +258,13 @@ mod tests {
         setup_mock_data(&engine)?;
         put_meta(engine.db, SYNC_ID_META_KEY, &"sync-id".to_string())?;
 
+        engine.set_global_sync_id("global-id")?;
+
         engine.reset()?;
         assert_reset(&engine)?;
         // Only an explicit reset kills the sync-id, so check that here.
         assert_eq!(get_meta::<String>(engine.db, SYNC_ID_META_KEY)?, None);
+        assert_eq!(engine.global_sync_id()?, None);
 
         Ok(())
     }
@@ -75,11 +75,11 @@ fn main() -> Result<()> {
     let cfg = Config::new(CONTENT_SERVER, CLIENT_ID, REDIRECT_URI);
     let mut acct = load_or_create_fxa_creds(cfg)?;
 
-    // Make sure the device and the send-tab command are registered.
+    // Make sure the device and the send-tab and close-tabs commands are registered.
     acct.initialize_device(
         DEFAULT_DEVICE_NAME,
         device::Type::Desktop,
-        &[device::Capability::SendTab],
+        &[device::Capability::SendTab, device::Capability::CloseTabs],
     )
     .unwrap();
     persist_fxa_state(&acct);
@@ -107,6 +107,16 @@ fn main() -> Result<()> {
                             };
                             webbrowser::open(&tab.url).unwrap();
                         }
+                        IncomingDeviceCommand::TabsClosed { sender, payload } => {
+                            match sender {
+                                Some(ref d) => println!(
+                                    "Tabs closed by {}: {}",
+                                    d.display_name,
+                                    payload.urls.join(", ")
+                                ),
+                                None => println!("Tabs closed: {}", payload.urls.join(", ")),
+                            };
+                        }
                     }
                 }
                 thread::sleep(time::Duration::from_secs(1));
@@ -118,7 +128,7 @@ fn main() -> Result<()> {
     loop {
         println!("Main menu:");
         let mut main_menu = Select::new();
-        main_menu.items(&["Set Display Name", "Send a Tab", "Quit"]);
+        main_menu.items(&["Set Display Name", "Send a Tab", "Close a Tab", "Quit"]);
         main_menu.default(0);
         let main_menu_selection = main_menu.interact().unwrap();
 
@@ -151,7 +161,27 @@ fn main() -> Result<()> {
                     .unwrap();
                 println!("Tab sent!");
             }
-            2 => ::std::process::exit(0),
+            2 => {
+                let devices = acct.lock().unwrap().get_devices(false).unwrap();
+                let devices_names: Vec<String> =
+                    devices.iter().map(|i| i.display_name.clone()).collect();
+                let mut targets_menu = Select::new();
+                targets_menu.default(0);
+                let devices_names_refs: Vec<&str> =
+                    devices_names.iter().map(AsRef::as_ref).collect();
+                targets_menu.items(&devices_names_refs);
+                println!("Choose a close-tabs target:");
+                let selection = targets_menu.interact().unwrap();
+                let target = &devices[selection];
+
+                let url: String = prompt_string("URL").unwrap();
+                acct.lock()
+                    .unwrap()
+                    .close_tabs(&target.id, vec![url])
+                    .unwrap();
+                println!("Tab closed!");
+            }
+            3 => ::std::process::exit(0),
             _ => panic!("Invalid choice!"),
         }
     }
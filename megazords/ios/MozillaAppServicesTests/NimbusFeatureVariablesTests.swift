@@ -135,6 +135,26 @@ class NimbusFeatureVariablesTests: XCTestCase {
         let ordering: [MenuItemId]? = variables.getEnumList("item-order")
         XCTAssertEqual(ordering, [.settings, .history, .addBookmark, .bookmarks])
     }
+
+    // The expressions the feature manifest code generator emits for maps keyed by enums.
+    func testGeneratedEnumMapAccessors() throws {
+        let variables: Variables = JSONVariables(with: [
+            "items": [
+                "settings": ["label": "Settings"],
+                "unknown": ["label": "Unknown"],
+            ],
+            "next-item": [
+                "settings": "history",
+                "history": "unknown",
+            ],
+        ])
+
+        let labels: [MenuItemId: String]? = variables.getVariablesMap("items")?.mapValues { $0.getString("label") ?? "" }.compactMapKeys(MenuItemId.init(rawValue:))
+        XCTAssertEqual(labels, [.settings: "Settings"])
+
+        let nextItems: [MenuItemId: MenuItemId]? = variables.getStringMap("next-item")?.compactMapValues(MenuItemId.init(rawValue:)).compactMapKeys(MenuItemId.init(rawValue:))
+        XCTAssertEqual(nextItems, [.settings: .history])
+    }
 }
 
 enum MenuItemId: String {
@@ -128,6 +128,35 @@ class NimbusTests: XCTestCase {
         XCTAssertEqual(noExperiments.count, 0)
     }
 
+    func testExplainEnrollment() throws {
+        let appSettings = NimbusAppSettings(appName: "test", channel: "nightly")
+        let nimbus = try Nimbus.create(nil, appSettings: appSettings, dbPath: createDatabasePath()) as! Nimbus
+
+        try nimbus.setExperimentsLocallyOnThisThread(minimalExperimentJSON())
+        try nimbus.applyPendingExperimentsOnThisThread()
+
+        let explanation = nimbus.explainEnrollment("secure-gold")
+        XCTAssertNotNil(explanation)
+        XCTAssertEqual(explanation?.branchSlug, nimbus.getExperimentBranch(experimentId: "secure-gold"))
+
+        XCTAssertNil(nimbus.explainEnrollment("not-an-experiment"))
+    }
+
+    func testGetFeatureConfigProvenance() throws {
+        let appSettings = NimbusAppSettings(appName: "test", channel: "nightly")
+        let nimbus = try Nimbus.create(nil, appSettings: appSettings, dbPath: createDatabasePath()) as! Nimbus
+
+        try nimbus.setExperimentsLocallyOnThisThread(minimalExperimentJSON())
+        try nimbus.applyPendingExperimentsOnThisThread()
+
+        let provenance = nimbus.getFeatureConfigProvenance(featureId: "aboutwelcome")
+        XCTAssertEqual(provenance.count, 1)
+        XCTAssertEqual(provenance.first?.slug, "secure-gold")
+        XCTAssertEqual(provenance.first?.branchSlug, nimbus.getExperimentBranch(experimentId: "secure-gold"))
+
+        XCTAssertTrue(nimbus.getFeatureConfigProvenance(featureId: "not-a-feature").isEmpty)
+    }
+
     func testSmokeTestAsync() throws {
         let appSettings = NimbusAppSettings(appName: "test", channel: "nightly")
         let nimbus = try Nimbus.create(nil, appSettings: appSettings, dbPath: createDatabasePath()) as! Nimbus
@@ -0,0 +1,104 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{new_account, sign_in};
+use fxa_client::{
+    AccountEvent, DeviceCapability, DeviceType, FirefoxAccount, IncomingDeviceCommand,
+};
+use fxa_fake_server::{FakeFxaServer, OLD_SYNC_SCOPE};
+
+fn new_device(server: &FakeFxaServer, name: &str, device_type: DeviceType) -> FirefoxAccount {
+    let fxa = new_account(server);
+    sign_in(server, &fxa, &["profile", OLD_SYNC_SCOPE]);
+    fxa.initialize_device(name, device_type, vec![DeviceCapability::SendTab])
+        .unwrap();
+    fxa
+}
+
+#[test]
+fn test_send_tab_through_push() {
+    let server = FakeFxaServer::new();
+    let phone = new_device(&server, "Phone", DeviceType::Mobile);
+    let laptop = new_device(&server, "Laptop", DeviceType::Desktop);
+    let laptop_id = laptop.get_current_device_id().unwrap();
+
+    phone
+        .send_single_tab(&laptop_id, "Example", "https://example.com/")
+        .unwrap();
+
+    let messages = server.take_push_messages(&laptop_id);
+    assert_eq!(messages.len(), 1);
+    let events = laptop.handle_push_message(&messages[0]).unwrap();
+    match events.as_slice() {
+        [AccountEvent::CommandReceived {
+            command: IncomingDeviceCommand::TabReceived { sender, payload },
+        }] => {
+            assert_eq!(sender.as_ref().unwrap().display_name, "Phone");
+            assert_eq!(payload.entries.len(), 1);
+            assert_eq!(payload.entries[0].title, "Example");
+            assert_eq!(payload.entries[0].url, "https://example.com/");
+        }
+        _ => panic!("Unexpected events: {:?}", events),
+    }
+    // The command was handled, so polling doesn't return it again.
+    assert!(laptop.poll_device_commands().unwrap().is_empty());
+}
+
+#[test]
+fn test_send_tab_through_polling() {
+    let server = FakeFxaServer::new();
+    let phone = new_device(&server, "Phone", DeviceType::Mobile);
+    let laptop = new_device(&server, "Laptop", DeviceType::Desktop);
+    let phone_id = phone.get_current_device_id().unwrap();
+
+    laptop
+        .send_single_tab(&phone_id, "First", "https://example.com/1")
+        .unwrap();
+    laptop
+        .send_single_tab(&phone_id, "Second", "https://example.com/2")
+        .unwrap();
+
+    let urls: Vec<String> = phone
+        .poll_device_commands()
+        .unwrap()
+        .into_iter()
+        .map(|command| match command {
+            IncomingDeviceCommand::TabReceived { payload, .. } => payload.entries[0].url.clone(),
+            _ => panic!("Unexpected command: {:?}", command),
+        })
+        .collect();
+    assert_eq!(urls, vec!["https://example.com/1", "https://example.com/2"]);
+    assert!(phone.poll_device_commands().unwrap().is_empty());
+}
+
+#[test]
+fn test_device_connected_and_disconnected() {
+    let server = FakeFxaServer::new();
+    let phone = new_device(&server, "Phone", DeviceType::Mobile);
+    let phone_id = phone.get_current_device_id().unwrap();
+    let laptop = new_device(&server, "Laptop", DeviceType::Desktop);
+    let laptop_id = laptop.get_current_device_id().unwrap();
+    assert_eq!(phone.get_devices(true).unwrap().len(), 2);
+
+    let messages = server.take_push_messages(&phone_id);
+    assert_eq!(messages.len(), 1);
+    let events = phone.handle_push_message(&messages[0]).unwrap();
+    assert!(matches!(
+        events.as_slice(),
+        [AccountEvent::DeviceConnected { device_name }] if device_name == "Laptop"
+    ));
+
+    laptop.disconnect();
+    let messages = server.take_push_messages(&phone_id);
+    assert_eq!(messages.len(), 1);
+    let events = phone.handle_push_message(&messages[0]).unwrap();
+    assert!(matches!(
+        events.as_slice(),
+        [AccountEvent::DeviceDisconnected { device_id, is_local_device: false }]
+            if device_id == &laptop_id
+    ));
+    let devices = phone.get_devices(false).unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].id, phone_id);
+}
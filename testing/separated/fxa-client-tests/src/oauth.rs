@@ -0,0 +1,84 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{new_account, sign_in, SESSION_SCOPE};
+use fxa_client::{AccountEvent, AccountState};
+use fxa_fake_server::{FakeFxaServer, OLD_SYNC_SCOPE};
+
+#[test]
+fn test_oauth_flow() {
+    let server = FakeFxaServer::new();
+    let fxa = new_account(&server);
+    assert_eq!(fxa.get_state(), AccountState::Disconnected);
+    sign_in(&server, &fxa, &["profile", OLD_SYNC_SCOPE]);
+    assert_eq!(fxa.get_state(), AccountState::Connected);
+
+    let profile = fxa.get_profile(false).unwrap();
+    assert_eq!(profile.uid, server.uid());
+    assert_eq!(profile.email, server.email());
+
+    let token = fxa.get_access_token(OLD_SYNC_SCOPE, None).unwrap();
+    let key = token
+        .key
+        .expect("The scoped key should have been delivered");
+    assert_eq!(key.scope, OLD_SYNC_SCOPE);
+    assert!(fxa.check_authorization_status().unwrap().active);
+}
+
+#[test]
+fn test_two_accounts_share_keys() {
+    let server = FakeFxaServer::new();
+    let first = new_account(&server);
+    let second = new_account(&server);
+    sign_in(&server, &first, &[OLD_SYNC_SCOPE]);
+    sign_in(&server, &second, &[OLD_SYNC_SCOPE]);
+    let first_key = first.get_access_token(OLD_SYNC_SCOPE, None).unwrap().key;
+    let second_key = second.get_access_token(OLD_SYNC_SCOPE, None).unwrap().key;
+    assert_eq!(first_key.unwrap().k, second_key.unwrap().k);
+}
+
+#[test]
+fn test_state_survives_serialization() {
+    let server = FakeFxaServer::new();
+    let fxa = new_account(&server);
+    sign_in(&server, &fxa, &["profile", OLD_SYNC_SCOPE]);
+    let restored = fxa_client::FirefoxAccount::from_json(&fxa.to_json().unwrap()).unwrap();
+    assert_eq!(restored.get_profile(true).unwrap().uid, server.uid());
+}
+
+#[test]
+fn test_scoped_key_rotation() {
+    let server = FakeFxaServer::new();
+    let fxa = new_account(&server);
+    sign_in(&server, &fxa, &[OLD_SYNC_SCOPE, SESSION_SCOPE]);
+    let old_key = fxa.get_access_token(OLD_SYNC_SCOPE, None).unwrap().key;
+
+    server.rotate_scoped_key(OLD_SYNC_SCOPE).unwrap();
+    fxa.clear_access_token_cache();
+    let token = fxa.get_access_token(OLD_SYNC_SCOPE, None).unwrap();
+    assert!(token.key.is_none());
+    assert_eq!(fxa.get_state(), AccountState::AuthProblem);
+    let events = fxa.take_account_events();
+    assert!(matches!(
+        events.as_slice(),
+        [AccountEvent::ScopedKeyChanged { scope }] if scope == OLD_SYNC_SCOPE
+    ));
+
+    // Signing in again gets us the new key.
+    sign_in(&server, &fxa, &[OLD_SYNC_SCOPE, SESSION_SCOPE]);
+    assert_eq!(fxa.get_state(), AccountState::Connected);
+    let new_key = fxa.get_access_token(OLD_SYNC_SCOPE, None).unwrap().key;
+    assert_ne!(old_key.unwrap().kid, new_key.unwrap().kid);
+}
+
+#[test]
+fn test_password_reset() {
+    let server = FakeFxaServer::new();
+    let fxa = new_account(&server);
+    sign_in(&server, &fxa, &["profile", OLD_SYNC_SCOPE]);
+    server.reset_password().unwrap();
+    fxa.clear_access_token_cache();
+    assert!(fxa.get_access_token("profile", None).is_err());
+    assert_eq!(fxa.get_state(), AccountState::AuthProblem);
+}
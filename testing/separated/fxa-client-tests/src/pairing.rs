@@ -0,0 +1,194 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::{new_account, sign_in, SESSION_SCOPE};
+use fxa_client::{AuthorizationParameters, PairingAuthorityState, PairingChannelTransport};
+use fxa_fake_server::{FakeFxaServer, OLD_SYNC_SCOPE};
+use jwcrypto::{
+    DecryptionParameters, EncryptionAlgorithm, EncryptionParameters, Jwk, JwkKeyParameters,
+};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use url::Url;
+
+// The pairing channel itself isn't involved here: we pass the supplicant's
+// authorization parameters to the authority directly, as it would receive them.
+#[test]
+fn test_pairing() {
+    let server = FakeFxaServer::new();
+    let authority = new_account(&server);
+    sign_in(
+        &server,
+        &authority,
+        &["profile", OLD_SYNC_SCOPE, SESSION_SCOPE],
+    );
+
+    let supplicant = new_account(&server);
+    let url = supplicant
+        .begin_oauth_flow(&[OLD_SYNC_SCOPE.to_owned()], "pairing", None)
+        .unwrap();
+    let params: HashMap<String, String> = Url::parse(&url)
+        .unwrap()
+        .query_pairs()
+        .into_owned()
+        .collect();
+    let code = authority
+        .authorize_code_using_session_token(AuthorizationParameters {
+            client_id: params["client_id"].clone(),
+            scope: vec![params["scope"].clone()],
+            state: params["state"].clone(),
+            access_type: params["access_type"].clone(),
+            code_challenge: params.get("code_challenge").cloned(),
+            code_challenge_method: params.get("code_challenge_method").cloned(),
+            keys_jwk: params.get("keys_jwk").cloned(),
+        })
+        .unwrap();
+    supplicant
+        .complete_oauth_flow(&code, &params["state"])
+        .unwrap();
+
+    let authority_key = authority
+        .get_access_token(OLD_SYNC_SCOPE, None)
+        .unwrap()
+        .key;
+    let supplicant_key = supplicant
+        .get_access_token(OLD_SYNC_SCOPE, None)
+        .unwrap()
+        .key;
+    assert_eq!(authority_key.unwrap().k, supplicant_key.unwrap().k);
+}
+
+// The channel between the two devices, with the messages from the authority
+// waiting to be read by the supplicant.
+#[derive(Clone, Default)]
+struct InMemoryChannel {
+    to_supplicant: Arc<Mutex<Vec<String>>>,
+}
+
+impl PairingChannelTransport for InMemoryChannel {
+    fn send(&self, message: String) {
+        self.to_supplicant.lock().unwrap().push(message);
+    }
+}
+
+impl InMemoryChannel {
+    // `FirefoxAccount` doesn't implement the supplicant side of the channel,
+    // so we encrypt and decrypt its messages here.
+    fn take_for_supplicant(&self, key: &Jwk) -> Vec<serde_json::Value> {
+        std::mem::take(&mut *self.to_supplicant.lock().unwrap())
+            .iter()
+            .map(|jwe| {
+                let plaintext =
+                    jwcrypto::decrypt_jwe(jwe, DecryptionParameters::Direct { jwk: key.clone() })
+                        .unwrap();
+                serde_json::from_str(&plaintext).unwrap()
+            })
+            .collect()
+    }
+}
+
+fn encrypt_from_supplicant(key: &Jwk, message: serde_json::Value) -> String {
+    jwcrypto::encrypt_to_jwe(
+        message.to_string().as_bytes(),
+        EncryptionParameters::Direct {
+            enc: EncryptionAlgorithm::A256GCM,
+            jwk: key,
+        },
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_pairing_channel() {
+    let server = FakeFxaServer::new();
+    let authority = new_account(&server);
+    sign_in(
+        &server,
+        &authority,
+        &["profile", OLD_SYNC_SCOPE, SESSION_SCOPE],
+    );
+    let channel = InMemoryChannel::default();
+    let pairing_url = authority
+        .begin_pairing_authority("channel", Box::new(channel.clone()))
+        .unwrap();
+
+    // The supplicant scans the QR code...
+    let fragment: HashMap<String, String> = url::form_urlencoded::parse(
+        Url::parse(&pairing_url)
+            .unwrap()
+            .fragment()
+            .unwrap()
+            .as_bytes(),
+    )
+    .into_owned()
+    .collect();
+    let key = Jwk {
+        kid: None,
+        key_parameters: JwkKeyParameters::Direct {
+            k: fragment["channel_key"].clone(),
+        },
+    };
+
+    // ...and sends its authorization request.
+    let supplicant = new_account(&server);
+    let url = supplicant
+        .begin_oauth_flow(&[OLD_SYNC_SCOPE.to_owned()], "pairing", None)
+        .unwrap();
+    let params: HashMap<String, String> = Url::parse(&url)
+        .unwrap()
+        .query_pairs()
+        .into_owned()
+        .collect();
+    let request = encrypt_from_supplicant(
+        &key,
+        json!({ "message": "pair:supp:request", "data": params }),
+    );
+    let state = authority.handle_pairing_message(&request).unwrap();
+    assert_eq!(
+        state,
+        PairingAuthorityState::AwaitingApproval {
+            client_id: params["client_id"].clone(),
+            scopes: vec![OLD_SYNC_SCOPE.to_owned()],
+        }
+    );
+    let messages = channel.take_for_supplicant(&key);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["message"], "pair:auth:metadata");
+    assert_eq!(messages[0]["data"]["email"], server.email());
+
+    // Both users approve.
+    let authorize = encrypt_from_supplicant(&key, json!({ "message": "pair:supp:authorize" }));
+    assert!(matches!(
+        authority.handle_pairing_message(&authorize).unwrap(),
+        PairingAuthorityState::AwaitingApproval { .. }
+    ));
+    assert_eq!(
+        authority.approve_pairing().unwrap(),
+        PairingAuthorityState::Completed
+    );
+
+    let messages = channel.take_for_supplicant(&key);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["message"], "pair:auth:authorize");
+    let data = &messages[0]["data"];
+    supplicant
+        .complete_oauth_flow(
+            data["code"].as_str().unwrap(),
+            data["state"].as_str().unwrap(),
+        )
+        .unwrap();
+
+    let authority_key = authority
+        .get_access_token(OLD_SYNC_SCOPE, None)
+        .unwrap()
+        .key;
+    let supplicant_key = supplicant
+        .get_access_token(OLD_SYNC_SCOPE, None)
+        .unwrap()
+        .key;
+    assert_eq!(authority_key.unwrap().k, supplicant_key.unwrap().k);
+}
@@ -0,0 +1,30 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use fxa_client::FirefoxAccount;
+use fxa_fake_server::FakeFxaServer;
+
+mod devices;
+mod oauth;
+mod pairing;
+
+const CLIENT_ID: &str = "3c49430b43dfba77";
+const REDIRECT_URI: &str = "https://accounts.firefox.com/oauth/success/3c49430b43dfba77";
+const SESSION_SCOPE: &str = "https://identity.mozilla.com/tokens/session";
+
+fn new_account(server: &FakeFxaServer) -> FirefoxAccount {
+    let _ = env_logger::try_init();
+    FirefoxAccount::new(&server.content_url(), CLIENT_ID, REDIRECT_URI, &None)
+}
+
+/// Go through an OAuth flow, with the fake server standing in for the user.
+fn sign_in(server: &FakeFxaServer, fxa: &FirefoxAccount, scopes: &[&str]) {
+    let scopes: Vec<String> = scopes.iter().map(ToString::to_string).collect();
+    let url = fxa
+        .begin_oauth_flow(&scopes, "integration-test", None)
+        .unwrap();
+    let authorization = server.authorize(&url).unwrap();
+    fxa.complete_oauth_flow(&authorization.code, &authorization.state)
+        .unwrap();
+}